@@ -8,6 +8,9 @@ use serde_json::Value;
 pub struct AFIJFunctionInfo {
     pub offset: u64,
     pub name: String,
+    /// The original name of the function when the name has been demangled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mangled_name: Option<String>,
     pub size: i128,
     #[serde(rename = "is-pure")]
     pub is_pure: String,
@@ -127,6 +130,61 @@ impl From<&AFIJFunctionInfo> for AFIJFeatureSubset {
     }
 }
 
+/// A function feature subset which also includes the call edges of the function
+///
+/// `callees` are the target addresses of the CALL refs made by the function and
+/// `callers` are the addresses of the CALL sites which reference the function.
+#[derive(Default, Debug, Clone, PartialEq, Hash, Eq, Serialize, Deserialize)]
+pub struct AFIJFeatureSubsetWithEdges {
+    pub name: String,
+    pub ninstrs: i64,
+    pub edges: i64,
+    pub indegree: i64,
+    pub outdegree: i64,
+    pub nlocals: i64,
+    pub nargs: i64,
+    pub signature: String,
+    pub callees: Vec<String>,
+    pub callers: Vec<i64>,
+}
+
+impl From<&AFIJFunctionInfo> for AFIJFeatureSubsetWithEdges {
+    fn from(src: &AFIJFunctionInfo) -> AFIJFeatureSubsetWithEdges {
+        let mut callees: Vec<String> = src
+            .callrefs
+            .iter()
+            .flatten()
+            .filter(|callref| callref.type_field == "CALL")
+            .map(|callref| callref.addr.clone())
+            .collect();
+        callees.sort_unstable();
+        callees.dedup();
+
+        let mut callers: Vec<i64> = src
+            .codexrefs
+            .iter()
+            .flatten()
+            .filter(|codexref| codexref.type_field == "CALL")
+            .map(|codexref| codexref.addr)
+            .collect();
+        callers.sort_unstable();
+        callers.dedup();
+
+        AFIJFeatureSubsetWithEdges {
+            name: src.name.clone(),
+            ninstrs: src.ninstrs,
+            edges: src.edges,
+            indegree: src.indegree.unwrap_or(0),
+            outdegree: src.outdegree.unwrap_or(0),
+            nlocals: src.nlocals.unwrap_or(0),
+            nargs: src.nargs.unwrap_or(0),
+            signature: src.signature.clone(),
+            callees,
+            callers,
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub struct AFIJFeatureSubsetExtended {
     pub name: String,
@@ -157,3 +215,127 @@ impl From<&AFIJFunctionInfo> for AFIJFeatureSubsetExtended {
         }
     }
 }
+
+/// Function level complexity features derived from r2's function info
+///
+/// Cyclomatic complexity is calculated as edges - nodes + 2 (McCabe, 1976) using the
+/// number of basic blocks as the number of nodes. Functions without any basic blocks
+/// have no CFG so are given a complexity of 0. Basic block density is the number
+/// of basic blocks per byte of the function.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComplexityFeatures {
+    pub name: String,
+    pub ninstrs: i64,
+    pub nbbs: u64,
+    pub edges: i64,
+    pub cyclomatic_complexity: i64,
+    pub bb_density: f64,
+    pub ins_bb_ratio: f64,
+}
+
+impl From<&AFIJFunctionInfo> for ComplexityFeatures {
+    fn from(src: &AFIJFunctionInfo) -> ComplexityFeatures {
+        let bb_density = if src.realsz > 0 {
+            src.nbbs as f64 / src.realsz as f64
+        } else {
+            0.0
+        };
+        let cyclomatic_complexity = if src.nbbs > 0 {
+            src.edges - src.nbbs as i64 + 2
+        } else {
+            0
+        };
+        let ins_bb_ratio = if src.nbbs > 0 {
+            src.ninstrs as f64 / src.nbbs as f64
+        } else {
+            0.0
+        };
+
+        ComplexityFeatures {
+            name: src.name.clone(),
+            ninstrs: src.ninstrs,
+            nbbs: src.nbbs,
+            edges: src.edges,
+            cyclomatic_complexity,
+            bb_density,
+            ins_bb_ratio,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function_info(ninstrs: i64, nbbs: u64, edges: i64, realsz: u64) -> AFIJFunctionInfo {
+        AFIJFunctionInfo {
+            name: "sym.test".to_string(),
+            ninstrs,
+            nbbs,
+            edges,
+            realsz,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_feature_subset_with_edges() {
+        let function: AFIJFunctionInfo = serde_json::from_str(
+            r#"{"offset":4425,"name":"main","size":80,"is-pure":"false","realsz":80,
+            "noreturn":false,"stackframe":24,"calltype":"amd64","cost":30,"cc":1,"bits":64,
+            "type":"fcn","nbbs":1,"is-lineal":true,"ninstrs":20,"edges":0,"ebbs":1,
+            "signature":"int main (int argc, char **argv);","minbound":4425,"maxbound":4505,
+            "callrefs":[{"addr":4192,"type":"CALL","at":4450},{"addr":4160,"type":"CALL","at":4460},
+            {"addr":4192,"type":"CALL","at":4470},{"addr":4480,"type":"CODE","at":4475}],
+            "codexrefs":[{"addr":4200,"type":"CALL","at":4425},{"addr":4220,"type":"DATA","at":4425},
+            {"addr":4180,"type":"CALL","at":4425},{"addr":4200,"type":"CALL","at":4425}],
+            "indegree":1,"outdegree":1,"nlocals":2,"nargs":2}"#,
+        )
+        .unwrap();
+
+        let subset = AFIJFeatureSubsetWithEdges::from(&function);
+        assert_eq!(subset.name, function.name);
+        assert_eq!(subset.ninstrs, function.ninstrs);
+        assert_eq!(subset.edges, function.edges);
+        assert_eq!(Some(subset.indegree), function.indegree);
+        assert_eq!(Some(subset.outdegree), function.outdegree);
+        assert_eq!(Some(subset.nlocals), function.nlocals);
+        assert_eq!(Some(subset.nargs), function.nargs);
+        assert_eq!(subset.signature, function.signature);
+        // Repeated calls are only listed once even when they are not adjacent
+        assert_eq!(subset.callees, vec!["4160".to_string(), "4192".to_string()]);
+        assert_eq!(subset.callers, vec![4180, 4200]);
+
+        let subset = AFIJFeatureSubsetWithEdges::from(&function_info(4, 1, 0, 16));
+        assert!(subset.callees.is_empty());
+        assert!(subset.callers.is_empty());
+    }
+
+    #[test]
+    fn test_complexity_features_straight_line_function() {
+        // A single block with no edges - 0 - 1 + 2 = 1
+        let features = ComplexityFeatures::from(&function_info(4, 1, 0, 16));
+        assert_eq!(features.cyclomatic_complexity, 1);
+        assert_eq!(features.bb_density, 1.0 / 16.0);
+        assert_eq!(features.ins_bb_ratio, 4.0);
+    }
+
+    #[test]
+    fn test_complexity_features_branching_function() {
+        // An if/else diamond and a loop back edge - 5 - 4 + 2 = 3
+        let features = ComplexityFeatures::from(&function_info(20, 4, 5, 80));
+        assert_eq!(features.name, "sym.test");
+        assert_eq!(features.cyclomatic_complexity, 3);
+        assert_eq!(features.bb_density, 0.05);
+        assert_eq!(features.ins_bb_ratio, 5.0);
+    }
+
+    #[test]
+    fn test_complexity_features_empty_function() {
+        // No basic blocks means there is no CFG to take paths through
+        let features = ComplexityFeatures::from(&function_info(0, 0, 0, 0));
+        assert_eq!(features.cyclomatic_complexity, 0);
+        assert_eq!(features.bb_density, 0.0);
+        assert_eq!(features.ins_bb_ratio, 0.0);
+    }
+}
@@ -1,13 +1,13 @@
 use crate::files::AGCJFile;
 use crate::networkx::{
     CallGraphFuncNameNode, CallGraphFuncWithMetadata, CallGraphTikNibFeatures,
-    CallGraphTikNibFinfoFeatures, NetworkxDiGraph,
+    CallGraphTikNibFinfoFeatures, GraphFormat, NetworkxDiGraph,
 };
-use crate::utils::{check_or_create_dir, get_save_file_path};
+use crate::utils::{check_or_create_dir, demangle_function_name, get_save_file_path};
 use itertools::Itertools;
-use petgraph::prelude::Graph;
+use petgraph::prelude::{Graph, NodeIndex};
 use serde::{Deserialize, Serialize};
-use std::fs::File;
+use std::collections::{BTreeSet, HashMap};
 use std::path::{Path, PathBuf};
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -18,6 +18,136 @@ pub struct AGCJFunctionCallGraph {
     pub imports: Option<Vec<String>>,
 }
 
+/// How self-loop edges (i.e. recursive calls) are handled in generated call graphs
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SelfLoops {
+    /// Keep self-loops where they are present in the call graph data
+    #[default]
+    Keep,
+    /// Remove all self-loops
+    Remove,
+    /// Add a self-loop to every node
+    Force,
+}
+
+impl SelfLoops {
+    pub fn new(no_self_loops: bool, add_self_loops: bool) -> SelfLoops {
+        if no_self_loops {
+            SelfLoops::Remove
+        } else if add_self_loops {
+            SelfLoops::Force
+        } else {
+            SelfLoops::Keep
+        }
+    }
+
+    pub fn apply(&self, graph: &mut Graph<String, u32>) {
+        match self {
+            SelfLoops::Keep => {}
+            SelfLoops::Remove => graph.retain_edges(|g, edge| {
+                let (source, target) = g.edge_endpoints(edge).unwrap();
+                source != target
+            }),
+            SelfLoops::Force => {
+                for node in graph.node_indices() {
+                    if graph.find_edge(node, node).is_none() {
+                        graph.add_edge(node, node, 0);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Add a call edge between two nodes.
+///
+/// When `weighted_edges` is set each call site increments the weight of the edge,
+/// otherwise repeated calls collapse into a single edge with a weight of 0.
+pub fn add_call_edge(
+    graph: &mut Graph<String, u32>,
+    caller: NodeIndex,
+    callee: NodeIndex,
+    weighted_edges: bool,
+) {
+    if weighted_edges {
+        match graph.find_edge(caller, callee) {
+            Some(edge_index) => graph[edge_index] += 1,
+            None => {
+                graph.add_edge(caller, callee, 1);
+            }
+        }
+    } else {
+        graph.update_edge(caller, callee, 0);
+    }
+}
+
+/// The difference between the global call graphs of two versions of a binary.
+///
+/// Functions are matched by name. Edges are represented as (caller, callee) pairs.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CallGraphDiff {
+    pub added_nodes: BTreeSet<String>,
+    pub removed_nodes: BTreeSet<String>,
+    pub added_edges: BTreeSet<(String, String)>,
+    pub removed_edges: BTreeSet<(String, String)>,
+}
+
+// Functions radare2 has named automatically. These names are derived from addresses
+// so rarely match across two versions of a binary
+fn is_auto_named(name: &str) -> bool {
+    name.starts_with("unk.") || name.starts_with("fcn.")
+}
+
+fn call_graph_nodes_and_edges(
+    graph: &Graph<String, u32>,
+    ignore_auto_named: bool,
+) -> (BTreeSet<String>, BTreeSet<(String, String)>) {
+    let keep = |name: &String| !(ignore_auto_named && is_auto_named(name));
+    let nodes = graph
+        .node_weights()
+        .filter(|name| keep(name))
+        .cloned()
+        .collect();
+    let edges = graph
+        .edge_indices()
+        .filter_map(|edge| graph.edge_endpoints(edge))
+        .map(|(source, target)| (&graph[source], &graph[target]))
+        .filter(|(source, target)| keep(source) && keep(target))
+        .map(|(source, target)| (source.clone(), target.clone()))
+        .collect();
+    (nodes, edges)
+}
+
+impl CallGraphDiff {
+    /// Diffs two global call graphs (i.e. from `build_global_call_graph`) where `old`
+    /// is the original binary and `new` is the patched version.
+    ///
+    /// If `ignore_auto_named` is set, `unk.` and `fcn.` functions (and any edges to/from
+    /// them) are excluded from both graphs before diffing.
+    pub fn new(
+        old: &Graph<String, u32>,
+        new: &Graph<String, u32>,
+        ignore_auto_named: bool,
+    ) -> CallGraphDiff {
+        let (old_nodes, old_edges) = call_graph_nodes_and_edges(old, ignore_auto_named);
+        let (new_nodes, new_edges) = call_graph_nodes_and_edges(new, ignore_auto_named);
+
+        CallGraphDiff {
+            added_nodes: new_nodes.difference(&old_nodes).cloned().collect(),
+            removed_nodes: old_nodes.difference(&new_nodes).cloned().collect(),
+            added_edges: new_edges.difference(&old_edges).cloned().collect(),
+            removed_edges: old_edges.difference(&new_edges).cloned().collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added_nodes.is_empty()
+            && self.removed_nodes.is_empty()
+            && self.added_edges.is_empty()
+            && self.removed_edges.is_empty()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AGCJParsedObjects {
     pub edge_property: String,
@@ -27,12 +157,25 @@ pub struct AGCJParsedObjects {
 }
 
 impl AGCJFunctionCallGraph {
+    /// Demangles the name of the function and the names of its callees
+    pub fn demangle(&mut self) {
+        if let Some(demangled) = demangle_function_name(&self.name) {
+            self.name = demangled
+        }
+        for import in self.imports.iter_mut().flatten() {
+            if let Some(demangled) = demangle_function_name(import) {
+                *import = demangled
+            }
+        }
+    }
+
     fn graph_to_json_func_node(
         &self,
         binary_name: &Path,
         output_path: &Path,
         networkx_graph: NetworkxDiGraph<CallGraphFuncNameNode>,
         type_suffix: &str,
+        graph_format: &GraphFormat,
     ) {
         let mut full_output_path = get_save_file_path(
             binary_name,
@@ -40,6 +183,7 @@ impl AGCJFunctionCallGraph {
             Some(".json".to_string()),
             Some(type_suffix.to_string()),
             None,
+            None,
         );
         check_or_create_dir(&full_output_path);
 
@@ -50,7 +194,12 @@ impl AGCJFunctionCallGraph {
             function_name = self.name[..75].to_string();
         }
 
-        let filename = format!("{}-{}.json", function_name, type_suffix);
+        let filename = format!(
+            "{}-{}.{}",
+            function_name,
+            type_suffix,
+            graph_format.extension()
+        );
 
         // Normalise string for windows
         let filename = filename.replace(&['(', ')', ',', '\"', ';', ':', '\''][..], "");
@@ -58,11 +207,9 @@ impl AGCJFunctionCallGraph {
 
         debug!("Filename to save graphs to: {:?}", full_output_path);
 
-        serde_json::to_writer(
-            &File::create(full_output_path).expect("Failed to create writer"),
-            &networkx_graph,
-        )
-        .expect("Unable to write JSON");
+        networkx_graph
+            .save(full_output_path, graph_format)
+            .expect("Unable to write graph");
     }
 
     fn graph_to_json_func_metadata_tiknib(
@@ -71,6 +218,7 @@ impl AGCJFunctionCallGraph {
         output_path: &Path,
         networkx_graph: NetworkxDiGraph<CallGraphTikNibFeatures>,
         type_suffix: &str,
+        graph_format: &GraphFormat,
     ) {
         let full_output_path = get_save_file_path(
             binary_name,
@@ -78,6 +226,7 @@ impl AGCJFunctionCallGraph {
             Some(".json".to_string()),
             Some(type_suffix.to_string()),
             None,
+            None,
         );
         check_or_create_dir(&full_output_path);
 
@@ -89,19 +238,18 @@ impl AGCJFunctionCallGraph {
         }
 
         let filename = format!(
-            "{}/{}-{}.json",
+            "{}/{}-{}.{}",
             full_output_path.to_string_lossy(),
             function_name,
-            type_suffix
+            type_suffix,
+            graph_format.extension()
         );
 
         let filename = PathBuf::from(filename);
 
-        serde_json::to_writer(
-            &File::create(filename).expect("Failed to create writer"),
-            &networkx_graph,
-        )
-        .expect("Unable to write JSON");
+        networkx_graph
+            .save(filename, graph_format)
+            .expect("Unable to write graph");
     }
 
     fn graph_to_json_func_tiknib_finfo(
@@ -110,6 +258,7 @@ impl AGCJFunctionCallGraph {
         output_path: &Path,
         networkx_graph: NetworkxDiGraph<CallGraphTikNibFinfoFeatures>,
         type_suffix: &str,
+        graph_format: &GraphFormat,
     ) {
         let full_output_path = get_save_file_path(
             binary_name,
@@ -117,6 +266,7 @@ impl AGCJFunctionCallGraph {
             None,
             Some(type_suffix.to_string()),
             None,
+            None,
         );
         check_or_create_dir(&full_output_path);
 
@@ -128,18 +278,17 @@ impl AGCJFunctionCallGraph {
         }
 
         let filename = format!(
-            "{}/{}-{}.json",
+            "{}/{}-{}.{}",
             full_output_path.to_string_lossy(),
             function_name,
-            type_suffix
+            type_suffix,
+            graph_format.extension()
         );
         let filename = PathBuf::from(filename);
 
-        serde_json::to_writer(
-            &File::create(filename).expect("Failed to create writer"),
-            &networkx_graph,
-        )
-        .expect("Unable to write JSON");
+        networkx_graph
+            .save(filename, graph_format)
+            .expect("Unable to write graph");
     }
 
     fn graph_to_json_func_metadata_finfo(
@@ -148,6 +297,7 @@ impl AGCJFunctionCallGraph {
         output_path: &Path,
         networkx_graph: NetworkxDiGraph<CallGraphFuncWithMetadata>,
         type_suffix: &str,
+        graph_format: &GraphFormat,
     ) {
         let mut full_output_path = get_save_file_path(
             binary_name,
@@ -155,6 +305,7 @@ impl AGCJFunctionCallGraph {
             Some(".json".to_string()),
             Some(type_suffix.to_string()),
             None,
+            None,
         );
         check_or_create_dir(&full_output_path);
         debug!("Built Path: {:?}", full_output_path);
@@ -165,33 +316,49 @@ impl AGCJFunctionCallGraph {
             function_name = self.name[..75].to_string();
         }
 
-        let filename = format!("{}-{}.json", function_name, type_suffix);
+        let filename = format!(
+            "{}-{}.{}",
+            function_name,
+            type_suffix,
+            graph_format.extension()
+        );
         // Normalise string for windows
         let filename = filename.replace(&['(', ')', ',', '\"', ';', ':', '\''][..], "");
         full_output_path.push(filename);
 
         debug!("Attempting to save to {:?}", full_output_path);
-        serde_json::to_writer(
-            &File::create(full_output_path).expect("Failed to create writer"),
-            &networkx_graph,
-        )
-        .expect("Unable to write JSON");
+        networkx_graph
+            .save(full_output_path, graph_format)
+            .expect("Unable to write graph");
     }
 
-    fn build_local_call_graph(&self, include_unk: &bool) -> Graph<String, u32> {
+    fn build_local_call_graph(
+        &self,
+        include_unk: &bool,
+        self_loops: &SelfLoops,
+        weighted_edges: &bool,
+    ) -> Graph<String, u32> {
         let mut graph = Graph::<String, u32>::new();
         let calling_func = graph.add_node(self.name.clone());
-        if self.imports.is_some() {
-            for ele in self.imports.as_ref().unwrap().iter() {
-                if !include_unk {
-                    if !ele.starts_with("unk.") {
-                        let callee = graph.add_node(ele.clone());
-                        graph.update_edge(calling_func, callee, 0);
-                    }
-                } else {
-                    let callee = graph.add_node(ele.clone());
-                    graph.update_edge(calling_func, callee, 0);
+        // Repeated callees share a node when weighting edges so the call sites can be counted
+        let mut callees: HashMap<String, NodeIndex> = HashMap::new();
+        if let Some(imports) = &self.imports {
+            for ele in imports.iter() {
+                if *self_loops == SelfLoops::Remove && *ele == self.name {
+                    debug!("Skipping self-loop {}", ele);
+                    continue;
+                }
+                if !include_unk && ele.starts_with("unk.") {
+                    continue;
                 }
+                let callee = if *weighted_edges {
+                    *callees
+                        .entry(ele.clone())
+                        .or_insert_with(|| graph.add_node(ele.clone()))
+                } else {
+                    graph.add_node(ele.clone())
+                };
+                add_call_edge(&mut graph, calling_func, callee, *weighted_edges);
             }
             graph
         } else {
@@ -207,9 +374,10 @@ impl AGCJFunctionCallGraph {
     ) {
         trace!("Starting getting callees of callees for: {:?}", self.name);
         trace!("Graph: {:?}", graph);
-        if self.imports.is_some() {
-            trace!("Imports: {:?}", self.imports);
-            for import in self.imports.as_ref().unwrap().iter() {
+        if let Some(imports) = &self.imports {
+            trace!("Imports: {:?}", imports);
+            // Each callee is only expanded once so call sites are not counted twice
+            for import in imports.iter().unique() {
                 trace! {"Starting to Process {:?}", import};
                 let import_object: &Vec<&AGCJFunctionCallGraph> = &global_cg
                     .function_call_graphs
@@ -224,10 +392,20 @@ impl AGCJFunctionCallGraph {
                         for importee in entry.imports.as_ref().unwrap().iter() {
                             if !include_unk {
                                 if !importee.starts_with("unk.") {
-                                    self.process_callee(graph, import, importee)
+                                    self.process_callee(
+                                        graph,
+                                        import,
+                                        importee,
+                                        global_cg.weighted_edges,
+                                    )
                                 }
                             } else {
-                                self.process_callee(graph, import, importee)
+                                self.process_callee(
+                                    graph,
+                                    import,
+                                    importee,
+                                    global_cg.weighted_edges,
+                                )
                             }
                         }
                     }
@@ -236,7 +414,13 @@ impl AGCJFunctionCallGraph {
         }
     }
 
-    fn process_callee(&self, graph: &mut Graph<String, u32>, import: &String, importee: &String) {
+    fn process_callee(
+        &self,
+        graph: &mut Graph<String, u32>,
+        import: &String,
+        importee: &String,
+        weighted_edges: bool,
+    ) {
         let import_node_index = graph.node_indices().find(|i| &graph[*i] == import).unwrap();
         let importee_node_index = graph.node_indices().find(|i| &graph[*i] == importee);
 
@@ -246,7 +430,12 @@ impl AGCJFunctionCallGraph {
                 import,
                 importee
             );
-            graph.update_edge(import_node_index, importee_node_index_value, 0);
+            add_call_edge(
+                graph,
+                import_node_index,
+                importee_node_index_value,
+                weighted_edges,
+            );
         } else {
             let importee_node_index = graph.add_node(importee.clone());
             trace!(
@@ -254,7 +443,12 @@ impl AGCJFunctionCallGraph {
                 import,
                 importee
             );
-            graph.update_edge(import_node_index, importee_node_index, 0);
+            add_call_edge(
+                graph,
+                import_node_index,
+                importee_node_index,
+                weighted_edges,
+            );
         }
     }
     fn get_target_func_callers(
@@ -273,14 +467,22 @@ impl AGCJFunctionCallGraph {
 
         for cg in callers.iter() {
             let caller = graph.add_node(cg.name.clone());
-            if !include_unk {
-                if !cg.name.starts_with("unk.") {
-                    let func_target_index = graph.node_indices().find(|i| graph[*i] == self.name);
-                    graph.update_edge(caller, func_target_index.unwrap(), 0);
-                }
-            } else {
-                let func_target_index = graph.node_indices().find(|i| graph[*i] == self.name);
-                graph.update_edge(caller, func_target_index.unwrap(), 0);
+            if !include_unk && cg.name.starts_with("unk.") {
+                continue;
+            }
+            let func_target_index = graph
+                .node_indices()
+                .find(|i| graph[*i] == self.name)
+                .unwrap();
+            // One edge update per call site so weighted edges count each call
+            for _ in cg
+                .imports
+                .as_ref()
+                .unwrap()
+                .iter()
+                .filter(|import| **import == self.name)
+            {
+                add_call_edge(graph, caller, func_target_index, global_cg.weighted_edges);
             }
         }
     }
@@ -295,7 +497,11 @@ impl AGCJFunctionCallGraph {
         include_unk: &bool,
         node_feature_type: Option<String>,
     ) {
-        let graph = self.build_local_call_graph(include_unk);
+        let graph = self.build_local_call_graph(
+            include_unk,
+            &global_cg.self_loops,
+            &global_cg.weighted_edges,
+        );
         debug!("{:?}", graph);
         self.convert_graph_to_networkx(
             graph,
@@ -319,7 +525,11 @@ impl AGCJFunctionCallGraph {
         include_unk: &bool,
         node_feature_type: Option<String>,
     ) {
-        let mut graph = self.build_local_call_graph(include_unk);
+        let mut graph = self.build_local_call_graph(
+            include_unk,
+            &global_cg.self_loops,
+            &global_cg.weighted_edges,
+        );
         self.get_callees_of_callees(global_cg, &mut graph, include_unk);
         debug!("{:?}", graph);
         self.convert_graph_to_networkx(
@@ -342,7 +552,11 @@ impl AGCJFunctionCallGraph {
         include_unk: &bool,
         node_feature_type: Option<String>,
     ) {
-        let mut graph = self.build_local_call_graph(include_unk);
+        let mut graph = self.build_local_call_graph(
+            include_unk,
+            &global_cg.self_loops,
+            &global_cg.weighted_edges,
+        );
         self.get_target_func_callers(global_cg, &mut graph, include_unk);
         debug!("{:?}", graph);
         self.convert_graph_to_networkx(
@@ -365,7 +579,11 @@ impl AGCJFunctionCallGraph {
         include_unk: &bool,
         node_feature_type: Option<String>,
     ) {
-        let mut graph = self.build_local_call_graph(include_unk);
+        let mut graph = self.build_local_call_graph(
+            include_unk,
+            &global_cg.self_loops,
+            &global_cg.weighted_edges,
+        );
 
         self.get_target_func_callers(global_cg, &mut graph, include_unk);
         self.get_callees_of_callees(global_cg, &mut graph, include_unk);
@@ -387,7 +605,7 @@ impl AGCJFunctionCallGraph {
     #[allow(clippy::too_many_arguments)]
     fn convert_graph_to_networkx(
         &self,
-        graph: Graph<String, u32>,
+        mut graph: Graph<String, u32>,
         global_cg: &AGCJFile,
         binary_name: &Path,
         output_path: &Path,
@@ -399,6 +617,7 @@ impl AGCJFunctionCallGraph {
         // can make it through and dont't play very well with the loading in PyG.
         // Need to devise a plan to format these correctly so they can still be loaded!
         // One option may be to include a self loop - Or probably better, just bounce em'
+        global_cg.self_loops.apply(&mut graph);
         if *with_metadata & node_feature_type.is_some() {
             if node_feature_type.as_ref().unwrap() == "finfo" {
                 let type_suffix = type_suffix.to_owned() + "-meta";
@@ -416,6 +635,7 @@ impl AGCJFunctionCallGraph {
                     output_path,
                     networkx_graph,
                     type_suffix.as_str(),
+                    &global_cg.graph_format,
                 )
             } else if node_feature_type.as_ref().unwrap() == "tiknib" {
                 let type_suffix = type_suffix.to_owned() + "-tiknib";
@@ -434,6 +654,7 @@ impl AGCJFunctionCallGraph {
                     output_path,
                     networkx_graph,
                     type_suffix.as_str(),
+                    &global_cg.graph_format,
                 )
             } else if node_feature_type.as_ref().unwrap() == "finfo-tiknib" {
                 let type_suffix = type_suffix.to_owned() + "-finfo-tiknib";
@@ -452,18 +673,29 @@ impl AGCJFunctionCallGraph {
                     output_path,
                     networkx_graph,
                     type_suffix.as_str(),
+                    &global_cg.graph_format,
                 )
             }
         } else {
             let networkx_graph = NetworkxDiGraph::from(graph);
-            self.graph_to_json_func_node(binary_name, output_path, networkx_graph, type_suffix)
+            self.graph_to_json_func_node(
+                binary_name,
+                output_path,
+                networkx_graph,
+                type_suffix,
+                &global_cg.graph_format,
+            )
         };
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::agcj::{AGCJFunctionCallGraph, CallGraphDiff, SelfLoops};
     use crate::files::AGCJFile;
+    use crate::networkx::GraphFormat;
+    use petgraph::prelude::Graph;
+    use std::collections::BTreeSet;
     use std::path::PathBuf;
 
     fn return_test_file_oject() -> AGCJFile {
@@ -473,6 +705,9 @@ mod tests {
             output_path: PathBuf::new(),
             function_metadata: None,
             include_unk: false,
+            weighted_edges: false,
+            self_loops: SelfLoops::Keep,
+            graph_format: GraphFormat::Json,
         };
 
         call_graph_file
@@ -487,10 +722,12 @@ mod tests {
         // Get main function - No Unks
         let raw_call_graph_data = &call_graph_file.function_call_graphs.clone().unwrap()[0];
         assert_eq!(raw_call_graph_data.name, "main".to_string());
-        let local_call_graph = raw_call_graph_data.build_local_call_graph(&true);
+        let local_call_graph =
+            raw_call_graph_data.build_local_call_graph(&true, &SelfLoops::Keep, &false);
         assert_eq!(local_call_graph.node_count(), 20);
         assert_eq!(local_call_graph.edge_count(), 19);
-        let local_call_graph = raw_call_graph_data.build_local_call_graph(&false);
+        let local_call_graph =
+            raw_call_graph_data.build_local_call_graph(&false, &SelfLoops::Keep, &false);
         assert_eq!(local_call_graph.node_count(), 20);
         assert_eq!(local_call_graph.edge_count(), 19);
     }
@@ -502,13 +739,15 @@ mod tests {
         // Unk False
         let raw_call_graph_data = &call_graph_file.function_call_graphs.clone().unwrap()[0];
         assert_eq!(raw_call_graph_data.name, "main".to_string());
-        let mut local_call_graph = raw_call_graph_data.build_local_call_graph(&true);
+        let mut local_call_graph =
+            raw_call_graph_data.build_local_call_graph(&true, &SelfLoops::Keep, &false);
         raw_call_graph_data.get_callees_of_callees(&call_graph_file, &mut local_call_graph, &true);
         assert_eq!(local_call_graph.node_count(), 37);
         assert_eq!(local_call_graph.edge_count(), 39);
 
         // Unk True
-        let mut local_call_graph = raw_call_graph_data.build_local_call_graph(&false);
+        let mut local_call_graph =
+            raw_call_graph_data.build_local_call_graph(&false, &SelfLoops::Keep, &false);
         raw_call_graph_data.get_callees_of_callees(&call_graph_file, &mut local_call_graph, &false);
         assert_eq!(local_call_graph.node_count(), 37);
         assert_eq!(local_call_graph.edge_count(), 39);
@@ -522,10 +761,12 @@ mod tests {
         let raw_call_graph_data = &call_graph_file.function_call_graphs.unwrap()[2];
         assert_eq!(raw_call_graph_data.name, "sym.func.100004d11".to_string());
 
-        let local_call_graph = raw_call_graph_data.build_local_call_graph(&true);
+        let local_call_graph =
+            raw_call_graph_data.build_local_call_graph(&true, &SelfLoops::Keep, &false);
         assert_eq!(local_call_graph.node_count(), 26);
         assert_eq!(local_call_graph.edge_count(), 25);
-        let local_call_graph = raw_call_graph_data.build_local_call_graph(&false);
+        let local_call_graph =
+            raw_call_graph_data.build_local_call_graph(&false, &SelfLoops::Keep, &false);
         assert_eq!(local_call_graph.node_count(), 25);
         assert_eq!(local_call_graph.edge_count(), 24);
     }
@@ -538,12 +779,14 @@ mod tests {
         let raw_call_graph_data = &call_graph_file.function_call_graphs.clone().unwrap()[2];
         assert_eq!(raw_call_graph_data.name, "sym.func.100004d11".to_string());
 
-        let mut local_call_graph = raw_call_graph_data.build_local_call_graph(&true);
+        let mut local_call_graph =
+            raw_call_graph_data.build_local_call_graph(&true, &SelfLoops::Keep, &false);
         raw_call_graph_data.get_callees_of_callees(&call_graph_file, &mut local_call_graph, &true);
         assert_eq!(local_call_graph.node_count(), 31);
         assert_eq!(local_call_graph.edge_count(), 33);
 
-        let mut local_call_graph = raw_call_graph_data.build_local_call_graph(&false);
+        let mut local_call_graph =
+            raw_call_graph_data.build_local_call_graph(&false, &SelfLoops::Keep, &false);
         raw_call_graph_data.get_callees_of_callees(&call_graph_file, &mut local_call_graph, &false);
         assert_eq!(local_call_graph.node_count(), 30);
         assert_eq!(local_call_graph.edge_count(), 32);
@@ -557,13 +800,15 @@ mod tests {
         let raw_call_graph_data = &call_graph_file.function_call_graphs.clone().unwrap()[2];
         assert_eq!(raw_call_graph_data.name, "sym.func.100004d11".to_string());
 
-        let mut local_call_graph = raw_call_graph_data.build_local_call_graph(&true);
+        let mut local_call_graph =
+            raw_call_graph_data.build_local_call_graph(&true, &SelfLoops::Keep, &false);
         raw_call_graph_data.get_callees_of_callees(&call_graph_file, &mut local_call_graph, &true);
         raw_call_graph_data.get_target_func_callers(&call_graph_file, &mut local_call_graph, &true);
         assert_eq!(local_call_graph.node_count(), 32);
         assert_eq!(local_call_graph.edge_count(), 34);
 
-        let mut local_call_graph = raw_call_graph_data.build_local_call_graph(&false);
+        let mut local_call_graph =
+            raw_call_graph_data.build_local_call_graph(&false, &SelfLoops::Keep, &false);
         raw_call_graph_data.get_callees_of_callees(&call_graph_file, &mut local_call_graph, &false);
         raw_call_graph_data.get_target_func_callers(
             &call_graph_file,
@@ -573,4 +818,139 @@ mod tests {
         assert_eq!(local_call_graph.node_count(), 31);
         assert_eq!(local_call_graph.edge_count(), 33);
     }
+
+    #[test]
+    fn test_local_call_graph_self_loops() {
+        let recursive_func = AGCJFunctionCallGraph {
+            name: "sym.fib".to_string(),
+            size: 0,
+            imports: Some(vec!["sym.fib".to_string(), "sym.imp.printf".to_string()]),
+        };
+        let has_self_loop = |graph: &petgraph::Graph<String, u32>| {
+            graph
+                .edge_indices()
+                .any(|e| matches!(graph.edge_endpoints(e), Some((s, t)) if s == t))
+        };
+
+        // By default a recursive call is a separate callee node, as it always has been
+        let mut local_call_graph =
+            recursive_func.build_local_call_graph(&false, &SelfLoops::Keep, &false);
+        assert_eq!(local_call_graph.node_count(), 3);
+        assert_eq!(local_call_graph.edge_count(), 2);
+        assert!(!has_self_loop(&local_call_graph));
+        SelfLoops::Keep.apply(&mut local_call_graph);
+        assert_eq!(local_call_graph.edge_count(), 2);
+
+        let mut local_call_graph =
+            recursive_func.build_local_call_graph(&false, &SelfLoops::Remove, &false);
+        SelfLoops::Remove.apply(&mut local_call_graph);
+        assert_eq!(local_call_graph.node_count(), 2);
+        assert_eq!(local_call_graph.edge_count(), 1);
+        assert!(!has_self_loop(&local_call_graph));
+
+        // Forcing self-loops leaves the weight of an existing self-loop alone
+        let existing_loop = local_call_graph.add_edge(0.into(), 0.into(), 3);
+        SelfLoops::Force.apply(&mut local_call_graph);
+        assert_eq!(local_call_graph.edge_count(), 3);
+        assert_eq!(local_call_graph[existing_loop], 3);
+        assert!(local_call_graph
+            .node_indices()
+            .all(|node| local_call_graph.find_edge(node, node).is_some()));
+    }
+
+    fn call_graph_file(functions: &[(&str, &[&str])]) -> AGCJFile {
+        AGCJFile {
+            filename: PathBuf::from("test_cg.json"),
+            function_call_graphs: Some(
+                functions
+                    .iter()
+                    .map(|(name, imports)| AGCJFunctionCallGraph {
+                        name: name.to_string(),
+                        size: 0,
+                        imports: Some(imports.iter().map(|x| x.to_string()).collect()),
+                    })
+                    .collect(),
+            ),
+            output_path: PathBuf::new(),
+            function_metadata: None,
+            include_unk: true,
+            weighted_edges: false,
+            self_loops: SelfLoops::Keep,
+            graph_format: GraphFormat::Json,
+        }
+    }
+
+    #[test]
+    fn test_call_graph_diff() {
+        let old = call_graph_file(&[
+            ("main", &["sym.parse", "sym.imp.strlen", "fcn.00001000"]),
+            ("sym.parse", &["sym.imp.strlen"]),
+        ])
+        .build_global_call_graph();
+        let new = call_graph_file(&[
+            ("main", &["sym.parse", "sym.validate", "fcn.00002000"]),
+            ("sym.parse", &["sym.imp.strlen"]),
+        ])
+        .build_global_call_graph();
+
+        let edge = |source: &str, target: &str| (source.to_string(), target.to_string());
+        let diff = CallGraphDiff::new(&old, &new, true);
+        assert_eq!(
+            diff.added_nodes,
+            BTreeSet::from(["sym.validate".to_string()])
+        );
+        assert!(diff.removed_nodes.is_empty());
+        assert_eq!(
+            diff.added_edges,
+            BTreeSet::from([edge("main", "sym.validate")])
+        );
+        assert_eq!(
+            diff.removed_edges,
+            BTreeSet::from([edge("main", "sym.imp.strlen")])
+        );
+
+        // Auto-named functions are only matched by name when they are not ignored
+        let diff = CallGraphDiff::new(&old, &new, false);
+        assert!(diff.added_nodes.contains("fcn.00002000"));
+        assert!(diff.removed_nodes.contains("fcn.00001000"));
+        assert_eq!(diff.added_edges.len(), 2);
+        assert!(CallGraphDiff::new(&old, &old, false).is_empty());
+    }
+
+    #[test]
+    fn test_local_and_one_hop_call_graph_weighted_edges() {
+        let mut call_graph_file = call_graph_file(&[
+            (
+                "main",
+                &["sym.parse", "sym.parse", "sym.imp.exit", "sym.parse"],
+            ),
+            ("sym.parse", &["sym.imp.strlen", "sym.imp.strlen"]),
+        ]);
+        call_graph_file.weighted_edges = true;
+        let main = &call_graph_file.function_call_graphs.as_ref().unwrap()[0];
+        let edge_weight = |graph: &Graph<String, u32>, source: &str, target: &str| {
+            let find = |name: &str| graph.node_indices().find(|i| graph[*i] == name).unwrap();
+            graph[graph.find_edge(find(source), find(target)).unwrap()]
+        };
+
+        let mut graph = main.build_local_call_graph(&true, &SelfLoops::Keep, &true);
+        assert_eq!(graph.node_count(), 3);
+        assert_eq!(edge_weight(&graph, "main", "sym.parse"), 3);
+        assert_eq!(edge_weight(&graph, "main", "sym.imp.exit"), 1);
+
+        main.get_callees_of_callees(&call_graph_file, &mut graph, &true);
+        assert_eq!(graph.node_count(), 4);
+        assert_eq!(edge_weight(&graph, "main", "sym.parse"), 3);
+        assert_eq!(edge_weight(&graph, "sym.parse", "sym.imp.strlen"), 2);
+
+        let parse = &call_graph_file.function_call_graphs.as_ref().unwrap()[1];
+        let mut graph = parse.build_local_call_graph(&true, &SelfLoops::Keep, &true);
+        parse.get_target_func_callers(&call_graph_file, &mut graph, &true);
+        assert_eq!(edge_weight(&graph, "main", "sym.parse"), 3);
+
+        // Unweighted call graphs are unchanged
+        let graph = main.build_local_call_graph(&true, &SelfLoops::Keep, &false);
+        assert_eq!(graph.node_count(), 5);
+        assert!(graph.edge_weights().all(|weight| *weight == 0));
+    }
 }
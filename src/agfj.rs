@@ -1,10 +1,13 @@
-use crate::bb::{ACFJBlock, FeatureType, TikNibFeaturesBB};
+use crate::bb::{ACFJBlock, BlockFeatureCache, FeatureType, TikNibFeaturesBB};
+#[cfg(feature = "inference")]
+use crate::errors::ExitCode;
 #[cfg(feature = "inference")]
 use crate::inference::InferenceJob;
 use crate::networkx::{
-    DGISNode, DisasmNode, DiscovreNode, EsilNode, GeminiNode, NetworkxDiGraph, NodeType,
-    PseudoNode, TiknibNode,
+    BytesNode, DGISNode, DisasmNode, DiscovreNode, EsilNode, GeminiNode, GraphFormat,
+    NetworkxDiGraph, NodeType, OpcodeHistogramNode, PseudoNode, TiknibNode,
 };
+use crate::normalisation::NormalizationConfig;
 use crate::utils::{average, check_or_create_dir, get_save_file_path};
 use enum_as_inner::EnumAsInner;
 use itertools::Itertools;
@@ -14,12 +17,12 @@ use petgraph::visit::Dfs;
 use serde::{Deserialize, Serialize};
 use serde_json;
 #[cfg(feature = "inference")]
-use serde_json::{Map, Value};
+use serde_json::Map;
+use serde_json::{json, Value};
+#[cfg(feature = "inference")]
 use std::fs::File;
 use std::path::Path;
 #[cfg(feature = "inference")]
-use std::process::exit;
-#[cfg(feature = "inference")]
 use std::sync::Arc;
 
 #[derive(Deserialize, Debug, Eq, Ord, PartialEq, PartialOrd)]
@@ -35,6 +38,16 @@ struct EdgePair {
     wt: u16,
 }
 
+/// A function whose recovered CFG does not have the same number of nodes as it
+/// has basic blocks. These are skipped during CFG generation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CfgMismatch {
+    pub filename: String,
+    pub function: String,
+    pub node_count: usize,
+    pub block_count: usize,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AGFJFunc {
     pub name: String,
@@ -45,8 +58,11 @@ pub struct AGFJFunc {
     size: Option<u64>,
     stack: u64,
     r#type: String,
+    // The bits of the binary (from ij) added at extraction time. Not part of r2's agfj output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bits: Option<u64>,
     pub blocks: Vec<ACFJBlock>,
-    addr_idx: Option<Vec<i64>>,
+    addr_idx: Option<Vec<u64>>,
     pub edge_list: Option<Vec<(u32, u32, u32)>>,
     graph: Option<Graph<String, u32>>,
 }
@@ -58,20 +74,41 @@ pub enum StringOrF64 {
 }
 
 impl AGFJFunc {
-    pub fn create_graph_struct_members(&mut self, min_blocks: &u16) {
-        self.create_bb_edge_list(min_blocks);
+    /// Checks the number of basic blocks within the function falls within the
+    /// provided bounds. Functions without any blocks are always rejected.
+    pub fn has_valid_block_count(&self, min_blocks: &u16, max_blocks: &Option<u16>) -> bool {
+        let n_blocks = self.blocks.len();
+        let below_max = match max_blocks {
+            Some(max_blocks) => n_blocks <= (*max_blocks).into(),
+            None => true,
+        };
+        !self.blocks.is_empty() && n_blocks >= (*min_blocks).into() && below_max
+    }
+
+    pub fn create_graph_struct_members(&mut self, min_blocks: &u16, max_blocks: &Option<u16>) {
+        self.create_bb_edge_list(min_blocks, max_blocks);
         self.create_petgraph_from_edgelist();
     }
 
+    /// Generates a single string of every ESIL instruction within the function.
+    ///
+    /// If `block_marker` is provided, it is inserted between each basic block.
     pub fn get_esil_function_string(
         &self,
         min_blocks: &u16,
-        reg_norm: bool,
+        max_blocks: &Option<u16>,
+        norm_config: &NormalizationConfig,
+        block_marker: Option<&str>,
     ) -> Option<(String, String)> {
         let mut esil_function = Vec::<String>::new();
-        if self.blocks.len() >= (*min_blocks).into() && self.blocks[0].offset != 1 {
-            for bb in &self.blocks {
-                let esil: Vec<String> = bb.get_esil_bb(reg_norm);
+        if self.has_valid_block_count(min_blocks, max_blocks) && self.blocks[0].offset != 1 {
+            for (i, bb) in self.blocks.iter().enumerate() {
+                if let Some(marker) = block_marker {
+                    if i > 0 {
+                        esil_function.push(marker.to_string());
+                    }
+                }
+                let esil: Vec<String> = bb.get_esil_bb(norm_config);
                 for ins in esil.iter() {
                     if !ins.is_empty() {
                         let split: Vec<String> = ins.split(',').map(|s| s.to_string()).collect();
@@ -90,12 +127,19 @@ impl AGFJFunc {
     pub fn get_disasm_function_string(
         &self,
         min_blocks: &u16,
-        reg_norm: bool,
+        max_blocks: &Option<u16>,
+        norm_config: &NormalizationConfig,
+        block_marker: Option<&str>,
     ) -> Option<(String, String)> {
         let mut disasm_function = Vec::<String>::new();
-        if self.blocks.len() >= (*min_blocks).into() && self.blocks[0].offset != 1 {
-            for bb in &self.blocks {
-                let disasm: Vec<String> = bb.get_disasm_bb(reg_norm);
+        if self.has_valid_block_count(min_blocks, max_blocks) && self.blocks[0].offset != 1 {
+            for (i, bb) in self.blocks.iter().enumerate() {
+                if let Some(marker) = block_marker {
+                    if i > 0 {
+                        disasm_function.push(marker.to_string());
+                    }
+                }
+                let disasm: Vec<String> = bb.get_disasm_bb(norm_config);
                 for ins in disasm.iter() {
                     if !ins.is_empty() {
                         let split: Vec<String> = ins.split(',').map(|s| s.to_string()).collect();
@@ -114,12 +158,19 @@ impl AGFJFunc {
     pub fn get_psuedo_function_string(
         &self,
         min_blocks: &u16,
-        reg_norm: bool,
+        max_blocks: &Option<u16>,
+        norm_config: &NormalizationConfig,
+        block_marker: Option<&str>,
     ) -> Option<(String, String)> {
         let mut psuedo_function = Vec::<String>::new();
-        if self.blocks.len() >= (*min_blocks).into() && self.blocks[0].offset != 1 {
-            for bb in &self.blocks {
-                let psuedo: Vec<String> = bb.get_psuedo_bb(reg_norm);
+        if self.has_valid_block_count(min_blocks, max_blocks) && self.blocks[0].offset != 1 {
+            for (i, bb) in self.blocks.iter().enumerate() {
+                if let Some(marker) = block_marker {
+                    if i > 0 {
+                        psuedo_function.push(marker.to_string());
+                    }
+                }
+                let psuedo: Vec<String> = bb.get_psuedo_bb(norm_config);
                 for ins in psuedo.iter() {
                     if !ins.is_empty() {
                         let split: Vec<String> = ins.split(',').map(|s| s.to_string()).collect();
@@ -134,13 +185,60 @@ impl AGFJFunc {
             None
         }
     }
-    pub fn create_bb_edge_list(&mut self, min_blocks: &u16) {
-        if self.blocks.len() > (*min_blocks).into() && self.blocks[0].offset != 1 {
-            let bb_start_addrs: Vec<i64> = self.blocks.iter().map(|x| x.offset).collect::<Vec<_>>();
+    /// Checks the function is structurally sound before generating a graph
+    ///
+    /// Returns a list of the issues found. An empty list means the function is valid.
+    pub fn validate_structure(&self, min_blocks: &u16) -> Vec<String> {
+        let mut violations = Vec::new();
+
+        if self.blocks.is_empty() {
+            if *min_blocks > 0 {
+                violations.push("function has no basic blocks".to_string());
+            }
+            return violations;
+        }
+
+        let bb_start_addrs: Vec<u64> = self.blocks.iter().map(|x| x.offset).collect::<Vec<_>>();
+        let unique_addrs = bb_start_addrs.iter().unique().count();
+        if unique_addrs != bb_start_addrs.len() {
+            violations.push(format!(
+                "{} duplicate basic block offsets",
+                bb_start_addrs.len() - unique_addrs
+            ));
+        }
+
+        let edge_list = match &self.edge_list {
+            Some(edge_list) => edge_list.clone(),
+            None => {
+                let mut edge_list = Vec::<(u32, u32, u32)>::new();
+                for bb in &self.blocks {
+                    bb.get_block_edges(&bb_start_addrs, &mut edge_list, false)
+                }
+                edge_list
+            }
+        };
+
+        for (src, dst, _) in edge_list.iter() {
+            if *src as usize >= self.blocks.len() || *dst as usize >= self.blocks.len() {
+                violations.push(format!(
+                    "edge ({}, {}) references a block index outside 0..{}",
+                    src,
+                    dst,
+                    self.blocks.len()
+                ));
+            }
+        }
+
+        violations
+    }
+
+    pub fn create_bb_edge_list(&mut self, min_blocks: &u16, max_blocks: &Option<u16>) {
+        if self.has_valid_block_count(min_blocks, max_blocks) && self.blocks[0].offset != 1 {
+            let bb_start_addrs: Vec<u64> = self.blocks.iter().map(|x| x.offset).collect::<Vec<_>>();
             let mut edge_list = Vec::<(u32, u32, u32)>::new();
 
             for bb in &self.blocks {
-                bb.get_block_edges(&bb_start_addrs, &mut edge_list)
+                bb.get_block_edges(&bb_start_addrs, &mut edge_list, false)
             }
             self.addr_idx = Some(bb_start_addrs);
             self.edge_list = Some(edge_list);
@@ -158,17 +256,18 @@ impl AGFJFunc {
         &mut self,
         esil: bool,
         min_blocks: &u16,
-        reg_norm: bool,
+        max_blocks: &Option<u16>,
+        norm_config: &NormalizationConfig,
     ) -> Option<Vec<String>> {
         let mut function_instructions = Vec::<Vec<String>>::new();
 
-        if self.blocks.len() >= (*min_blocks).into() {
+        if self.has_valid_block_count(min_blocks, max_blocks) {
             for bb in &self.blocks {
                 if esil {
-                    let bb_ins = bb.get_esil_bb(reg_norm);
+                    let bb_ins = bb.get_esil_bb(norm_config);
                     function_instructions.push(bb_ins)
                 } else {
-                    let bb_ins = bb.get_ins(reg_norm);
+                    let bb_ins = bb.get_ins(norm_config);
                     function_instructions.push(bb_ins)
                 }
             }
@@ -178,25 +277,51 @@ impl AGFJFunc {
             None
         }
     }
+
+    /// Generates sliding window opcode n-grams across every instruction within a function.
+    ///
+    /// Only the mnemonic of each instruction is used and the mnemonics within each
+    /// n-gram are space separated. Functions with fewer than `n` instructions yield
+    /// no n-grams.
+    pub fn get_opcode_ngrams(
+        &mut self,
+        n: usize,
+        min_blocks: &u16,
+        max_blocks: &Option<u16>,
+    ) -> Option<Vec<String>> {
+        let instructions = self.get_function_instructions(
+            false,
+            min_blocks,
+            max_blocks,
+            &NormalizationConfig::default(),
+        )?;
+        let mnemonics: Vec<&str> = instructions
+            .iter()
+            .filter_map(|ins| ins.split_whitespace().next())
+            .collect();
+
+        Some(mnemonics.windows(n).map(|ngram| ngram.join(" ")).collect())
+    }
+
     // This function traverses the functions control flow graph and currently
     // calculates the number of instructions per node
     pub fn dfs_cfg(
         &self,
-        max_hops: u8,
+        max_hops: u16,
         esil: bool,
-        reg_norm: bool,
+        norm_config: &NormalizationConfig,
         pairs: bool,
     ) -> Vec<Vec<String>> {
         let graph = self.graph.as_ref().unwrap();
         let mut disasm_walks = Vec::<Vec<String>>::new();
-        let mut hop_counter: u8 = 0;
 
         for start in graph.node_indices() {
+            // Hops are counted per walk so each start node gets the full budget
+            let mut hop_counter: u16 = 0;
             let mut single_disasm_walk = Vec::new();
             let mut dfs = Dfs::new(&graph, start);
             while let Some(visited) = dfs.next(&graph) {
                 if hop_counter >= max_hops {
-                    hop_counter = 0;
                     break;
                 }
                 let block_offset = self.addr_idx.as_ref().unwrap()[visited.index()];
@@ -208,10 +333,10 @@ impl AGFJFunc {
 
                 if !basic_block.is_empty() {
                     if esil {
-                        let bb_esil = basic_block.first().unwrap().get_esil_bb(reg_norm);
+                        let bb_esil = basic_block.first().unwrap().get_esil_bb(norm_config);
                         single_disasm_walk.push(bb_esil)
                     } else {
-                        let bb_ins = basic_block.first().unwrap().get_ins(reg_norm);
+                        let bb_ins = basic_block.first().unwrap().get_ins(norm_config);
                         single_disasm_walk.push(bb_ins)
                     }
                 }
@@ -253,13 +378,15 @@ impl AGFJFunc {
     pub fn disasm_random_walks(
         &mut self,
         min_blocks: &u16,
+        max_blocks: &Option<u16>,
+        max_hops: u16,
         esil: bool,
-        reg_norm: bool,
+        norm_config: &NormalizationConfig,
         pairs: bool,
     ) -> Option<Vec<Vec<String>>> {
-        if self.blocks.len() > (*min_blocks).into() && self.blocks[0].offset != 1 {
-            self.create_graph_struct_members(min_blocks);
-            let disasm_walks = self.dfs_cfg(10, esil, reg_norm, pairs);
+        if self.has_valid_block_count(min_blocks, max_blocks) && self.blocks[0].offset != 1 {
+            self.create_graph_struct_members(min_blocks, max_blocks);
+            let disasm_walks = self.dfs_cfg(max_hops, esil, norm_config, pairs);
             Some(disasm_walks)
         } else {
             None
@@ -271,6 +398,7 @@ impl AGFJFunc {
         &self,
         path: &PathBuf,
         min_blocks: &u16,
+        max_blocks: &Option<u16>,
         output_path: &PathBuf,
         feature_type: FeatureType,
         inference_job: &Option<Arc<InferenceJob>>,
@@ -283,20 +411,26 @@ impl AGFJFunc {
         - General refactor
          */
         info!("Processing {:?}", self.name);
-        let full_output_path =
-            get_save_file_path(path, output_path, Some(".json".to_string()), None, None);
+        let full_output_path = get_save_file_path(
+            path,
+            output_path,
+            Some(".json".to_string()),
+            None,
+            None,
+            None,
+        );
         check_or_create_dir(&full_output_path);
 
         // offset != 1 has been added to skip functions with invalid instructions
-        if self.blocks.len() >= (*min_blocks).into() && self.blocks[0].offset != 1 {
-            let bb_start_addrs: Vec<i64> = self.blocks.iter().map(|x| x.offset).collect::<Vec<_>>();
+        if self.has_valid_block_count(min_blocks, max_blocks) && self.blocks[0].offset != 1 {
+            let bb_start_addrs: Vec<u64> = self.blocks.iter().map(|x| x.offset).collect::<Vec<_>>();
             let mut edge_list = Vec::<(u32, u32, u32)>::new();
 
             let mut feature_vecs = Vec::<_>::new();
             let mut feature_vec_of_vecs = Vec::<_>::new();
 
             for bb in &self.blocks {
-                bb.get_block_edges(&bb_start_addrs, &mut edge_list);
+                bb.get_block_edges(&bb_start_addrs, &mut edge_list, false);
                 if inference_job.is_some() {
                     let inference = inference_job.as_ref().unwrap().clone();
                     match feature_type {
@@ -314,7 +448,7 @@ impl AGFJFunc {
                     }
                 } else {
                     info!("Unable to generated embedded CFG as inference job is none!");
-                    exit(1)
+                    ExitCode::BadArgs.exit()
                 }
             }
 
@@ -357,20 +491,184 @@ impl AGFJFunc {
         }
     }
 
+    /// Get the function level metadata for the function
+    pub fn get_function_metadata(&self) -> Value {
+        json!({
+            "offset": self.offset,
+            "nargs": self.nargs,
+            "nlocals": self.nlocals,
+            "size": self.size,
+        })
+    }
+
+    /// Build an attributed CFG for the function in memory without writing anything to disk
+    ///
+    /// Returns `None` if the function is outside of the block count bounds, has no edges,
+    /// the recovered CFG does not have a node for every basic block or the feature type
+    /// is not supported.
+    pub fn to_networkx(
+        &self,
+        feature_type: FeatureType,
+        architecture: &String,
+        min_blocks: &u16,
+    ) -> Option<NetworkxDiGraph<NodeType>> {
+        self.build_attributed_cfg(min_blocks, &None, feature_type, architecture, false, None)?
+            .ok()
+    }
+
+    /// Builds the attributed CFG for `to_networkx` and `generate_attributed_cfg`
+    ///
+    /// The outer `None` is returned when no graph can be built for the function. An
+    /// `Err` is returned if the recovered CFG does not have a node for every basic block.
+    /// The filename of the [`CfgMismatch`] is left empty for the caller to fill in.
+    fn build_attributed_cfg(
+        &self,
+        min_blocks: &u16,
+        max_blocks: &Option<u16>,
+        feature_type: FeatureType,
+        architecture: &String,
+        edge_types: bool,
+        mut cache: Option<&mut BlockFeatureCache>,
+    ) -> Option<Result<NetworkxDiGraph<NodeType>, CfgMismatch>> {
+        // offset != 1 has been added to skip functions with invalid instructions
+        if !self.has_valid_block_count(min_blocks, max_blocks) || self.blocks[0].offset == 1 {
+            trace!("Function has fewer basic blocks than the minimum. Skipping...");
+            return None;
+        }
+
+        let mut edge_list = Vec::<(u32, u32, u32)>::new();
+
+        let mut feature_vecs: StringOrF64 = match feature_type {
+            FeatureType::Tiknib
+            | FeatureType::Gemini
+            | FeatureType::DiscovRE
+            | FeatureType::DGIS
+            | FeatureType::OpcodeHistogram => StringOrF64::F64(Vec::new()),
+            FeatureType::Esil
+            | FeatureType::Disasm
+            | FeatureType::Pseudo
+            | FeatureType::Bytes
+            | FeatureType::Pcode => StringOrF64::String(Vec::new()),
+            FeatureType::ModelEmbedded | FeatureType::Encoded | FeatureType::Invalid => {
+                info!("Invalid Feature Type. Skipping..");
+                return None;
+            }
+        };
+
+        let bb_start_addrs: Vec<u64> = self.blocks.iter().map(|x| x.offset).collect::<Vec<_>>();
+
+        match feature_type {
+            FeatureType::Tiknib
+            | FeatureType::Gemini
+            | FeatureType::DiscovRE
+            | FeatureType::DGIS
+            | FeatureType::OpcodeHistogram => {
+                let feature_vecs = feature_vecs.as_f64_mut().unwrap();
+                for bb in &self.blocks {
+                    bb.get_block_edges(&bb_start_addrs, &mut edge_list, edge_types);
+                    match cache.as_deref_mut() {
+                        Some(cache) => cache.generate_bb_feature_vec(
+                            bb,
+                            feature_vecs,
+                            feature_type,
+                            architecture,
+                        ),
+                        None => {
+                            bb.generate_bb_feature_vec(feature_vecs, feature_type, architecture)
+                        }
+                    }
+                }
+                debug!("Number of Feature Vecs: {}", feature_vecs.len());
+                assert_eq!(self.blocks.len(), feature_vecs.len())
+            }
+            FeatureType::Esil | FeatureType::Disasm | FeatureType::Pseudo | FeatureType::Bytes => {
+                let feature_vecs = feature_vecs.as_string_mut().unwrap();
+                for bb in &self.blocks {
+                    bb.get_block_edges(&bb_start_addrs, &mut edge_list, edge_types);
+                    bb.generate_bb_feature_strings(feature_vecs, feature_type, true);
+                }
+                debug!("Number of Feature Vecs: {}", feature_vecs.len());
+                assert_eq!(self.blocks.len(), feature_vecs.len())
+            }
+            FeatureType::ModelEmbedded | FeatureType::Encoded | FeatureType::Invalid => {
+                info!("Invalid Feature Type. Skipping..");
+                return None;
+            }
+            _ => {}
+        };
+
+        debug!(
+            "Edge List Empty: {} Edge List Dims: {}",
+            edge_list.is_empty(),
+            edge_list.len()
+        );
+
+        if edge_list.is_empty() {
+            debug!(
+                "Function {} has less than the minimum number of blocks. Skipping..",
+                self.name
+            );
+            return None;
+        }
+
+        let mut graph = Graph::<String, u32>::from_edges(&edge_list);
+        Self::str_to_hex_node_idxs(&mut graph, &bb_start_addrs);
+        if graph.node_count() != self.blocks.len() {
+            debug!("Graph for {} does not have the same number of nodes as basic blocks - N: {} B: {}. This suggests \
+            there is something wrong with the CFG edge recovery. If this is a problem, please raise a GitHub issue!",
+            self.name, graph.node_count(), self.blocks.len());
+            return Some(Err(CfgMismatch {
+                filename: String::new(),
+                function: self.name.clone(),
+                node_count: graph.node_count(),
+                block_count: self.blocks.len(),
+            }));
+        }
+
+        let networkx_graph = match feature_vecs {
+            StringOrF64::F64(feature_vecs) => {
+                NetworkxDiGraph::<NodeType>::try_from((&graph, &feature_vecs, feature_type))
+            }
+            StringOrF64::String(feature_vecs) => {
+                NetworkxDiGraph::<NodeType>::try_from((&graph, &feature_vecs, feature_type))
+            }
+        };
+
+        match networkx_graph {
+            Ok(networkx_graph) => Some(Ok(networkx_graph)),
+            Err(e) => {
+                error!("Skipping graph for {}: {}", self.name, e);
+                None
+            }
+        }
+    }
+
+    /// Generate and save an attributed CFG for the function
+    ///
+    /// Returns a [`CfgMismatch`] if the recovered CFG does not have a node for every
+    /// basic block. The graph is not saved in this case.
+    #[allow(clippy::too_many_arguments)]
     pub fn generate_attributed_cfg(
         &self,
         path: &Path,
         min_blocks: &u16,
+        max_blocks: &Option<u16>,
         output_path: &Path,
         feature_type: FeatureType,
         architecture: &String,
-    ) {
+        graph_format: &GraphFormat,
+        embed_func_meta: bool,
+        edge_types: bool,
+        relative_addrs: bool,
+        cache: Option<&mut BlockFeatureCache>,
+    ) -> Option<CfgMismatch> {
         let full_output_path = get_save_file_path(
             path,
             output_path,
             None,
             Some(feature_type.to_string()),
             None,
+            None,
         );
         check_or_create_dir(&full_output_path);
         let file_name = path.file_name().unwrap();
@@ -385,223 +683,125 @@ impl AGFJFunc {
         };
 
         let fname_string = format!(
-            "{}/{}-{}.json",
+            "{}/{}-{}.{}",
             &full_output_path.to_string_lossy(),
             binary_name[0],
-            function_name
+            function_name,
+            graph_format.extension()
         );
 
-        if !Path::new(&fname_string).is_file() {
-            // offset != 1 has been added to skip functions with invalid instructions
-            if self.blocks.len() >= (*min_blocks).into() && self.blocks[0].offset != 1 {
-                let mut edge_list = Vec::<(u32, u32, u32)>::new();
-
-                let mut feature_vecs: StringOrF64 = match feature_type {
-                    FeatureType::Tiknib
-                    | FeatureType::Gemini
-                    | FeatureType::DiscovRE
-                    | FeatureType::DGIS => StringOrF64::F64(Vec::new()),
-                    FeatureType::Esil
-                    | FeatureType::Disasm
-                    | FeatureType::Pseudo
-                    | FeatureType::Pcode => StringOrF64::String(Vec::new()),
-                    FeatureType::ModelEmbedded | FeatureType::Encoded | FeatureType::Invalid => {
-                        info!("Invalid Feature Type. Skipping..");
-                        return;
-                    }
-                };
+        if Path::new(&fname_string).is_file() {
+            debug!(
+                "Function {} has already been processed. Skipping...",
+                self.name
+            );
+            return None;
+        }
 
-                let bb_start_addrs: Vec<i64> =
-                    self.blocks.iter().map(|x| x.offset).collect::<Vec<_>>();
-
-                match feature_type {
-                    FeatureType::Tiknib
-                    | FeatureType::Gemini
-                    | FeatureType::DiscovRE
-                    | FeatureType::DGIS => {
-                        let feature_vecs = feature_vecs.as_f64_mut().unwrap();
-                        for bb in &self.blocks {
-                            bb.get_block_edges(&bb_start_addrs, &mut edge_list);
-                            bb.generate_bb_feature_vec(feature_vecs, feature_type, architecture);
-                        }
-                        debug!("Number of Feature Vecs: {}", feature_vecs.len());
-                        assert_eq!(self.blocks.len(), feature_vecs.len())
-                    }
-                    FeatureType::Esil | FeatureType::Disasm | FeatureType::Pseudo => {
-                        let feature_vecs = feature_vecs.as_string_mut().unwrap();
-                        for bb in &self.blocks {
-                            bb.get_block_edges(&bb_start_addrs, &mut edge_list);
-                            bb.generate_bb_feature_strings(feature_vecs, feature_type, true);
-                        }
-                        debug!("Number of Feature Vecs: {}", feature_vecs.len());
-                        assert_eq!(self.blocks.len(), feature_vecs.len())
-                    }
-                    FeatureType::ModelEmbedded | FeatureType::Encoded | FeatureType::Invalid => {
-                        info!("Invalid Feature Type. Skipping..");
-                        return;
-                    }
-                    _ => {}
-                };
+        let networkx_graph = match self.build_attributed_cfg(
+            min_blocks,
+            max_blocks,
+            feature_type,
+            architecture,
+            edge_types,
+            cache,
+        )? {
+            Ok(networkx_graph) => networkx_graph,
+            Err(mismatch) => {
+                return Some(CfgMismatch {
+                    filename: path.to_string_lossy().to_string(),
+                    ..mismatch
+                })
+            }
+        };
 
-                debug!(
-                    "Edge List Empty: {} Edge List Dims: {}",
-                    edge_list.is_empty(),
-                    edge_list.len()
-                );
+        let networkx_graph = networkx_graph.with_edge_types(edge_types);
+        let networkx_graph = if relative_addrs {
+            networkx_graph.with_node_labels(Some(self.block_addr_labels(true)))
+        } else {
+            networkx_graph
+        };
 
-                if !edge_list.is_empty() {
-                    let mut graph = Graph::<String, u32>::from_edges(&edge_list);
-                    Self::str_to_hex_node_idxs(&mut graph, &bb_start_addrs);
-                    if graph.node_count() != self.blocks.len() {
-                        debug!("Graph for {} does not have the same number of nodes as basic blocks - N: {} B: {}. This suggests \
-                        there is something wrong with the CFG edge recovery. If this is a problem, please raise a GitHub issue!",
-                        self.name, graph.node_count(), self.blocks.len());
-                        return;
-                    }
+        let graph_meta = if embed_func_meta {
+            Some(self.get_function_metadata())
+        } else {
+            None
+        };
 
-                    // Unpack the NodeTypes to the inner Types
-                    if feature_type == FeatureType::Gemini {
-                        let networkx_graph: NetworkxDiGraph<NodeType> =
-                            NetworkxDiGraph::<NodeType>::from((
-                                &graph,
-                                feature_vecs.as_f64().unwrap(),
-                                feature_type,
-                            ));
-
-                        let networkx_graph_inners: NetworkxDiGraph<GeminiNode> =
-                            NetworkxDiGraph::<GeminiNode>::from(networkx_graph);
-
-                        info!("Saving to JSON..");
-                        serde_json::to_writer(
-                            &File::create(fname_string).expect("Failed to create writer"),
-                            &networkx_graph_inners,
-                        )
-                        .expect("Unable to write JSON");
-                    } else if feature_type == FeatureType::DGIS {
-                        let networkx_graph: NetworkxDiGraph<NodeType> =
-                            NetworkxDiGraph::<NodeType>::from((
-                                &graph,
-                                feature_vecs.as_f64().unwrap(),
-                                feature_type,
-                            ));
-
-                        let networkx_graph_inners: NetworkxDiGraph<DGISNode> =
-                            NetworkxDiGraph::<DGISNode>::from(networkx_graph);
-                        info!("Saving to JSON..");
-                        serde_json::to_writer(
-                            &File::create(fname_string).expect("Failed to create writer"),
-                            &networkx_graph_inners,
-                        )
-                        .expect("Unable to write JSON");
-                    } else if feature_type == FeatureType::DiscovRE {
-                        let networkx_graph: NetworkxDiGraph<NodeType> =
-                            NetworkxDiGraph::<NodeType>::from((
-                                &graph,
-                                feature_vecs.as_f64().unwrap(),
-                                feature_type,
-                            ));
-
-                        let networkx_graph_inners: NetworkxDiGraph<DiscovreNode> =
-                            NetworkxDiGraph::<DiscovreNode>::from(networkx_graph);
-                        info!("Saving to JSON..");
-                        serde_json::to_writer(
-                            &File::create(fname_string).expect("Failed to create writer"),
-                            &networkx_graph_inners,
-                        )
-                        .expect("Unable to write JSON");
-                    } else if feature_type == FeatureType::Tiknib {
-                        let networkx_graph: NetworkxDiGraph<NodeType> =
-                            NetworkxDiGraph::<NodeType>::from((
-                                &graph,
-                                feature_vecs.as_f64().unwrap(),
-                                feature_type,
-                            ));
-
-                        let networkx_graph_inners: NetworkxDiGraph<TiknibNode> =
-                            NetworkxDiGraph::<TiknibNode>::from(networkx_graph);
-                        info!("Saving to JSON..");
-                        serde_json::to_writer(
-                            &File::create(fname_string).expect("Failed to create writer"),
-                            &networkx_graph_inners,
-                        )
-                        .expect("Unable to write JSON");
-                    } else if feature_type == FeatureType::Disasm {
-                        let networkx_graph: NetworkxDiGraph<NodeType> =
-                            NetworkxDiGraph::<NodeType>::from((
-                                &graph,
-                                feature_vecs.as_string().unwrap(),
-                                feature_type,
-                            ));
-
-                        let networkx_graph_inners: NetworkxDiGraph<DisasmNode> =
-                            NetworkxDiGraph::<DisasmNode>::from(networkx_graph);
-                        info!("Saving to JSON..");
-                        serde_json::to_writer(
-                            &File::create(fname_string).expect("Failed to create writer"),
-                            &networkx_graph_inners,
-                        )
-                        .expect("Unable to write JSON");
-                    } else if feature_type == FeatureType::Esil {
-                        let networkx_graph: NetworkxDiGraph<NodeType> =
-                            NetworkxDiGraph::<NodeType>::from((
-                                &graph,
-                                feature_vecs.as_string().unwrap(),
-                                feature_type,
-                            ));
-
-                        let networkx_graph_inners: NetworkxDiGraph<EsilNode> =
-                            NetworkxDiGraph::<EsilNode>::from(networkx_graph);
-                        info!("Saving to JSON..");
-                        serde_json::to_writer(
-                            &File::create(fname_string).expect("Failed to create writer"),
-                            &networkx_graph_inners,
-                        )
-                        .expect("Unable to write JSON");
-                    } else if feature_type == FeatureType::Pseudo {
-                        let networkx_graph: NetworkxDiGraph<NodeType> =
-                            NetworkxDiGraph::<NodeType>::from((
-                                &graph,
-                                feature_vecs.as_string().unwrap(),
-                                feature_type,
-                            ));
-
-                        let networkx_graph_inners: NetworkxDiGraph<PseudoNode> =
-                            NetworkxDiGraph::<PseudoNode>::from(networkx_graph);
-                        info!("Saving to JSON..");
-                        serde_json::to_writer(
-                            &File::create(fname_string).expect("Failed to create writer"),
-                            &networkx_graph_inners,
-                        )
-                        .expect("Unable to write JSON");
-                    } else {
-                        info!("Function {} has no edges. Skipping...", self.name)
-                    }
-                } else {
-                    debug!(
-                        "Function {} has less than the minimum number of blocks. Skipping..",
-                        self.name
-                    );
-                }
-            } else {
-                trace!("Function has fewer basic blocks than the minimum. Skipping...");
+        // Unpack the NodeTypes to the inner Types
+        info!("Saving to {}..", graph_format.extension());
+        let saved = match feature_type {
+            FeatureType::Gemini => NetworkxDiGraph::<GeminiNode>::from(networkx_graph)
+                .with_graph_meta(graph_meta)
+                .save(&fname_string, graph_format),
+            FeatureType::DGIS => NetworkxDiGraph::<DGISNode>::from(networkx_graph)
+                .with_graph_meta(graph_meta)
+                .save(&fname_string, graph_format),
+            FeatureType::DiscovRE => NetworkxDiGraph::<DiscovreNode>::from(networkx_graph)
+                .with_graph_meta(graph_meta)
+                .save(&fname_string, graph_format),
+            FeatureType::Tiknib => NetworkxDiGraph::<TiknibNode>::from(networkx_graph)
+                .with_graph_meta(graph_meta)
+                .save(&fname_string, graph_format),
+            FeatureType::OpcodeHistogram => {
+                NetworkxDiGraph::<OpcodeHistogramNode>::from(networkx_graph)
+                    .with_graph_meta(graph_meta)
+                    .save(&fname_string, graph_format)
             }
-        } else {
-            debug!(
-                "Function {} has already been processed. Skipping...",
-                self.name
-            )
-        }
+            FeatureType::Disasm => NetworkxDiGraph::<DisasmNode>::from(networkx_graph)
+                .with_graph_meta(graph_meta)
+                .save(&fname_string, graph_format),
+            FeatureType::Esil => NetworkxDiGraph::<EsilNode>::from(networkx_graph)
+                .with_graph_meta(graph_meta)
+                .save(&fname_string, graph_format),
+            FeatureType::Pseudo => NetworkxDiGraph::<PseudoNode>::from(networkx_graph)
+                .with_graph_meta(graph_meta)
+                .save(&fname_string, graph_format),
+            FeatureType::Bytes => NetworkxDiGraph::<BytesNode>::from(networkx_graph)
+                .with_graph_meta(graph_meta)
+                .save(&fname_string, graph_format),
+            _ => {
+                info!("Function {} has no edges. Skipping...", self.name);
+                return None;
+            }
+        };
+        saved.expect("Unable to write graph");
+        None
     }
 
     // Convert string memory address to hex / string
-    fn str_to_hex_node_idxs(graph: &mut Graph<String, u32>, addr_idxs: &[i64]) {
+    fn str_to_hex_node_idxs(graph: &mut Graph<String, u32>, addr_idxs: &[u64]) {
         for idx in graph.node_indices() {
             let i_idx = idx.index();
-            let hex = addr_idxs[i_idx];
-            graph[idx] = format!("{hex:#x} / {hex}");
+            graph[idx] = Self::block_addr_label(addr_idxs[i_idx], None);
+        }
+    }
+
+    // Absolute addresses are labelled hex / decimal and relative addresses as an
+    // offset from the base (i.e +0x20)
+    fn block_addr_label(addr: u64, base: Option<u64>) -> String {
+        match base {
+            // Blocks can sit before the function entry point so the offset may be negative
+            Some(base) if addr < base => format!("-{:#x}", base - addr),
+            Some(base) => format!("+{:#x}", addr - base),
+            None => format!("{addr:#x} / {addr}"),
         }
     }
 
+    /// The address label of each basic block, in node order. Relative labels are the
+    /// offset of the block from the start of the function
+    pub fn block_addr_labels(&self, relative_addrs: bool) -> Vec<String> {
+        let base = if relative_addrs {
+            Some(self.offset)
+        } else {
+            None
+        };
+        self.blocks
+            .iter()
+            .map(|block| Self::block_addr_label(block.offset, base))
+            .collect()
+    }
+
     pub fn generate_tiknib_cfg_global_features(&self, architecture: &String) -> TikNibFunc {
         let mut basic_block_features = Vec::new();
 
@@ -670,6 +870,27 @@ impl Default for TikNibFuncFeatures {
     }
 }
 
+impl TikNibFuncFeatures {
+    pub fn to_vec(&self) -> Vec<f64> {
+        vec![
+            self.avg_arithshift.into_inner() as f64,
+            self.avg_compare.into_inner() as f64,
+            self.avg_ctransfer.into_inner() as f64,
+            self.avg_ctransfercond.into_inner() as f64,
+            self.avg_dtransfer.into_inner() as f64,
+            self.avg_float.into_inner() as f64,
+            self.avg_total.into_inner() as f64,
+            self.sum_arithshift.into_inner() as f64,
+            self.sum_compare.into_inner() as f64,
+            self.sum_ctransfer.into_inner() as f64,
+            self.sum_ctransfercond.into_inner() as f64,
+            self.sum_dtransfer.into_inner() as f64,
+            self.sum_float.into_inner() as f64,
+            self.sum_total.into_inner() as f64,
+        ]
+    }
+}
+
 // This is a bit odd but is to make sure the JSON output is formatted nice!
 impl From<(&String, Vec<TikNibFeaturesBB>)> for TikNibFunc {
     fn from(input: (&String, Vec<TikNibFeaturesBB>)) -> Self {
@@ -719,27 +940,402 @@ impl From<(&String, Vec<TikNibFeaturesBB>)> for TikNibFunc {
 
 #[cfg(test)]
 mod tests {
-    use crate::bb::FeatureType;
-    use std::path::PathBuf;
-
+    use crate::agfj::CfgMismatch;
+    use crate::bb::{ACFJBlock, FeatureType};
+    use crate::networkx::{BytesNode, GraphFormat, NetworkxDiGraph};
+    use crate::normalisation::NormalizationConfig;
+    use petgraph::visit::Dfs;
+    use serde_json::{json, Value};
+    use std::path::{Path, PathBuf};
+    use walkdir::WalkDir;
+
+    use crate::files::NlpCorpusRecord;
     use crate::AGFJFile;
 
+    fn test_agfj_file(filename: impl AsRef<Path>) -> AGFJFile {
+        AGFJFile {
+            functions: None,
+            filename: filename.as_ref().to_path_buf(),
+            output_path: PathBuf::from("output.json"),
+            min_blocks: 1,
+            max_blocks: None,
+            feature_type: None,
+            architecture: None,
+            norm_config: NormalizationConfig::default(),
+            graph_format: GraphFormat::Json,
+            embed_func_meta: false,
+            edge_types: false,
+            relative_addrs: false,
+        }
+    }
+
     #[test]
     fn test_example_in_graph_rs() {
         assert_eq!(1, 1);
     }
 
+    #[test]
+    fn test_gzipped_file_load_and_deserialize() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let data = std::fs::read("test-files/r2-output-samples/test_bin_agfj.json").unwrap();
+        let gz_path =
+            std::env::temp_dir().join(format!("bin2ml-test-{}_cfg.json.gz", std::process::id()));
+        let mut encoder = GzEncoder::new(
+            std::fs::File::create(&gz_path).unwrap(),
+            Compression::default(),
+        );
+        encoder.write_all(&data).unwrap();
+        encoder.finish().unwrap();
+
+        let mut file = test_agfj_file(&gz_path);
+        file.load_and_deserialize()
+            .expect("Failed to load and deserialize gzipped file");
+        std::fs::remove_file(gz_path).unwrap();
+
+        assert_eq!(file.functions.unwrap().len(), 11);
+        assert_eq!(file.architecture, Some("X86".to_string()));
+    }
+
+    #[test]
+    fn test_architecture_override() {
+        let file_path = PathBuf::from("test-files/r2-output-samples/test_bin_agfj.json");
+        let new_file = |architecture: Option<String>| AGFJFile {
+            architecture,
+            ..test_agfj_file(&file_path)
+        };
+
+        // Detected when no override is provided
+        let mut file = new_file(None);
+        file.load_and_deserialize().unwrap();
+        assert_eq!(file.architecture, Some("X86".to_string()));
+
+        // The override bypasses detection
+        let mut file = new_file(Some("MIPS".to_string()));
+        file.load_and_deserialize().unwrap();
+        assert_eq!(file.architecture, Some("MIPS".to_string()));
+        assert_eq!(file.detect_architecture(), Some("X86".to_string()));
+
+        // Detection fails cleanly when there are no call instructions
+        file.functions
+            .as_mut()
+            .unwrap()
+            .retain(|func| func[0].name == "sym._fini");
+        assert_eq!(file.functions.as_ref().unwrap().len(), 1);
+        assert_eq!(file.detect_architecture(), None);
+    }
+
+    #[test]
+    fn test_for_each_function_matches_batch() {
+        let file_path = PathBuf::from("test-files/r2-output-samples/test_bin_agfj.json");
+        let mut file = test_agfj_file(&file_path);
+
+        let mut streamed_names = Vec::new();
+        let num_streamed = file
+            .for_each_function(|func| streamed_names.push(func.name))
+            .unwrap();
+
+        // The architecture is detected once and every function sees the same one
+        let mut streamed_archs = Vec::new();
+        let mut names_with_arch = Vec::new();
+        file.for_each_function_with_architecture(|func, architecture| {
+            names_with_arch.push(func.name);
+            streamed_archs.push(architecture.cloned());
+        })
+        .unwrap();
+
+        file.load_and_deserialize().unwrap();
+        let batch_architecture = file.detect_architecture();
+        let batch_names: Vec<String> = file
+            .functions
+            .unwrap()
+            .iter()
+            .map(|func| func[0].name.clone())
+            .collect();
+        assert_eq!(num_streamed, batch_names.len());
+        assert_eq!(streamed_names, batch_names);
+        assert_eq!(names_with_arch, batch_names);
+        assert!(batch_architecture.is_some());
+        assert!(streamed_archs
+            .iter()
+            .all(|architecture| *architecture == batch_architecture));
+    }
+
+    #[test]
+    fn test_streamed_func_strings_use_architecture_norm_config() {
+        let output_dir = std::env::temp_dir().join(format!(
+            "bin2ml-test-{}-streamed-norm-config",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&output_dir).unwrap();
+        // MIPS style offsets are only normalised when the architecture is unknown
+        let input_path = output_dir.join("x86_cfg.json");
+        let functions = Value::Array(vec![cfg_func(
+            &[
+                ("push rbp", "rpush"),
+                ("call sym.imp.puts", "call"),
+                ("lea rax, 0x1c(rip)", "lea"),
+                ("ret", "ret"),
+            ],
+            None,
+        )]);
+        std::fs::write(&input_path, functions.to_string()).unwrap();
+
+        let generate = |run: &str, low_memory: bool| {
+            let output_path = output_dir.join(run);
+            std::fs::create_dir_all(&output_path).unwrap();
+            let file = AGFJFile {
+                output_path: output_path.clone(),
+                min_blocks: 0,
+                norm_config: NormalizationConfig::from_preset("aggressive").unwrap(),
+                ..test_agfj_file(&input_path)
+            };
+            file.generate_disasm_func_strings(None, low_memory, true);
+            std::fs::read_to_string(output_path.join("x86_cfg-dfs.json")).unwrap()
+        };
+
+        let batch = generate("batch", false);
+        let streamed = generate("streamed", true);
+        std::fs::remove_dir_all(&output_dir).unwrap();
+
+        assert!(batch.contains("0x1c(rip)"));
+        assert_eq!(batch, streamed);
+    }
+
+    #[test]
+    fn test_func_string_output_is_reproducible() {
+        let output_dir =
+            std::env::temp_dir().join(format!("bin2ml-test-{}-reproducible", std::process::id()));
+        let generate = |run: &str, low_memory: bool, sort_output: bool| {
+            let output_path = output_dir.join(run);
+            std::fs::create_dir_all(&output_path).unwrap();
+            let file = AGFJFile {
+                output_path: output_path.clone(),
+                ..test_agfj_file("test-files/r2-output-samples/test_bin_agfj.json")
+            };
+            file.generate_esil_func_strings(None, low_memory, sort_output);
+            std::fs::read(output_path.join("test_bin_agfj-efs.json")).unwrap()
+        };
+
+        let first = generate("first", false, true);
+        let second = generate("second", false, true);
+        let streamed = generate("streamed", true, true);
+        let unsorted = generate("unsorted", false, false);
+        std::fs::remove_dir_all(&output_dir).unwrap();
+
+        assert!(!first.is_empty());
+        assert_eq!(first, second);
+        assert_eq!(first, streamed);
+
+        // Unsorted output has the same content but keeps the input function order
+        let sorted_json: Value = serde_json::from_slice(&first).unwrap();
+        let unsorted_json: Value = serde_json::from_slice(&unsorted).unwrap();
+        assert_eq!(sorted_json, unsorted_json);
+
+        let mut file = AGFJFile {
+            output_path: PathBuf::new(),
+            ..test_agfj_file("test-files/r2-output-samples/test_bin_agfj.json")
+        };
+        file.load_and_deserialize().unwrap();
+        let input_order: Vec<String> = file
+            .functions
+            .unwrap()
+            .iter()
+            .map(|func| func[0].name.clone())
+            .filter(|name| sorted_json.get(name).is_some())
+            .collect();
+        let key_order = |output: &[u8]| {
+            let output = String::from_utf8(output.to_vec()).unwrap();
+            let mut keys: Vec<(usize, String)> = sorted_json
+                .as_object()
+                .unwrap()
+                .keys()
+                .map(|name| {
+                    (
+                        output.find(&format!("\"{}\":", name)).unwrap(),
+                        name.clone(),
+                    )
+                })
+                .collect();
+            keys.sort();
+            keys.into_iter()
+                .map(|(_, name)| name)
+                .collect::<Vec<String>>()
+        };
+        let mut sorted_names = input_order.clone();
+        sorted_names.sort();
+        assert_ne!(input_order, sorted_names);
+        assert_eq!(key_order(&first), sorted_names);
+        assert_eq!(key_order(&unsorted), input_order);
+    }
+
+    #[test]
+    fn test_linear_walk_jsonl_records_function_names() {
+        let output_path =
+            std::env::temp_dir().join(format!("bin2ml-test-{}-jsonl", std::process::id()));
+        std::fs::create_dir_all(&output_path).unwrap();
+        let file = AGFJFile {
+            output_path: output_path.clone(),
+            ..test_agfj_file("test-files/r2-output-samples/test_bin_agfj.json")
+        };
+        file.generate_linear_bb_walk(false, true);
+
+        let corpus =
+            std::fs::read_to_string(output_path.join("test_bin_agfj-dis-singles.jsonl")).unwrap();
+        std::fs::remove_dir_all(&output_path).unwrap();
+
+        let mut file = test_agfj_file("test-files/r2-output-samples/test_bin_agfj.json");
+        file.load_and_deserialize().unwrap();
+        let expected: Vec<(String, Vec<String>)> = file
+            .functions
+            .unwrap()
+            .iter_mut()
+            .filter_map(|func| {
+                func[0]
+                    .get_function_instructions(false, &1, &None, &NormalizationConfig::default())
+                    .map(|instructions| (func[0].name.clone(), instructions))
+            })
+            .collect();
+
+        let records: Vec<NlpCorpusRecord> = corpus
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("Invalid JSONL line"))
+            .collect();
+        assert_eq!(records.len(), expected.len());
+        for (record, (name, instructions)) in records.iter().zip(expected) {
+            assert_eq!(record.function, name);
+            assert_eq!(record.instructions, instructions);
+            assert_eq!(record.arch, Some("X86".to_string()));
+        }
+        assert!(records.iter().any(|record| record.function == "main"));
+    }
+
+    fn cfg_func(ops: &[(&str, &str)], bits: Option<u64>) -> Value {
+        let ops: Vec<Value> = ops
+            .iter()
+            .map(|(disasm, r#type)| json!({"offset": 0, "disasm": disasm, "type": r#type}))
+            .collect();
+        json!([{
+            "name": "main", "nargs": 0, "ninstr": ops.len(), "nlocals": 0, "offset": 0,
+            "size": 0, "stack": 0, "type": "fcn", "bits": bits,
+            "blocks": [{"offset": 0, "ops": ops}]
+        }])
+    }
+
+    fn file_from_cfgs(functions: Vec<Value>) -> AGFJFile {
+        AGFJFile {
+            functions: Some(serde_json::from_value(Value::Array(functions)).unwrap()),
+            ..test_agfj_file("test.json")
+        }
+    }
+
+    #[test]
+    fn test_detect_architecture_riscv() {
+        // jal is shared with MIPS so RISC-V specific mnemonics are used to disambiguate
+        let riscv = file_from_cfgs(vec![cfg_func(
+            &[
+                ("addi sp, sp, -16", "add"),
+                ("jal ra, sym.imp.puts", "call"),
+                ("mv a0, zero", "mov"),
+                ("ret", "ret"),
+            ],
+            None,
+        )]);
+        assert_eq!(riscv.detect_architecture(), Some("RISCV".to_string()));
+
+        let mips = file_from_cfgs(vec![cfg_func(
+            &[
+                ("addiu sp, sp, -0x20", "add"),
+                ("jal sym.imp.puts", "call"),
+                ("jr ra", "ret"),
+            ],
+            None,
+        )]);
+        assert_eq!(mips.detect_architecture(), Some("MIPS".to_string()));
+
+        let x86 = file_from_cfgs(vec![cfg_func(
+            &[
+                ("push rbp", "rpush"),
+                ("call sym.imp.puts", "call"),
+                ("ret", "ret"),
+            ],
+            None,
+        )]);
+        assert_eq!(x86.detect_architecture(), Some("X86".to_string()));
+
+        let riscv_call = file_from_cfgs(vec![cfg_func(
+            &[("c.addi sp, -16", "add"), ("call sym.imp.puts", "call")],
+            None,
+        )]);
+        assert_eq!(riscv_call.detect_architecture(), Some("RISCV".to_string()));
+    }
+
+    #[test]
+    fn test_detect_architecture_aarch64() {
+        let arm_ops = [
+            ("push {r4, lr}", "rpush"),
+            ("bl sym.imp.puts", "call"),
+            ("pop {r4, pc}", "rpop"),
+        ];
+        let aarch64_ops = [
+            ("stp x29, x30, [sp, -0x10]!", "store"),
+            ("bl sym.imp.puts", "call"),
+            ("blr x1", "rcall"),
+            ("ldp x29, x30, [sp], 0x10", "load"),
+            ("ret", "ret"),
+        ];
+
+        // The bits recorded at extraction time take priority
+        let arm = file_from_cfgs(vec![cfg_func(&arm_ops, Some(32))]);
+        assert_eq!(arm.detect_architecture(), Some("ARM".to_string()));
+        let aarch64 = file_from_cfgs(vec![cfg_func(&arm_ops, Some(64))]);
+        assert_eq!(aarch64.detect_architecture(), Some("AARCH64".to_string()));
+
+        // Falls back to AArch64 only mnemonics when the bits are not available
+        let arm = file_from_cfgs(vec![cfg_func(&arm_ops, None)]);
+        assert_eq!(arm.detect_architecture(), Some("ARM".to_string()));
+        let aarch64 = file_from_cfgs(vec![cfg_func(&aarch64_ops, None)]);
+        assert_eq!(aarch64.detect_architecture(), Some("AARCH64".to_string()));
+    }
+
+    #[test]
+    fn test_validate_structure() {
+        let file_path = PathBuf::from("test-files/r2-output-samples/test_bin_agfj.json");
+        let mut file = AGFJFile {
+            feature_type: Some(crate::bb::FeatureType::Gemini),
+            ..test_agfj_file(&file_path)
+        };
+        file.load_and_deserialize()
+            .expect("Failed to load and deserialize file");
+        assert!(file.validate_structure().is_empty());
+
+        let functions = file.functions.as_mut().unwrap();
+        let target = functions
+            .iter_mut()
+            .find(|func| func[0].blocks.len() > 1)
+            .expect("Unable to find a function with more than one block");
+        target[0].blocks[1].offset = target[0].blocks[0].offset;
+        target[0].edge_list = Some(vec![(0, 1000, 1)]);
+        let name = target[0].name.clone();
+
+        let violations = file.validate_structure();
+        assert_eq!(violations.len(), 2);
+        assert!(violations.iter().all(|v| v.function_name == name));
+
+        let num_functions = file.functions.as_ref().unwrap().len();
+        file.remove_invalid_functions();
+        assert_eq!(file.functions.as_ref().unwrap().len(), num_functions - 1);
+    }
+
     #[test]
     fn file_struct_creation() {
         let file_path = PathBuf::from("../sample-tool-outputs/r2/example_agfj@@F_output.json");
         let file = AGFJFile {
-            functions: None,
-            filename: file_path.to_owned(),
-            output_path: PathBuf::from("output.json"),
             min_blocks: 5,
             feature_type: Some(crate::bb::FeatureType::Gemini),
-            architecture: None,
-            reg_norm: false,
+            ..test_agfj_file(&file_path)
         };
 
         assert!(file.functions.is_none());
@@ -756,13 +1352,9 @@ mod tests {
     fn test_file_load_and_desearlize() {
         let file_path = PathBuf::from("test-files/r2-output-samples/example_agfj@@F_output.json");
         let mut file = AGFJFile {
-            functions: None,
-            filename: file_path.to_owned(),
-            output_path: PathBuf::from("output.json"),
             min_blocks: 5,
             feature_type: Some(crate::bb::FeatureType::Gemini),
-            architecture: None,
-            reg_norm: false,
+            ..test_agfj_file(&file_path)
         };
 
         let ret = file.load_and_deserialize();
@@ -794,12 +1386,12 @@ mod tests {
         );
         assert_eq!(
             file.functions.as_ref().unwrap()[0][0].blocks[0].jump,
-            4294980968
+            Some(4294980968)
         );
         assert!(!file.functions.as_ref().unwrap()[0][0].blocks[0]
             .ops
             .is_empty());
-        assert_eq!(file.functions.as_ref().unwrap()[0][0].blocks[0].fail, -1);
+        assert_eq!(file.functions.as_ref().unwrap()[0][0].blocks[0].fail, None);
 
         assert!(file.functions.as_ref().unwrap()[0][0].blocks[0]
             .switchop
@@ -812,14 +1404,14 @@ mod tests {
         );
         assert_eq!(
             file.functions.as_ref().unwrap()[0][0].blocks[1].jump,
-            4294981019
+            Some(4294981019)
         );
         assert!(!file.functions.as_ref().unwrap()[0][0].blocks[1]
             .ops
             .is_empty());
         assert_eq!(
             file.functions.as_ref().unwrap()[0][0].blocks[1].fail,
-            4294980986
+            Some(4294980986)
         );
         assert!(file.functions.as_ref().unwrap()[0][0].blocks[1]
             .switchop
@@ -835,13 +1427,9 @@ mod tests {
     fn test_func_edge_list_generation() {
         let file_path = PathBuf::from("test-files/r2-output-samples/test_bin_agfj.json");
         let mut file = AGFJFile {
-            functions: None,
-            filename: file_path.to_owned(),
-            output_path: PathBuf::from("output.json"),
             min_blocks: 5,
             feature_type: Some(crate::bb::FeatureType::Gemini),
-            architecture: None,
-            reg_norm: false,
+            ..test_agfj_file(&file_path)
         };
 
         file.load_and_deserialize().unwrap();
@@ -853,7 +1441,7 @@ mod tests {
         // Check edge and address lists are blank before processing
         assert!(target_func.edge_list.is_none());
         assert!(target_func.addr_idx.is_none());
-        target_func.create_bb_edge_list(&1);
+        target_func.create_bb_edge_list(&1, &None);
 
         // Check edge list is now not blank before processing
         assert!(target_func.edge_list.is_some());
@@ -877,4 +1465,410 @@ mod tests {
 
         assert_eq!(target_func.edge_list, expected_edge_list)
     }
+
+    #[test]
+    fn test_dfs_cfg_respects_max_hops() {
+        let file_path = PathBuf::from("test-files/r2-output-samples/test_bin_agfj.json");
+        let mut file = AGFJFile {
+            min_blocks: 5,
+            ..test_agfj_file(&file_path)
+        };
+
+        file.load_and_deserialize().unwrap();
+        let target_func = &mut file.functions.unwrap()[9][0];
+        assert_eq!(target_func.name, "main");
+        target_func.create_graph_struct_members(&1, &None);
+
+        let graph = target_func.graph.as_ref().unwrap();
+        let addr_idx = target_func.addr_idx.as_ref().unwrap();
+        let block_ins_count = |idx: usize| {
+            target_func
+                .blocks
+                .iter()
+                .find(|x| x.offset == addr_idx[idx])
+                .unwrap()
+                .get_ins(&NormalizationConfig::default())
+                .len()
+        };
+
+        for max_hops in [1, 3, 10] {
+            let walks =
+                target_func.dfs_cfg(max_hops, false, &NormalizationConfig::default(), false);
+            assert_eq!(walks.len(), graph.node_count());
+
+            // Every walk should cover exactly the first max_hops reachable blocks
+            for (start, walk) in graph.node_indices().zip(walks.iter()) {
+                let mut dfs = Dfs::new(graph, start);
+                let mut expected_len = 0;
+                let mut hops = 0;
+                while let Some(visited) = dfs.next(graph) {
+                    if hops >= max_hops {
+                        break;
+                    }
+                    expected_len += block_ins_count(visited.index());
+                    hops += 1;
+                }
+                assert_eq!(walk.len(), expected_len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_opcode_ngrams() {
+        let file_path = PathBuf::from("test-files/r2-output-samples/test_bin_agfj.json");
+        let mut file = test_agfj_file(&file_path);
+
+        file.load_and_deserialize().unwrap();
+        let target_func = &mut file.functions.unwrap()[1][0];
+        assert_eq!(target_func.name, "sym.deregister_tm_clones");
+
+        let bigrams = target_func.get_opcode_ngrams(2, &1, &None).unwrap();
+        assert_eq!(
+            bigrams,
+            vec![
+                "lea lea", "lea cmp", "cmp je", "je mov", "mov test", "test je", "je jmp",
+                "jmp ret"
+            ]
+        );
+
+        let trigrams = target_func.get_opcode_ngrams(3, &1, &None).unwrap();
+        assert_eq!(trigrams.len(), 7);
+        assert_eq!(trigrams[0], "lea lea cmp");
+
+        // Fewer instructions than n and too few blocks
+        assert!(target_func
+            .get_opcode_ngrams(10, &1, &None)
+            .unwrap()
+            .is_empty());
+        assert!(target_func.get_opcode_ngrams(2, &5, &None).is_none());
+    }
+
+    #[test]
+    fn test_function_string_block_markers() {
+        let file_path = PathBuf::from("test-files/r2-output-samples/test_bin_agfj.json");
+        let mut file = test_agfj_file(&file_path);
+
+        file.load_and_deserialize().unwrap();
+        let target_func = &file.functions.unwrap()[9][0];
+        assert_eq!(target_func.name, "main");
+
+        let (_, esil) = target_func
+            .get_esil_function_string(&1, &None, &NormalizationConfig::default(), Some("[BB]"))
+            .unwrap();
+        assert_eq!(esil.matches("[BB]").count(), target_func.blocks.len() - 1);
+        assert!(!esil.starts_with("[BB]") && !esil.ends_with("[BB]"));
+
+        let (_, disasm) = target_func
+            .get_disasm_function_string(&1, &None, &NormalizationConfig::default(), Some("<bb>"))
+            .unwrap();
+        assert_eq!(disasm.matches("<bb>").count(), target_func.blocks.len() - 1);
+
+        let (_, unmarked) = target_func
+            .get_disasm_function_string(&1, &None, &NormalizationConfig::default(), None)
+            .unwrap();
+        assert_eq!(disasm.replace(" <bb>", ""), unmarked);
+    }
+
+    #[test]
+    fn test_max_blocks_skips_large_functions() {
+        let file_path = PathBuf::from("test-files/r2-output-samples/test_bin_agfj.json");
+        let mut file = AGFJFile {
+            min_blocks: 5,
+            ..test_agfj_file(&file_path)
+        };
+
+        file.load_and_deserialize().unwrap();
+        let target_func = &mut file.functions.unwrap()[9][0];
+        assert_eq!(target_func.name, "main");
+        assert_eq!(target_func.blocks.len(), 9);
+
+        // Within bounds
+        assert!(target_func.has_valid_block_count(&0, &None));
+        assert!(target_func.has_valid_block_count(&9, &Some(9)));
+        assert!(target_func
+            .get_esil_function_string(&1, &Some(9), &NormalizationConfig::default(), None)
+            .is_some());
+
+        // Above max_blocks
+        assert!(!target_func.has_valid_block_count(&1, &Some(8)));
+        assert!(target_func
+            .get_esil_function_string(&1, &Some(8), &NormalizationConfig::default(), None)
+            .is_none());
+        assert!(target_func
+            .get_disasm_function_string(&1, &Some(8), &NormalizationConfig::default(), None)
+            .is_none());
+        assert!(target_func
+            .disasm_random_walks(
+                &1,
+                &Some(8),
+                10,
+                false,
+                &NormalizationConfig::default(),
+                false
+            )
+            .is_none());
+
+        // Below min_blocks
+        assert!(!target_func.has_valid_block_count(&10, &None));
+    }
+
+    #[test]
+    fn test_cfg_embeds_function_metadata() {
+        let file_path = PathBuf::from("test-files/r2-output-samples/test_bin_agfj.json");
+        let mut file = AGFJFile {
+            min_blocks: 5,
+            feature_type: Some(FeatureType::Gemini),
+            embed_func_meta: true,
+            ..test_agfj_file(&file_path)
+        };
+
+        file.load_and_deserialize().unwrap();
+        let target_func = &file.functions.as_ref().unwrap()[9][0];
+        assert_eq!(target_func.name, "main");
+
+        let output_dir =
+            std::env::temp_dir().join(format!("bin2ml-embed-func-meta-{}", std::process::id()));
+        if output_dir.exists() {
+            std::fs::remove_dir_all(&output_dir).unwrap();
+        }
+
+        target_func.generate_attributed_cfg(
+            &file_path,
+            &5,
+            &None,
+            &output_dir,
+            FeatureType::Gemini,
+            &"X86".to_string(),
+            &GraphFormat::Json,
+            true,
+            false,
+            false,
+            None,
+        );
+
+        let cfg_path = WalkDir::new(&output_dir)
+            .into_iter()
+            .filter_map(|file| file.ok())
+            .find(|file| file.file_name().to_string_lossy().ends_with("-main.json"))
+            .expect("Unable to find generated CFG");
+        let cfg: Value =
+            serde_json::from_str(&std::fs::read_to_string(cfg_path.path()).unwrap()).unwrap();
+
+        assert_eq!(
+            cfg["function_metadata"],
+            json!({"offset": 4425, "nargs": 0, "nlocals": 2, "size": 161})
+        );
+        assert!(cfg["nodes"].as_array().is_some());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn test_cfg_relative_addr_labels() {
+        let file_path = PathBuf::from("test-files/r2-output-samples/test_bin_agfj.json");
+        let mut file = AGFJFile {
+            min_blocks: 5,
+            feature_type: Some(FeatureType::Gemini),
+            relative_addrs: true,
+            ..test_agfj_file(&file_path)
+        };
+
+        file.load_and_deserialize().unwrap();
+        let target_func = &file.functions.as_ref().unwrap()[9][0];
+        assert_eq!(target_func.name, "main");
+        assert_eq!(target_func.offset, 0x1149);
+
+        let expected: Vec<String> = target_func
+            .blocks
+            .iter()
+            .map(|block| format!("+{:#x}", block.offset - 0x1149))
+            .collect();
+        assert_eq!(expected[0], "+0x0");
+        assert_eq!(target_func.block_addr_labels(true), expected);
+        assert_eq!(target_func.block_addr_labels(false)[0], "0x1149 / 4425");
+
+        let output_dir =
+            std::env::temp_dir().join(format!("bin2ml-cfg-relative-addrs-{}", std::process::id()));
+        if output_dir.exists() {
+            std::fs::remove_dir_all(&output_dir).unwrap();
+        }
+
+        target_func.generate_attributed_cfg(
+            &file_path,
+            &5,
+            &None,
+            &output_dir,
+            FeatureType::Gemini,
+            &"X86".to_string(),
+            &GraphFormat::Json,
+            false,
+            false,
+            true,
+            None,
+        );
+
+        let cfg_path = WalkDir::new(&output_dir)
+            .into_iter()
+            .filter_map(|file| file.ok())
+            .find(|file| file.file_name().to_string_lossy().ends_with("-main.json"))
+            .expect("Unable to find generated CFG");
+        let cfg: Value =
+            serde_json::from_str(&std::fs::read_to_string(cfg_path.path()).unwrap()).unwrap();
+
+        assert_eq!(cfg["nodeLabels"], json!(expected));
+        assert_eq!(
+            cfg["nodeLabels"].as_array().unwrap().len(),
+            cfg["nodes"].as_array().unwrap().len()
+        );
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn test_cfg_bytes_nodes() {
+        let file_path = PathBuf::from("test-files/r2-output-samples/test_bin_agfj.json");
+        let mut file = AGFJFile {
+            min_blocks: 5,
+            feature_type: Some(FeatureType::Bytes),
+            ..test_agfj_file(&file_path)
+        };
+
+        file.load_and_deserialize().unwrap();
+        let target_func = &file.functions.as_ref().unwrap()[9][0];
+        assert_eq!(target_func.name, "main");
+
+        let output_dir =
+            std::env::temp_dir().join(format!("bin2ml-cfg-bytes-nodes-{}", std::process::id()));
+        if output_dir.exists() {
+            std::fs::remove_dir_all(&output_dir).unwrap();
+        }
+
+        target_func.generate_attributed_cfg(
+            &file_path,
+            &5,
+            &None,
+            &output_dir,
+            FeatureType::Bytes,
+            &"X86".to_string(),
+            &GraphFormat::Json,
+            false,
+            false,
+            false,
+            None,
+        );
+
+        let cfg_path = WalkDir::new(&output_dir)
+            .into_iter()
+            .filter_map(|file| file.ok())
+            .find(|file| file.file_name().to_string_lossy().ends_with("-main.json"))
+            .expect("Unable to find generated CFG");
+        let cfg: NetworkxDiGraph<BytesNode> =
+            serde_json::from_str(&std::fs::read_to_string(cfg_path.path()).unwrap()).unwrap();
+
+        assert_eq!(cfg.nodes.len(), target_func.blocks.len());
+        for (node, block) in cfg.nodes.iter().zip(&target_func.blocks) {
+            let expected: String = block
+                .ops
+                .iter()
+                .map(|op| op.bytes.clone().unwrap_or_default())
+                .collect();
+            assert!(!node.bytes.is_empty());
+            assert_eq!(node.bytes, expected);
+        }
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn test_to_networkx_main() {
+        let file_path = PathBuf::from("test-files/r2-output-samples/test_bin_agfj.json");
+        let mut file = AGFJFile {
+            min_blocks: 5,
+            feature_type: Some(FeatureType::Gemini),
+            ..test_agfj_file(&file_path)
+        };
+
+        file.load_and_deserialize().unwrap();
+        let target_func = &file.functions.as_ref().unwrap()[9][0];
+        assert_eq!(target_func.name, "main");
+
+        let networkx_graph = target_func
+            .to_networkx(FeatureType::Gemini, &"X86".to_string(), &5)
+            .expect("Unable to build graph for main");
+        assert_eq!(networkx_graph.nodes.len(), target_func.blocks.len());
+        assert_eq!(networkx_graph.adjacency.len(), target_func.blocks.len());
+        assert!(networkx_graph
+            .nodes
+            .iter()
+            .all(|node| node.as_gemini().is_some()));
+        assert!(networkx_graph.graph_meta.is_none());
+
+        // Below min_blocks
+        assert!(target_func
+            .to_networkx(FeatureType::Gemini, &"X86".to_string(), &100)
+            .is_none());
+    }
+
+    #[test]
+    fn test_cfg_node_block_mismatch_is_reported() {
+        let file_path = PathBuf::from("test-files/r2-output-samples/test_bin_agfj.json");
+        let mut file = AGFJFile {
+            min_blocks: 5,
+            feature_type: Some(FeatureType::Esil),
+            ..test_agfj_file(&file_path)
+        };
+
+        file.load_and_deserialize().unwrap();
+        let mut target_func = file.functions.unwrap().swap_remove(9).remove(0);
+        assert_eq!(target_func.name, "main");
+
+        // Append an unreachable block with no edges so the recovered CFG is missing a node
+        let mut unreachable_block: ACFJBlock =
+            serde_json::from_value(serde_json::to_value(&target_func.blocks[0]).unwrap()).unwrap();
+        unreachable_block.offset = u64::MAX;
+        unreachable_block.jump = None;
+        unreachable_block.fail = None;
+        unreachable_block.switchop = None;
+        target_func.blocks.push(unreachable_block);
+
+        let output_dir = std::env::temp_dir().join(format!(
+            "bin2ml-cfg-node-block-mismatch-{}",
+            std::process::id()
+        ));
+        if output_dir.exists() {
+            std::fs::remove_dir_all(&output_dir).unwrap();
+        }
+
+        let mismatch = target_func.generate_attributed_cfg(
+            &file_path,
+            &5,
+            &None,
+            &output_dir,
+            FeatureType::Esil,
+            &"X86".to_string(),
+            &GraphFormat::Json,
+            false,
+            false,
+            false,
+            None,
+        );
+
+        assert_eq!(
+            mismatch,
+            Some(CfgMismatch {
+                filename: file_path.to_string_lossy().to_string(),
+                function: "main".to_string(),
+                node_count: target_func.blocks.len() - 1,
+                block_count: target_func.blocks.len(),
+            })
+        );
+        assert!(!WalkDir::new(&output_dir)
+            .into_iter()
+            .filter_map(|file| file.ok())
+            .any(|file| file.file_name().to_string_lossy().ends_with("-main.json")));
+
+        if output_dir.exists() {
+            std::fs::remove_dir_all(&output_dir).unwrap();
+        }
+    }
 }
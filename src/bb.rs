@@ -1,12 +1,12 @@
 use crate::consts::*;
 #[cfg(feature = "inference")]
 use crate::inference::InferenceJob;
-use crate::normalisation::{normalise_disasm_simple, normalise_esil_simple};
+use crate::normalisation::{normalise_disasm_simple, normalise_esil_simple, NormalizationConfig};
 use serde::{Deserialize, Serialize};
 use serde_aux::prelude::*;
 use serde_json::Value;
 use serde_with::{serde_as, DefaultOnError};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::string::String;
 #[cfg(feature = "inference")]
@@ -27,6 +27,8 @@ pub enum FeatureType {
     Invalid,
     Pcode,
     Pseudo,
+    OpcodeHistogram,
+    Bytes,
 }
 
 impl fmt::Display for FeatureType {
@@ -43,6 +45,8 @@ impl fmt::Display for FeatureType {
             FeatureType::Invalid => "invalid",
             FeatureType::Pcode => "pcode",
             FeatureType::Pseudo => "pseudo",
+            FeatureType::OpcodeHistogram => "opcodehist",
+            FeatureType::Bytes => "bytes",
         };
         write!(f, "{}", feature_type_str)
     }
@@ -100,24 +104,20 @@ pub struct Op {
     pub val: Option<u64>,
 }
 
-// Function to set offset, jump and fail to default values
-fn return_minus_one() -> i64 {
-    -1
-}
-
+// Addresses are stored as u64 so that the full address range can be represented.
+// Blocks without a jump or fail target have a value of None rather than a sentinel
+// value which was previously -1 and silently used for any address larger than an i64.
 #[serde_as]
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ACFJBlock {
-    #[serde(default = "return_minus_one")]
-    pub offset: i64,
-    #[serde(default = "return_minus_one")]
+    #[serde(default)]
+    pub offset: u64,
+    #[serde(default)]
     #[serde_as(deserialize_as = "DefaultOnError")]
-    // This has been added to eliminate an error where
-    // the jump address from x86-64 binaries is larger than
-    // an i64.
-    pub jump: i64,
-    #[serde(default = "return_minus_one")]
-    pub fail: i64,
+    pub jump: Option<u64>,
+    #[serde(default)]
+    #[serde_as(deserialize_as = "DefaultOnError")]
+    pub fail: Option<u64>,
     pub ops: Vec<Op>,
     pub size: Option<i64>,
     pub switchop: Option<SwitchOp>,
@@ -164,6 +164,27 @@ impl From<&Vec<f64>> for TikNibFeaturesBB {
     }
 }
 impl FeatureType {
+    /// Parses a feature type provided on the command line. Unknown feature types
+    /// are returned as `FeatureType::Invalid`
+    pub fn new(feature_type: &str) -> FeatureType {
+        match feature_type {
+            "gemini" => FeatureType::Gemini,
+            "discovre" => FeatureType::DiscovRE,
+            "dgis" => FeatureType::DGIS,
+            "encode" => FeatureType::Encoded,
+            "tiknib" => FeatureType::Tiknib,
+            "disasm" => FeatureType::Disasm,
+            "esil" => FeatureType::Esil,
+            #[cfg(feature = "inference")]
+            "embed" => FeatureType::ModelEmbedded,
+            "pcode" => FeatureType::Pcode,
+            "pseudo" => FeatureType::Pseudo,
+            "opcodehist" => FeatureType::OpcodeHistogram,
+            "bytes" => FeatureType::Bytes,
+            _ => FeatureType::Invalid,
+        }
+    }
+
     // Returns the corresponding feature map given a provided FeatureType
     // These feature maps are used to provide the functionality that handles
     // writing the output graphs to Networkx compatible JSON with
@@ -178,6 +199,102 @@ impl FeatureType {
     }
 }
 
+/// Memoizes the numeric feature vectors of basic blocks within a single file-processing run
+///
+/// Compiler generated stubs mean many blocks are identical across functions. Blocks
+/// are keyed on each instruction's bytes alongside its disassembly and the number of
+/// offspring. The bytes alone are not enough as PC-relative operands resolve to
+/// different targets (i.e. a library vs local call) which changes some features.
+///
+/// A cache is only valid for a single feature type and architecture. Once `capacity`
+/// entries are held the least recently used entry is evicted to bound memory usage.
+#[derive(Debug, Default)]
+pub struct BlockFeatureCache {
+    entries: HashMap<BlockKey, (Vec<f64>, u64)>,
+    // The key of each entry by when it was last used
+    recency: BTreeMap<u64, BlockKey>,
+    tick: u64,
+    capacity: usize,
+    pub hits: usize,
+    pub misses: usize,
+}
+
+// The bytes, disassembly, opcode and type of an instruction
+type OpKey = (Option<String>, Option<String>, Option<String>, String);
+
+/// The contents of a basic block which determine its numeric feature vector
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct BlockKey {
+    ops: Vec<OpKey>,
+    num_offspring: u64,
+}
+
+impl BlockFeatureCache {
+    pub fn new(capacity: usize) -> BlockFeatureCache {
+        BlockFeatureCache {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    fn block_key(block: &ACFJBlock) -> BlockKey {
+        BlockKey {
+            ops: block
+                .ops
+                .iter()
+                .map(|op| {
+                    (
+                        op.bytes.clone(),
+                        op.disasm.clone(),
+                        op.opcode.clone(),
+                        op.r#type.clone(),
+                    )
+                })
+                .collect(),
+            num_offspring: block.get_no_offspring().to_bits(),
+        }
+    }
+
+    /// A cached equivalent of `ACFJBlock::generate_bb_feature_vec`
+    pub fn generate_bb_feature_vec(
+        &mut self,
+        block: &ACFJBlock,
+        feature_vecs: &mut Vec<Vec<f64>>,
+        feature_type: FeatureType,
+        architecture: &String,
+    ) {
+        let key = Self::block_key(block);
+        self.tick += 1;
+        if let Some((feature_vector, last_used)) = self.entries.get_mut(&key) {
+            self.hits += 1;
+            self.recency.remove(last_used);
+            *last_used = self.tick;
+            self.recency.insert(self.tick, key);
+            feature_vecs.push(feature_vector.clone());
+            return;
+        }
+
+        self.misses += 1;
+        let mut computed = Vec::new();
+        block.generate_bb_feature_vec(&mut computed, feature_type, architecture);
+        if let Some(feature_vector) = computed.pop() {
+            if self.capacity == 0 {
+                feature_vecs.push(feature_vector);
+                return;
+            }
+            if self.entries.len() >= self.capacity {
+                if let Some((_, lru_key)) = self.recency.pop_first() {
+                    self.entries.remove(&lru_key);
+                }
+            }
+            self.recency.insert(self.tick, key.clone());
+            self.entries
+                .insert(key, (feature_vector.clone(), self.tick));
+            feature_vecs.push(feature_vector);
+        }
+    }
+}
+
 impl ACFJBlock {
     // Generates integer encodings of a basic blocks ESIL instructions
     //
@@ -191,7 +308,7 @@ impl ACFJBlock {
         inference_job: Arc<InferenceJob>,
     ) {
         let mut basic_block: Vec<_> = Vec::new();
-        let normalised_esil = self.get_esil_bb(false);
+        let normalised_esil = self.get_esil_bb(&NormalizationConfig::default());
         for normed_esil_ins in normalised_esil {
             let embedded_esil: Vec<i32> = inference_job.encode(normed_esil_ins.as_str());
             let casted_esil: Vec<f64> = embedded_esil.iter().map(|&val| val as f64).collect();
@@ -238,6 +355,7 @@ impl ACFJBlock {
             FeatureType::Gemini => self.gemini_features(architecture, false),
             FeatureType::DGIS => self.dgis_features(architecture),
             FeatureType::Tiknib => self.get_tiknib_features_vec(architecture),
+            FeatureType::OpcodeHistogram => self.opcode_histogram_features(architecture),
             _ => unreachable!(),
         };
 
@@ -254,10 +372,12 @@ impl ACFJBlock {
         feature_type: FeatureType,
         normalise: bool,
     ) {
+        let norm_config = NormalizationConfig::from_reg_norm(normalise);
         let feature_vector: Vec<String> = match feature_type {
-            FeatureType::Disasm => self.get_disasm_bb(normalise),
-            FeatureType::Esil => self.get_esil_bb(normalise),
-            FeatureType::Pseudo => self.get_psuedo_bb(normalise),
+            FeatureType::Disasm => self.get_disasm_bb(&norm_config),
+            FeatureType::Esil => self.get_esil_bb(&norm_config),
+            FeatureType::Pseudo => self.get_psuedo_bb(&norm_config),
+            FeatureType::Bytes => vec![self.get_bytes_bb()],
             _ => unreachable!(),
         };
         if feature_vector.is_empty() {
@@ -298,6 +418,14 @@ impl ACFJBlock {
                     } else if ARM_ARITHMETIC.contains(&opcode) {
                         feature_vector[2] += 1. // No. of Arithmetic Instructions
                     }
+                } else if architecture == "AARCH64" {
+                    if AARCH64_CALL.contains(&opcode) {
+                        feature_vector[0] += 1. // Number of Calls
+                    } else if AARCH64_TRANSFER.contains(&opcode) {
+                        feature_vector[1] += 1. // Number of Transfer Instructions
+                    } else if AARCH64_ARITHMETIC.contains(&opcode) {
+                        feature_vector[2] += 1. // No. of Arithmetic Instructions
+                    }
                 } else if architecture == "X86" {
                     if X86_CALL.contains(&opcode) {
                         feature_vector[0] += 1. // Number of Calls
@@ -314,6 +442,14 @@ impl ACFJBlock {
                     } else if MIPS_ARITHMETIC.contains(&opcode) {
                         feature_vector[2] += 1. // No. of Arithmetic Instructions
                     }
+                } else if architecture == "RISCV" {
+                    if RISCV_CALL.contains(&opcode) {
+                        feature_vector[0] += 1. // Number of Calls
+                    } else if RISCV_TRANSFER.contains(&opcode) {
+                        feature_vector[1] += 1. // Number of Transfer Instructions
+                    } else if RISCV_ARITHMETIC.contains(&opcode) {
+                        feature_vector[2] += 1. // No. of Arithmetic Instructions
+                    }
                 } else {
                     unreachable!(
                         "Invalid Architecture - This shouldn't happen! Got {}",
@@ -339,6 +475,41 @@ impl ACFJBlock {
         feature_vector
     }
 
+    // Generates a histogram of the mnemonics within the basic block counted against
+    // a fixed architecture specific vocabulary (see consts.rs). The final element of
+    // the feature vector counts any mnemonics not present within the vocabulary
+    pub fn opcode_histogram_features(&self, architecture: &String) -> Vec<f64> {
+        let vocab: &[&str] = match architecture.as_str() {
+            "ARM" => &ARM_OPCODE_HISTOGRAM_VOCAB,
+            "AARCH64" => &AARCH64_OPCODE_HISTOGRAM_VOCAB,
+            "X86" => &X86_OPCODE_HISTOGRAM_VOCAB,
+            "MIPS" => &MIPS_OPCODE_HISTOGRAM_VOCAB,
+            "RISCV" => &RISCV_OPCODE_HISTOGRAM_VOCAB,
+            _ => unreachable!(
+                "Invalid Architecture - This shouldn't happen! Got {}",
+                architecture
+            ),
+        };
+
+        let mut feature_vector: Vec<f64> = vec![0.0; vocab.len() + 1];
+        for ins in self.ops.iter() {
+            if ins.r#type != "invalid" {
+                let mnemonic = ins
+                    .opcode
+                    .as_ref()
+                    .and_then(|opcode| opcode.split_whitespace().next());
+
+                if let Some(mnemonic) = mnemonic {
+                    match vocab.iter().position(|ele| *ele == mnemonic) {
+                        Some(idx) => feature_vector[idx] += 1.,
+                        None => feature_vector[vocab.len()] += 1., // Out of vocabulary
+                    }
+                }
+            }
+        }
+        feature_vector
+    }
+
     // Implements the basic block feature extraction for DGIS - Liu et al (2022)
     // Dual-Granularity Interactive Semantic Learning Based Vulnerability Detection
     // Approach for Cross-Platform Binaries.
@@ -374,6 +545,28 @@ impl ACFJBlock {
                     } else {
                         feature_vector[7] += 1. // No. of generic instructions (mov, lea)
                     }
+                } else if architecture == "AARCH64" {
+                    if AARCH64_STACK.contains(&opcode)
+                        && ins.disasm.as_ref().unwrap().contains("[sp")
+                    {
+                        feature_vector[0] += 1. // No. of Stack Operations
+                    } else if AARCH64_ARITHMETIC.contains(&opcode) {
+                        feature_vector[1] += 1. // No. of Arithmetic Instructions
+                    } else if AARCH64_LOGIC.contains(&opcode) {
+                        feature_vector[2] += 1. // No. of Logical Instructions
+                    } else if AARCH64_COMPARE.contains(&opcode) {
+                        feature_vector[3] += 1. // No. of comparative instructions
+                    } else if AARCH64_CALL.contains(&opcode)
+                        && ins.disasm.as_ref().unwrap().contains("imp")
+                    {
+                        feature_vector[4] += 1. // No. of library function calls
+                    } else if AARCH64_UNCOND.contains(&opcode) {
+                        feature_vector[5] += 1. // No. of unconditional jumps
+                    } else if AARCH64_COND.contains(&opcode) {
+                        feature_vector[6] += 1. // No. of conditional jumps
+                    } else {
+                        feature_vector[7] += 1. // No. of generic instructions (mov, lea)
+                    }
                 } else if architecture == "X86" {
                     if X86_STACK.contains(&opcode) {
                         feature_vector[0] += 1. // No. of Stack Operations
@@ -414,6 +607,26 @@ impl ACFJBlock {
                     } else {
                         feature_vector[7] += 1. // No. of generic instructions (mov, lea)
                     }
+                } else if architecture == "RISCV" {
+                    // As with MIPS, RISC-V has no dedicated push/pop "stack" operations
+                    feature_vector[0] += 0.; // No. of Stack Operations
+                    if RISCV_ARITHMETIC.contains(&opcode) {
+                        feature_vector[1] += 1. // No. of Arithmetic Instructions
+                    } else if RISCV_LOGIC.contains(&opcode) {
+                        feature_vector[2] += 1. // No. of Logical Instructions
+                    } else if RISCV_COMPARE.contains(&opcode) {
+                        feature_vector[3] += 1. // No. of comparative instructions
+                    } else if RISCV_CALL.contains(&opcode)
+                        && ins.disasm.as_ref().unwrap().contains("imp")
+                    {
+                        feature_vector[4] += 1. // No. of library function calls
+                    } else if RISCV_UNCOND.contains(&opcode) {
+                        feature_vector[5] += 1. // No. of unconditional jumps
+                    } else if RISCV_COND.contains(&opcode) {
+                        feature_vector[6] += 1. // No. of conditional jumps
+                    } else {
+                        feature_vector[7] += 1. // No. of generic instructions (mov, lea)
+                    }
                 } else {
                     unreachable!(
                         "Invalid Architecture - This shouldn't happen! Got {}",
@@ -430,14 +643,17 @@ impl ACFJBlock {
     // check to see if the fail/jump targets are also switch case targets.
     // This could result in incorrect counts
     // TODO: Fix this.
+    //
+    // A missing jump/fail target was previously stored as -1 so was counted. This is
+    // kept so that the generated features do not change.
     fn get_no_offspring(&self) -> f64 {
         let mut num_offspring: f64 = 0.;
 
-        if self.fail != 0 {
+        if self.fail != Some(0) {
             num_offspring += 1.
         }
 
-        if self.jump != 0 {
+        if self.jump != Some(0) {
             num_offspring += 1.
         }
 
@@ -446,21 +662,52 @@ impl ACFJBlock {
         }
         num_offspring
     }
-    pub fn get_block_edges(&self, bb_start_addrs: &[i64], edge_list: &mut Vec<(u32, u32, u32)>) {
+    /// Pushes the outgoing edges of the block onto the edge list as (src, dst, weight)
+    ///
+    /// By default only jump and fail edges are added, each with a weight of 1. If
+    /// `edge_types` is set, switch case edges are also added and the weight is the
+    /// edge type, one of [`CFG_EDGE_JUMP`], [`CFG_EDGE_FAIL`] or [`CFG_EDGE_SWITCH`]
+    pub fn get_block_edges(
+        &self,
+        bb_start_addrs: &[u64],
+        edge_list: &mut Vec<(u32, u32, u32)>,
+        edge_types: bool,
+    ) {
         let offset_idx = bb_start_addrs.iter().position(|&p| p == self.offset);
 
         if let Some(offset_idx) = offset_idx {
-            if self.jump != -1 {
-                let jump_idx = bb_start_addrs.iter().position(|&p| p == self.jump);
+            let first_edge = edge_list.len();
+            if let Some(jump) = self.jump {
+                let jump_idx = bb_start_addrs.iter().position(|&p| p == jump);
                 if let Some(jump_idx) = jump_idx {
-                    edge_list.push((offset_idx as u32, jump_idx as u32, 1));
+                    edge_list.push((offset_idx as u32, jump_idx as u32, CFG_EDGE_JUMP));
                 }
             }
 
-            if self.fail != -1 {
-                let fail_idx = bb_start_addrs.iter().position(|&p| p == self.fail);
+            if let Some(fail) = self.fail {
+                let fail_idx = bb_start_addrs.iter().position(|&p| p == fail);
                 if let Some(fail_idx) = fail_idx {
-                    edge_list.push((offset_idx as u32, fail_idx as u32, 1));
+                    let weight = if edge_types { CFG_EDGE_FAIL } else { 1 };
+                    edge_list.push((offset_idx as u32, fail_idx as u32, weight));
+                }
+            }
+
+            if !edge_types {
+                return;
+            }
+
+            if let Some(switchop) = &self.switchop {
+                for case in &switchop.cases {
+                    let case_idx = bb_start_addrs.iter().position(|&p| p == case.jump as u64);
+                    if let Some(case_idx) = case_idx {
+                        // Several cases commonly share a target so only add one edge per target
+                        let exists = edge_list[first_edge..]
+                            .iter()
+                            .any(|(_, dst, _)| *dst == case_idx as u32);
+                        if !exists {
+                            edge_list.push((offset_idx as u32, case_idx as u32, CFG_EDGE_SWITCH));
+                        }
+                    }
                 }
             }
         }
@@ -468,13 +715,13 @@ impl ACFJBlock {
 
     // Creates a vector containing the ESIL representation for
     // each instruction within a given basic block
-    pub fn get_esil_bb(&self, reg_norm: bool) -> Vec<String> {
+    pub fn get_esil_bb(&self, norm_config: &NormalizationConfig) -> Vec<String> {
         let mut esil_ins: Vec<String> = Vec::new();
         for op in &self.ops {
             if op.esil.is_some() && op.esil.as_ref().unwrap().len() > 1 {
                 let esil_single = &op.esil.as_ref().unwrap();
                 debug!("ESIL Single (prior to norm): {:?}", esil_single);
-                let normd = normalise_esil_simple(esil_single, &op.r#type, reg_norm);
+                let normd = normalise_esil_simple(esil_single, &op.r#type, norm_config);
                 esil_ins.push((*normd).to_string())
             }
         }
@@ -482,36 +729,45 @@ impl ACFJBlock {
         esil_ins
     }
 
-    pub fn get_disasm_bb(&self, reg_norm: bool) -> Vec<String> {
+    pub fn get_disasm_bb(&self, norm_config: &NormalizationConfig) -> Vec<String> {
         let mut disasm_ins: Vec<String> = Vec::new();
         for op in &self.ops {
             if op.disasm.is_some() && op.disasm.as_ref().unwrap().len() > 1 {
                 let disasm_single = &op.disasm.as_ref().unwrap();
-                let normd = normalise_disasm_simple(disasm_single, reg_norm);
+                let normd = normalise_disasm_simple(disasm_single, norm_config);
                 disasm_ins.push((*normd).to_string());
             }
         }
         disasm_ins
     }
 
-    pub fn get_psuedo_bb(&self, reg_norm: bool) -> Vec<String> {
+    pub fn get_psuedo_bb(&self, norm_config: &NormalizationConfig) -> Vec<String> {
         let mut psuedo_ins: Vec<String> = Vec::new();
         for op in &self.ops {
             if op.opcode.is_some() && op.opcode.as_ref().unwrap().len() > 1 {
                 let opcode_single = &op.opcode.as_ref().unwrap();
-                let normd = normalise_disasm_simple(opcode_single, reg_norm);
+                let normd = normalise_disasm_simple(opcode_single, norm_config);
                 psuedo_ins.push((*normd).to_string());
             }
         }
         psuedo_ins
     }
 
-    pub fn get_ins(&self, reg_norm: bool) -> Vec<String> {
+    /// Returns the raw bytes of the basic block as a hex string, concatenating
+    /// the bytes of each op
+    pub fn get_bytes_bb(&self) -> String {
+        self.ops
+            .iter()
+            .filter_map(|op| op.bytes.as_deref())
+            .collect()
+    }
+
+    pub fn get_ins(&self, norm_config: &NormalizationConfig) -> Vec<String> {
         let mut disasm_ins: Vec<String> = Vec::new();
         for op in &self.ops {
             if op.disasm.is_some() {
                 let disasm_single = &op.disasm.as_ref().unwrap();
-                let normd = normalise_disasm_simple(disasm_single, reg_norm);
+                let normd = normalise_disasm_simple(disasm_single, norm_config);
                 disasm_ins.push((*normd).to_string())
             }
         }
@@ -583,6 +839,42 @@ impl ACFJBlock {
                     }
                     // total
                     features.total += 1.0
+                } else if architecture == "AARCH64" {
+                    // Arith + Shifts
+                    if AARCH64_GRP_ARITH.contains(&opcode) || AARCH64_GRP_SHIFT.contains(&opcode) {
+                        features.arithshift += 1.0
+                    }
+                    // Compare
+                    if AARCH64_GRP_CMP.contains(&opcode) || AARCH64_GRP_FLOAT_CMP.contains(&opcode)
+                    {
+                        features.compare += 1.0
+                    }
+                    // Call Transfer
+                    if AARCH64_GRP_CTRANSFER.contains(&opcode) {
+                        features.ctransfer += 1.0
+                    }
+                    // Call Transfer + Cond
+                    if AARCH64_GRP_CTRANSFER.contains(&opcode)
+                        || AARCH64_GRP_COND_CTRANSFER.contains(&opcode)
+                    {
+                        features.ctransfercond += 1.0
+                    }
+                    // Data Transfer
+                    if AARCH64_GRP_DTRANSFER.contains(&opcode)
+                        || AARCH64_GRP_FLOAT_DTRANSFER.contains(&opcode)
+                    {
+                        features.dtransfer += 1.0
+                    }
+
+                    // FLoat Operations
+                    if AARCH64_GRP_FLOAT_DTRANSFER.contains(&opcode)
+                        || AARCH64_GRP_FLOAT_CMP.contains(&opcode)
+                        || AARCH64_GRP_FLOAT_ARITH.contains(&opcode)
+                    {
+                        features.float += 1.0
+                    }
+                    // total
+                    features.total += 1.0
                 } else if architecture == "MIPS" {
                     // Arith + Shifts
                     if MIPS_GRP_ARITH.contains(&opcode) || MIPS_GRP_SHIFT.contains(&opcode) {
@@ -653,6 +945,41 @@ impl ACFJBlock {
                     }
                     // total
                     features.total += 1.0
+                } else if architecture == "RISCV" {
+                    // Arith + Shifts
+                    if RISCV_GRP_ARITH.contains(&opcode) || RISCV_GRP_SHIFT.contains(&opcode) {
+                        features.arithshift += 1.0
+                    }
+                    // Compare
+                    if RISCV_GRP_CMP.contains(&opcode) || RISCV_GRP_FLOAT_CMP.contains(&opcode) {
+                        features.compare += 1.0
+                    }
+                    // Call Transfer
+                    if RISCV_GRP_CTRANSFER.contains(&opcode) {
+                        features.ctransfer += 1.0
+                    }
+                    // Call Transfer + Cond
+                    if RISCV_GRP_CTRANSFER.contains(&opcode)
+                        || RISCV_GRP_COND_CTRANSFER.contains(&opcode)
+                    {
+                        features.ctransfercond += 1.0
+                    }
+                    // Data Transfer
+                    if RISCV_GRP_DTRANSFER.contains(&opcode)
+                        || RISCV_GRP_FLOAT_DTRANSFER.contains(&opcode)
+                    {
+                        features.dtransfer += 1.0
+                    }
+
+                    // FLoat Operations
+                    if RISCV_GRP_FLOAT_DTRANSFER.contains(&opcode)
+                        || RISCV_GRP_FLOAT_CMP.contains(&opcode)
+                        || RISCV_GRP_FLOAT_ARITH.contains(&opcode)
+                    {
+                        features.float += 1.0
+                    }
+                    // total
+                    features.total += 1.0
                 } else {
                     unreachable!("The architecture provided is not possible.")
                 }
@@ -665,11 +992,363 @@ impl ACFJBlock {
     }
 }
 
+#[cfg(test)]
 mod tests {
+    use crate::bb::{ACFJBlock, BlockFeatureCache, FeatureType};
+    use crate::consts::{
+        AARCH64_OPCODE_HISTOGRAM_VOCAB, CFG_EDGE_FAIL, CFG_EDGE_JUMP, CFG_EDGE_SWITCH,
+        RISCV_OPCODE_HISTOGRAM_VOCAB, SUPPORTED_ARCHITECTURES, X86_OPCODE_HISTOGRAM_VOCAB,
+    };
+    use crate::networkx::{NetworkxDiGraph, NodeType};
+    use petgraph::prelude::Graph;
 
     // Lol - something for anyone reviewing this \o/
     #[test]
     fn test_example_in_bb_rs() {
         assert_eq!(1, 1);
     }
+
+    fn op(opcode: &str, r#type: &str) -> serde_json::Value {
+        serde_json::json!({
+            "offset": 0,
+            "opcode": opcode,
+            "disasm": opcode,
+            "type": r#type,
+        })
+    }
+
+    #[test]
+    fn test_opcode_histogram_features() {
+        let block: ACFJBlock = serde_json::from_value(serde_json::json!({
+            "offset": 4096,
+            "ops": [
+                op("push rbp", "rpush"),
+                op("mov rbp, rsp", "mov"),
+                op("mov eax, 0", "mov"),
+                op("vpbroadcastb ymm0, xmm1", "mov"),
+                op("invalid", "invalid"),
+                op("ret", "ret"),
+            ]
+        }))
+        .unwrap();
+
+        let mut feature_vecs = Vec::new();
+        block.generate_bb_feature_vec(
+            &mut feature_vecs,
+            FeatureType::OpcodeHistogram,
+            &"X86".to_string(),
+        );
+        let histogram = &feature_vecs[0];
+
+        let index_of = |mnemonic: &str| {
+            X86_OPCODE_HISTOGRAM_VOCAB
+                .iter()
+                .position(|ele| *ele == mnemonic)
+                .unwrap()
+        };
+        assert_eq!(histogram.len(), X86_OPCODE_HISTOGRAM_VOCAB.len() + 1);
+        assert_eq!(histogram[index_of("mov")], 2.0);
+        assert_eq!(histogram[index_of("push")], 1.0);
+        assert_eq!(histogram[index_of("ret")], 1.0);
+        // vpbroadcastb is outside of the vocabulary and the invalid op is skipped
+        assert_eq!(histogram[X86_OPCODE_HISTOGRAM_VOCAB.len()], 1.0);
+        assert_eq!(histogram.iter().sum::<f64>(), 5.0);
+    }
+
+    fn riscv_block() -> ACFJBlock {
+        serde_json::from_value(serde_json::json!({
+            "offset": 4096,
+            "ops": [
+                op("addi sp, sp, -32", "add"),
+                op("sd ra, 24(sp)", "store"),
+                op("li a0, 0x10", "mov"),
+                op("slli a1, a0, 0x2", "shl"),
+                op("slt a2, a0, a1", "cmp"),
+                op("jal ra, sym.imp.puts", "call"),
+                op("beqz a0, 0x1040", "cjmp"),
+                op("ld ra, 24(sp)", "load"),
+                op("ret", "ret"),
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_riscv_bb_features() {
+        let block = riscv_block();
+        let riscv = "RISCV".to_string();
+        let mut feature_vecs = Vec::new();
+        block.generate_bb_feature_vec(&mut feature_vecs, FeatureType::Gemini, &riscv);
+        block.generate_bb_feature_vec(&mut feature_vecs, FeatureType::DGIS, &riscv);
+        block.generate_bb_feature_vec(&mut feature_vecs, FeatureType::Tiknib, &riscv);
+        block.generate_bb_feature_vec(&mut feature_vecs, FeatureType::OpcodeHistogram, &riscv);
+
+        // Calls, transfers, arithmetic, instructions, numeric consts, string consts, offspring
+        assert_eq!(feature_vecs[0], vec![1.0, 3.0, 1.0, 9.0, 3.0, 0.0, 2.0]);
+        // Stack, arithmetic, logic, compare, library calls, uncond, cond, generic
+        assert_eq!(
+            feature_vecs[1],
+            vec![0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 3.0]
+        );
+        // Arith + shift, compare, ctransfer, ctransfer + cond, dtransfer, float, total
+        assert_eq!(feature_vecs[2], vec![2.0, 1.0, 2.0, 3.0, 3.0, 0.0, 9.0]);
+        // Every mnemonic is within the vocabulary so nothing lands in the other bucket
+        let histogram = &feature_vecs[3];
+        assert_eq!(histogram.len(), RISCV_OPCODE_HISTOGRAM_VOCAB.len() + 1);
+        assert_eq!(histogram.iter().sum::<f64>(), 9.0);
+        assert_eq!(histogram[RISCV_OPCODE_HISTOGRAM_VOCAB.len()], 0.0);
+    }
+
+    #[test]
+    fn test_aarch64_bb_features() {
+        let block: ACFJBlock = serde_json::from_value(serde_json::json!({
+            "offset": 4096,
+            "ops": [
+                op("stp x29, x30, [sp, -0x20]!", "store"),
+                op("mov x29, sp", "mov"),
+                op("ldp x1, x2, [x0]", "load"),
+                op("add x0, x1, x2", "add"),
+                op("cmp x0, 0x10", "cmp"),
+                op("bl sym.imp.puts", "call"),
+                op("blr x3", "rcall"),
+                op("b.ne 0x1040", "cjmp"),
+                op("ldp x29, x30, [sp], 0x20", "load"),
+                op("ret", "ret"),
+            ]
+        }))
+        .unwrap();
+        let aarch64 = "AARCH64".to_string();
+        let mut feature_vecs = Vec::new();
+        block.generate_bb_feature_vec(&mut feature_vecs, FeatureType::Gemini, &aarch64);
+        block.generate_bb_feature_vec(&mut feature_vecs, FeatureType::DGIS, &aarch64);
+        block.generate_bb_feature_vec(&mut feature_vecs, FeatureType::Tiknib, &aarch64);
+        block.generate_bb_feature_vec(&mut feature_vecs, FeatureType::OpcodeHistogram, &aarch64);
+
+        // bl and blr are both calls and ldp/stp are transfers
+        assert_eq!(feature_vecs[0], vec![2.0, 4.0, 1.0, 10.0, 2.0, 0.0, 2.0]);
+        // Only the ldp/stp which address the stack pointer are stack operations
+        assert_eq!(
+            feature_vecs[1],
+            vec![2.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 3.0]
+        );
+        assert_eq!(feature_vecs[2], vec![1.0, 1.0, 3.0, 4.0, 4.0, 0.0, 10.0]);
+        let histogram = &feature_vecs[3];
+        assert_eq!(histogram.len(), AARCH64_OPCODE_HISTOGRAM_VOCAB.len() + 1);
+        assert_eq!(histogram[AARCH64_OPCODE_HISTOGRAM_VOCAB.len()], 0.0);
+        assert_eq!(histogram.iter().sum::<f64>(), 10.0);
+    }
+
+    #[test]
+    fn test_supported_architectures_generate_features() {
+        assert!(SUPPORTED_ARCHITECTURES.contains(&"AARCH64"));
+        assert!(SUPPORTED_ARCHITECTURES.contains(&"RISCV"));
+
+        // Every architecture accepted by --architecture must be handled by each generator
+        let block = riscv_block();
+        for architecture in SUPPORTED_ARCHITECTURES {
+            let architecture = architecture.to_string();
+            let mut feature_vecs = Vec::new();
+            for feature_type in [
+                FeatureType::Gemini,
+                FeatureType::DiscovRE,
+                FeatureType::DGIS,
+                FeatureType::Tiknib,
+                FeatureType::OpcodeHistogram,
+            ] {
+                block.generate_bb_feature_vec(&mut feature_vecs, feature_type, &architecture);
+            }
+            assert_eq!(feature_vecs.len(), 5, "{}", architecture);
+        }
+    }
+
+    #[test]
+    fn test_block_feature_cache() {
+        let x86 = "X86".to_string();
+        let block = |offset: u64, call: &str| -> ACFJBlock {
+            serde_json::from_value(serde_json::json!({
+                "offset": offset,
+                "ops": [
+                    op("push rbp", "rpush"),
+                    op("mov rbp, rsp", "mov"),
+                    op(call, "call"),
+                    op("ret", "ret"),
+                ]
+            }))
+            .unwrap()
+        };
+        let stub = block(4096, "call sym.imp.puts");
+        let identical_stub = block(8192, "call sym.imp.puts");
+        let local_call = block(12288, "call sym.foo");
+
+        let mut cache = BlockFeatureCache::new(10);
+        let mut feature_vecs = Vec::new();
+        cache.generate_bb_feature_vec(&stub, &mut feature_vecs, FeatureType::DGIS, &x86);
+        cache.generate_bb_feature_vec(&identical_stub, &mut feature_vecs, FeatureType::DGIS, &x86);
+        assert_eq!((cache.hits, cache.misses), (1, 1));
+        assert_eq!(feature_vecs[0], feature_vecs[1]);
+
+        // Only the library call is counted as such so the blocks must not share an entry
+        cache.generate_bb_feature_vec(&local_call, &mut feature_vecs, FeatureType::DGIS, &x86);
+        assert_eq!((cache.hits, cache.misses), (1, 2));
+        assert_ne!(feature_vecs[0], feature_vecs[2]);
+
+        let mut uncached = Vec::new();
+        for bb in [&stub, &identical_stub, &local_call] {
+            bb.generate_bb_feature_vec(&mut uncached, FeatureType::DGIS, &x86);
+        }
+        assert_eq!(feature_vecs, uncached);
+    }
+
+    #[test]
+    fn test_block_feature_cache_evicts_least_recently_used() {
+        let x86 = "X86".to_string();
+        let block = |offset: u64, call: &str| -> ACFJBlock {
+            serde_json::from_value(serde_json::json!({
+                "offset": offset,
+                "ops": [op(call, "call"), op("ret", "ret")]
+            }))
+            .unwrap()
+        };
+        let first = block(4096, "call sym.imp.puts");
+        let second = block(8192, "call sym.imp.printf");
+        let third = block(12288, "call sym.imp.exit");
+
+        let mut cache = BlockFeatureCache::new(2);
+        let mut feature_vecs = Vec::new();
+        for bb in [&first, &second, &first, &third] {
+            cache.generate_bb_feature_vec(bb, &mut feature_vecs, FeatureType::DGIS, &x86);
+        }
+        // The second block was the least recently used so was evicted for the third
+        assert_eq!((cache.hits, cache.misses), (1, 3));
+        assert_eq!(cache.entries.len(), 2);
+        for bb in [&first, &third] {
+            cache.generate_bb_feature_vec(bb, &mut feature_vecs, FeatureType::DGIS, &x86);
+        }
+        assert_eq!((cache.hits, cache.misses), (3, 3));
+        cache.generate_bb_feature_vec(&second, &mut feature_vecs, FeatureType::DGIS, &x86);
+        assert_eq!((cache.hits, cache.misses), (3, 4));
+        assert_eq!(cache.entries.len(), 2);
+    }
+
+    #[test]
+    fn test_block_edges_above_i64_max() {
+        let blocks: Vec<ACFJBlock> = serde_json::from_str(
+            r#"[
+                {"offset": 18446744073709490000, "jump": 18446744073709490100, "fail": 18446744073709490050, "ops": []},
+                {"offset": 18446744073709490050, "ops": []},
+                {"offset": 18446744073709490100, "ops": []}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(blocks[0].jump, Some(18446744073709490100));
+        assert!(blocks[0].jump.unwrap() > 0x7fffffffffffffff);
+        assert_eq!(blocks[1].jump, None);
+        assert_eq!(blocks[1].fail, None);
+        // Missing targets are counted as offspring as they were when stored as -1
+        assert_eq!(blocks[0].get_no_offspring(), 2.0);
+        assert_eq!(blocks[1].get_no_offspring(), 2.0);
+        let block: ACFJBlock = serde_json::from_str(r#"{"jump": 0, "ops": []}"#).unwrap();
+        assert_eq!(block.offset, 0);
+        assert_eq!(block.get_no_offspring(), 1.0);
+
+        let bb_start_addrs: Vec<u64> = blocks.iter().map(|x| x.offset).collect();
+        let mut edge_list = Vec::new();
+        for bb in &blocks {
+            bb.get_block_edges(&bb_start_addrs, &mut edge_list, false);
+        }
+        assert_eq!(edge_list, vec![(0, 2, 1), (0, 1, 1)]);
+    }
+
+    #[test]
+    fn test_cfg_edge_types_branch_and_switch() {
+        let blocks: Vec<ACFJBlock> = serde_json::from_str(
+            r#"[
+                {"offset": 4096, "jump": 4128, "fail": 4112, "ops": []},
+                {"offset": 4112, "jump": 4176, "ops": [], "switchop": {
+                    "cases": [
+                        {"jump": 4128, "offset": 4112, "value": 0},
+                        {"jump": 4144, "offset": 4112, "value": 1},
+                        {"jump": 4144, "offset": 4112, "value": 2},
+                        {"jump": 4160, "offset": 4112, "value": 3}
+                    ],
+                    "defval": 4176, "maxval": 3, "minval": 0, "offset": 4112
+                }},
+                {"offset": 4128, "jump": 4176, "ops": []},
+                {"offset": 4144, "jump": 4176, "ops": []},
+                {"offset": 4160, "jump": 4176, "ops": []},
+                {"offset": 4176, "ops": []}
+            ]"#,
+        )
+        .unwrap();
+
+        let bb_start_addrs: Vec<u64> = blocks.iter().map(|x| x.offset).collect();
+
+        // Without edge types, only jump and fail edges are added and all have a weight of 1
+        let mut edge_list = Vec::new();
+        for bb in &blocks {
+            bb.get_block_edges(&bb_start_addrs, &mut edge_list, false);
+        }
+        assert_eq!(
+            edge_list,
+            vec![
+                (0, 2, 1),
+                (0, 1, 1),
+                (1, 5, 1),
+                (2, 5, 1),
+                (3, 5, 1),
+                (4, 5, 1)
+            ]
+        );
+
+        let mut edge_list = Vec::new();
+        for bb in &blocks {
+            bb.get_block_edges(&bb_start_addrs, &mut edge_list, true);
+        }
+        assert_eq!(
+            edge_list[..6],
+            [
+                (0, 2, CFG_EDGE_JUMP),
+                (0, 1, CFG_EDGE_FAIL),
+                (1, 5, CFG_EDGE_JUMP),
+                (1, 2, CFG_EDGE_SWITCH),
+                (1, 3, CFG_EDGE_SWITCH),
+                (1, 4, CFG_EDGE_SWITCH),
+            ]
+        );
+
+        let graph = Graph::<String, u32>::from_edges(&edge_list);
+        let node_features = vec![Vec::<String>::new(); blocks.len()];
+        let networkx_graph =
+            NetworkxDiGraph::<NodeType>::try_from((&graph, &node_features, FeatureType::Esil))
+                .unwrap();
+
+        let edge_types: Vec<(usize, Option<&str>)> = networkx_graph.adjacency[1]
+            .iter()
+            .map(|edge| (edge.id, edge.edge_type.as_deref()))
+            .collect();
+        assert_eq!(edge_types.len(), 4);
+        for expected in [
+            (5, Some("jump")),
+            (2, Some("switch")),
+            (3, Some("switch")),
+            (4, Some("switch")),
+        ] {
+            assert!(edge_types.contains(&expected));
+        }
+        assert!(networkx_graph
+            .adjacency
+            .iter()
+            .flatten()
+            .all(|edge| edge.weight == 1));
+        assert!(networkx_graph.adjacency[0]
+            .iter()
+            .any(|edge| edge.id == 1 && edge.edge_type.as_deref() == Some("fail")));
+
+        // Edge types are only serialized when requested
+        let json = serde_json::to_value(networkx_graph.clone().with_edge_types(true)).unwrap();
+        assert!(json["adjacency"][1][0]["edgeType"].is_string());
+        let json = serde_json::to_value(networkx_graph.with_edge_types(false)).unwrap();
+        assert!(json["adjacency"][1][0].get("edgeType").is_none());
+    }
 }
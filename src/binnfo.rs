@@ -1,25 +1,251 @@
-use goblin::{error, Object};
+use anyhow::{bail, Result};
+use goblin::mach::{cputype, Mach};
+use goblin::pe::header::{
+    COFF_MACHINE_ARM, COFF_MACHINE_ARM64, COFF_MACHINE_ARMNT, COFF_MACHINE_X86, COFF_MACHINE_X86_64,
+};
+use goblin::{elf, Object};
+use serde::Serialize;
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-pub fn goblin_info(fpath: &PathBuf) -> error::Result<()> {
-    let buffer = fs::read(fpath)?;
-    match Object::parse(&buffer)? {
-        Object::Elf(elf) => {
-            println!("elf: {:#?}", &elf);
-        }
-        Object::PE(pe) => {
-            println!("pe: {:#?}", &pe);
-        }
-        Object::Mach(mach) => {
-            println!("mach: {:#?}", &mach);
-        }
-        Object::Archive(archive) => {
-            println!("archive: {:#?}", &archive);
+/// Summary information gathered for a binary by goblin
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct BinaryInfo {
+    pub format: String,
+    pub arch: Option<String>,
+    pub bits: Option<u8>,
+    pub little_endian: Option<bool>,
+    pub entrypoint: Option<u64>,
+    pub sections: Vec<SectionInfo>,
+    pub num_imports: usize,
+    pub num_exports: usize,
+    pub libraries: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct SectionInfo {
+    pub name: String,
+    pub addr: u64,
+    pub size: u64,
+}
+
+fn bits(is_64: bool) -> Option<u8> {
+    Some(if is_64 { 64 } else { 32 })
+}
+
+fn elf_info(elf: &elf::Elf) -> BinaryInfo {
+    let sections = elf
+        .section_headers
+        .iter()
+        .filter_map(|sh| {
+            elf.shdr_strtab.get_at(sh.sh_name).map(|name| SectionInfo {
+                name: name.to_string(),
+                addr: sh.sh_addr,
+                size: sh.sh_size,
+            })
+        })
+        .filter(|section| !section.name.is_empty())
+        .collect();
+
+    BinaryInfo {
+        format: "elf".to_string(),
+        arch: Some(elf::header::machine_to_str(elf.header.e_machine).to_string()),
+        bits: bits(elf.is_64),
+        little_endian: Some(elf.little_endian),
+        entrypoint: Some(elf.entry),
+        sections,
+        num_imports: elf.dynsyms.iter().filter(|sym| sym.is_import()).count(),
+        num_exports: elf
+            .dynsyms
+            .iter()
+            .filter(|sym| {
+                !sym.is_import() && sym.st_value != 0 && sym.st_bind() != elf::sym::STB_LOCAL
+            })
+            .count(),
+        libraries: elf.libraries.iter().map(|lib| lib.to_string()).collect(),
+    }
+}
+
+fn pe_info(pe: &goblin::pe::PE) -> BinaryInfo {
+    let arch = match pe.header.coff_header.machine {
+        COFF_MACHINE_X86 => "x86".to_string(),
+        COFF_MACHINE_X86_64 => "x86-64".to_string(),
+        COFF_MACHINE_ARM | COFF_MACHINE_ARMNT => "arm".to_string(),
+        COFF_MACHINE_ARM64 => "aarch64".to_string(),
+        machine => format!("{:#x}", machine),
+    };
+    let sections = pe
+        .sections
+        .iter()
+        .map(|section| SectionInfo {
+            name: section.name().unwrap_or_default().to_string(),
+            addr: section.virtual_address as u64,
+            size: section.virtual_size as u64,
+        })
+        .collect();
+
+    BinaryInfo {
+        format: "pe".to_string(),
+        arch: Some(arch),
+        bits: bits(pe.is_64),
+        little_endian: Some(true),
+        entrypoint: Some(pe.entry as u64),
+        sections,
+        num_imports: pe.imports.len(),
+        num_exports: pe.exports.len(),
+        libraries: pe.libraries.iter().map(|lib| lib.to_string()).collect(),
+    }
+}
+
+fn macho_info(macho: &goblin::mach::MachO) -> BinaryInfo {
+    let mut sections = Vec::new();
+    for segment in macho.segments.iter() {
+        if let Ok(segment_sections) = segment.sections() {
+            for (section, _) in segment_sections {
+                sections.push(SectionInfo {
+                    name: section.name().unwrap_or_default().to_string(),
+                    addr: section.addr,
+                    size: section.size,
+                })
+            }
         }
-        Object::Unknown(magic) => {
-            println!("unknown magic: {:#x}", magic)
+    }
+
+    BinaryInfo {
+        format: "mach".to_string(),
+        arch: cputype::get_arch_name_from_types(macho.header.cputype, macho.header.cpusubtype)
+            .map(|arch| arch.to_string()),
+        bits: bits(macho.is_64),
+        little_endian: Some(macho.little_endian),
+        entrypoint: Some(macho.entry),
+        sections,
+        num_imports: macho.imports().map(|imports| imports.len()).unwrap_or(0),
+        num_exports: macho.exports().map(|exports| exports.len()).unwrap_or(0),
+        libraries: macho.libs.iter().map(|lib| lib.to_string()).collect(),
+    }
+}
+
+/// Gather the summary information for a single binary. Fat Mach-O binaries and
+/// archives contain several binaries so are reported as unsupported.
+pub fn goblin_info(fpath: &PathBuf) -> Result<BinaryInfo> {
+    let buffer = fs::read(fpath)?;
+    let info = match Object::parse(&buffer)? {
+        Object::Elf(elf) => elf_info(&elf),
+        Object::PE(pe) => pe_info(&pe),
+        Object::Mach(Mach::Binary(macho)) => macho_info(&macho),
+        Object::Mach(Mach::Fat(_)) => bail!(
+            "{:?} is a fat Mach-O binary which is not supported - Extract the individual architectures first (i.e with lipo)",
+            fpath
+        ),
+        Object::Archive(_) => bail!(
+            "{:?} is an archive which is not supported - Extract the individual object files first (i.e with ar)",
+            fpath
+        ),
+        Object::Unknown(magic) => bail!("{:?} has an unknown magic: {:#x}", fpath, magic),
+    };
+    Ok(info)
+}
+
+impl fmt::Display for BinaryInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let or_unknown = |value: Option<String>| value.unwrap_or_else(|| "unknown".to_string());
+        writeln!(f, "Format: {}", self.format)?;
+        writeln!(f, "Architecture: {}", or_unknown(self.arch.clone()))?;
+        writeln!(f, "Bits: {}", or_unknown(self.bits.map(|b| b.to_string())))?;
+        writeln!(
+            f,
+            "Endianness: {}",
+            or_unknown(
+                self.little_endian.map(|little_endian| {
+                    if little_endian { "little" } else { "big" }.to_string()
+                })
+            )
+        )?;
+        writeln!(
+            f,
+            "Entrypoint: {}",
+            or_unknown(self.entrypoint.map(|entry| format!("{:#x}", entry)))
+        )?;
+        writeln!(f, "Imports: {}", self.num_imports)?;
+        writeln!(f, "Exports: {}", self.num_exports)?;
+        writeln!(f, "Libraries: {}", self.libraries.join(", "))?;
+        writeln!(f, "Sections ({}):", self.sections.len())?;
+        for section in self.sections.iter() {
+            writeln!(
+                f,
+                "  {:<24} {:#012x} {:#x}",
+                section.name, section.addr, section.size
+            )?;
         }
+        Ok(())
+    }
+}
+
+/// Write the info for a binary as JSON to a file or stdout if no path is provided
+pub fn write_goblin_info_json(info: &BinaryInfo, output_path: Option<&Path>) -> Result<()> {
+    match output_path {
+        Some(output_path) => serde_json::to_writer_pretty(fs::File::create(output_path)?, info)?,
+        None => println!("{}", serde_json::to_string_pretty(info)?),
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_goblin_info_json_elf() {
+        let info = goblin_info(&PathBuf::from("test-files/test_bin")).unwrap();
+        assert_eq!(info.format, "elf");
+        assert_eq!(info.bits, Some(64));
+        assert!(!info.sections.is_empty());
+
+        let json = serde_json::to_value(&info).unwrap();
+        for key in [
+            "format",
+            "arch",
+            "bits",
+            "little_endian",
+            "entrypoint",
+            "sections",
+            "num_imports",
+            "num_exports",
+            "libraries",
+        ] {
+            assert!(json.get(key).is_some(), "Missing key {}", key);
+        }
+        assert!(json["sections"][0].get("name").is_some());
+    }
+
+    #[test]
+    fn test_goblin_info_text() {
+        let info = goblin_info(&PathBuf::from("test-files/test_bin")).unwrap();
+        let text = info.to_string();
+        assert!(text.starts_with("Format: elf\n"));
+        assert!(text.contains("Bits: 64\n"));
+        assert!(text.contains(".text"));
+    }
+
+    #[test]
+    fn test_goblin_info_archive_unsupported() {
+        let archive_path =
+            std::env::temp_dir().join(format!("bin2ml-info-archive-{}.a", std::process::id()));
+        let mut archive = b"!<arch>\n".to_vec();
+        archive.extend_from_slice(
+            format!(
+                "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+                "a.o/", 0, 0, 0, 644, 2
+            )
+            .as_bytes(),
+        );
+        archive.extend_from_slice(b"hi");
+        fs::write(&archive_path, archive).unwrap();
+
+        let err = goblin_info(&archive_path).unwrap_err();
+        assert!(err.to_string().contains("not supported"), "{}", err);
+
+        fs::remove_file(&archive_path).unwrap();
+    }
+}
@@ -1,34 +1,41 @@
 use crate::afij::AFIJFunctionInfo;
-use crate::agfj::TikNibFuncFeatures;
-use crate::errors::FileLoadError;
+use crate::agfj::{TikNibFunc, TikNibFuncFeatures};
+use crate::errors::{ExitCode, FileLoadError};
+use crate::extract::StringXrefEntry;
 use crate::files::{AFIJFile, TikNibFuncMetaFile};
-use crate::utils::{get_json_paths_from_dir, get_save_file_path};
+use crate::utils::{get_json_paths_from_dir, get_save_file_path, read_json_to_string};
 use anyhow::{anyhow, Error};
 use ordered_float::OrderedFloat;
 use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
-use std::fs::read_to_string;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::path::{Path, PathBuf};
-use std::process::exit;
 
 #[derive(Debug)]
 pub enum ComboTypes {
     FinfoTikib,
+    FinfoTiknibStrings,
 }
 
 impl ComboTypes {
     pub fn new(combo_type: &str) -> ComboTypes {
         match combo_type {
             "finfo+tiknib" => ComboTypes::FinfoTikib,
+            "finfo+tiknib+strings" => ComboTypes::FinfoTiknibStrings,
             _ => unreachable!("Unable to determine combo type"),
         }
     }
-    pub fn to_combo_file_types(&self) -> Result<(ComboFileTypes, ComboFileTypes), Error> {
+    pub fn to_combo_file_types(&self) -> Result<Vec<ComboFileTypes>, Error> {
         match self {
-            ComboTypes::FinfoTikib => Ok((
+            ComboTypes::FinfoTikib => Ok(vec![
                 ComboFileTypes::AFIJFunctionInfo,
                 ComboFileTypes::TikNibFuncFeatures,
-            )),
+            ]),
+            ComboTypes::FinfoTiknibStrings => Ok(vec![
+                ComboFileTypes::AFIJFunctionInfo,
+                ComboFileTypes::TikNibFuncFeatures,
+                ComboFileTypes::StringXrefs,
+            ]),
         }
     }
 }
@@ -37,22 +44,52 @@ impl ComboTypes {
 pub enum ComboFileTypes {
     AFIJFunctionInfo,
     TikNibFuncFeatures,
+    StringXrefs,
+}
+/// How functions are matched by name when joining combo input files
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComboJoinType {
+    /// Keep every finfo function, filling default features if the other side is missing
+    Left,
+    /// Keep only functions present in both files
+    Inner,
+    /// Keep functions present in either file, filling defaults for the missing side
+    Outer,
+}
+
+impl ComboJoinType {
+    pub fn new(join_type: &str) -> ComboJoinType {
+        match join_type {
+            "left" => ComboJoinType::Left,
+            "inner" => ComboJoinType::Inner,
+            "outer" => ComboJoinType::Outer,
+            _ => unreachable!("Unable to determine join type"),
+        }
+    }
 }
+
 #[derive(Debug)]
 pub struct ComboJob {
     pub combo_type: ComboTypes,
+    pub join_type: ComboJoinType,
     pub input_path: PathBuf,
     pub output_path: PathBuf,
 }
 
 impl ComboJob {
-    pub fn new(combo_type: &str, input_path: &Path, output_path: &Path) -> Result<ComboJob, Error> {
+    pub fn new(
+        combo_type: &str,
+        join_type: &str,
+        input_path: &Path,
+        output_path: &Path,
+    ) -> Result<ComboJob, Error> {
         let combo_type = ComboTypes::new(combo_type);
         let combo_file_types = combo_type.to_combo_file_types();
 
         if combo_file_types.is_ok() {
             Ok(ComboJob {
                 combo_type,
+                join_type: ComboJoinType::new(join_type),
                 input_path: input_path.to_path_buf(),
                 output_path: output_path.to_path_buf(),
             })
@@ -71,57 +108,108 @@ impl ComboJob {
 
         if finfo_paths.len() != tiknib_paths.len() {
             error!("Mismatch in number of files found. Exiting.");
-            exit(1)
+            ExitCode::InputNotFound.exit()
         }
 
         let joint_par_iter = finfo_paths.par_iter().zip(tiknib_paths.par_iter());
         joint_par_iter.for_each(|(finfo, tiknib)| {
             info!("{} -> {}", finfo, tiknib);
+            let generated_combos = self.combine_finfo_tiknib(finfo, tiknib);
+            self.save_combos(finfo, "tiknib", &generated_combos);
+        });
+    }
 
-            let mut finfo_obj: AFIJFile = AFIJFile {
-                filename: finfo.parse().unwrap(),
-                function_info: None,
-                output_path: self.output_path.clone(),
-            };
-            let finfo_load_ret = finfo_obj.load_and_deserialize();
+    /// Combines finfo and tiknib features with the number of strings referenced by each
+    /// function (from the strings-xrefs extraction mode)
+    pub fn process_finfo_tiknib_strings(self) {
+        let mut finfo_paths = get_json_paths_from_dir(&self.input_path, Some("_finfo".to_string()));
+        let mut tiknib_paths =
+            get_json_paths_from_dir(&self.input_path, Some("cfg-tiknib".to_string()));
+        let mut strings_paths =
+            get_json_paths_from_dir(&self.input_path, Some("_strings-xrefs".to_string()));
 
-            let mut tiknib_obj: TikNibFuncMetaFile = TikNibFuncMetaFile {
-                filename: tiknib.parse().unwrap(),
-                function_info: None,
-                output_path: self.output_path.clone(),
-            };
-            let tiknib_load_ret = tiknib_obj.load_and_deserialize();
+        finfo_paths.sort();
+        tiknib_paths.sort();
+        strings_paths.sort();
 
-            let mut generated_combos = Vec::new();
+        if finfo_paths.len() != tiknib_paths.len() || finfo_paths.len() != strings_paths.len() {
+            error!("Mismatch in number of files found. Exiting.");
+            ExitCode::InputNotFound.exit()
+        }
 
-            if finfo_load_ret.is_ok() & tiknib_load_ret.is_ok() {
-                let finfo_obj_functions = finfo_obj.function_info.unwrap();
-                let tiknib_obj_functions = tiknib_obj.function_info.unwrap();
+        let joint_par_iter = finfo_paths
+            .par_iter()
+            .zip(tiknib_paths.par_iter())
+            .zip(strings_paths.par_iter());
+        joint_par_iter.for_each(|((finfo, tiknib), strings)| {
+            info!("{} -> {} -> {}", finfo, tiknib, strings);
+            let generated_combos = self.combine_finfo_tiknib(finfo, tiknib);
 
-                for (finfo, tiknib) in finfo_obj_functions
-                    .into_iter()
-                    .zip(tiknib_obj_functions.into_iter())
-                {
-                    let combined = FinfoTiknib::from((finfo, tiknib.features));
-                    generated_combos.push(combined);
+            let string_xrefs = read_json_to_string(strings)
+                .map_err(FileLoadError::from)
+                .and_then(|data| {
+                    serde_json::from_str::<Vec<StringXrefEntry>>(&data).map_err(FileLoadError::from)
+                });
+            let string_xrefs = match string_xrefs {
+                Ok(string_xrefs) => string_xrefs,
+                Err(e) => {
+                    error!("Failed to load and deserialize {} - {}", strings, e);
+                    Vec::new()
                 }
-            } else {
-                error!("Failed to load and deserialize files");
-            }
-            // Save combined object to JSON file
-            let save_path = get_save_file_path(
-                &finfo_obj.filename.to_owned(),
-                &self.output_path,
-                Some(".json".to_string()),
-                Some("tiknib".to_string()),
-                None,
-            );
-            debug!("Save Path: {:?}", save_path);
+            };
 
-            let save_file = std::fs::File::create(save_path).expect("Unable to create file");
-            serde_json::to_writer(&save_file, &generated_combos).expect("Unable to write to file");
+            let generated_combos = FinfoTiknibStrings::from_combos(generated_combos, &string_xrefs);
+            self.save_combos(finfo, "tiknib-strings", &generated_combos);
         });
     }
+
+    /// Loads a finfo and tiknib file and combines the features of each function,
+    /// matching functions by name according to the job's join type
+    fn combine_finfo_tiknib(&self, finfo: &str, tiknib: &str) -> Vec<FinfoTiknib> {
+        let mut finfo_obj: AFIJFile = AFIJFile {
+            filename: finfo.parse().unwrap(),
+            function_info: None,
+            output_path: self.output_path.clone(),
+        };
+        let finfo_load_ret = finfo_obj.load_and_deserialize();
+
+        let mut tiknib_obj: TikNibFuncMetaFile = TikNibFuncMetaFile {
+            filename: tiknib.parse().unwrap(),
+            function_info: None,
+            output_path: self.output_path.clone(),
+        };
+        let tiknib_load_ret = tiknib_obj.load_and_deserialize();
+
+        let mut generated_combos = Vec::new();
+
+        if finfo_load_ret.is_ok() & tiknib_load_ret.is_ok() {
+            generated_combos = join_finfo_tiknib(
+                finfo_obj.function_info.unwrap(),
+                tiknib_obj.function_info.unwrap(),
+                self.join_type,
+            );
+            info!("{} -> {} combined functions", finfo, generated_combos.len());
+        } else {
+            error!("Failed to load and deserialize files");
+        }
+        generated_combos
+    }
+
+    /// Save combined objects to a JSON file named after the finfo file
+    fn save_combos<T: Serialize>(&self, finfo: &str, suffix: &str, generated_combos: &[T]) {
+        let save_path = get_save_file_path(
+            Path::new(finfo),
+            &self.output_path,
+            Some(".json".to_string()),
+            Some(suffix.to_string()),
+            None,
+            None,
+        );
+        debug!("Save Path: {:?}", save_path);
+
+        let save_file = std::fs::File::create(save_path).expect("Unable to create file");
+        serde_json::to_writer(&save_file, &generated_combos).expect("Unable to write to file");
+    }
     /*
     To be implemented
     pub fn process(&self) {}
@@ -172,7 +260,7 @@ pub struct FinfoTiknibFile {
 
 impl FinfoTiknibFile {
     pub fn load_and_deserialize(&mut self) -> Result<(), FileLoadError> {
-        let data = read_to_string(&self.filename)?;
+        let data = read_json_to_string(&self.filename)?;
 
         #[allow(clippy::expect_fun_call)]
         // Kept in to ensure that the JSON decode error message is printed alongside the filename
@@ -183,6 +271,124 @@ impl FinfoTiknibFile {
     }
 }
 
+/// Joins finfo and tiknib functions by name. Functions missing from one side are
+/// dropped or filled with default values depending on the join type.
+///
+/// Names are not always unique within a binary (i.e. local symbols), so functions
+/// which share a name are matched in the order they appear in each file.
+pub fn join_finfo_tiknib(
+    finfo_functions: Vec<AFIJFunctionInfo>,
+    tiknib_functions: Vec<TikNibFunc>,
+    join_type: ComboJoinType,
+) -> Vec<FinfoTiknib> {
+    let mut tiknib_order: Vec<String> = Vec::new();
+    let mut tiknib_lookup: HashMap<String, VecDeque<TikNibFuncFeatures>> = HashMap::new();
+    for function in tiknib_functions {
+        let entries = tiknib_lookup.entry(function.name.clone()).or_default();
+        if entries.is_empty() {
+            tiknib_order.push(function.name);
+        }
+        entries.push_back(function.features);
+    }
+
+    let finfo_names: HashSet<&String> = finfo_functions.iter().map(|f| &f.name).collect();
+    let tiknib_count: usize = tiknib_lookup.values().map(|entries| entries.len()).sum();
+    let duplicates =
+        (finfo_functions.len() - finfo_names.len()) + (tiknib_count - tiknib_order.len());
+    if duplicates > 0 {
+        warn!(
+            "{} functions share a name with another function - These are matched in the order they appear",
+            duplicates
+        );
+    }
+
+    let mut matched = 0;
+    let mut finfo_only = 0;
+    let mut generated_combos = Vec::new();
+
+    for finfo in finfo_functions {
+        match tiknib_lookup
+            .get_mut(&finfo.name)
+            .and_then(|entries| entries.pop_front())
+        {
+            Some(features) => {
+                matched += 1;
+                generated_combos.push(FinfoTiknib::from((finfo, features)));
+            }
+            None => {
+                finfo_only += 1;
+                if join_type != ComboJoinType::Inner {
+                    generated_combos
+                        .push(FinfoTiknib::from((finfo, TikNibFuncFeatures::default())));
+                }
+            }
+        }
+    }
+
+    let tiknib_only: Vec<(String, TikNibFuncFeatures)> = tiknib_order
+        .into_iter()
+        .flat_map(|name| {
+            let entries = tiknib_lookup.remove(&name).unwrap_or_default();
+            entries
+                .into_iter()
+                .map(move |features| (name.clone(), features))
+        })
+        .collect();
+
+    info!(
+        "Matched {} functions ({} finfo only, {} tiknib only)",
+        matched,
+        finfo_only,
+        tiknib_only.len()
+    );
+
+    if join_type == ComboJoinType::Outer {
+        for (name, features) in tiknib_only {
+            let finfo = AFIJFunctionInfo {
+                name,
+                ..Default::default()
+            };
+            generated_combos.push(FinfoTiknib::from((finfo, features)));
+        }
+    }
+
+    generated_combos
+}
+
+/// A [`FinfoTiknib`] record extended with the number of strings referenced by the function
+#[derive(Default, PartialEq, Clone, Debug, Deserialize, Serialize)]
+pub struct FinfoTiknibStrings {
+    #[serde(flatten)]
+    pub finfo_tiknib: FinfoTiknib,
+    pub num_string_refs: usize,
+}
+
+impl FinfoTiknibStrings {
+    /// Joins each combo with the number of strings which reference the function by name
+    pub fn from_combos(
+        combos: Vec<FinfoTiknib>,
+        string_xrefs: &[StringXrefEntry],
+    ) -> Vec<FinfoTiknibStrings> {
+        let mut string_ref_counts: HashMap<&str, usize> = HashMap::new();
+        for string_xref in string_xrefs {
+            for function_name in &string_xref.referenced_by {
+                *string_ref_counts.entry(function_name.as_str()).or_default() += 1;
+            }
+        }
+
+        combos
+            .into_iter()
+            .map(|finfo_tiknib| FinfoTiknibStrings {
+                num_string_refs: string_ref_counts
+                    .get(finfo_tiknib.name.as_str())
+                    .copied()
+                    .unwrap_or(0),
+                finfo_tiknib,
+            })
+            .collect()
+    }
+}
+
 impl From<(AFIJFunctionInfo, TikNibFuncFeatures)> for FinfoTiknib {
     fn from(value: (AFIJFunctionInfo, TikNibFuncFeatures)) -> Self {
         FinfoTiknib {
@@ -209,3 +415,179 @@ impl From<(AFIJFunctionInfo, TikNibFuncFeatures)> for FinfoTiknib {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract::StringEntry;
+    use std::fs;
+
+    #[test]
+    fn test_finfo_tiknib_strings_combo() {
+        let input_dir =
+            std::env::temp_dir().join(format!("bin2ml-combo-strings-{}", std::process::id()));
+        let output_dir = input_dir.join("output");
+        fs::create_dir_all(&output_dir).unwrap();
+
+        let finfo: Vec<serde_json::Value> = serde_json::from_str(
+            &fs::read_to_string("test-files/cg_dedup/raw/test_bin_finfo.json").unwrap(),
+        )
+        .unwrap();
+        let finfo: Vec<serde_json::Value> = finfo
+            .into_iter()
+            .filter(|function| ["main", "entry0"].contains(&function["name"].as_str().unwrap()))
+            .collect();
+        fs::write(
+            input_dir.join("test_bin_finfo.json"),
+            serde_json::to_string(&finfo).unwrap(),
+        )
+        .unwrap();
+
+        let tiknib: Vec<TikNibFunc> = finfo
+            .iter()
+            .map(|function| TikNibFunc {
+                name: function["name"].as_str().unwrap().to_string(),
+                features: TikNibFuncFeatures::default(),
+            })
+            .collect();
+        fs::write(
+            input_dir.join("test_bin_cfg-tiknib.json"),
+            serde_json::to_string(&tiknib).unwrap(),
+        )
+        .unwrap();
+
+        let string_xref = |string: &str, referenced_by: Vec<&str>| StringXrefEntry {
+            string: StringEntry {
+                string: string.to_string(),
+                ..Default::default()
+            },
+            referenced_by: referenced_by.into_iter().map(|f| f.to_string()).collect(),
+        };
+        let strings = vec![
+            string_xref("usage", vec!["main"]),
+            string_xref("hello", vec!["main", "sym.foo"]),
+        ];
+        fs::write(
+            input_dir.join("test_bin_strings-xrefs.json"),
+            serde_json::to_string(&strings).unwrap(),
+        )
+        .unwrap();
+
+        let combo_job =
+            ComboJob::new("finfo+tiknib+strings", "inner", &input_dir, &output_dir).unwrap();
+        combo_job.process_finfo_tiknib_strings();
+
+        let combos: Vec<serde_json::Value> = serde_json::from_str(
+            &fs::read_to_string(output_dir.join("test_bin_finfo-tiknib-strings.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(combos.len(), 2);
+        for combo in &combos {
+            // The finfo and tiknib fields are flattened into the same record
+            assert!(combo["edges"].is_i64());
+            assert!(combo["avg_total"].is_f64());
+            let expected_refs = if combo["name"] == "main" { 2 } else { 0 };
+            assert_eq!(combo["num_string_refs"], expected_refs);
+        }
+
+        fs::remove_dir_all(&input_dir).unwrap();
+    }
+
+    fn mismatched_finfo_tiknib() -> (Vec<AFIJFunctionInfo>, Vec<TikNibFunc>) {
+        let finfo = ["entry0", "main"]
+            .iter()
+            .map(|name| AFIJFunctionInfo {
+                name: name.to_string(),
+                edges: 1,
+                ..Default::default()
+            })
+            .collect();
+        let tiknib = ["main", "sym.foo"]
+            .iter()
+            .map(|name| TikNibFunc {
+                name: name.to_string(),
+                features: TikNibFuncFeatures {
+                    sum_total: OrderedFloat(4.0),
+                    ..Default::default()
+                },
+            })
+            .collect();
+        (finfo, tiknib)
+    }
+
+    fn combo_names(combos: &[FinfoTiknib]) -> Vec<&str> {
+        combos.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_join_finfo_tiknib_inner() {
+        let (finfo, tiknib) = mismatched_finfo_tiknib();
+        let combos = join_finfo_tiknib(finfo, tiknib, ComboJoinType::Inner);
+        assert_eq!(combo_names(&combos), vec!["main"]);
+        assert_eq!(combos[0].edges, 1);
+        assert_eq!(combos[0].sum_total, OrderedFloat(4.0));
+    }
+
+    #[test]
+    fn test_join_finfo_tiknib_left() {
+        let (finfo, tiknib) = mismatched_finfo_tiknib();
+        let combos = join_finfo_tiknib(finfo, tiknib, ComboJoinType::Left);
+        assert_eq!(combo_names(&combos), vec!["entry0", "main"]);
+        // entry0 has no tiknib features so they are defaulted
+        assert_eq!(combos[0].edges, 1);
+        assert_eq!(combos[0].sum_total, OrderedFloat(0.0));
+        assert_eq!(combos[1].sum_total, OrderedFloat(4.0));
+    }
+
+    #[test]
+    fn test_join_finfo_tiknib_outer() {
+        let (finfo, tiknib) = mismatched_finfo_tiknib();
+        let combos = join_finfo_tiknib(finfo, tiknib, ComboJoinType::Outer);
+        assert_eq!(combo_names(&combos), vec!["entry0", "main", "sym.foo"]);
+        // sym.foo has no finfo so its function info is defaulted
+        assert_eq!(combos[2].edges, 0);
+        assert_eq!(combos[2].sum_total, OrderedFloat(4.0));
+    }
+
+    #[test]
+    fn test_join_finfo_tiknib_duplicate_names() {
+        let finfo = [("sym.helper", 1), ("main", 2), ("sym.helper", 3)]
+            .iter()
+            .map(|(name, edges)| AFIJFunctionInfo {
+                name: name.to_string(),
+                edges: *edges,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let tiknib = [
+            ("sym.helper", 10.0),
+            ("sym.helper", 30.0),
+            ("sym.helper", 50.0),
+        ]
+        .iter()
+        .map(|(name, sum_total)| TikNibFunc {
+            name: name.to_string(),
+            features: TikNibFuncFeatures {
+                sum_total: OrderedFloat(*sum_total),
+                ..Default::default()
+            },
+        })
+        .collect::<Vec<_>>();
+
+        // Duplicates are matched in order rather than collapsing into a single entry
+        let combos = join_finfo_tiknib(finfo.clone(), tiknib.clone(), ComboJoinType::Inner);
+        let matched: Vec<(i64, OrderedFloat<f32>)> =
+            combos.iter().map(|c| (c.edges, c.sum_total)).collect();
+        assert_eq!(
+            matched,
+            vec![(1, OrderedFloat(10.0)), (3, OrderedFloat(30.0))]
+        );
+
+        let combos = join_finfo_tiknib(finfo, tiknib, ComboJoinType::Outer);
+        assert_eq!(
+            combo_names(&combos),
+            vec!["sym.helper", "main", "sym.helper", "sym.helper"]
+        );
+        assert_eq!(combos[3].sum_total, OrderedFloat(50.0));
+    }
+}
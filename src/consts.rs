@@ -1,3 +1,15 @@
+// ARCHITECTURES
+// The architecture names used throughout for architecture specific feature generation
+
+pub const SUPPORTED_ARCHITECTURES: [&str; 5] = ["X86", "ARM", "AARCH64", "MIPS", "RISCV"];
+
+// CFG EDGE TYPES
+// Used as the edge weight within recovered CFG edge lists to record how a block is left
+
+pub const CFG_EDGE_JUMP: u32 = 1;
+pub const CFG_EDGE_FAIL: u32 = 2;
+pub const CFG_EDGE_SWITCH: u32 = 3;
+
 // FEATURE MAPS
 
 pub const GEMINI_FEATURE_MAP: [&str; 7] = [
@@ -30,6 +42,49 @@ pub const DGIS_FEATURE_MAP: [&str; 8] = [
     "num generic ins",
 ];
 
+// OPCODE HISTOGRAM VOCABULARIES
+// Fixed per-architecture mnemonic vocabularies used to generate opcode histogram
+// features. Any mnemonic outside of the vocabulary is counted in a final "other" bucket
+
+pub const X86_OPCODE_HISTOGRAM_VOCAB: [&str; 58] = [
+    "mov", "movzx", "movsx", "movsxd", "lea", "push", "pop", "xchg", "add", "sub", "imul", "mul",
+    "idiv", "div", "inc", "dec", "neg", "and", "or", "xor", "not", "shl", "shr", "sar", "rol",
+    "ror", "test", "cmp", "jmp", "je", "jne", "jg", "jge", "jl", "jle", "ja", "jae", "jb", "jbe",
+    "js", "jns", "call", "ret", "leave", "cmove", "cmovne", "sete", "setne", "cdqe", "cqo",
+    "movss", "movsd", "movaps", "movups", "pxor", "nop", "endbr64", "hlt",
+];
+
+pub const ARM_OPCODE_HISTOGRAM_VOCAB: [&str; 49] = [
+    "mov", "mvn", "ldr", "ldrb", "ldrh", "str", "strb", "strh", "ldm", "stm", "push", "pop", "add",
+    "adc", "sub", "sbc", "rsb", "mul", "mla", "udiv", "sdiv", "and", "orr", "eor", "bic", "lsl",
+    "lsr", "asr", "ror", "cmp", "cmn", "tst", "teq", "b", "bl", "blx", "bx", "beq", "bne", "bgt",
+    "bge", "blt", "ble", "bhi", "bls", "cbz", "cbnz", "svc", "nop",
+];
+
+pub const AARCH64_OPCODE_HISTOGRAM_VOCAB: [&str; 58] = [
+    "mov", "movz", "movk", "ldr", "ldrb", "ldrh", "ldrsw", "ldur", "str", "strb", "strh", "stur",
+    "ldp", "stp", "adr", "adrp", "add", "adds", "sub", "subs", "mul", "madd", "msub", "sdiv",
+    "udiv", "neg", "and", "ands", "orr", "eor", "bic", "lsl", "lsr", "asr", "cmp", "cmn", "tst",
+    "csel", "cset", "b", "bl", "blr", "br", "ret", "b.eq", "b.ne", "b.lt", "b.le", "b.gt", "b.ge",
+    "b.hi", "b.ls", "cbz", "cbnz", "tbz", "tbnz", "svc", "nop",
+];
+
+pub const MIPS_OPCODE_HISTOGRAM_VOCAB: [&str; 51] = [
+    "move", "li", "lui", "lw", "lb", "lbu", "lh", "lhu", "sw", "sb", "sh", "addiu", "addu", "subu",
+    "mult", "multu", "div", "divu", "mflo", "mfhi", "and", "andi", "or", "ori", "xor", "xori",
+    "nor", "sll", "srl", "sra", "sllv", "srlv", "slt", "sltu", "slti", "sltiu", "beq", "bne",
+    "beqz", "bnez", "bgez", "bgtz", "blez", "bltz", "b", "j", "jal", "jalr", "jr", "syscall",
+    "nop",
+];
+
+pub const RISCV_OPCODE_HISTOGRAM_VOCAB: [&str; 62] = [
+    "mv", "li", "la", "lui", "auipc", "lb", "lbu", "lh", "lhu", "lw", "lwu", "ld", "sb", "sh",
+    "sw", "sd", "add", "addi", "addw", "addiw", "sub", "subw", "mul", "div", "divu", "rem", "remu",
+    "and", "andi", "or", "ori", "xor", "xori", "not", "neg", "sll", "slli", "srl", "srli", "sra",
+    "srai", "slt", "sltu", "seqz", "snez", "j", "jal", "jalr", "jr", "call", "tail", "ret", "beq",
+    "bne", "blt", "bge", "bltu", "bgeu", "beqz", "bnez", "ecall", "nop",
+];
+
 // REGISTERS BY SIZE
 // Link to FP regs https://software-dl.ti.com/codegen/docs/tiarmclang/compiler_tools_user_guide/compiler_manual/runtime_environment/register-conventions-stdz0543031.html
 
@@ -2464,6 +2519,46 @@ pub const ARM_CALL: [&str; 2] = ["bl", "blx"];
 
 pub const ARM_COMPARE: [&str; 4] = ["cmp", "cmn", "teq", "tst"];
 
+// AARCH64
+// Kept separate from 32-bit ARM as AArch64 has no conditional execution, uses
+// b.<cond> for conditional branches and replaces push/pop with ldp/stp pair operations
+
+pub const AARCH64_ARITHMETIC: [&str; 34] = [
+    "add", "adds", "sub", "subs", "adc", "adcs", "sbc", "sbcs", "neg", "negs", "mul", "madd",
+    "msub", "mneg", "smull", "umull", "smulh", "umulh", "smaddl", "umaddl", "sdiv", "udiv", "fadd",
+    "fsub", "fmul", "fdiv", "fmadd", "fmsub", "fnmul", "fsqrt", "fabs", "fneg", "fmax", "fmin",
+];
+
+pub const AARCH64_LOGIC: [&str; 12] = [
+    "and", "ands", "orr", "orn", "eor", "eon", "bic", "bics", "lsl", "lsr", "asr", "ror",
+];
+
+// ldp/stp are only counted as stack operations when they address the stack pointer
+pub const AARCH64_STACK: [&str; 2] = ["stp", "ldp"];
+
+pub const AARCH64_UNCOND: [&str; 7] = ["b", "br", "ret", "braa", "brab", "retaa", "retab"];
+
+pub const AARCH64_COND: [&str; 22] = [
+    "b.eq", "b.ne", "b.cs", "b.hs", "b.cc", "b.lo", "b.mi", "b.pl", "b.vs", "b.vc", "b.hi", "b.ls",
+    "b.ge", "b.lt", "b.gt", "b.le", "b.al", "b.nv", "cbz", "cbnz", "tbz", "tbnz",
+];
+
+pub const AARCH64_TRANSFER: [&str; 31] = [
+    "mov", "movz", "movn", "movk", "mvn", "ldr", "ldrb", "ldrh", "ldrsb", "ldrsh", "ldrsw", "ldur",
+    "ldurb", "ldurh", "ldursw", "str", "strb", "strh", "stur", "sturb", "sturh", "ldp", "stp",
+    "ldpsw", "ldxr", "stxr", "ldar", "stlr", "ldaxr", "stlxr", "fmov",
+];
+
+pub const AARCH64_CALL: [&str; 4] = ["bl", "blr", "blraa", "blrab"];
+
+pub const AARCH64_COMPARE: [&str; 7] = ["cmp", "cmn", "tst", "ccmp", "ccmn", "fcmp", "fcmpe"];
+
+// Mnemonics only found in AArch64. Used to split ARM and AArch64 during architecture
+// detection when the bits of the binary were not recorded at extraction time
+pub const AARCH64_ONLY_OPCODES: [&str; 10] = [
+    "blr", "ldp", "stp", "adrp", "ret", "ldur", "stur", "movk", "ldrsw", "csel",
+];
+
 // MIPS
 
 pub const MIPS_ARITHMETIC: [&str; 107] = [
@@ -2612,6 +2707,102 @@ pub const MIPS_CALL: [&str; 8] = [
 
 pub const MIPS_COMPARE: [&str; 4] = ["slt", "sltu", "slti", "sltiu"];
 
+// RISC-V
+// Covers RV32/RV64 base integer, M, A, F and D extensions plus the compressed (c.*)
+// forms and the common assembler pseudo-instructions radare2 emits (mv, li, j, ret etc)
+
+pub const RISCV_ARITHMETIC: [&str; 54] = [
+    "add",
+    "addi",
+    "addw",
+    "addiw",
+    "sub",
+    "subw",
+    "neg",
+    "negw",
+    "mul",
+    "mulh",
+    "mulhsu",
+    "mulhu",
+    "mulw",
+    "div",
+    "divu",
+    "divw",
+    "divuw",
+    "rem",
+    "remu",
+    "remw",
+    "remuw",
+    "lui",
+    "auipc",
+    "c.add",
+    "c.addi",
+    "c.addw",
+    "c.addiw",
+    "c.sub",
+    "c.subw",
+    "c.addi16sp",
+    "c.addi4spn",
+    "c.lui",
+    "fadd.s",
+    "fadd.d",
+    "fsub.s",
+    "fsub.d",
+    "fmul.s",
+    "fmul.d",
+    "fdiv.s",
+    "fdiv.d",
+    "fsqrt.s",
+    "fsqrt.d",
+    "fmadd.s",
+    "fmadd.d",
+    "fmsub.s",
+    "fmsub.d",
+    "fnmadd.s",
+    "fnmadd.d",
+    "fnmsub.s",
+    "fnmsub.d",
+    "fmin.s",
+    "fmin.d",
+    "fmax.s",
+    "fmax.d",
+];
+
+pub const RISCV_LOGIC: [&str; 26] = [
+    "and", "andi", "or", "ori", "xor", "xori", "not", "sll", "slli", "sllw", "slliw", "srl",
+    "srli", "srlw", "srliw", "sra", "srai", "sraw", "sraiw", "c.and", "c.andi", "c.or", "c.xor",
+    "c.slli", "c.srli", "c.srai",
+];
+
+pub const RISCV_UNCOND: [&str; 9] = [
+    "j", "jr", "c.j", "c.jr", "ret", "tail", "ecall", "ebreak", "c.ebreak",
+];
+
+pub const RISCV_COND: [&str; 18] = [
+    "beq", "bne", "blt", "bge", "bltu", "bgeu", "beqz", "bnez", "blez", "bgez", "bltz", "bgtz",
+    "bgt", "ble", "bgtu", "bleu", "c.beqz", "c.bnez",
+];
+
+pub const RISCV_TRANSFER: [&str; 45] = [
+    "lb", "lbu", "lh", "lhu", "lw", "lwu", "ld", "sb", "sh", "sw", "sd", "mv", "li", "la", "lla",
+    "c.mv", "c.li", "c.lw", "c.sw", "c.ld", "c.sd", "c.lwsp", "c.swsp", "c.ldsp", "c.sdsp", "flw",
+    "fld", "fsw", "fsd", "c.flw", "c.fld", "c.fsw", "c.fsd", "c.fldsp", "c.fsdsp", "fmv.s",
+    "fmv.d", "fmv.x.w", "fmv.w.x", "fmv.x.d", "fmv.d.x", "lr.w", "lr.d", "sc.w", "sc.d",
+];
+
+pub const RISCV_CALL: [&str; 5] = ["jal", "jalr", "call", "c.jal", "c.jalr"];
+
+pub const RISCV_COMPARE: [&str; 14] = [
+    "slt", "slti", "sltu", "sltiu", "seqz", "snez", "sltz", "sgtz", "feq.s", "feq.d", "flt.s",
+    "flt.d", "fle.s", "fle.d",
+];
+
+// Mnemonics radare2 emits for RISC-V but not for x86, ARM or MIPS. Used during
+// architecture detection as the RISC-V call mnemonics overlap with x86 and MIPS
+pub const RISCV_ONLY_OPCODES: [&str; 10] = [
+    "mv", "c.ld", "ecall", "addiw", "sext.w", "c.addi", "c.li", "c.mv", "c.jr", "c.sd",
+];
+
 // TikNib Instruction Categories
 // Shamlessly taken from https://github.com/SoftSec-KAIST/TikNib/blob/bb8d3f33808d4cbe8128d52e252525ebd6f05c3e/tiknib/feature/asm_const.py
 // I think all of these have been derived from Capstone some how - Something to look at another day
@@ -3321,6 +3512,67 @@ pub const ARM_GRP_COND_CTRANSFER: [&str; 74] = [
     "cbz", "cbnz",
 ];
 
+// ==================== AARCH64 =============================================
+// data transfer
+// reference : https://developer.arm.com/documentation/ddi0602/latest/
+pub const AARCH64_GRP_DTRANSFER: [&str; 43] = [
+    "ldr", "ldrb", "ldrh", "ldrsb", "ldrsh", "ldrsw", "ldur", "ldurb", "ldurh", "ldursb", "ldursh",
+    "ldursw", "str", "strb", "strh", "stur", "sturb", "sturh", "ldp", "stp", "ldpsw", "ldnp",
+    "stnp", "ldxr", "ldxp", "stxr", "stxp", "ldar", "ldarb", "ldarh", "stlr", "stlrb", "stlrh",
+    "ldaxr", "stlxr", "mov", "movz", "movn", "movk", "mvn", "adr", "adrp", "prfm",
+];
+
+pub const AARCH64_GRP_FLOAT_DTRANSFER: [&str; 22] = [
+    "fmov", "fcvt", "fcvtzs", "fcvtzu", "fcvtas", "fcvtau", "fcvtms", "fcvtmu", "fcvtns", "fcvtnu",
+    "fcvtps", "fcvtpu", "scvtf", "ucvtf", "ld1", "st1", "ld2", "st2", "ins", "dup", "umov", "smov",
+];
+
+// binary arithmetic instructions:
+pub const AARCH64_GRP_ARITH: [&str; 29] = [
+    "add", "adds", "sub", "subs", "adc", "adcs", "sbc", "sbcs", "neg", "negs", "ngc", "ngcs",
+    "mul", "madd", "msub", "mneg", "smull", "umull", "smulh", "umulh", "smaddl", "umaddl",
+    "smsubl", "umsubl", "sdiv", "udiv", "cinc", "cdec", "cneg",
+];
+
+pub const AARCH64_GRP_FLOAT_ARITH: [&str; 23] = [
+    "fadd", "fsub", "fmul", "fdiv", "fmadd", "fmsub", "fnmadd", "fnmsub", "fnmul", "fsqrt", "fabs",
+    "fneg", "fmax", "fmin", "fmaxnm", "fminnm", "frinta", "frinti", "frintm", "frintn", "frintp",
+    "frintx", "frintz",
+];
+
+pub const AARCH64_GRP_SHIFT: [&str; 8] =
+    ["lsl", "lsr", "asr", "ror", "lslv", "lsrv", "asrv", "rorv"];
+
+pub const AARCH64_GRP_CMP: [&str; 11] = [
+    "cmp", "cmn", "tst", "ccmp", "ccmn", "cset", "csetm", "csel", "csinc", "csinv", "csneg",
+];
+
+pub const AARCH64_GRP_FLOAT_CMP: [&str; 5] = ["fcmp", "fcmpe", "fccmp", "fccmpe", "fcsel"];
+
+// Logical Instructions:
+pub const AARCH64_GRP_LOGIC: [&str; 8] = ["and", "ands", "orr", "orn", "eor", "eon", "bic", "bics"];
+
+// bit and byte instructions:
+pub const AARCH64_GRP_BIT: [&str; 19] = [
+    "bfi", "bfxil", "bfm", "sbfm", "ubfm", "sbfx", "ubfx", "sxtb", "sxth", "sxtw", "uxtb", "uxth",
+    "clz", "cls", "rbit", "rev", "rev16", "rev32", "extr",
+];
+
+pub const AARCH64_GRP_MISC: [&str; 13] = [
+    "nop", "hint", "yield", "wfe", "wfi", "sev", "sevl", "dmb", "dsb", "isb", "hlt", "msr", "mrs",
+];
+
+// control transfer instructions:
+pub const AARCH64_GRP_CTRANSFER: [&str; 16] = [
+    "b", "br", "bl", "blr", "ret", "braa", "brab", "blraa", "blrab", "retaa", "retab", "svc",
+    "hvc", "smc", "brk", "eret",
+];
+
+pub const AARCH64_GRP_COND_CTRANSFER: [&str; 22] = [
+    "b.eq", "b.ne", "b.cs", "b.hs", "b.cc", "b.lo", "b.mi", "b.pl", "b.vs", "b.vc", "b.hi", "b.ls",
+    "b.ge", "b.lt", "b.gt", "b.le", "b.al", "b.nv", "cbz", "cbnz", "tbz", "tbnz",
+];
+
 // ==================== MIPS 32 =============================================
 // data transfer
 // refernce : https://www.cs.cornell.edu/courses/cs3410/2008fa/MIPS_Vol2.pdf
@@ -3411,6 +3663,150 @@ pub const MIPS_GRP_COND_CTRANSFER: [&str; 33] = [
     "tne", "tnei", "beql", "bgezall", "bgezl", "bgtzl", "blezl", "bltzall", "bltzl", "bnel",
 ];
 
+// ==================== RISC-V =============================================
+// data transfer
+// reference : https://riscv.org/technical/specifications/
+pub const RISCV_GRP_DTRANSFER: [&str; 34] = [
+    "lb",
+    "lbu",
+    "lh",
+    "lhu",
+    "lw",
+    "lwu",
+    "ld",
+    "sb",
+    "sh",
+    "sw",
+    "sd",
+    "mv",
+    "li",
+    "la",
+    "lla",
+    "lui",
+    "auipc",
+    "c.mv",
+    "c.li",
+    "c.lui",
+    "c.lw",
+    "c.sw",
+    "c.ld",
+    "c.sd",
+    "c.lwsp",
+    "c.swsp",
+    "c.ldsp",
+    "c.sdsp",
+    "lr.w",
+    "lr.d",
+    "sc.w",
+    "sc.d",
+    "amoswap.w",
+    "amoswap.d",
+];
+
+pub const RISCV_GRP_FLOAT_DTRANSFER: [&str; 28] = [
+    "flw",
+    "fld",
+    "fsw",
+    "fsd",
+    "c.flw",
+    "c.fld",
+    "c.fsw",
+    "c.fsd",
+    "c.fldsp",
+    "c.fsdsp",
+    "fmv.s",
+    "fmv.d",
+    "fmv.x.w",
+    "fmv.w.x",
+    "fmv.x.d",
+    "fmv.d.x",
+    "fcvt.w.s",
+    "fcvt.s.w",
+    "fcvt.wu.s",
+    "fcvt.s.wu",
+    "fcvt.w.d",
+    "fcvt.d.w",
+    "fcvt.wu.d",
+    "fcvt.d.wu",
+    "fcvt.l.d",
+    "fcvt.d.l",
+    "fcvt.s.d",
+    "fcvt.d.s",
+];
+
+// binary arithmetic instructions:
+pub const RISCV_GRP_ARITH: [&str; 32] = [
+    "add",
+    "addi",
+    "addw",
+    "addiw",
+    "sub",
+    "subw",
+    "neg",
+    "negw",
+    "mul",
+    "mulh",
+    "mulhsu",
+    "mulhu",
+    "mulw",
+    "div",
+    "divu",
+    "divw",
+    "divuw",
+    "rem",
+    "remu",
+    "remw",
+    "remuw",
+    "sext.w",
+    "c.add",
+    "c.addi",
+    "c.addw",
+    "c.addiw",
+    "c.sub",
+    "c.subw",
+    "c.addi16sp",
+    "c.addi4spn",
+    "amoadd.w",
+    "amoadd.d",
+];
+
+pub const RISCV_GRP_CMP: [&str; 8] = [
+    "slt", "slti", "sltu", "sltiu", "seqz", "snez", "sltz", "sgtz",
+];
+
+pub const RISCV_GRP_FLOAT_CMP: [&str; 8] = [
+    "feq.s", "feq.d", "flt.s", "flt.d", "fle.s", "fle.d", "fclass.s", "fclass.d",
+];
+
+pub const RISCV_GRP_SHIFT: [&str; 15] = [
+    "sll", "slli", "sllw", "slliw", "srl", "srli", "srlw", "srliw", "sra", "srai", "sraw", "sraiw",
+    "c.slli", "c.srli", "c.srai",
+];
+
+pub const RISCV_GRP_FLOAT_ARITH: [&str; 26] = [
+    "fadd.s", "fadd.d", "fsub.s", "fsub.d", "fmul.s", "fmul.d", "fdiv.s", "fdiv.d", "fsqrt.s",
+    "fsqrt.d", "fmadd.s", "fmadd.d", "fmsub.s", "fmsub.d", "fnmadd.s", "fnmadd.d", "fnmsub.s",
+    "fnmsub.d", "fmin.s", "fmin.d", "fmax.s", "fmax.d", "fabs.s", "fabs.d", "fneg.s", "fneg.d",
+];
+
+// Logical Instructions:
+pub const RISCV_GRP_LOGIC: [&str; 11] = [
+    "and", "andi", "or", "ori", "xor", "xori", "not", "c.and", "c.andi", "c.or", "c.xor",
+];
+
+pub const RISCV_GRP_MISC: [&str; 5] = ["nop", "c.nop", "fence", "fence.i", "wfi"];
+
+// control transfer instructions:
+pub const RISCV_GRP_CTRANSFER: [&str; 16] = [
+    "j", "jal", "jr", "jalr", "call", "tail", "ret", "c.j", "c.jal", "c.jr", "c.jalr", "ecall",
+    "ebreak", "c.ebreak", "mret", "sret",
+];
+
+pub const RISCV_GRP_COND_CTRANSFER: [&str; 18] = [
+    "beq", "bne", "blt", "bge", "bltu", "bgeu", "beqz", "bnez", "blez", "bgez", "bltz", "bgtz",
+    "bgt", "ble", "bgtu", "bleu", "c.beqz", "c.bnez",
+];
+
 // ================= POWERPC 32 =============================================
 // data transfer
 // reference : https://www.ibm.com/docs/en/aix/7.3?topic=reference-appendix-f-powerpc-instructions
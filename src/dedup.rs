@@ -1,6 +1,7 @@
 use crate::networkx::{CallGraphNodeFeatureType, CallGraphTypes};
+use crate::utils::{read_json_to_string, write_json_to_file};
 use anyhow::Result;
-use indicatif::ParallelProgressIterator;
+use indicatif::{ProgressBar, ProgressIterator, ProgressStyle};
 use itertools::Itertools;
 use prettytable::row;
 use prettytable::Table;
@@ -11,8 +12,11 @@ use std::collections::hash_map::DefaultHasher;
 use std::collections::{HashMap, HashSet};
 use std::fs::{read_dir, read_to_string, File};
 use std::hash::{Hash, Hasher};
+use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::string::String;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
 
 use std::process::exit;
 use std::{fs, vec};
@@ -37,6 +41,18 @@ impl From<(String, u64, String, String)> for DedupEntry {
     }
 }
 
+/// A function's MinHash cluster assignment produced by [`EsilFuncStringCorpus::minhash_dedup`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MinHashClusterEntry {
+    pub name: String,
+    pub binary: String,
+    pub arch: String,
+    pub cluster: usize,
+}
+
+/// Number of consecutive ESIL tokens used for each MinHash shingle
+const MINHASH_SHINGLE_SIZE: usize = 3;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct EsilFuncString {
     func_name: String,
@@ -230,17 +246,56 @@ impl EsilFuncStringCorpus {
 
     /// Get the unique values within a collection of function has tuples using the
     /// hash as the value to derive unique values
-    fn get_uniques(&self, func_hash_tuples: Vec<(String, u64, String, String)>) -> Vec<DedupEntry> {
+    ///
+    /// If `verify_collisions` is set, entries which share a hash are grouped and their
+    /// data strings compared. Entries whose data differs from every other entry in the
+    /// group are genuine hash collisions so a warning is logged and they are kept.
+    fn get_uniques(
+        &self,
+        func_hash_tuples: Vec<(String, u64, String, String)>,
+        verify_collisions: bool,
+    ) -> Vec<DedupEntry> {
+        let mut seen: HashMap<u64, Vec<String>> = HashMap::new();
         func_hash_tuples
             .into_iter()
-            .unique_by(|s| s.1)
+            .filter(|func_hash_tuple| {
+                Self::is_unique_entry(&mut seen, func_hash_tuple, verify_collisions)
+            })
             .map(DedupEntry::from)
             .collect()
     }
 
-    /// Generate hash statistics from a func hash tuple collection
-    fn hash_stats(&self, original_len: usize, unique_func_has_tuples: &[DedupEntry]) {
-        let unique_len = unique_func_has_tuples.len();
+    /// Check whether a function hash tuple has been seen before, recording it if not.
+    /// Data strings are only stored (and compared) when `verify_collisions` is set
+    fn is_unique_entry(
+        seen: &mut HashMap<u64, Vec<String>>,
+        func_hash_tuple: &(String, u64, String, String),
+        verify_collisions: bool,
+    ) -> bool {
+        match seen.get_mut(&func_hash_tuple.1) {
+            None => {
+                let seen_data = if verify_collisions {
+                    vec![func_hash_tuple.2.clone()]
+                } else {
+                    Vec::new()
+                };
+                seen.insert(func_hash_tuple.1, seen_data);
+                true
+            }
+            Some(seen_data) if verify_collisions && !seen_data.contains(&func_hash_tuple.2) => {
+                warn!(
+                    "Hash collision detected for {} ({}). Keeping both entries",
+                    func_hash_tuple.0, func_hash_tuple.1
+                );
+                seen_data.push(func_hash_tuple.2.clone());
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
+    /// Generate hash statistics from the number of functions before and after dedup
+    fn hash_stats(&self, original_len: usize, unique_len: usize) {
         let percent_difference: f32 =
             ((original_len as f32 - unique_len as f32) / original_len as f32) * 100.0;
 
@@ -262,6 +317,7 @@ impl EsilFuncStringCorpus {
         print_stats: bool,
         just_stats: bool,
         hash_just_value: bool,
+        verify_collisions: bool,
     ) {
         let fp_idxs = self.get_target_binary_fp_idxs(target_binary_name);
         let loaded_subset = self.load_subset(&fp_idxs);
@@ -273,11 +329,11 @@ impl EsilFuncStringCorpus {
         };
 
         let original_len = func_hash_tuples.len();
-        let unique_func_hash_tuples = self.get_uniques(func_hash_tuples);
+        let unique_func_hash_tuples = self.get_uniques(func_hash_tuples, verify_collisions);
 
         if print_stats || just_stats {
             println!("Stats for {}", target_binary_name);
-            self.hash_stats(original_len, &unique_func_hash_tuples);
+            self.hash_stats(original_len, unique_func_hash_tuples.len());
         }
 
         if !just_stats {
@@ -293,6 +349,298 @@ impl EsilFuncStringCorpus {
             .expect("Unable to write JSON");
         }
     }
+
+    /// Split an ESIL function string into overlapping token shingles and hash each one
+    fn esil_shingles(esil_str: &str) -> HashSet<u64> {
+        let tokens: Vec<&str> = esil_str
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|token| !token.is_empty())
+            .collect();
+
+        if tokens.len() <= MINHASH_SHINGLE_SIZE {
+            return HashSet::from([Self::calculate_hash(&tokens)]);
+        }
+
+        tokens
+            .windows(MINHASH_SHINGLE_SIZE)
+            .map(|shingle| Self::calculate_hash(&shingle))
+            .collect()
+    }
+
+    /// Calculate a MinHash signature of length `num_hashes` for an ESIL function string
+    fn minhash_signature(esil_str: &str, num_hashes: usize) -> Vec<u64> {
+        let shingles = Self::esil_shingles(esil_str);
+        (0..num_hashes)
+            .map(|seed| {
+                shingles
+                    .iter()
+                    .map(|shingle| Self::calculate_hash(&(seed, shingle)))
+                    .min()
+                    .unwrap_or(u64::MAX)
+            })
+            .collect()
+    }
+
+    /// Estimate the Jaccard similarity of two functions from their MinHash signatures
+    fn minhash_similarity(sig_a: &[u64], sig_b: &[u64]) -> f32 {
+        let matching = sig_a.iter().zip(sig_b).filter(|(a, b)| a == b).count();
+        matching as f32 / sig_a.len() as f32
+    }
+
+    /// Pick the number of rows per LSH band so that the band collision threshold,
+    /// approximately (1 / bands) ^ (1 / rows), is as close as possible to `threshold`
+    fn lsh_rows_per_band(num_hashes: usize, threshold: f32) -> usize {
+        (1..=num_hashes)
+            .min_by(|a, b| {
+                let band_threshold =
+                    |rows: &usize| (1.0 / (num_hashes / rows) as f32).powf(1.0 / *rows as f32);
+                (band_threshold(a) - threshold)
+                    .abs()
+                    .total_cmp(&(band_threshold(b) - threshold).abs())
+            })
+            .unwrap_or(1)
+    }
+
+    /// Find the root of an element within a union-find parent vector
+    fn find_root(parents: &mut [usize], idx: usize) -> usize {
+        let mut root = idx;
+        while parents[root] != root {
+            root = parents[root];
+        }
+        let mut current = idx;
+        while parents[current] != root {
+            let next = parents[current];
+            parents[current] = root;
+            current = next;
+        }
+        root
+    }
+
+    /// Cluster near-duplicate functions across the whole corpus using MinHash
+    ///
+    /// Each function's ESIL string is broken into token shingles and summarised as a
+    /// MinHash signature of `num_hashes` values. Signatures are bucketed using LSH banding
+    /// and candidate pairs whose estimated Jaccard similarity meets `threshold` are merged
+    /// into the same cluster. Only the signatures are held in memory, not the ESIL strings.
+    /// Cluster assignments for every function are written to `minhash-clusters.json`.
+    pub fn minhash_dedup(
+        &self,
+        num_hashes: usize,
+        threshold: f32,
+        print_stats: bool,
+        just_stats: bool,
+    ) {
+        let mut entries: Vec<MinHashClusterEntry> = Vec::new();
+        let mut signatures: Vec<Vec<u64>> = Vec::new();
+
+        for idx in (0..self.filepaths.len()).progress() {
+            let loaded_file = self.load_subset(&vec![idx]).remove(0);
+            let esil_fstrs = match loaded_file.esil_fstrs {
+                Some(esil_fstrs) => esil_fstrs,
+                None => continue,
+            };
+            let mut file_signatures: Vec<(String, Vec<u64>)> = esil_fstrs
+                .into_par_iter()
+                .map(|(name, esil_str)| {
+                    let signature = Self::minhash_signature(&esil_str, num_hashes);
+                    (name, signature)
+                })
+                .collect();
+            file_signatures.sort_by(|a, b| a.0.cmp(&b.0));
+
+            for (name, signature) in file_signatures {
+                entries.push(MinHashClusterEntry {
+                    name,
+                    binary: loaded_file.binary_name.clone(),
+                    arch: loaded_file.arch.clone(),
+                    cluster: 0,
+                });
+                signatures.push(signature);
+            }
+        }
+
+        let rows_per_band = Self::lsh_rows_per_band(num_hashes, threshold);
+        let mut parents: Vec<usize> = (0..entries.len()).collect();
+        let mut buckets: HashMap<(usize, u64), Vec<usize>> = HashMap::new();
+        for (idx, signature) in signatures.iter().enumerate() {
+            for (band_idx, band) in signature.chunks(rows_per_band).enumerate() {
+                buckets
+                    .entry((band_idx, Self::calculate_hash(&band)))
+                    .or_default()
+                    .push(idx);
+            }
+        }
+
+        for bucket in buckets.values().filter(|bucket| bucket.len() > 1) {
+            for (i, &a) in bucket.iter().enumerate() {
+                for &b in &bucket[i + 1..] {
+                    let (root_a, root_b) = (
+                        Self::find_root(&mut parents, a),
+                        Self::find_root(&mut parents, b),
+                    );
+                    if root_a != root_b
+                        && Self::minhash_similarity(&signatures[a], &signatures[b]) >= threshold
+                    {
+                        parents[root_b.max(root_a)] = root_a.min(root_b);
+                    }
+                }
+            }
+        }
+
+        let mut cluster_ids: HashMap<usize, usize> = HashMap::new();
+        for (idx, entry) in entries.iter_mut().enumerate() {
+            let root = Self::find_root(&mut parents, idx);
+            let next_id = cluster_ids.len();
+            entry.cluster = *cluster_ids.entry(root).or_insert(next_id);
+        }
+
+        if print_stats || just_stats {
+            println!("MinHash stats for entire corpus");
+            self.hash_stats(entries.len(), cluster_ids.len());
+        }
+
+        if !just_stats {
+            let mut fname_string = PathBuf::new();
+            fname_string.push(self.output_path.clone());
+            fname_string.push("minhash-clusters.json");
+            write_json_to_file(&fname_string, &entries).expect("Unable to write JSON");
+        }
+    }
+
+    /// De-duplicate functions across every file within the corpus rather than per binary
+    ///
+    /// Files are loaded and hashed one at a time, keeping the first seen entry for each
+    /// hash. Unique entries are streamed to `global-dedup.json` as they are found so
+    /// only the set of seen hashes needs to be held in memory. When `verify_collisions` is
+    /// set the data strings are also held so hash collisions can be detected.
+    pub fn dedup_global(
+        &self,
+        print_stats: bool,
+        just_stats: bool,
+        hash_just_value: bool,
+        verify_collisions: bool,
+    ) {
+        let mut seen: HashMap<u64, Vec<String>> = HashMap::new();
+        let mut original_len = 0;
+        let mut unique_len = 0;
+
+        let mut writer = if !just_stats {
+            let mut fname_string = PathBuf::new();
+            fname_string.push(self.output_path.clone());
+            fname_string.push("global-dedup.json");
+            let mut writer =
+                BufWriter::new(File::create(fname_string).expect("Failed to create writer"));
+            writer.write_all(b"[").expect("Unable to write JSON");
+            Some(writer)
+        } else {
+            None
+        };
+
+        for idx in (0..self.filepaths.len()).progress() {
+            let loaded_file = self.load_subset(&vec![idx]);
+            let func_hash_tuples = if hash_just_value {
+                self.hash_subset_val(loaded_file)
+            } else {
+                self.hash_subset_key_val(loaded_file)
+            };
+
+            original_len += func_hash_tuples.len();
+            for func_hash_tuple in func_hash_tuples {
+                if Self::is_unique_entry(&mut seen, &func_hash_tuple, verify_collisions) {
+                    unique_len += 1;
+                    if let Some(writer) = writer.as_mut() {
+                        if unique_len > 1 {
+                            writer.write_all(b",").expect("Unable to write JSON");
+                        }
+                        serde_json::to_writer(&mut *writer, &DedupEntry::from(func_hash_tuple))
+                            .expect("Unable to write JSON");
+                    }
+                }
+            }
+        }
+
+        if let Some(mut writer) = writer {
+            writer.write_all(b"]").expect("Unable to write JSON");
+            writer.flush().expect("Unable to write JSON");
+        }
+
+        if print_stats || just_stats {
+            println!("Stats for entire corpus");
+            self.hash_stats(original_len, unique_len);
+        }
+    }
+}
+
+/// Default number of graphs in each chunk when de-duplicating a large binary subset inplace
+pub const DEFAULT_CHUNK_SIZE: usize = 2_000_000;
+/// Default number of graphs a binary subset must exceed before it is chunked inplace
+pub const DEFAULT_CHUNK_THRESHOLD: usize = 2_500_000;
+
+/// Number of graphs before and after de-duplicating a single binary's call graphs
+#[derive(Debug, Clone, PartialEq)]
+pub struct CGDedupStats {
+    pub binary: PathBuf,
+    pub graphs_in: usize,
+    pub graphs_out: usize,
+}
+
+impl CGDedupStats {
+    pub fn num_removed(&self) -> usize {
+        self.graphs_in - self.graphs_out
+    }
+}
+
+/// Tracks the number of graphs processed during a call graph dedup run, updating a
+/// progress bar sized by graph count and periodically logging the processing rate
+struct GraphThroughput {
+    total_graphs: usize,
+    processed: AtomicUsize,
+    start: Instant,
+    progress_bar: ProgressBar,
+}
+
+impl GraphThroughput {
+    /// Number of times throughput is logged over the course of a run
+    const LOG_INTERVALS: usize = 10;
+
+    fn new(total_graphs: usize) -> GraphThroughput {
+        let progress_bar = ProgressBar::new(total_graphs as u64);
+        progress_bar.set_style(
+            ProgressStyle::with_template(
+                "[{elapsed_precise}] {wide_bar} {pos}/{len} graphs ({per_sec}, ETA: {eta})",
+            )
+            .unwrap(),
+        );
+        GraphThroughput {
+            total_graphs,
+            processed: AtomicUsize::new(0),
+            start: Instant::now(),
+            progress_bar,
+        }
+    }
+
+    /// Record that a subset of `num_graphs` graphs has been processed
+    fn record(&self, num_graphs: usize) {
+        self.progress_bar.inc(num_graphs as u64);
+        let before = self.processed.fetch_add(num_graphs, Ordering::Relaxed);
+        let after = before + num_graphs;
+
+        let interval =
+            |processed: usize| processed * Self::LOG_INTERVALS / self.total_graphs.max(1);
+        if interval(after) > interval(before) {
+            let elapsed = self.start.elapsed().as_secs_f64();
+            info!(
+                "Processed {}/{} graphs ({:.1} graphs/sec)",
+                after,
+                self.total_graphs,
+                after as f64 / elapsed.max(f64::EPSILON)
+            );
+        }
+    }
+
+    fn finish(&self) {
+        self.progress_bar.finish();
+    }
 }
 
 /// Struct and Impl for de-duplicating Call Graph Corpus's
@@ -302,6 +650,11 @@ pub struct CGCorpus {
     pub output_path: PathBuf,
     pub filepath_format: String,
     pub node_type: CallGraphNodeFeatureType,
+    pub structural: bool,
+    pub input_root: PathBuf,
+    pub preserve_tree: bool,
+    pub chunk_size: usize,
+    pub chunk_threshold: usize,
 }
 
 impl CGCorpus {
@@ -327,7 +680,8 @@ impl CGCorpus {
             .into_iter()
             .filter_map(|file| file.ok())
         {
-            if file.path().to_string_lossy().ends_with(".json") {
+            let file_path = file.path().to_string_lossy();
+            if file_path.ends_with(".json") || file_path.ends_with(".json.gz") {
                 filepaths.push(PathBuf::from(file.clone().path()));
             }
         }
@@ -342,9 +696,43 @@ impl CGCorpus {
             output_path,
             filepath_format: filepath_format.to_string(),
             node_type,
+            structural: false,
+            input_root: directory.to_owned(),
+            preserve_tree: false,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            chunk_threshold: DEFAULT_CHUNK_THRESHOLD,
         })
     }
 
+    /// Set the chunking used by [`CGCorpus::process_corpus_inplace`]. Binary subsets with
+    /// more than `chunk_threshold` graphs are split into chunks of `chunk_size` graphs
+    pub fn with_chunking(mut self, chunk_size: usize, chunk_threshold: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self.chunk_threshold = chunk_threshold;
+        self
+    }
+
+    /// Save deduplicated graphs using their full path relative to the input root rather
+    /// than only the last two path components. See [`CGCorpus::generate_tree_dedup_filepath`]
+    pub fn with_preserve_tree(mut self, preserve_tree: bool) -> Self {
+        self.preserve_tree = preserve_tree;
+        self
+    }
+
+    /// Deduplicate using a structural hash of each graph rather than a hash of the whole
+    /// graph. See [`CallGraphTypes::structural_hash`]
+    pub fn with_structural_hash(mut self, structural: bool) -> Self {
+        self.structural = structural;
+        self
+    }
+
+    fn graph_hash(&self, data_ele: &Option<CallGraphTypes>) -> u64 {
+        match data_ele {
+            Some(graph) if self.structural => graph.structural_hash(),
+            _ => Self::calculate_hash(data_ele),
+        }
+    }
+
     fn calculate_hash<T: Hash>(t: &T) -> u64 {
         let mut s = DefaultHasher::new();
         t.hash(&mut s);
@@ -352,13 +740,13 @@ impl CGCorpus {
     }
 
     //fn dedup_corpus<N: Hash>(data: &mut Vec<Option<CallGraphTypes>>, filepaths: &mut Vec<String>) {
-    fn dedup_corpus(data: &mut Vec<Option<CallGraphTypes>>, filepaths: &mut Vec<PathBuf>) {
+    fn dedup_corpus(&self, data: &mut Vec<Option<CallGraphTypes>>, filepaths: &mut Vec<PathBuf>) {
         debug!("Creating the removal index");
 
         let mut seen = HashSet::new();
         let mut indices_to_remove = Vec::new();
-        for (i, data_ele) in data.iter_mut().enumerate() {
-            let hash_value = Self::calculate_hash(&data_ele);
+        for (i, data_ele) in data.iter().enumerate() {
+            let hash_value = self.graph_hash(data_ele);
 
             if seen.contains(&hash_value) {
                 indices_to_remove.push(i)
@@ -373,10 +761,10 @@ impl CGCorpus {
         }
     }
 
-    fn dedup_corpus_inplace(data: &mut [Option<CallGraphTypes>], filepaths: &mut [PathBuf]) {
+    fn dedup_corpus_inplace(&self, data: &mut [Option<CallGraphTypes>], filepaths: &mut [PathBuf]) {
         let mut seen = HashSet::new();
         for (i, data_ele) in data.iter().enumerate() {
-            let hash_value = Self::calculate_hash(&data_ele);
+            let hash_value = self.graph_hash(data_ele);
 
             if seen.contains(&hash_value) {
                 let ret = fs::remove_file(&filepaths[i]);
@@ -418,13 +806,14 @@ impl CGCorpus {
     fn get_binary_name_binarycorp(filepath: &PathBuf) -> PathBuf {
         //abseil-cpp-libabsl_flags_internal.so.2103.0.1-O0-0c4d4b7fa8d2e49da0f70b07c726ceaa
         //fifechan-git-libfifechan_sdl.so.0.1.5-O0-39f42250f8e0d261c64854ccacf5a415
+        // Split on the last "-O" so binary names which contain "-O" themselves
+        // (e.g. ogre-next-git-OgreMeshTool) are not truncated
         let binary_intermediate = Path::new(filepath).parent().unwrap().file_name().unwrap();
-        PathBuf::from(
-            binary_intermediate
-                .to_string_lossy()
-                .split("-O")
-                .collect_vec()[0],
-        )
+        let binary_intermediate = binary_intermediate.to_string_lossy();
+        match binary_intermediate.rsplit_once("-O") {
+            Some((binary, _)) => PathBuf::from(binary),
+            None => PathBuf::from(binary_intermediate.as_ref()),
+        }
     }
 
     fn extract_binary_from_fps(&self) -> Vec<PathBuf> {
@@ -445,6 +834,11 @@ impl CGCorpus {
         fp_binaries
     }
 
+    /// Get the unique binary names in the same order as [`CGCorpus::get_unique_binary_fps`]
+    fn get_unique_binaries(fp_binaries: &[PathBuf]) -> Vec<PathBuf> {
+        fp_binaries.iter().unique().cloned().collect()
+    }
+
     fn get_unique_binary_fps(&self, fp_binaries: Vec<PathBuf>) -> Vec<Vec<PathBuf>> {
         // Generate binary specific filepath vectors
         let unique_binaries: Vec<&PathBuf> = fp_binaries.iter().unique().collect();
@@ -458,10 +852,18 @@ impl CGCorpus {
         unique_binaries_fps
     }
 
+    /// Count the total number of graphs across every binary subset
+    fn count_graphs(unique_binaries_fps: &[Vec<PathBuf>]) -> usize {
+        unique_binaries_fps
+            .iter()
+            .map(|fp_subset| fp_subset.len())
+            .sum()
+    }
+
     fn load_subset(&self, fp_subset: &[PathBuf]) -> Vec<Option<CallGraphTypes>> {
         let mut subset_loaded_data = Vec::new();
         for ele in fp_subset.iter() {
-            let data = read_to_string(ele).expect(&format!("Unable to read file - {:?}", ele));
+            let data = read_json_to_string(ele).expect(&format!("Unable to read file - {:?}", ele));
 
             let json = serde_json::from_str::<CallGraphTypes>(&data);
 
@@ -485,31 +887,74 @@ impl CGCorpus {
         subset_loaded_data
     }
 
-    pub fn process_corpus(&self) {
+    /// Print a table of the number of graphs removed for each binary along with a total
+    fn dedup_stats(stats: &[CGDedupStats]) {
+        let mut table = Table::new();
+        table.add_row(row!["Binary", "With Dups", "Without Dups", "Num Removed"]);
+        for binary_stats in stats {
+            table.add_row(row![
+                binary_stats.binary.to_string_lossy(),
+                binary_stats.graphs_in,
+                binary_stats.graphs_out,
+                binary_stats.num_removed()
+            ]);
+        }
+
+        let graphs_in: usize = stats.iter().map(|s| s.graphs_in).sum();
+        let graphs_out: usize = stats.iter().map(|s| s.graphs_out).sum();
+        table.add_row(row!["Total", graphs_in, graphs_out, graphs_in - graphs_out]);
+
+        table.printstd();
+    }
+
+    /// De-duplicate the graphs of each binary within the corpus, saving the unique graphs
+    /// to the output path unless `just_stats` is set. Returns the number of graphs before
+    /// and after de-duplication for each binary
+    pub fn process_corpus(&self, print_stats: bool, just_stats: bool) -> Vec<CGDedupStats> {
         let fp_binaries = self.extract_binary_from_fps();
+        let unique_binaries = Self::get_unique_binaries(&fp_binaries);
 
         // Generate binary specific filepath vectors
         let mut unique_binaries_fps = self.get_unique_binary_fps(fp_binaries);
 
+        let throughput = GraphThroughput::new(Self::count_graphs(&unique_binaries_fps));
+
         info!("Loading the filepaths");
-        unique_binaries_fps
+        let stats: Vec<CGDedupStats> = unique_binaries_fps
             .par_iter_mut()
-            .progress()
             .enumerate()
-            .for_each(|(idx, fp_subset)| {
+            .map(|(idx, fp_subset)| {
+                let graphs_in = fp_subset.len();
                 let mut subset_loaded_data: Vec<Option<CallGraphTypes>> =
                     self.load_subset(fp_subset);
                 debug!(
                     "Starting to deduplicate the corpus - {} (Example: {:?})",
                     idx, fp_subset[0]
                 );
-                Self::dedup_corpus(&mut subset_loaded_data, fp_subset);
+                self.dedup_corpus(&mut subset_loaded_data, fp_subset);
                 let subset_loaded_data: Vec<CallGraphTypes> =
                     subset_loaded_data.into_iter().flatten().collect();
-                debug!("Starting to save - {}", idx);
-                self.save_corpus(subset_loaded_data, fp_subset);
+                let graphs_out = subset_loaded_data.len();
+                if !just_stats {
+                    debug!("Starting to save - {}", idx);
+                    self.save_corpus(subset_loaded_data, fp_subset);
+                }
                 debug!("File processing complete - {}", idx);
-            });
+                throughput.record(graphs_in);
+                CGDedupStats {
+                    binary: unique_binaries[idx].clone(),
+                    graphs_in,
+                    graphs_out,
+                }
+            })
+            .collect();
+        throughput.finish();
+
+        if print_stats || just_stats {
+            Self::dedup_stats(&stats);
+        }
+
+        stats
     }
 
     pub fn process_corpus_inplace(&self) {
@@ -518,44 +963,53 @@ impl CGCorpus {
         // Generate binary specific filepath vectors
         let mut unique_binaries_fps = self.get_unique_binary_fps(fp_binaries);
 
+        let throughput = GraphThroughput::new(Self::count_graphs(&unique_binaries_fps));
+
         info!("Loading the filepaths");
         unique_binaries_fps
             .par_iter_mut()
-            .progress()
             .enumerate()
             .for_each(|(idx, fp_subset)| {
                 debug!("Subset Length: {}", fp_subset.len());
-                if fp_subset.len() > 3500000 {
-                    info!("Encountered a binary subset with more than 2.5M graphs. Chunking. Will have to repeat!");
-                    let mut chunked: Vec<_> = fp_subset
-                        .chunks(2000000)
-                        .map(|s| {
-                            let mut inner_vec = Vec::new();
-                            for ele in s {
-                                inner_vec.push(ele.to_owned());
-                            }
-                            inner_vec
-                        })
-                        .collect();
-
-                    info!("Created {} chunks of 2M (approx.)", chunked.len());
+                if fp_subset.len() > self.chunk_threshold {
+                    info!(
+                        "Encountered a binary subset with more than {} graphs. Chunking. Will have to repeat!",
+                        self.chunk_threshold
+                    );
+                    let mut chunked = self.chunk_subset(fp_subset);
+
+                    info!(
+                        "Created {} chunks of up to {} graphs",
+                        chunked.len(),
+                        self.chunk_size
+                    );
                     for (i, ele) in chunked.iter_mut().enumerate() {
                         let mut subset_loaded_data: Vec<Option<CallGraphTypes>> =
                             self.load_subset(ele);
                         debug!("Starting to deduplicate chunk {} for corpus {}", i, idx);
-                        Self::dedup_corpus_inplace(&mut subset_loaded_data, ele);
+                        self.dedup_corpus_inplace(&mut subset_loaded_data, ele);
                     }
                 } else {
                     let mut subset_loaded_data: Vec<Option<CallGraphTypes>> =
                         self.load_subset(fp_subset);
                     debug!("Starting to deduplicate the corpus - {}", idx);
-                    Self::dedup_corpus_inplace(&mut subset_loaded_data, fp_subset);
+                    self.dedup_corpus_inplace(&mut subset_loaded_data, fp_subset);
                 }
+                throughput.record(fp_subset.len());
             });
+        throughput.finish();
 
         Self::clean_up_empty_dirs(&self.output_path);
     }
 
+    /// Split a binary subset into chunks of at most `chunk_size` filepaths
+    fn chunk_subset(&self, fp_subset: &[PathBuf]) -> Vec<Vec<PathBuf>> {
+        fp_subset
+            .chunks(self.chunk_size.max(1))
+            .map(|chunk| chunk.to_vec())
+            .collect()
+    }
+
     fn clean_up_empty_dirs(output_path: &PathBuf) {
         for dir in WalkDir::new(output_path)
             .into_iter()
@@ -589,32 +1043,61 @@ impl CGCorpus {
 
         final_path
     }
+
+    /// Generate an output path which mirrors the location of `filepath` relative to
+    /// `input_root`. This avoids collisions between binaries in different subtrees which
+    /// share the same parent directory name
+    fn generate_tree_dedup_filepath(
+        output_path: &Path,
+        input_root: &Path,
+        filepath: &Path,
+    ) -> PathBuf {
+        match filepath.strip_prefix(input_root) {
+            Ok(relative_path) => output_path.join(relative_path),
+            Err(_) => {
+                warn!(
+                    "{:?} is not within {:?}. Falling back to the default output path",
+                    filepath, input_root
+                );
+                Self::generate_dedup_filepath(output_path, filepath)
+            }
+        }
+    }
+
     pub fn save_corpus(&self, subset_loaded_data: Vec<CallGraphTypes>, fp_subset: &mut [PathBuf]) {
         subset_loaded_data
             .iter()
             .zip(fp_subset.iter())
             .for_each(|(data_ele, filepath)| {
-                let save_path = Self::generate_dedup_filepath(&self.output_path, filepath);
+                let save_path = if self.preserve_tree {
+                    Self::generate_tree_dedup_filepath(
+                        &self.output_path,
+                        &self.input_root,
+                        filepath,
+                    )
+                } else {
+                    Self::generate_dedup_filepath(&self.output_path, filepath)
+                };
                 let dirs = save_path.parent().unwrap_or(Path::new(""));
                 fs::create_dir_all(dirs).expect("Failed to create output directory!");
 
-                serde_json::to_writer(
-                    &File::create(save_path).expect("Failed to create writer"),
-                    &data_ele,
-                )
-                .expect("Unable to write JSON");
+                // Gzipped (.json.gz) graphs are written back compressed
+                write_json_to_file(save_path, &data_ele).expect("Unable to write JSON");
             });
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::dedup::CGCorpus;
+    use crate::dedup::{CGCorpus, EsilFuncStringCorpus, MinHashClusterEntry};
     use crate::networkx::{
-        CallGraphFuncWithMetadata, CallGraphNodeFeatureType, CallGraphTypes, NetworkxDiGraph,
+        CallGraphFuncNameNode, CallGraphFuncWithMetadata, CallGraphNodeFeatureType, CallGraphTypes,
+        GraphFormat, NetworkxDiGraph,
     };
+    use petgraph::prelude::Graph;
     use std::fs;
     use std::fs::read_to_string;
+    use std::hash::{Hash, Hasher};
     use std::path::{Path, PathBuf};
     use walkdir::WalkDir;
 
@@ -768,7 +1251,7 @@ mod tests {
 
         // Prior to dedup
         assert_eq!(subset_loaded.len(), 8);
-        CGCorpus::dedup_corpus(&mut subset_loaded, &mut unique_binary_fps[0]);
+        corpus.dedup_corpus(&mut subset_loaded, &mut unique_binary_fps[0]);
 
         // Subset
         assert_eq!(subset_loaded.len(), 4);
@@ -948,6 +1431,309 @@ mod tests {
             "gammaray-libgammaray_widget_export_actions-qt5_15-x86_64.so-O1-ba7a0e23ab8cd2ee36c088e3bce111b1_cg-onehopcgcallers-meta/sym.dummy-func-onehopcgcallers-meta.json"
         )),
                    PathBuf::from("gammaray-libgammaray_widget_export_actions-qt5_15-x86_64.so")
+        );
+        assert_eq!(CGCorpus::get_binary_name_binarycorp(&PathBuf::from(
+            "ogre-next-git-OgreMeshTool-O2-0c4d4b7fa8d2e49da0f70b07c726ceaa_cg-onehopcgcallers-meta/sym.dummy-func-onehopcgcallers-meta.json"
+        )),
+                   PathBuf::from("ogre-next-git-OgreMeshTool")
+        )
+    }
+
+    #[test]
+    fn test_esil_global_dedup() {
+        let corpus_dir =
+            std::env::temp_dir().join(format!("bin2ml-esil-global-dedup-{}", std::process::id()));
+        let output_dir = corpus_dir.join("output");
+        fs::create_dir_all(&output_dir).unwrap();
+
+        // The same function appears in two different binaries
+        fs::write(
+            corpus_dir.join("arm_gcc_O0_libfoo-efs.json"),
+            r#"{"memcpy": "rdi,rsi,=", "foo": "1,rax,="}"#,
         )
+        .unwrap();
+        fs::write(
+            corpus_dir.join("x86_gcc_O0_libbar-efs.json"),
+            r#"{"memcpy": "rdi,rsi,=", "bar": "2,rax,="}"#,
+        )
+        .unwrap();
+
+        let corpus = EsilFuncStringCorpus::new(&corpus_dir, &output_dir).unwrap();
+        assert_eq!(corpus.uniq_binaries.len(), 2);
+        corpus.dedup_global(false, false, false, false);
+
+        let deduped: Vec<serde_json::Value> =
+            serde_json::from_str(&read_to_string(output_dir.join("global-dedup.json")).unwrap())
+                .unwrap();
+        assert_eq!(deduped.len(), 3);
+        assert_eq!(
+            deduped
+                .iter()
+                .filter(|entry| entry["name"] == "memcpy")
+                .count(),
+            1
+        );
+
+        fs::remove_dir_all(&corpus_dir).unwrap();
+    }
+
+    /// A hasher which returns the same value for every input to force hash collisions
+    struct CollidingHasher;
+
+    impl Hasher for CollidingHasher {
+        fn finish(&self) -> u64 {
+            42
+        }
+
+        fn write(&mut self, _bytes: &[u8]) {}
+    }
+
+    #[test]
+    fn test_esil_dedup_verify_collisions() {
+        let corpus_dir = std::env::temp_dir().join(format!(
+            "bin2ml-esil-verify-collisions-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&corpus_dir).unwrap();
+        fs::write(
+            corpus_dir.join("x86_gcc_O0_libfoo-efs.json"),
+            r#"{"foo": "1,rax,="}"#,
+        )
+        .unwrap();
+        let corpus = EsilFuncStringCorpus::new(&corpus_dir, &corpus_dir).unwrap();
+
+        let func_hash_tuples: Vec<(String, u64, String, String)> =
+            [("foo", "1,rax,="), ("bar", "2,rax,="), ("foo", "1,rax,=")]
+                .iter()
+                .map(|(name, esil_str)| {
+                    let mut hasher = CollidingHasher;
+                    esil_str.hash(&mut hasher);
+                    (
+                        name.to_string(),
+                        hasher.finish(),
+                        esil_str.to_string(),
+                        "x86".to_string(),
+                    )
+                })
+                .collect();
+
+        // Without verification the colliding entry is silently dropped
+        assert_eq!(corpus.get_uniques(func_hash_tuples.clone(), false).len(), 1);
+
+        // With verification distinct data is kept but true duplicates are still removed
+        let uniques = corpus.get_uniques(func_hash_tuples, true);
+        assert_eq!(uniques.len(), 2);
+        assert_eq!(uniques[0].data, "1,rax,=");
+        assert_eq!(uniques[1].data, "2,rax,=");
+
+        fs::remove_dir_all(&corpus_dir).unwrap();
+    }
+
+    #[test]
+    fn test_esil_minhash_dedup_clusters_near_duplicates() {
+        let corpus_dir =
+            std::env::temp_dir().join(format!("bin2ml-esil-minhash-dedup-{}", std::process::id()));
+        let output_dir = corpus_dir.join("output");
+        fs::create_dir_all(&output_dir).unwrap();
+
+        let esil_str = (0..40)
+            .map(|i| format!("{},rax,+=", i))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let near_dup = esil_str.replacen("20,rax,+=", "20,rbx,+=", 1);
+        let different = (0..40)
+            .map(|i| format!("rsp,[{}],rdi,=", i * 8))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        fs::write(
+            corpus_dir.join("arm_gcc_O0_libfoo-efs.json"),
+            serde_json::to_string(&serde_json::json!({ "foo": esil_str, "baz": different }))
+                .unwrap(),
+        )
+        .unwrap();
+        fs::write(
+            corpus_dir.join("x86_gcc_O0_libbar-efs.json"),
+            serde_json::to_string(&serde_json::json!({ "bar": near_dup })).unwrap(),
+        )
+        .unwrap();
+
+        let corpus = EsilFuncStringCorpus::new(&corpus_dir, &output_dir).unwrap();
+        corpus.minhash_dedup(128, 0.5, false, false);
+
+        let clusters: Vec<MinHashClusterEntry> = serde_json::from_str(
+            &read_to_string(output_dir.join("minhash-clusters.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(clusters.len(), 3);
+        let cluster_of = |name: &str| {
+            clusters
+                .iter()
+                .find(|entry| entry.name == name)
+                .unwrap()
+                .cluster
+        };
+        assert_eq!(cluster_of("foo"), cluster_of("bar"));
+        assert_ne!(cluster_of("foo"), cluster_of("baz"));
+
+        fs::remove_dir_all(&corpus_dir).unwrap();
+    }
+
+    #[test]
+    fn test_preserve_tree_avoids_output_collisions() {
+        let input_dir =
+            std::env::temp_dir().join(format!("bin2ml-cg-preserve-tree-{}", std::process::id()));
+        let output_dir = input_dir.join("deduped");
+        let binary_dir = "x86-gcc-9-O3_nping_cg-onehopcgcallers";
+
+        let mut graph_paths = Vec::new();
+        for (subtree, callee_name) in [("release", "sym.foo"), ("debug", "sym.bar")] {
+            fs::create_dir_all(input_dir.join(subtree).join(binary_dir)).unwrap();
+            let mut graph = Graph::<String, u32>::new();
+            let main = graph.add_node("main".to_string());
+            let callee = graph.add_node(callee_name.to_string());
+            graph.add_edge(main, callee, 1);
+            let graph: NetworkxDiGraph<CallGraphFuncNameNode> = NetworkxDiGraph::from(graph);
+
+            let graph_path = input_dir
+                .join(subtree)
+                .join(binary_dir)
+                .join("main-onehopcgcallers.json");
+            graph.save(&graph_path, &GraphFormat::Json).unwrap();
+            graph_paths.push(graph_path);
+        }
+
+        // Without preserving the tree both graphs map onto the same output path
+        assert_eq!(
+            CGCorpus::generate_dedup_filepath(&output_dir, &graph_paths[0]),
+            CGCorpus::generate_dedup_filepath(&output_dir, &graph_paths[1])
+        );
+
+        let corpus = CGCorpus::new(
+            &input_dir,
+            &output_dir,
+            &"cisco".to_string(),
+            CallGraphNodeFeatureType::CGName,
+        )
+        .unwrap()
+        .with_preserve_tree(true);
+        corpus.process_corpus(false, false);
+
+        for subtree in ["release", "debug"] {
+            assert!(output_dir
+                .join(subtree)
+                .join(binary_dir)
+                .join("main-onehopcgcallers.json")
+                .is_file());
+        }
+
+        fs::remove_dir_all(&input_dir).unwrap();
+    }
+
+    #[test]
+    fn test_cg_dedup_stats_match_saved_graphs() {
+        let output_dir =
+            std::env::temp_dir().join(format!("bin2ml-cg-dedup-stats-{}", std::process::id()));
+        let corpus = CGCorpus::new(
+            &PathBuf::from("test-files/cg_dedup/to_dedup"),
+            &output_dir,
+            &"cisco".to_string(),
+            CallGraphNodeFeatureType::CGMeta,
+        )
+        .unwrap();
+        let num_input_graphs = corpus.filepaths.len();
+
+        let stats = corpus.process_corpus(false, false);
+        let num_saved_graphs = WalkDir::new(&output_dir)
+            .into_iter()
+            .filter_map(|file| file.ok())
+            .filter(|file| file.path().is_file())
+            .count();
+
+        assert_eq!(stats.len(), 2);
+        assert_eq!(
+            stats.iter().map(|s| s.graphs_in).sum::<usize>(),
+            num_input_graphs
+        );
+        assert_eq!(
+            stats.iter().map(|s| s.num_removed()).sum::<usize>(),
+            num_input_graphs - num_saved_graphs
+        );
+
+        fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn test_count_graphs() {
+        let corpus = CGCorpus::new(
+            &PathBuf::from("test-files/cg_dedup/to_dedup"),
+            &std::env::temp_dir().join(format!("bin2ml-cg-count-{}", std::process::id())),
+            &"cisco".to_string(),
+            CallGraphNodeFeatureType::CGMeta,
+        )
+        .unwrap();
+        let fp_binaries = corpus.extract_binary_from_fps();
+        let unique_binary_fps = corpus.get_unique_binary_fps(fp_binaries);
+
+        assert_eq!(unique_binary_fps.len(), 2);
+        assert_eq!(CGCorpus::count_graphs(&unique_binary_fps), 12);
+
+        fs::remove_dir_all(&corpus.output_path).unwrap();
+    }
+
+    #[test]
+    fn test_inplace_chunking_threshold() {
+        let corpus = CGCorpus::new(
+            &PathBuf::from("test-files/cg_dedup/to_dedup"),
+            &std::env::temp_dir().join(format!("bin2ml-cg-chunking-{}", std::process::id())),
+            &"cisco".to_string(),
+            CallGraphNodeFeatureType::CGMeta,
+        )
+        .unwrap()
+        .with_chunking(3, 5);
+        assert_eq!(corpus.chunk_size, 3);
+        assert_eq!(corpus.chunk_threshold, 5);
+
+        assert!(corpus.filepaths.len() > corpus.chunk_threshold);
+        let chunks = corpus.chunk_subset(&corpus.filepaths);
+        assert_eq!(chunks.len(), corpus.filepaths.len().div_ceil(3));
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 3));
+        assert_eq!(chunks.concat(), corpus.filepaths);
+
+        fs::remove_dir_all(&corpus.output_path).unwrap();
+    }
+
+    #[test]
+    fn test_cg_corpus_loads_compressed_graphs() {
+        let input_dir = std::env::temp_dir().join(format!("bin2ml-cg-gz-{}", std::process::id()));
+        let binary_dir = input_dir.join("x86-gcc-9-O3_nping_cg-onehopcgcallers-meta");
+        fs::create_dir_all(&binary_dir).unwrap();
+
+        let mut graph = Graph::<String, u32>::new();
+        let main = graph.add_node("main".to_string());
+        let callee = graph.add_node("sym.foo".to_string());
+        graph.add_edge(main, callee, 1);
+        let graph: NetworkxDiGraph<CallGraphFuncNameNode> = NetworkxDiGraph::from(graph);
+
+        let graph_path = binary_dir.join(format!(
+            "main-onehopcgcallers.{}",
+            GraphFormat::JsonGz.extension()
+        ));
+        graph.save(&graph_path, &GraphFormat::JsonGz).unwrap();
+        assert_eq!(fs::read(&graph_path).unwrap()[..2], [0x1f, 0x8b]);
+
+        let corpus = CGCorpus::new(
+            &input_dir,
+            &input_dir.join("deduped"),
+            &"cisco".to_string(),
+            CallGraphNodeFeatureType::CGName,
+        )
+        .unwrap();
+        assert_eq!(corpus.filepaths, vec![graph_path]);
+
+        let loaded = corpus.load_subset(&corpus.filepaths);
+        assert_eq!(loaded, vec![Some(CallGraphTypes::CGName(graph))]);
+
+        fs::remove_dir_all(&input_dir).unwrap();
     }
 }
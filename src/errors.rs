@@ -1,5 +1,6 @@
 use std::fmt::Display;
 use std::io;
+use std::process;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -33,3 +34,101 @@ impl Display for FileLoadError {
         }
     }
 }
+
+/// Errors raised when parsing an output template (i.e `{arch}/{binary}-{mode}`)
+#[derive(Error, Debug, PartialEq)]
+pub enum OutputTemplateError {
+    Empty,
+    UnknownPlaceholder(String),
+    UnclosedPlaceholder(String),
+}
+
+impl Display for OutputTemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            OutputTemplateError::Empty => f.write_str("output template is empty"),
+            OutputTemplateError::UnknownPlaceholder(placeholder) => write!(
+                f,
+                "unknown placeholder {{{}}} in output template - expected one of {}",
+                placeholder,
+                crate::utils::OUTPUT_TEMPLATE_PLACEHOLDERS
+                    .map(|p| format!("{{{}}}", p))
+                    .join(", ")
+            ),
+            OutputTemplateError::UnclosedPlaceholder(template) => {
+                write!(f, "unclosed placeholder in output template {}", template)
+            }
+        }
+    }
+}
+
+/// Exit codes returned by the CLI so that it can be reliably scripted around
+///
+/// BadArgs matches the code used by clap when it fails to parse the arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    Ok = 0,
+    Failure = 1,
+    BadArgs = 2,
+    InputNotFound = 3,
+    R2Failure = 4,
+    PartialSuccess = 5,
+}
+
+impl ExitCode {
+    pub fn exit(self) -> ! {
+        process::exit(self as i32)
+    }
+}
+
+/// Counts of the files handled by a run, printed to stderr once it completes
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RunSummary {
+    pub processed: usize,
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+impl RunSummary {
+    /// A run where only some files failed is a partial success. If every file
+    /// failed then r2 is assumed to be the problem.
+    pub fn exit_code(&self) -> ExitCode {
+        match (self.processed, self.failed) {
+            (_, 0) => ExitCode::Ok,
+            (0, _) => ExitCode::R2Failure,
+            _ => ExitCode::PartialSuccess,
+        }
+    }
+}
+
+impl Display for RunSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} file(s) processed, {} file(s) skipped, {} file(s) failed",
+            self.processed, self.skipped, self.failed
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_summary_exit_code() {
+        let summary = |processed, failed| RunSummary {
+            processed,
+            skipped: 1,
+            failed,
+        };
+        assert_eq!(summary(3, 0).exit_code(), ExitCode::Ok);
+        assert_eq!(summary(0, 0).exit_code(), ExitCode::Ok);
+        assert_eq!(summary(2, 1).exit_code(), ExitCode::PartialSuccess);
+        assert_eq!(summary(0, 3).exit_code(), ExitCode::R2Failure);
+        assert_eq!(
+            summary(2, 1).to_string(),
+            "2 file(s) processed, 1 file(s) skipped, 1 file(s) failed"
+        );
+    }
+}
@@ -1,30 +1,58 @@
 use crate::afij::AFIJFunctionInfo;
 use crate::agcj::AGCJFunctionCallGraph;
+use crate::agfj::AGFJFunc;
+use crate::bb::FeatureType;
+use crate::features::byte_entropy::{
+    byte_entropy_histogram, shannon_entropy, BYTE_ENTROPY_STEP, BYTE_ENTROPY_WINDOW,
+};
+use crate::features::byte_histogram::byte_histogram;
+use crate::features::string_stats::compute_string_stats;
+use crate::files::AGFJFile;
+use crate::networkx::GraphFormat;
+use crate::normalisation::NormalizationConfig;
+use crate::storage::sqlite::FunctionStore;
+use crate::utils::{
+    demangle_function_name, get_save_file_path, infer_arch_from_path, is_gzipped, parse_hex_string,
+    to_hex_string, OutputTemplate, OutputTemplateValues,
+};
 
 use anyhow::anyhow;
 use anyhow::bail;
 use anyhow::Error;
 use anyhow::Result;
+use flate2::read::GzDecoder;
+use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
+use itertools::Itertools;
 use r2pipe::R2Pipe;
 use r2pipe::R2PipeSpawnOptions;
+use rayon::prelude::*;
+use regex::Regex;
 
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_aux::prelude::*;
 use serde_json;
+use sha2::{Digest, Sha256};
 
 use serde_json::{json, Value};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashSet};
 use std::env;
 
 use std::fs;
 use std::fs::File;
+use std::io::{BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 use walkdir::WalkDir;
 
 #[derive(PartialEq, Debug)]
 pub enum PathType {
     File,
     Dir,
+    Archive, // A tar archive (optionally gzipped) of binaries
     Unk,
 }
 #[derive(Debug, PartialEq)]
@@ -34,6 +62,7 @@ pub enum ExtractionJobType {
     BasicBlocks,
     RegisterBehaviour,
     FunctionXrefs,
+    DataXrefs,
     CFG,
     CallGraphs,
     FuncInfo,
@@ -41,8 +70,62 @@ pub enum ExtractionJobType {
     PCodeFunc,
     PCodeBB,
     LocalVariableXrefs,
+    DefUseChains,
     GlobalStrings,
+    GlobalStringsXrefs,
+    StringStats,
+    Sections,
+    Header,
     FunctionBytes,
+    Imports,
+    Exports,
+    Relocations,
+    EntryPoints,
+    ByteEntropy,
+    ByteHistogram,
+    CFGEnriched,
+    MachO,
+}
+
+impl ExtractionJobType {
+    /// Whether the job type requires radare2 or just operates on the raw file bytes
+    pub fn requires_r2(&self) -> bool {
+        !matches!(
+            self,
+            ExtractionJobType::ByteEntropy | ExtractionJobType::ByteHistogram
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Json,   // Single JSON document per binary
+    Ndjson, // One JSON object per line (per function)
+}
+
+/// The radare2 analysis run on each binary before extraction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnalysisLevel {
+    Aa,   // Basic analysis
+    Aaa,  // Extended analysis
+    Aaaa, // Experimental analysis including emulation. Slowest but recovers the most functions
+}
+
+impl AnalysisLevel {
+    /// The r2 command used to run the analysis
+    pub fn command(&self) -> &'static str {
+        match self {
+            AnalysisLevel::Aa => "aa",
+            AnalysisLevel::Aaa => "aaa",
+            AnalysisLevel::Aaaa => "aaaa",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BytesFormat {
+    Bin,     // One .bin file per function within a directory per binary
+    Archive, // Single JSON document per binary mapping each function to its hex encoded bytes
 }
 
 #[derive(Debug)]
@@ -52,6 +135,148 @@ pub struct FileToBeProcessed {
     pub job_type_suffix: String,
     pub r2p_config: R2PipeConfig,
     pub with_annotations: bool,
+    pub output_format: OutputFormat,
+    pub func_filename: String,
+    pub feature_type: Option<FeatureType>,
+    pub sqlite_path: Option<PathBuf>,
+    pub nested_output: bool,
+    pub bytes_format: BytesFormat,
+    /// Optional naming scheme for output files. See [`OutputTemplate`]
+    pub output_template: Option<OutputTemplate>,
+    /// SHA-256 checksums of each output file written for the binary
+    pub output_checksums: Mutex<Vec<OutputChecksum>>,
+}
+
+/// SHA-256 checksum of a single extracted output file
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputChecksum {
+    pub path: PathBuf,
+    pub sha256: String,
+}
+
+/// Writer for an extracted output file which tees the written bytes through a
+/// SHA-256 hasher. The checksum of the file is recorded once the writer is dropped,
+/// as long as every write and the final flush succeeded.
+pub struct ChecksummedWriter<'a> {
+    writer: BufWriter<File>,
+    hasher: Sha256,
+    path: PathBuf,
+    checksums: &'a Mutex<Vec<OutputChecksum>>,
+    // The first error hit while writing, reported by finish
+    error: Option<String>,
+}
+
+impl ChecksummedWriter<'_> {
+    fn record_error(&mut self, e: &std::io::Error) {
+        if self.error.is_none() {
+            self.error = Some(e.to_string())
+        }
+    }
+
+    /// Flushes the file, returning an error if any write to it failed
+    fn finish(mut self) -> Result<()> {
+        // A failed flush is recorded as the first error if nothing else failed
+        let _ = self.flush();
+        match &self.error {
+            Some(e) => bail!("Unable to write {:?} - {}", self.path, e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Write for ChecksummedWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self
+            .writer
+            .write(buf)
+            .inspect_err(|e| self.record_error(e))?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush().inspect_err(|e| self.record_error(e))
+    }
+}
+
+impl Drop for ChecksummedWriter<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            error!("Unable to flush {:?}: {}", self.path, e);
+        }
+        if self.error.is_some() {
+            warn!(
+                "Not recording a checksum for {:?} as writing to it failed",
+                self.path
+            );
+            return;
+        }
+        let checksum = OutputChecksum {
+            path: self.path.clone(),
+            sha256: format!("{:x}", self.hasher.finalize_reset()),
+        };
+        if let Ok(mut checksums) = self.checksums.lock() {
+            checksums.retain(|existing| existing.path != checksum.path);
+            checksums.push(checksum);
+        }
+    }
+}
+
+/// Summary of a single extraction mode run against a binary
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModeResult {
+    pub mode: String,
+    pub success: bool,
+    pub functions_processed: usize,
+    pub functions_skipped: usize,
+    pub errors: Vec<String>,
+}
+
+impl ModeResult {
+    fn new(mode: &str) -> ModeResult {
+        ModeResult {
+            mode: mode.to_string(),
+            success: true,
+            ..Default::default()
+        }
+    }
+
+    fn with_functions_processed(mut self, functions_processed: usize) -> ModeResult {
+        self.functions_processed = functions_processed;
+        self
+    }
+
+    /// Logs and records an error for a single function which has been skipped
+    fn skip(&mut self, error: String) {
+        error!("{}", error);
+        self.functions_skipped += 1;
+        self.errors.push(error);
+    }
+
+    /// Logs and records an error which caused the whole mode to fail
+    fn fail(mut self, error: String) -> ModeResult {
+        error!("{}", error);
+        self.success = false;
+        self.errors.push(error);
+        self
+    }
+
+    /// Fails the mode if writing its output failed
+    fn with_output_written(self, written: Result<()>) -> ModeResult {
+        match written {
+            Ok(()) => self,
+            Err(e) => self.fail(e.to_string()),
+        }
+    }
+}
+
+// Manifest written alongside the extracted outputs for a binary
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExtractionManifest {
+    pub binary_path: PathBuf,
+    pub modes: Vec<ModeResult>,
+    #[serde(default)]
+    pub outputs: Vec<OutputChecksum>,
 }
 
 #[derive(Debug)]
@@ -61,13 +286,194 @@ pub struct ExtractionJob {
     pub job_type: ExtractionJobType,
     pub files_to_be_processed: Vec<FileToBeProcessed>,
     pub output_path: PathBuf, // Remove - Kept for backwards compat
+    // The temporary directory an archive input was unpacked into. Removed on drop.
+    pub archive_dir: Option<UnpackedArchive>,
+}
+
+/// A temporary directory an archive has been unpacked into
+///
+/// The directory is removed when this is dropped, so it is cleaned up even if
+/// unpacking or creating the extraction job fails part way through.
+#[derive(Debug)]
+pub struct UnpackedArchive {
+    path: PathBuf,
+}
+
+impl UnpackedArchive {
+    fn create(path: PathBuf) -> Result<UnpackedArchive, Error> {
+        fs::create_dir_all(&path)?;
+        Ok(UnpackedArchive { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for UnpackedArchive {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.path) {
+            warn!("Unable to remove unpacked archive {:?} - {}", self.path, e)
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct R2PipeConfig {
     pub debug: bool,
-    pub extended_analysis: bool,
+    pub analysis_level: AnalysisLevel,
     pub use_curl_pdb: bool,
+    pub func_timeout: Option<Duration>,
+    pub r2_path: PathBuf,
+    pub intra_file_threads: usize,
+    pub progress: bool,
+    pub function_filter: Option<FunctionFilter>,
+    pub auto_reanalyze: bool,
+    pub public_only: bool,
+    pub key_by_offset: bool,
+    pub asm_pseudo: bool,
+    pub asm_comments: bool,
+    pub demangle: bool,
+    pub max_func_bytes: u64,
+    pub min_instructions: u64,
+    /// The min number of basic blocks for a function to be included in cfg-enriched output
+    pub min_blocks: u16,
+    /// A file of r2 commands (or an r2 project directory) applied after analysis
+    pub r2_script: Option<PathBuf>,
+}
+
+impl R2PipeConfig {
+    /// Arguments passed to r2 when spawning a new pipe
+    ///
+    /// asm.pseudo and asm.comments are always set explicitly as they change the
+    /// disassembly returned for a function and therefore the resulting tokens.
+    fn spawn_args(&self) -> Vec<&'static str> {
+        let mut args = if self.debug {
+            vec!["-e bin.cache=true", "-e log.level=0"]
+        } else {
+            vec!["-e bin.cache=true", "-e log.level=1", "-2"]
+        };
+        args.push(if self.asm_pseudo {
+            "-e asm.pseudo=true"
+        } else {
+            "-e asm.pseudo=false"
+        });
+        args.push(if self.asm_comments {
+            "-e asm.comments=true"
+        } else {
+            "-e asm.comments=false"
+        });
+        args
+    }
+
+    /// The r2 commands to run after the analysis step
+    ///
+    /// A directory is treated as an r2 project and opened with `Po`. As `Po` takes a
+    /// project name rather than a path, `dir.projects` is pointed at the parent
+    /// directory first. Otherwise the file is read as one r2 command per line,
+    /// skipping blank lines and # comments.
+    fn r2_script_commands(&self) -> Result<Vec<String>, Error> {
+        let r2_script = match &self.r2_script {
+            Some(r2_script) => r2_script,
+            None => return Ok(Vec::new()),
+        };
+
+        if r2_script.is_dir() {
+            let project_dir = fs::canonicalize(r2_script)
+                .map_err(|e| anyhow!("Unable to open r2 project {:?} - {}", r2_script, e))?;
+            let (projects_dir, project_name) = match (project_dir.parent(), project_dir.file_name())
+            {
+                (Some(projects_dir), Some(project_name)) => (projects_dir, project_name),
+                _ => bail!("{:?} is not a valid r2 project directory", r2_script),
+            };
+            return Ok(vec![
+                format!("e dir.projects={}", projects_dir.display()),
+                format!("Po {}", project_name.to_string_lossy()),
+            ]);
+        }
+
+        let script = fs::read_to_string(r2_script)
+            .map_err(|e| anyhow!("Unable to read r2 script {:?} - {}", r2_script, e))?;
+        Ok(script
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.to_string())
+            .collect())
+    }
+}
+
+/// Restricts extraction to a subset of functions within a binary
+///
+/// Each entry is either a 0x prefixed function address or a symbol name. Symbol
+/// names can contain glob style wildcards (i.e sym.*crypto*).
+#[derive(Debug, Clone)]
+pub struct FunctionFilter {
+    addresses: Vec<u64>,
+    names: Vec<Regex>,
+}
+
+impl FunctionFilter {
+    /// Create a new filter from a comma separated list of symbol names and/or addresses
+    pub fn new(filter: &str) -> Result<FunctionFilter, Error> {
+        let mut addresses = Vec::new();
+        let mut names = Vec::new();
+
+        for entry in filter
+            .split(',')
+            .map(|e| e.trim())
+            .filter(|e| !e.is_empty())
+        {
+            if let Some(address) = entry
+                .strip_prefix("0x")
+                .or_else(|| entry.strip_prefix("0X"))
+            {
+                let address = u64::from_str_radix(address, 16)
+                    .map_err(|e| anyhow!("Invalid function address {} - {}", entry, e))?;
+                addresses.push(address)
+            } else {
+                let pattern = entry
+                    .split('*')
+                    .map(|part| part.split('?').map(regex::escape).join("."))
+                    .join(".*");
+                names.push(Regex::new(&format!("^{}$", pattern))?)
+            }
+        }
+
+        if addresses.is_empty() && names.is_empty() {
+            bail!("Function filter is empty - got {}", filter)
+        }
+
+        Ok(FunctionFilter { addresses, names })
+    }
+
+    pub fn is_match(&self, function: &AFIJFunctionInfo) -> bool {
+        self.addresses.contains(&function.offset)
+            || self.names.iter().any(|name| name.is_match(&function.name))
+    }
+}
+
+/// Checks whether a function is a real, named symbol within the binary
+///
+/// Functions auto-named by r2 (i.e fcn.00001040) and imports (i.e sym.imp.printf)
+/// are excluded as they are not defined by the binary itself.
+pub fn is_public_function(function: &AFIJFunctionInfo) -> bool {
+    match function.type_field.as_str() {
+        "imp" => false,
+        "fcn" | "sym" => {
+            !function.name.starts_with("fcn.")
+                && !function.name.starts_with("sym.imp.")
+                && !function.name.starts_with("loc.imp.")
+        }
+        _ => false,
+    }
+}
+
+/// Check whether a function has at least `min_instructions` instructions
+///
+/// Used to skip tiny stub functions at extraction time. A minimum of 0 keeps everything.
+pub fn has_min_instructions(function: &AFIJFunctionInfo, min_instructions: u64) -> bool {
+    function.ninstrs >= 0 && function.ninstrs as u64 >= min_instructions
 }
 
 impl std::fmt::Display for ExtractionJob {
@@ -195,13 +601,43 @@ pub struct AEAFJRegisterBehaviour {
     #[serde(default)]
     pub n: Vec<String>,
     #[serde(rename = "@R")]
-    #[serde(default)]
+    #[serde(default, deserialize_with = "deserialize_lossy_u64_vec")]
     pub r2: Vec<u64>,
     #[serde(rename = "@W")]
-    #[serde(default)]
+    #[serde(default, deserialize_with = "deserialize_lossy_u64_vec")]
     pub w2: Vec<u64>,
 }
 
+/// Deserialize a list of addresses without failing on out of range values
+///
+/// radare2 can emit addresses within the aeafj @R/@W arrays which do not fit
+/// into a u64 (negative values or values which have been promoted to floats). Rather
+/// than failing the deserialization of the whole function, negative values are
+/// wrapped, values too large are clamped to u64::MAX and anything else is skipped.
+fn deserialize_lossy_u64_vec<'de, D>(deserializer: D) -> Result<Vec<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let values: Vec<Value> = Vec::deserialize(deserializer)?;
+    let mut addrs = Vec::with_capacity(values.len());
+    for value in values {
+        if let Some(addr) = value.as_u64() {
+            addrs.push(addr)
+        } else if let Some(addr) = value.as_i64() {
+            addrs.push(addr as u64)
+        } else if let Some(addr) = value.as_f64() {
+            warn!(
+                "Address {} out of range for u64 - Clamping to u64::MAX",
+                addr
+            );
+            addrs.push(u64::MAX)
+        } else {
+            warn!("Unable to parse {} as an address - Skipping", value)
+        }
+    }
+    Ok(addrs)
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 // Created using the axffj command
@@ -214,21 +650,37 @@ pub struct FunctionXrefDetails {
     pub name: String,
 }
 
-impl std::fmt::Display for AFLJFuncDetails {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "name: {}", self.name)
+// Data references made by a function. Derived from the DATA and STRING entries of axffj
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DataXrefDetails {
+    pub from: u64,
+    pub to: u64,
+    // The flag (i.e str.*, obj.*) at the referenced address if there is one
+    pub target_name: Option<String>,
+}
+
+impl DataXrefDetails {
+    /// Keeps the data references from a function's xrefs, dropping code and call xrefs
+    pub fn from_function_xrefs(xrefs: Vec<FunctionXrefDetails>) -> Vec<DataXrefDetails> {
+        xrefs
+            .into_iter()
+            .filter(|xref| xref.type_field == "DATA" || xref.type_field == "STRING")
+            .map(|xref| DataXrefDetails {
+                from: xref.at as u64,
+                to: xref.ref_field as u64,
+                target_name: if xref.name.is_empty() {
+                    None
+                } else {
+                    Some(xref.name)
+                },
+            })
+            .collect()
     }
 }
 
-impl From<(String, String, String, R2PipeConfig, bool)> for FileToBeProcessed {
-    fn from(orig: (String, String, String, R2PipeConfig, bool)) -> FileToBeProcessed {
-        FileToBeProcessed {
-            file_path: PathBuf::from(orig.0),
-            output_path: PathBuf::from(orig.1),
-            job_type_suffix: orig.2,
-            r2p_config: orig.3,
-            with_annotations: orig.4,
-        }
+impl std::fmt::Display for AFLJFuncDetails {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "name: {}", self.name)
     }
 }
 
@@ -314,6 +766,57 @@ pub struct Writes {
     pub addrs: Vec<i64>,
 }
 
+/// A write to a local variable paired with the reads which use the written value
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DefUseChain {
+    pub var: String,
+    pub def_addr: i64,
+    pub use_addrs: Vec<i64>,
+}
+
+impl LocalVariableXrefs {
+    /// Builds the def-use chains for each variable by pairing each write with the reads
+    /// that follow it (ordered by address) up to and including the next write.
+    ///
+    /// A read at the same address as the next write (i.e add [var], 1) uses the
+    /// previous value so belongs to the previous chain. Reads before the first write
+    /// are not part of any chain. This is based on address order alone so does not
+    /// account for control flow.
+    pub fn def_use_chains(&self) -> Vec<DefUseChain> {
+        let mut reads: BTreeMap<&str, Vec<i64>> = BTreeMap::new();
+        for read in &self.reads {
+            reads.entry(&read.name).or_default().extend(&read.addrs)
+        }
+        let mut writes: BTreeMap<&str, Vec<i64>> = BTreeMap::new();
+        for write in &self.writes {
+            writes.entry(&write.name).or_default().extend(&write.addrs)
+        }
+
+        let mut chains = Vec::new();
+        for (var, mut def_addrs) in writes {
+            def_addrs.sort_unstable();
+            def_addrs.dedup();
+            let mut use_addrs = reads.get(var).cloned().unwrap_or_default();
+            use_addrs.sort_unstable();
+            use_addrs.dedup();
+
+            for (i, def_addr) in def_addrs.iter().enumerate() {
+                let next_def_addr = def_addrs.get(i + 1).copied().unwrap_or(i64::MAX);
+                chains.push(DefUseChain {
+                    var: var.to_string(),
+                    def_addr: *def_addr,
+                    use_addrs: use_addrs
+                        .iter()
+                        .filter(|addr| **addr > *def_addr && **addr <= next_def_addr)
+                        .copied()
+                        .collect(),
+                })
+            }
+        }
+        chains
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct StringEntry {
     pub vaddr: i64,
@@ -327,24 +830,299 @@ pub struct StringEntry {
     pub string: String,
 }
 
+// Strings augmented with the names of the functions that reference them
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StringXrefEntry {
+    #[serde(flatten)]
+    pub string: StringEntry,
+    pub referenced_by: Vec<String>,
+}
+
+// Structs for axtj - Xrefs to an address JSON output
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct XrefToEntry {
+    pub from: u64,
+    #[serde(rename = "type")]
+    pub type_field: String,
+    pub fcn_addr: Option<u64>,
+    pub fcn_name: Option<String>,
+}
+
+// Structs for ihj - Header fields JSON output
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HeaderField {
+    pub name: String,
+    pub vaddr: Option<u64>,
+    pub paddr: Option<u64>,
+    pub value: Option<Value>,
+    pub comment: Option<String>,
+    pub format: Option<String>,
+}
+
+impl HeaderField {
+    // r2 reports header values as either numbers or (hex) strings depending on the format
+    fn value_as_u64(&self) -> Option<u64> {
+        match self.value.as_ref()? {
+            Value::Number(number) => number.as_u64(),
+            Value::String(string) => match string.strip_prefix("0x") {
+                Some(hex) => u64::from_str_radix(hex, 16).ok(),
+                None => string.parse::<u64>().ok(),
+            },
+            _ => None,
+        }
+    }
+}
+
+// Combined output of ij and ihj. The PE specific fields are only populated for PE files.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HeaderInfo {
+    pub bintype: String,
+    pub class: Option<String>,
+    pub arch: Option<String>,
+    pub bits: Option<u64>,
+    pub machine: Option<String>,
+    pub os: Option<String>,
+    pub endian: Option<String>,
+    pub stripped: Option<bool>,
+    pub timestamp: Option<u64>,
+    pub subsystem: Option<u64>,
+    pub major_linker_version: Option<u64>,
+    pub minor_linker_version: Option<u64>,
+    pub data_directory_sizes: Option<BTreeMap<String, u64>>,
+    pub fields: Vec<HeaderField>,
+}
+
+impl HeaderInfo {
+    pub fn new(bininfo: &Value, fields: Vec<HeaderField>) -> HeaderInfo {
+        let bin = &bininfo["bin"];
+        let as_string = |key: &str| bin[key].as_str().map(|s| s.to_string());
+        let mut header_info = HeaderInfo {
+            bintype: as_string("bintype").unwrap_or_default(),
+            class: as_string("class"),
+            arch: as_string("arch"),
+            bits: bin["bits"].as_u64(),
+            machine: as_string("machine"),
+            os: as_string("os"),
+            endian: as_string("endian"),
+            stripped: bin["stripped"].as_bool(),
+            ..Default::default()
+        };
+
+        if header_info.bintype == "pe" {
+            let field_value = |name: &str| {
+                fields
+                    .iter()
+                    .find(|field| field.name == name)
+                    .and_then(|field| field.value_as_u64())
+            };
+            header_info.timestamp = field_value("TimeDateStamp");
+            header_info.subsystem = field_value("Subsystem");
+            header_info.major_linker_version = field_value("MajorLinkerVersion");
+            header_info.minor_linker_version = field_value("MinorLinkerVersion");
+            header_info.data_directory_sizes = Some(
+                fields
+                    .iter()
+                    .filter_map(|field| {
+                        let directory = field.name.strip_prefix("SIZE_IMAGE_DIRECTORY_ENTRY_")?;
+                        Some((directory.to_string(), field.value_as_u64()?))
+                    })
+                    .collect(),
+            );
+        }
+        header_info.fields = fields;
+        header_info
+    }
+}
+
+// Structs for iSj - Sections JSON output
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SectionEntry {
+    pub name: String,
+    pub size: u64,
+    pub vsize: u64,
+    pub paddr: u64,
+    pub vaddr: u64,
+    pub perm: String,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub entropy: Option<f64>,
+}
+
+// Structs for iij - Imports JSON output
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportEntry {
+    pub ordinal: u64,
+    pub bind: String,
+    #[serde(rename = "type")]
+    pub type_field: String,
+    pub name: String,
+    pub libname: Option<String>,
+    pub plt: Option<u64>,
+}
+
+// Structs for iEj - Exports JSON output
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportEntry {
+    pub name: String,
+    pub flagname: Option<String>,
+    pub realname: Option<String>,
+    pub ordinal: u64,
+    pub bind: String,
+    pub size: u64,
+    #[serde(rename = "type")]
+    pub type_field: String,
+    pub vaddr: u64,
+    pub paddr: u64,
+    #[serde(default)]
+    pub is_imported: bool,
+}
+
+// Structs for iej - Entrypoints JSON output
+// type is i.e program, init, fini, main or tls (PE TLS callbacks)
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntryPoint {
+    pub vaddr: u64,
+    pub paddr: u64,
+    #[serde(rename = "type")]
+    pub type_field: String,
+}
+
+// A single Mach-O load command reported by ihj (i.e load_command_0_LC_SEGMENT_64)
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MachOLoadCommand {
+    pub index: u64,
+    pub name: String,
+    pub paddr: Option<u64>,
+}
+
+// Combined output of ij, ihj, iej and ilj for Mach-O binaries
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MachOInfo {
+    pub bintype: String,
+    pub arch: Option<String>,
+    pub bits: Option<u64>,
+    pub has_code_signature: bool,
+    pub entrypoints: Vec<EntryPoint>,
+    pub load_commands: Vec<MachOLoadCommand>,
+    pub linked_dylibs: Vec<String>,
+}
+
+impl MachOInfo {
+    /// Whether the ij output of r2 describes a Mach-O binary
+    pub fn is_macho(bininfo: &Value) -> bool {
+        bininfo["bin"]["bintype"]
+            .as_str()
+            .is_some_and(|bintype| bintype.starts_with("mach0"))
+    }
+
+    pub fn new(
+        bininfo: &Value,
+        fields: &[HeaderField],
+        entrypoints: Vec<EntryPoint>,
+        libraries: &Value,
+    ) -> MachOInfo {
+        let bin = &bininfo["bin"];
+        let load_commands: Vec<MachOLoadCommand> = fields
+            .iter()
+            .filter_map(|field| {
+                let (index, name) = field.name.strip_prefix("load_command_")?.split_once('_')?;
+                Some(MachOLoadCommand {
+                    index: index.parse().ok()?,
+                    name: name.to_string(),
+                    paddr: field.paddr,
+                })
+            })
+            .collect();
+
+        // ilj is a list of library names in older versions of r2 and a list of objects
+        // with a name field in newer versions
+        let linked_dylibs = libraries
+            .as_array()
+            .map(|libraries| {
+                libraries
+                    .iter()
+                    .filter_map(|library| {
+                        library
+                            .as_str()
+                            .or_else(|| library["name"].as_str())
+                            .map(|name| name.to_string())
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        MachOInfo {
+            bintype: bin["bintype"].as_str().unwrap_or_default().to_string(),
+            arch: bin["arch"].as_str().map(|s| s.to_string()),
+            bits: bin["bits"].as_u64(),
+            has_code_signature: bin["signed"].as_bool().unwrap_or(false)
+                || load_commands
+                    .iter()
+                    .any(|command| command.name == "LC_CODE_SIGNATURE"),
+            entrypoints,
+            load_commands,
+            linked_dylibs,
+        }
+    }
+}
+
+// Structs for irj - Relocations JSON output
+// The fields r2 includes vary by binary format so most are optional
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelocEntry {
+    pub name: Option<String>,
+    pub demname: Option<String>,
+    #[serde(rename = "type")]
+    pub type_field: Option<String>,
+    pub vaddr: u64,
+    pub paddr: Option<u64>,
+    pub sym_va: Option<u64>,
+    #[serde(default)]
+    pub is_ifunc: bool,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FuncBytes {
     pub bytes: Vec<u8>,
 }
 
 impl ExtractionJob {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         input_path: &PathBuf,
         output_path: &PathBuf,
         mode: &str,
         debug: &bool,
-        extended_analysis: &bool,
+        analysis_level: &str,
         use_curl_pdb: &bool,
         with_annotations: &bool,
+        output_format: &str,
+        func_timeout_secs: &Option<u64>,
+        r2_path: &Path,
+        func_filename: &str,
+        intra_file_threads: &usize,
+        progress: &bool,
+        function_filter: &Option<String>,
+        feature_type: &Option<String>,
+        sqlite_path: &Option<PathBuf>,
+        nested_output: &bool,
+        auto_reanalyze: &bool,
+        public_only: &bool,
+        key_by_offset: &bool,
+        asm_pseudo: &bool,
+        asm_comments: &bool,
+        demangle: &bool,
+        max_func_bytes: &u64,
+        min_instructions: &u64,
+        min_blocks: &u16,
+        bytes_format: &str,
+        output_template: &Option<String>,
+        r2_script: &Option<PathBuf>,
     ) -> Result<ExtractionJob, Error> {
         fn get_path_type(bin_path: &PathBuf) -> PathType {
             let fpath_md = fs::metadata(bin_path).unwrap();
-            if fpath_md.is_file() {
+            if fpath_md.is_file() && is_tar_archive(bin_path) {
+                PathType::Archive
+            } else if fpath_md.is_file() {
                 PathType::File
             } else if fpath_md.is_dir() {
                 PathType::Dir
@@ -362,63 +1140,209 @@ impl ExtractionJob {
                 "reg" => Ok(ExtractionJobType::RegisterBehaviour),
                 "cfg" => Ok(ExtractionJobType::CFG),
                 "func-xrefs" => Ok(ExtractionJobType::FunctionXrefs),
+                "data-xrefs" => Ok(ExtractionJobType::DataXrefs),
                 "cg" => Ok(ExtractionJobType::CallGraphs),
                 "decomp" => Ok(ExtractionJobType::Decompilation),
                 "pcode-func" => Ok(ExtractionJobType::PCodeFunc),
                 "pcode-bb" => Ok(ExtractionJobType::PCodeBB),
                 "localvar-xrefs" => Ok(ExtractionJobType::LocalVariableXrefs),
+                "defuse" => Ok(ExtractionJobType::DefUseChains),
                 "strings" => Ok(ExtractionJobType::GlobalStrings),
+                "strings-xrefs" => Ok(ExtractionJobType::GlobalStringsXrefs),
+                "string-stats" => Ok(ExtractionJobType::StringStats),
+                "sections" => Ok(ExtractionJobType::Sections),
+                "header" => Ok(ExtractionJobType::Header),
                 "bytes" => Ok(ExtractionJobType::FunctionBytes),
+                "imports" => Ok(ExtractionJobType::Imports),
+                "exports" => Ok(ExtractionJobType::Exports),
+                "relocs" => Ok(ExtractionJobType::Relocations),
+                "entrypoints" => Ok(ExtractionJobType::EntryPoints),
+                "byte-entropy" => Ok(ExtractionJobType::ByteEntropy),
+                "byte-histogram" => Ok(ExtractionJobType::ByteHistogram),
+                "cfg-enriched" => Ok(ExtractionJobType::CFGEnriched),
+                "macho" => Ok(ExtractionJobType::MachO),
                 _ => bail!("Incorrect command type - got {}", mode),
             }
         }
 
+        fn feature_type_matcher(feature_type: &str) -> Result<FeatureType, Error> {
+            match FeatureType::new(feature_type) {
+                FeatureType::Invalid => bail!("Incorrect feature type - got {}", feature_type),
+                feature_type => Ok(feature_type),
+            }
+        }
+
+        fn output_format_matcher(output_format: &str) -> Result<OutputFormat, Error> {
+            match output_format {
+                "json" => Ok(OutputFormat::Json),
+                "ndjson" => Ok(OutputFormat::Ndjson),
+                _ => bail!("Incorrect output format - got {}", output_format),
+            }
+        }
+
+        fn analysis_level_matcher(analysis_level: &str) -> Result<AnalysisLevel, Error> {
+            match analysis_level {
+                "aa" => Ok(AnalysisLevel::Aa),
+                "aaa" => Ok(AnalysisLevel::Aaa),
+                "aaaa" => Ok(AnalysisLevel::Aaaa),
+                _ => bail!("Incorrect analysis level - got {}", analysis_level),
+            }
+        }
+
+        fn bytes_format_matcher(bytes_format: &str) -> Result<BytesFormat, Error> {
+            match bytes_format {
+                "bin" => Ok(BytesFormat::Bin),
+                "archive" => Ok(BytesFormat::Archive),
+                _ => bail!("Incorrect bytes format - got {}", bytes_format),
+            }
+        }
+
+        let p_type = get_path_type(input_path);
+
+        if *progress && p_type != PathType::File {
+            warn!("Per-function progress is only reported when extracting a single file. Ignoring.")
+        }
+
         let r2_handle_config = R2PipeConfig {
             debug: *debug,
-            extended_analysis: *extended_analysis,
+            analysis_level: analysis_level_matcher(analysis_level)?,
             use_curl_pdb: *use_curl_pdb,
+            func_timeout: func_timeout_secs.map(Duration::from_secs),
+            r2_path: r2_path.to_owned(),
+            intra_file_threads: *intra_file_threads,
+            progress: *progress && p_type == PathType::File,
+            function_filter: function_filter
+                .as_ref()
+                .map(|filter| FunctionFilter::new(filter))
+                .transpose()?,
+            auto_reanalyze: *auto_reanalyze,
+            public_only: *public_only,
+            key_by_offset: *key_by_offset,
+            asm_pseudo: *asm_pseudo,
+            asm_comments: *asm_comments,
+            demangle: *demangle,
+            max_func_bytes: *max_func_bytes,
+            min_instructions: *min_instructions,
+            min_blocks: *min_blocks,
+            r2_script: r2_script.to_owned(),
         };
 
-        let p_type = get_path_type(input_path);
         let job_type = extraction_job_matcher(mode).unwrap();
 
-        if job_type != ExtractionJobType::Decompilation && *with_annotations {
-            warn!("Annotations are only supported for decompilation extraction")
+        if job_type.requires_r2() {
+            validate_r2_path(r2_path)?;
+        }
+        if let Some(r2_script) = r2_script {
+            if !r2_script.exists() {
+                bail!("r2 script does not exist - {:?}", r2_script)
+            }
+            if !job_type.requires_r2() {
+                warn!(
+                    "The r2 script is not used by {} extraction. Will ignore.",
+                    mode
+                )
+            }
+        }
+        let output_format = output_format_matcher(output_format)?;
+        let bytes_format = bytes_format_matcher(bytes_format)?;
+        let output_template = output_template
+            .as_deref()
+            .map(OutputTemplate::new)
+            .transpose()?;
+        if let Some(template) = &output_template {
+            if job_type == ExtractionJobType::FunctionBytes
+                && bytes_format == BytesFormat::Bin
+                && !template.has_placeholder("func")
+            {
+                bail!("The output template must contain {{func}} for bytes extraction in the bin format, otherwise every function is written to the same file")
+            }
+            if p_type != PathType::File && !template.has_placeholder("binary") {
+                bail!("The output template must contain {{binary}} when processing a directory, otherwise every binary is written to the same file")
+            }
+        }
+        let feature_type = feature_type
+            .as_ref()
+            .map(|feature_type| feature_type_matcher(feature_type))
+            .transpose()?;
+
+        if job_type == ExtractionJobType::CFGEnriched && feature_type.is_none() {
+            bail!("A feature type is required for cfg-enriched extraction")
+        } else if job_type != ExtractionJobType::CFGEnriched && feature_type.is_some() {
+            warn!("The feature type is only used for cfg-enriched extraction. Will ignore.")
         };
 
-        if p_type == PathType::File {
-            let file = FileToBeProcessed {
-                file_path: input_path.to_owned(),
-                output_path: output_path.to_owned(),
-                job_type_suffix: (*mode).to_string(),
-                r2p_config: r2_handle_config,
-                with_annotations: *with_annotations,
-            };
-            Ok(ExtractionJob {
-                input_path: input_path.to_owned(),
-                input_path_type: p_type,
-                job_type,
-                files_to_be_processed: vec![file],
-                output_path: output_path.to_owned(),
-            })
-        } else if p_type == PathType::Dir {
-            let files = ExtractionJob::get_file_paths_dir(input_path);
+        if func_timeout_secs.is_some()
+            && ![
+                ExtractionJobType::Decompilation,
+                ExtractionJobType::PCodeFunc,
+                ExtractionJobType::PCodeBB,
+            ]
+            .contains(&job_type)
+        {
+            warn!("The function timeout is only supported for decomp, pcode-func and pcode-bb extraction")
+        };
 
-            let files_with_output_path: Vec<(String, String, String, R2PipeConfig, bool)> = files
-                .into_iter()
-                .map(|f| {
-                    (
-                        f,
-                        output_path.to_string_lossy().to_string(),
-                        mode.to_string(),
-                        r2_handle_config,
-                        *with_annotations,
-                    )
-                })
-                .collect();
-            let files_to_be_processed: Vec<FileToBeProcessed> = files_with_output_path
+        let output_format = if job_type == ExtractionJobType::FunctionBytes
+            && output_format == OutputFormat::Ndjson
+        {
+            warn!("The ndjson output format is not supported for bytes extraction. Will ignore.");
+            OutputFormat::Json
+        } else {
+            output_format
+        };
+
+        if job_type != ExtractionJobType::FunctionBytes && bytes_format != BytesFormat::Bin {
+            warn!("The bytes format is only used for bytes extraction. Will ignore.")
+        };
+
+        if job_type != ExtractionJobType::FuncInfo && sqlite_path.is_some() {
+            warn!("SQLite output is only supported for finfo extraction. Will ignore.")
+        };
+
+        if job_type != ExtractionJobType::Decompilation && *with_annotations {
+            warn!("Annotations are only supported for decompilation extraction")
+        };
+
+        let file_to_be_processed = |file_path: PathBuf| FileToBeProcessed {
+            file_path,
+            output_path: output_path.to_owned(),
+            job_type_suffix: (*mode).to_string(),
+            r2p_config: r2_handle_config.clone(),
+            with_annotations: *with_annotations,
+            output_format,
+            func_filename: func_filename.to_string(),
+            feature_type,
+            sqlite_path: sqlite_path.to_owned(),
+            nested_output: *nested_output,
+            bytes_format,
+            output_template: output_template.clone(),
+            output_checksums: Mutex::default(),
+        };
+
+        if p_type == PathType::File {
+            Ok(ExtractionJob {
+                input_path: input_path.to_owned(),
+                input_path_type: p_type,
+                job_type,
+                files_to_be_processed: vec![file_to_be_processed(input_path.to_owned())],
+                output_path: output_path.to_owned(),
+                archive_dir: None,
+            })
+        } else if p_type == PathType::Dir || p_type == PathType::Archive {
+            let archive_dir = if p_type == PathType::Archive {
+                Some(ExtractionJob::unpack_archive(input_path)?)
+            } else {
+                None
+            };
+            let files = ExtractionJob::get_file_paths_dir(
+                archive_dir
+                    .as_ref()
+                    .map_or(input_path.as_path(), |archive_dir| archive_dir.path()),
+            );
+
+            let files_to_be_processed: Vec<FileToBeProcessed> = files
                 .into_iter()
-                .map(FileToBeProcessed::from)
+                .map(|file| file_to_be_processed(PathBuf::from(file)))
                 .collect();
             Ok(ExtractionJob {
                 input_path: input_path.to_owned(),
@@ -426,13 +1350,146 @@ impl ExtractionJob {
                 job_type,
                 files_to_be_processed,
                 output_path: output_path.to_owned(),
+                archive_dir,
             })
         } else {
             bail!("Failed to create extraction job.")
         }
     }
 
-    fn get_file_paths_dir(input_path: &PathBuf) -> Vec<String> {
+    /// Removes files which have already been extracted from the job
+    ///
+    /// This allows large extraction jobs to be resumed after a crash without
+    /// re-processing binaries whose output already exists. Returns the number
+    /// of files removed.
+    pub fn remove_already_extracted(&mut self) -> usize {
+        let job_type = &self.job_type;
+        let num_files = self.files_to_be_processed.len();
+        self.files_to_be_processed.retain(|file| {
+            let output_filepath = file.get_existing_output_path(job_type);
+            if output_filepath.exists() {
+                info!("{:?} already exists. Skipping", output_filepath);
+                false
+            } else {
+                true
+            }
+        });
+        num_files - self.files_to_be_processed.len()
+    }
+
+    /// Extracts each file within the job in parallel
+    ///
+    /// A file which fails (i.e r2 crashes on a corrupt binary) is logged and
+    /// the remaining files are still extracted rather than aborting the whole
+    /// run. Returns the files which failed.
+    pub fn extract_all(&self, manifest: bool, checksums: bool) -> Vec<PathBuf> {
+        self.files_to_be_processed
+            .par_iter()
+            .progress()
+            .filter_map(|file| {
+                let mode_result = file.extract(&self.job_type);
+                let mut success = mode_result.success;
+                let written = if manifest {
+                    file.write_manifest(vec![mode_result])
+                } else if checksums {
+                    file.write_checksums()
+                } else {
+                    Ok(())
+                };
+                if let Err(e) = written {
+                    error!("{}", e);
+                    success = false
+                }
+                if success {
+                    None
+                } else {
+                    warn!("Failed to extract {:?}", file.file_path);
+                    Some(file.file_path.clone())
+                }
+            })
+            .collect()
+    }
+
+    /// Describes the files, job type and output paths of the job
+    ///
+    /// Used by --dry-run to show what an extraction would do without
+    /// spawning radare2 or writing any output.
+    pub fn describe(&self) -> String {
+        let mut description = format!(
+            "Extraction job: {:?} ({} file(s))\n",
+            self.job_type,
+            self.files_to_be_processed.len()
+        );
+        for file in &self.files_to_be_processed {
+            description += &format!("{:?}\n", file.file_path);
+            description += &format!("  job type: {:?}\n", self.job_type);
+            match (&self.job_type, &file.sqlite_path) {
+                (ExtractionJobType::FuncInfo, Some(sqlite_path)) => {
+                    description += &format!("  output: {:?} (sqlite)\n", sqlite_path)
+                }
+                (ExtractionJobType::FunctionBytes, _) if file.bytes_format == BytesFormat::Bin => {
+                    description += &format!(
+                        "  output: {:?} (directory)\n",
+                        file.get_existing_output_path(&self.job_type)
+                    )
+                }
+                _ => {
+                    description += &format!(
+                        "  output: {:?}\n",
+                        file.get_existing_output_path(&self.job_type)
+                    )
+                }
+            }
+        }
+        description
+    }
+
+    /// Unpacks the binaries within a tar archive into a temporary directory so they
+    /// can be extracted in the same way as a directory of binaries
+    ///
+    /// Each entry is streamed to its own file rather than reading the whole archive
+    /// into memory. JSON files are skipped in the same way as for directories.
+    fn unpack_archive(archive_path: &Path) -> Result<UnpackedArchive, Error> {
+        let archive_name = archive_path.file_name().unwrap_or_default();
+        // Any early return from here on removes the partially unpacked directory
+        let unpack_dir = UnpackedArchive::create(env::temp_dir().join(format!(
+            "bin2ml-{}-{}",
+            archive_name.to_string_lossy(),
+            std::process::id()
+        )))?;
+
+        let file = File::open(archive_path)?;
+        let reader: Box<dyn Read> =
+            if is_gzipped(archive_path) || archive_name.to_string_lossy().ends_with(".tgz") {
+                Box::new(GzDecoder::new(file))
+            } else {
+                Box::new(file)
+            };
+
+        let mut archive = tar::Archive::new(reader);
+        let mut num_unpacked = 0;
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file()
+                || entry.path()?.to_string_lossy().ends_with(".json")
+            {
+                continue;
+            }
+            // unpack_in skips any entries which would be written outside of unpack_dir
+            if entry.unpack_in(unpack_dir.path())? {
+                num_unpacked += 1;
+            }
+        }
+        info!(
+            "Unpacked {} files from {:?} into {:?}",
+            num_unpacked,
+            archive_path,
+            unpack_dir.path()
+        );
+        Ok(unpack_dir)
+    }
+
+    fn get_file_paths_dir(input_path: &Path) -> Vec<String> {
         let mut str_vec: Vec<String> = Vec::new();
         for file in WalkDir::new(input_path)
             .into_iter()
@@ -450,326 +1507,1732 @@ impl ExtractionJob {
     }
 }
 
+/// Checks if a path is a tar archive based on its extension (.tar, .tar.gz or .tgz)
+fn is_tar_archive(path: &Path) -> bool {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    [".tar", ".tar.gz", ".tgz"]
+        .iter()
+        .any(|extension| file_name.ends_with(extension))
+}
+
+/// Forcefully kills a process (i.e a hung r2 instance), waiting for the kill command to exit
+fn kill_process(pid: u32) -> std::io::Result<ExitStatus> {
+    if cfg!(windows) {
+        Command::new("taskkill")
+            .args(["/F", "/PID", &pid.to_string()])
+            .status()
+    } else {
+        Command::new("kill").args(["-9", &pid.to_string()]).status()
+    }
+}
+
+/// Checks that the radare2 executable exists and is executable
+///
+/// Bare executable names (i.e "radare2" or "r2") are searched for within
+/// the directories listed in PATH
+fn validate_r2_path(r2_path: &Path) -> Result<(), Error> {
+    fn is_executable(path: &Path) -> bool {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            path.metadata()
+                .map(|md| md.is_file() && md.permissions().mode() & 0o111 != 0)
+                .unwrap_or(false)
+        }
+        #[cfg(not(unix))]
+        {
+            path.is_file()
+        }
+    }
+
+    let found = if r2_path.components().count() > 1 {
+        is_executable(r2_path)
+    } else {
+        env::var_os("PATH")
+            .map(|paths| env::split_paths(&paths).any(|dir| is_executable(&dir.join(r2_path))))
+            .unwrap_or(false)
+    };
+
+    if found {
+        Ok(())
+    } else {
+        bail!(
+            "Unable to find radare2 executable at {:?}. Check it is installed and executable or provide --r2-path",
+            r2_path
+        )
+    }
+}
+
+/// Demangles the name of a function, keeping the original name in mangled_name
+fn demangle_function(function: &mut AFIJFunctionInfo) {
+    if let Some(demangled) = demangle_function_name(&function.name) {
+        function.mangled_name = Some(std::mem::replace(&mut function.name, demangled));
+    }
+}
+
+/// Makes a function name safe to use as a filename
+///
+/// Path separators and other characters which are invalid in filenames
+/// on common platforms are replaced with underscores
+fn sanitize_function_name(function_name: &str) -> String {
+    function_name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Resolves the filename used for a function within the bytes extraction mode
+///
+/// Supports "symbol" (the function name), "address" (the function offset in hex)
+/// or a template containing {symbol} and/or {address} placeholders
+fn format_function_filename(template: &str, function_name: &str, function_offset: u64) -> String {
+    let address = format!("{:#x}", function_offset);
+    match template {
+        "symbol" => function_name.to_string(),
+        "address" => address,
+        _ => template
+            .replace("{address}", &address)
+            .replace("{symbol}", function_name),
+    }
+}
+
+// Default maximum reported size of a function for its bytes to be extracted (1 MiB)
+pub const DEFAULT_MAX_FUNC_BYTES: u64 = 1024 * 1024;
+
+// Maximum number of characters of r2 output included within error messages
+const R2_OUTPUT_ERROR_LEN: usize = 200;
+
+/// Parses the output of agfj @@f into the CFG of each function
+///
+/// r2 writes one JSON array per function, each on its own line, with functions
+/// without a CFG written as an empty array. Each record is parsed independently
+/// so a single malformed function does not prevent the rest from being parsed.
+/// Empty arrays are skipped and an error is returned for each record which fails to parse.
+fn parse_agfj_output(output: &str) -> (Vec<Value>, Vec<String>) {
+    let mut functions = Vec::new();
+    let mut errors = Vec::new();
+
+    for record in output
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+    {
+        match serde_json::from_str::<Value>(record) {
+            Ok(Value::Array(function)) if function.is_empty() => {}
+            Ok(function) => functions.push(function),
+            Err(e) => {
+                let truncated: String = record.chars().take(R2_OUTPUT_ERROR_LEN).collect();
+                errors.push(format!(
+                    "Unable to parse agfj @@f record: {} - {}",
+                    e, truncated
+                ))
+            }
+        }
+    }
+
+    (functions, errors)
+}
+
+/// Lists the functions within a binary using aflj
+///
+/// If no functions are found and reanalyze is set, the deeper 'aaa' analysis is
+/// run once before retrying. This helps with stripped binaries where the default
+/// analysis can miss every function, which otherwise results in empty outputs.
+fn list_functions_with_reanalysis<F>(
+    mut cmd: F,
+    reanalyze: bool,
+) -> Result<Vec<AFIJFunctionInfo>, Error>
+where
+    F: FnMut(&str) -> Result<String, Error>,
+{
+    let functions: Vec<AFIJFunctionInfo> = deserialize_r2_output("aflj", &cmd("aflj")?)?;
+    if !functions.is_empty() || !reanalyze {
+        return Ok(functions);
+    }
+
+    info!("No functions found - triggering reanalysis with 'aaa'");
+    cmd("aaa")?;
+    deserialize_r2_output("aflj", &cmd("aflj")?)
+}
+
+/// Keeps the CFGs (from agfj @@f) of the selected functions, matched on the function offset
+fn retain_selected_cfgs(json: &mut Vec<Value>, selected: &[AFIJFunctionInfo]) {
+    let offsets: HashSet<u64> = selected.iter().map(|function| function.offset).collect();
+    json.retain(|cfg| {
+        cfg[0]["offset"]
+            .as_u64()
+            .is_some_and(|offset| offsets.contains(&offset))
+    });
+}
+
+/// Keeps the call graphs (from agCj) of the selected functions, matched on the function name
+/// as agCj does not include the function offset. The callees of each function are kept.
+fn retain_selected_call_graphs(
+    call_graphs: &mut Vec<AGCJFunctionCallGraph>,
+    selected: &[AFIJFunctionInfo],
+) {
+    let names: HashSet<&str> = selected
+        .iter()
+        .map(|function| function.name.as_str())
+        .collect();
+    call_graphs.retain(|call_graph| names.contains(call_graph.name.as_str()));
+}
+
+/// Wraps the plain text output of pdg as a decompilation without annotations
+///
+/// pdg terminates the decompiled code with a newline which is not present
+/// within the code field of pdgj so it is removed to keep both consistent.
+fn parse_pdg_output(output: &str) -> DecompJSON {
+    DecompJSON {
+        code: output.strip_suffix('\n').unwrap_or(output).to_string(),
+        annotations: Vec::new(),
+    }
+}
+
+/// Records the bits of the binary against each function CFG
+///
+/// agfj does not include the bits so this is needed to tell 32-bit ARM and AArch64
+/// apart when the CFGs are later used to generate graphs.
+fn add_bits_to_cfgs(functions: &mut [Value], bits: u64) {
+    for function in functions.iter_mut() {
+        if let Some(function) = function.get_mut(0).and_then(|func| func.as_object_mut()) {
+            function.insert("bits".to_string(), json!(bits));
+        }
+    }
+}
+
+/// Deserializes the JSON output of an r2 command
+///
+/// Rather than panicking, a failure returns an error which includes the
+/// command and a truncated copy of the offending output
+fn deserialize_r2_output<T: DeserializeOwned>(command: &str, output: &str) -> Result<T, Error> {
+    serde_json::from_str(output).map_err(|e| {
+        let truncated: String = output.chars().take(R2_OUTPUT_ERROR_LEN).collect();
+        anyhow!(
+            "Unable to deserialize {} output ({}): {}",
+            command,
+            e,
+            truncated
+        )
+    })
+}
+
 impl FileToBeProcessed {
-    pub fn extract_register_behaviour(&self) {
+    /// Runs the extraction for the given job type and returns a summary of the run
+    pub fn extract(&self, job_type: &ExtractionJobType) -> ModeResult {
+        match job_type {
+            ExtractionJobType::CFG => self.extract_func_cfgs(),
+            ExtractionJobType::RegisterBehaviour => self.extract_register_behaviour(),
+            ExtractionJobType::FunctionXrefs => self.extract_function_xrefs(),
+            ExtractionJobType::DataXrefs => self.extract_data_xrefs(),
+            ExtractionJobType::CallGraphs => self.extract_function_call_graphs(),
+            ExtractionJobType::FuncInfo => self.extract_function_info(),
+            ExtractionJobType::Decompilation => self.extract_decompilation(),
+            ExtractionJobType::PCodeFunc => self.extract_pcode_function(),
+            ExtractionJobType::PCodeBB => self.extract_pcode_basic_block(),
+            ExtractionJobType::LocalVariableXrefs => self.extract_local_variable_xrefs(),
+            ExtractionJobType::DefUseChains => self.extract_def_use_chains(),
+            ExtractionJobType::GlobalStrings => self.extract_global_strings(),
+            ExtractionJobType::GlobalStringsXrefs => self.extract_global_strings_xrefs(),
+            ExtractionJobType::StringStats => self.extract_string_stats(),
+            ExtractionJobType::Sections => self.extract_sections(),
+            ExtractionJobType::Header => self.extract_header_info(),
+            ExtractionJobType::FunctionBytes => self.extract_function_bytes(),
+            ExtractionJobType::Imports => self.extract_imports(),
+            ExtractionJobType::Exports => self.extract_exports(),
+            ExtractionJobType::Relocations => self.extract_relocations(),
+            ExtractionJobType::EntryPoints => self.extract_entrypoints(),
+            ExtractionJobType::ByteEntropy => self.extract_byte_entropy(),
+            ExtractionJobType::ByteHistogram => self.extract_byte_histogram(),
+            ExtractionJobType::CFGEnriched => self.extract_func_cfgs_enriched(),
+            ExtractionJobType::MachO => self.extract_macho_info(),
+            ExtractionJobType::BinInfo | ExtractionJobType::BasicBlocks => {
+                ModeResult::new(&self.job_type_suffix)
+                    .fail(format!("Unsupported ExtractionJobType of {:?}", job_type))
+            }
+        }
+    }
+
+    pub fn extract_register_behaviour(&self) -> ModeResult {
         info!("Starting register behaviour extraction");
-        let mut r2p = self.setup_r2_pipe();
+        let mut result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
         let function_details = self.get_function_name_list(&mut r2p);
-        if function_details.is_ok() {
-            let mut register_behaviour_vec: HashMap<String, AEAFJRegisterBehaviour> =
-                HashMap::new();
+        if let Ok(function_details) = function_details {
+            let mut register_behaviour_vec: BTreeMap<String, AEAFJRegisterBehaviour> =
+                BTreeMap::new();
+            let mut ndjson_writer = match self.get_ndjson_writer() {
+                Ok(ndjson_writer) => ndjson_writer,
+                Err(e) => {
+                    r2p.close();
+                    return result.fail(e.to_string());
+                }
+            };
             info!("Executing aeafj for each function");
-            for function in function_details.unwrap().iter() {
-                r2p.cmd(format!("s @ {}", &function.name).as_str())
-                    .expect("Command failed..");
-                let json = r2p.cmd("aeafj").expect("Command failed..");
-                let json_obj: AEAFJRegisterBehaviour =
-                    serde_json::from_str(&json).expect("Unable to convert to JSON object!");
-                register_behaviour_vec.insert(function.name.clone(), json_obj);
-            }
+            let (r2p, errors) = self.for_each_function(
+                r2p,
+                &function_details,
+                |function, mut r2p| {
+                    let ret = r2p
+                        .cmd(format!("s {}", function.offset).as_str())
+                        .and_then(|_| r2p.cmd("aeafj"))
+                        .map_err(|e| anyhow!("{:?}", e))
+                        .and_then(|json| {
+                            deserialize_r2_output::<AEAFJRegisterBehaviour>("aeafj", &json)
+                        });
+                    (Some(r2p), ret)
+                },
+                |function, ret| match ret {
+                    Ok(json_obj) => {
+                        result.functions_processed += 1;
+                        match ndjson_writer.as_mut() {
+                            Some(writer) => Self::write_ndjson_line(
+                                writer,
+                                &json!({ self.get_function_key(function): json_obj }),
+                            ),
+                            None => {
+                                self.insert_function_output(
+                                    &mut register_behaviour_vec,
+                                    function,
+                                    json_obj,
+                                );
+                            }
+                        }
+                    }
+                    Err(e) => result.skip(format!(
+                        "Unable to get register behaviour for {} in {:?} - Skipping: {}",
+                        function.name, self.file_path, e
+                    )),
+                },
+            );
             info!("All functions processed");
-            r2p.close();
+            for error in errors {
+                result.skip(error)
+            }
+            if let Some(mut r2p) = r2p {
+                r2p.close();
+            }
             info!("r2p closed");
 
-            info!("Writing extracted data to file");
-            self.write_to_json(&json!(register_behaviour_vec))
+            let written = match ndjson_writer {
+                Some(ndjson_writer) => ndjson_writer.finish(),
+                None => {
+                    info!("Writing extracted data to file");
+                    self.write_to_json(&json!(register_behaviour_vec))
+                }
+            };
+            result.with_output_written(written)
         } else {
-            error!(
+            result.fail(format!(
                 "Failed to extract function details to generate register behaviour - Error in r2 extraction for {:?}",
                 self.file_path
-            )
+            ))
         }
     }
 
-    pub fn extract_func_cfgs(&self) {
-        let mut fp_filename = Path::new(&self.file_path)
-            .file_name()
-            .expect("Unable to get filename")
-            .to_string_lossy()
-            .to_string();
-        fp_filename = fp_filename + "_" + &self.job_type_suffix.clone();
-        let f_name = format!("{:?}/{}.json", &self.output_path, fp_filename);
-        if !Path::new(&f_name).exists() {
-            info!("{} not found. Continuing processing.", f_name);
-            let mut r2p = self.setup_r2_pipe();
-            info!("Executing agfj @@f on {:?}", self.file_path);
-            let mut json = r2p
-                .cmd("agfj @@f")
-                .expect("Failed to extract control flow graph information.");
-            info!("Closing r2p process for {:?}", self.file_path);
-            r2p.close();
-            info!("Starting JSON fixup for {:?}", self.file_path);
-            // Fix JSON object
-            json = json.replace("[]\n", ",");
-            json = json.replace("}]\n[{", "}],\n[{");
-            json.insert(0, '[');
-            json.push(']');
-            json = json.replace("}]\n,]", "}]\n]");
-            json = json.replace("\n,,[{", "\n,[{");
-            json = json.replace("\n,,[{", "\n,[{");
-            info!("JSON fixup finished for {:?}", self.file_path);
-
-            if json != "[,]" {
-                #[allow(clippy::expect_fun_call)]
-                // Kept in to ensure that the JSON decode error message is printed alongside the filename
-                let json: Value = serde_json::from_str(&json).expect(&format!(
-                    "Unable to parse json for {}: {}",
-                    fp_filename, json
-                ));
+    pub fn extract_func_cfgs(&self) -> ModeResult {
+        let mut result = ModeResult::new(&self.job_type_suffix);
+        let (json, errors) = match self.get_func_cfgs_json() {
+            Ok(json) => json,
+            Err(e) => return result.fail(e),
+        };
+        for error in errors {
+            result.skip(error)
+        }
 
-                self.write_to_json(&json);
-            } else {
-                error!(
-                    "File empty after JSON fixup - Only contains [,] - {}",
-                    f_name
-                )
+        result
+            .with_functions_processed(json.len())
+            .with_output_written(self.write_output(&json))
+    }
+
+    /// Extracts the CFGs for each function and generates attributed CFGs in a single pass
+    ///
+    /// The output of agfj @@f is parsed in memory and the attributed CFGs (with function
+    /// metadata embedded) are written directly, matching the output of generate graphs. The
+    /// tradeoff is that the raw CFG JSON is never written so it cannot be reused to generate
+    /// graphs with a different feature type without re-running extraction.
+    pub fn extract_func_cfgs_enriched(&self) -> ModeResult {
+        let mut result = ModeResult::new(&self.job_type_suffix);
+        let (json, errors) = match self.get_func_cfgs_json() {
+            Ok(json) => json,
+            Err(e) => return result.fail(e),
+        };
+        for error in errors {
+            result.skip(error)
+        }
+        self.write_func_cfgs_enriched(json, result)
+    }
+
+    /// Generates and writes the attributed CFGs for the parsed output of agfj @@f
+    fn write_func_cfgs_enriched(&self, json: Vec<Value>, mut result: ModeResult) -> ModeResult {
+        let mut functions: Vec<Vec<AGFJFunc>> = Vec::new();
+        for function in json {
+            match serde_json::from_value(function) {
+                Ok(function) => functions.push(function),
+                Err(e) => result.skip(format!(
+                    "Unable to parse function CFG for {:?}: {}",
+                    self.file_path, e
+                )),
+            }
+        }
+        let num_functions = functions.len();
+
+        let mut file = AGFJFile {
+            filename: self.get_cfg_enriched_filename(),
+            functions: Some(functions),
+            output_path: self.get_output_dir(),
+            min_blocks: self.r2p_config.min_blocks,
+            max_blocks: None,
+            feature_type: self.feature_type,
+            architecture: None,
+            norm_config: NormalizationConfig::default(),
+            graph_format: GraphFormat::Json,
+            embed_func_meta: true,
+            edge_types: false,
+            relative_addrs: false,
+        };
+        file.architecture = file.detect_architecture();
+
+        if file.architecture.is_none() {
+            return result.fail(format!(
+                "Unable to detect the architecture of {:?}",
+                self.file_path
+            ));
+        }
+
+        info!("Generating attributed CFGs for {:?}", self.file_path);
+        let mismatches = file.paralell_attributed_cfg_gen();
+        if !mismatches.is_empty() {
+            warn!(
+                "Skipped {} function(s) in {:?} due to a CFG node/block count mismatch",
+                mismatches.len(),
+                self.file_path
+            );
+        }
+        result.with_functions_processed(num_functions)
+    }
+
+    /// Runs agfj @@f and parses the CFG of each function
+    ///
+    /// Returns the parsed CFGs alongside an error for each function which failed to parse.
+    fn get_func_cfgs_json(&self) -> Result<(Vec<Value>, Vec<String>), String> {
+        let mut r2p = self.setup_r2_pipe().map_err(|e| e.to_string())?;
+        info!("Executing agfj @@f on {:?}", self.file_path);
+        let output = match r2p.cmd("agfj @@f") {
+            Ok(output) => output,
+            Err(e) => {
+                r2p.close();
+                return Err(format!(
+                    "Failed to extract control flow graph information for {:?} - {:?}",
+                    self.file_path, e
+                ));
             }
+        };
+        let bits = r2p
+            .cmdj("ij")
+            .ok()
+            .and_then(|info| info["bin"]["bits"].as_u64());
+        let selected = self.get_selected_graph_functions(&mut r2p);
+        info!("Closing r2p process for {:?}", self.file_path);
+        r2p.close();
+
+        let (mut json, errors) = parse_agfj_output(&output);
+        if let Some(bits) = bits {
+            add_bits_to_cfgs(&mut json, bits)
+        }
+        if let Some(selected) = selected.map_err(|e| e.to_string())? {
+            retain_selected_cfgs(&mut json, &selected);
+            info!(
+                "{} function CFGs selected for {:?}",
+                json.len(),
+                self.file_path
+            );
+        }
+        if json.is_empty() {
+            Err(format!(
+                "No function CFGs found in agfj @@f output - {:?}",
+                self.file_path
+            ))
         } else {
-            info!("{} as already exists. Skipping", f_name)
+            Ok((json, errors))
         }
     }
 
-    pub fn extract_function_call_graphs(&self) {
+    /// The filename the enriched CFGs are generated from. This mirrors the filename of a
+    /// cfg extraction so that the graphs are saved to the same location as generate graphs
+    fn get_cfg_enriched_filename(&self) -> PathBuf {
+        let file_name = self
+            .file_path
+            .file_name()
+            .expect("Unable to get filename")
+            .to_string_lossy()
+            .to_string();
+        PathBuf::from(format!("{}_cfg.json", file_name))
+    }
+
+    pub fn extract_function_call_graphs(&self) -> ModeResult {
         info!("Starting function call graph extraction");
-        let mut r2p = self.setup_r2_pipe();
-        let json = r2p.cmd("agCj").expect("agCj command failed to execute");
-        let function_call_graphs: Vec<AGCJFunctionCallGraph> =
-            serde_json::from_str(&json).expect("Unable to convert to JSON object!");
-        info!("Function call graph extracted.");
+        let result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
+        let json = r2p.cmd("agCj");
+        let selected = self.get_selected_graph_functions(&mut r2p);
         r2p.close();
         info!("r2p closed");
 
+        let mut function_call_graphs: Vec<AGCJFunctionCallGraph> = match json
+            .map_err(|e| anyhow!("{:?}", e))
+            .and_then(|json| deserialize_r2_output("agCj", &json))
+        {
+            Ok(function_call_graphs) => function_call_graphs,
+            Err(e) => {
+                return result.fail(format!(
+                    "Failed to extract function call graphs for {:?}: {}",
+                    self.file_path, e
+                ))
+            }
+        };
+        info!("Function call graph extracted.");
+
+        match selected {
+            Ok(Some(selected)) => {
+                retain_selected_call_graphs(&mut function_call_graphs, &selected);
+                info!(
+                    "{} function call graphs selected for {:?}",
+                    function_call_graphs.len(),
+                    self.file_path
+                );
+            }
+            Ok(None) => {}
+            Err(e) => {
+                return result.fail(format!(
+                    "Failed to select functions for {:?}: {}",
+                    self.file_path, e
+                ))
+            }
+        }
+
+        if self.r2p_config.demangle {
+            function_call_graphs
+                .iter_mut()
+                .for_each(AGCJFunctionCallGraph::demangle);
+        }
+
         info!("Writing extracted data to file");
-        self.write_to_json(&json!(function_call_graphs))
+        result
+            .with_functions_processed(function_call_graphs.len())
+            .with_output_written(self.write_output(&function_call_graphs))
     }
 
-    pub fn extract_function_xrefs(&self) {
-        let mut r2p = self.setup_r2_pipe();
+    pub fn extract_function_xrefs(&self) -> ModeResult {
+        let mut result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
         let function_details = self.get_function_name_list(&mut r2p);
-        let mut function_xrefs: HashMap<String, Vec<FunctionXrefDetails>> = HashMap::new();
+        let mut function_xrefs: BTreeMap<String, Vec<FunctionXrefDetails>> = BTreeMap::new();
         info!("Extracting xrefs for each function");
-        if function_details.is_ok() {
-            for function in function_details.unwrap().iter() {
-                let ret = self.get_function_xref_details(function.offset, &mut r2p);
-                function_xrefs.insert(function.name.clone(), ret);
-            }
+        if let Ok(function_details) = function_details {
+            let mut ndjson_writer = match self.get_ndjson_writer() {
+                Ok(ndjson_writer) => ndjson_writer,
+                Err(e) => {
+                    r2p.close();
+                    return result.fail(e.to_string());
+                }
+            };
+            let (r2p, errors) = self.for_each_function(
+                r2p,
+                &function_details,
+                |function, mut r2p| {
+                    let ret = self.get_function_xref_details(function.offset, &mut r2p);
+                    (Some(r2p), ret)
+                },
+                |function, ret| match ret {
+                    Ok(ret) => {
+                        result.functions_processed += 1;
+                        match ndjson_writer.as_mut() {
+                            Some(writer) => Self::write_ndjson_line(
+                                writer,
+                                &json!({ self.get_function_key(function): ret }),
+                            ),
+                            None => {
+                                self.insert_function_output(&mut function_xrefs, function, ret);
+                            }
+                        }
+                    }
+                    Err(e) => result.skip(format!(
+                        "Failed to get xrefs for function @ {:#x} in {:?} - Skipping: {}",
+                        function.offset, self.file_path, e
+                    )),
+                },
+            );
             info!("All functions processed");
-            r2p.close();
+            for error in errors {
+                result.skip(error)
+            }
+            if let Some(mut r2p) = r2p {
+                r2p.close();
+            }
             info!("r2p closed");
 
-            info!("Writing extracted data to file");
-            self.write_to_json(&json!(function_xrefs))
+            let written = match ndjson_writer {
+                Some(ndjson_writer) => ndjson_writer.finish(),
+                None => {
+                    info!("Writing extracted data to file");
+                    self.write_to_json(&json!(function_xrefs))
+                }
+            };
+            result.with_output_written(written)
         } else {
-            error!(
+            result.fail(format!(
                 "Failed to extract function xrefs - Error in r2 extraction for {:?}",
                 self.file_path
-            )
+            ))
+        }
+    }
+
+    pub fn extract_data_xrefs(&self) -> ModeResult {
+        info!("Starting data xref extraction");
+        let mut result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
+        let function_details = self.get_function_name_list(&mut r2p);
+        let mut function_data_xrefs: BTreeMap<String, Vec<DataXrefDetails>> = BTreeMap::new();
+
+        if let Ok(function_details) = function_details {
+            let mut ndjson_writer = match self.get_ndjson_writer() {
+                Ok(ndjson_writer) => ndjson_writer,
+                Err(e) => {
+                    r2p.close();
+                    return result.fail(e.to_string());
+                }
+            };
+            let (r2p, errors) = self.for_each_function(
+                r2p,
+                &function_details,
+                |function, mut r2p| {
+                    let ret = self.get_data_xref_details(function.offset, &mut r2p);
+                    (Some(r2p), ret)
+                },
+                |function, ret| match ret {
+                    Ok(ret) => {
+                        result.functions_processed += 1;
+                        match ndjson_writer.as_mut() {
+                            Some(writer) => Self::write_ndjson_line(
+                                writer,
+                                &json!({ self.get_function_key(function): ret }),
+                            ),
+                            None => {
+                                self.insert_function_output(
+                                    &mut function_data_xrefs,
+                                    function,
+                                    ret,
+                                );
+                            }
+                        }
+                    }
+                    Err(e) => result.skip(format!(
+                        "Failed to get data xrefs for function @ {:#x} in {:?} - Skipping: {}",
+                        function.offset, self.file_path, e
+                    )),
+                },
+            );
+            info!("All functions processed");
+            for error in errors {
+                result.skip(error)
+            }
+            if let Some(mut r2p) = r2p {
+                r2p.close();
+            }
+            info!("r2p closed");
+
+            let written = match ndjson_writer {
+                Some(ndjson_writer) => ndjson_writer.finish(),
+                None => {
+                    info!("Writing extracted data to file");
+                    self.write_to_json(&json!(function_data_xrefs))
+                }
+            };
+            result.with_output_written(written)
+        } else {
+            result.fail(format!(
+                "Failed to extract data xrefs - Error in r2 extraction for {:?}",
+                self.file_path
+            ))
         }
     }
 
-    pub fn extract_function_info(&self) {
+    pub fn extract_function_info(&self) -> ModeResult {
         info!("Starting function metdata extraction");
-        let mut fp_filename = self
-            .file_path
-            .file_name()
-            .expect("Unable to get filename")
-            .to_string_lossy()
-            .to_string();
+        let result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
 
-        fp_filename = fp_filename + "_" + &self.job_type_suffix.clone();
-        let f_name = format!("{:?}/{}.json", self.output_path, fp_filename);
-        if !Path::new(&f_name).exists() {
-            let mut r2p = self.setup_r2_pipe();
+        let function_details: Result<Vec<AFIJFunctionInfo>, Error> =
+            self.get_function_name_list(&mut r2p);
 
-            let function_details: Result<Vec<AFIJFunctionInfo>, r2pipe::Error> =
-                self.get_function_name_list(&mut r2p);
+        if let Err(e) = function_details {
+            r2p.close();
+            info!("r2p closed");
+            result.fail(format!(
+                "Unable to extract function info for {:?}: {}",
+                self.file_path, e
+            ))
+        } else {
+            r2p.close();
+            info!("r2p closed");
 
-            if function_details.is_err() {
-                error!("Unable to extract function info for {:?}", self.file_path);
-                r2p.close();
-                info!("r2p closed");
+            let function_details = function_details.unwrap();
+            if let Some(sqlite_path) = &self.sqlite_path {
+                info!("Writing extracted data to {:?}", sqlite_path);
+                let binary = self.file_path.file_name().unwrap().to_string_lossy();
+                match FunctionStore::open(sqlite_path)
+                    .and_then(|mut store| store.insert_functions(&binary, &function_details))
+                {
+                    Ok(inserted) => result.with_functions_processed(inserted),
+                    Err(e) => result.fail(format!(
+                        "Unable to write function info for {:?} to {:?}: {}",
+                        self.file_path, sqlite_path, e
+                    )),
+                }
             } else {
-                r2p.close();
-                info!("r2p closed");
-
                 info!("Writing extracted data to file");
-                self.write_to_json(&json!(function_details.unwrap()))
+                result
+                    .with_functions_processed(function_details.len())
+                    .with_output_written(self.write_output(&function_details))
             }
         }
     }
 
-    pub fn extract_decompilation(&self) {
+    pub fn extract_decompilation(&self) -> ModeResult {
         info!("Starting decompilation extraction!");
-        let mut r2p = self.setup_r2_pipe();
+        let mut result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
         let function_details = self.get_function_name_list(&mut r2p);
-        let mut function_decomp: HashMap<String, DecompJSON> = HashMap::new();
+        let mut function_decomp: BTreeMap<String, DecompJSON> = BTreeMap::new();
 
-        if function_details.is_ok() {
-            for function in function_details.unwrap().iter() {
-                let ret = self.get_ghidra_decomp(function.offset, &mut r2p);
-                function_decomp.insert(function.name.clone(), ret.unwrap());
-            }
+        if let Ok(function_details) = function_details {
+            let mut ndjson_writer = match self.get_ndjson_writer() {
+                Ok(ndjson_writer) => ndjson_writer,
+                Err(e) => {
+                    r2p.close();
+                    return result.fail(e.to_string());
+                }
+            };
+            let (r2p, errors) = self.for_each_function(
+                r2p,
+                &function_details,
+                |function, r2p| self.get_ghidra_decomp(function.offset, r2p),
+                |function, ret| match ret {
+                    Ok(ret) => {
+                        result.functions_processed += 1;
+                        match ndjson_writer.as_mut() {
+                            Some(writer) => Self::write_ndjson_line(
+                                writer,
+                                &json!({ self.get_function_key(function): ret }),
+                            ),
+                            None => {
+                                self.insert_function_output(&mut function_decomp, function, ret);
+                            }
+                        }
+                    }
+                    Err(e) => result.skip(format!(
+                        "Failed to decompile function @ {:#x} in {:?} - Skipping: {}",
+                        function.offset, self.file_path, e
+                    )),
+                },
+            );
             info!("Decompilation extracted successfully for all functions.");
-            r2p.close();
+            for error in errors {
+                result.skip(error)
+            }
+            if let Some(mut r2p) = r2p {
+                r2p.close();
+            }
             info!("r2p closed");
 
-            info!("Writing extracted data to file");
-            self.write_to_json(&json!(function_decomp))
+            let written = match ndjson_writer {
+                Some(ndjson_writer) => ndjson_writer.finish(),
+                None => {
+                    info!("Writing extracted data to file");
+                    self.write_to_json(&json!(function_decomp))
+                }
+            };
+            result.with_output_written(written)
         } else {
-            error!(
+            result.fail(format!(
                 "Failed to extract function decompilation - Error in r2 extraction for {:?}",
                 self.file_path
-            )
+            ))
         }
     }
 
-    pub fn extract_pcode_function(&self) {
+    pub fn extract_pcode_function(&self) -> ModeResult {
         info!("Starting pcode extraction at a function level");
-        let mut r2p = self.setup_r2_pipe();
+        let mut result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
         let function_details = self.get_function_name_list(&mut r2p);
         let mut function_pcode = Vec::new();
 
-        if function_details.is_ok() {
-            for function in function_details.unwrap().iter() {
-                let ret =
-                    self.get_ghidra_pcode_function(function.offset, function.ninstrs, &mut r2p);
+        if let Ok(function_details) = function_details {
+            let mut ndjson_writer = match self.get_ndjson_writer() {
+                Ok(ndjson_writer) => ndjson_writer,
+                Err(e) => {
+                    r2p.close();
+                    return result.fail(e.to_string());
+                }
+            };
+            let (r2p, errors) = self.for_each_function(
+                r2p,
+                &function_details,
+                |function, r2p| {
+                    self.get_ghidra_pcode_function(function.offset, function.ninstrs, r2p)
+                },
+                |function, ret| match ret {
+                    Ok(ret) => {
+                        result.functions_processed += 1;
 
-                let formatted_obj = PCodeJSONWithFuncName {
-                    function_name: function.name.clone(),
-                    pcode: ret.unwrap(),
-                };
+                        let formatted_obj = PCodeJSONWithFuncName {
+                            function_name: self.get_function_key(function),
+                            pcode: ret,
+                        };
 
-                function_pcode.push(formatted_obj);
-            }
+                        match ndjson_writer.as_mut() {
+                            Some(writer) => Self::write_ndjson_line(writer, &json!(formatted_obj)),
+                            None => function_pcode.push(formatted_obj),
+                        }
+                    }
+                    Err(e) => result.skip(format!(
+                        "Failed to extract pcode for function @ {:#x} in {:?} - Skipping: {}",
+                        function.offset, self.file_path, e
+                    )),
+                },
+            );
             info!("Pcode extracted successfully for all functions.");
-            r2p.close();
+            for error in errors {
+                result.skip(error)
+            }
+            if let Some(mut r2p) = r2p {
+                r2p.close();
+            }
             info!("r2p closed");
-            info!("Writing extracted data to file");
-            self.write_to_json(&json!(function_pcode))
+            let written = match ndjson_writer {
+                Some(ndjson_writer) => ndjson_writer.finish(),
+                None => {
+                    info!("Writing extracted data to file");
+                    self.write_to_json(&json!(function_pcode))
+                }
+            };
+            result.with_output_written(written)
         } else {
-            error!(
+            result.fail(format!(
                 "Failed to extract function decompilation - Error in r2 extraction for {:?}",
                 self.file_path
-            )
+            ))
         }
     }
 
-    pub fn extract_pcode_basic_block(&self) {
+    pub fn extract_pcode_basic_block(&self) -> ModeResult {
         info!("Starting pcode extraction for each basic block in each function within the binary");
-        let mut r2p = self.setup_r2_pipe();
+        let mut result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
         let function_details = self.get_function_name_list(&mut r2p);
         let mut function_pcode = Vec::new();
 
-        if function_details.is_ok() {
-            for function in function_details.unwrap().iter() {
-                let bb_addresses = self.get_basic_block_addresses(function.offset, &mut r2p);
-                let mut bb_pcode: Vec<PCodeJsonWithBB> = Vec::new();
-                for bb in bb_addresses.unwrap().iter() {
-                    let ret = self.get_ghidra_pcode_function(
-                        bb.addr,
-                        bb.ninstr.try_into().unwrap(),
-                        &mut r2p,
-                    );
-                    if ret.is_ok() {
-                        let ret = ret.unwrap();
-                        let pcode_json = PCodeJsonWithBB {
-                            block_start_adr: bb.addr,
-                            pcode: ret.pcode,
-                            asm: ret.asm,
-                            bb_info: bb.clone(),
+        if let Ok(function_details) = function_details {
+            let mut ndjson_writer = match self.get_ndjson_writer() {
+                Ok(ndjson_writer) => ndjson_writer,
+                Err(e) => {
+                    r2p.close();
+                    return result.fail(e.to_string());
+                }
+            };
+            let (r2p, errors) = self.for_each_function(
+                r2p,
+                &function_details,
+                |function, mut r2p| {
+                    let bb_addresses =
+                        match self.get_basic_block_addresses(function.offset, &mut r2p) {
+                            Ok(bb_addresses) => bb_addresses,
+                            Err(e) => return (Some(r2p), Err(e)),
                         };
-                        bb_pcode.push(pcode_json);
+                    let mut bb_pcode: Vec<PCodeJsonWithBB> = Vec::new();
+                    let mut errors = Vec::new();
+                    let mut r2p = Some(r2p);
+                    for bb in bb_addresses.iter() {
+                        let pipe = match r2p.take() {
+                            Some(pipe) => pipe,
+                            None => {
+                                errors.push(format!(
+                                    "r2pipe lost while extracting pcode for {} in {:?} - \
+                                    Skipping remaining basic blocks",
+                                    function.name, self.file_path
+                                ));
+                                break;
+                            }
+                        };
+                        let (pipe, ret) = self.get_ghidra_pcode_function(
+                            bb.addr,
+                            bb.ninstr.try_into().unwrap(),
+                            pipe,
+                        );
+                        r2p = pipe;
+                        match ret {
+                            Ok(ret) => {
+                                let pcode_json = PCodeJsonWithBB {
+                                    block_start_adr: bb.addr,
+                                    pcode: ret.pcode,
+                                    asm: ret.asm,
+                                    bb_info: bb.clone(),
+                                };
+                                bb_pcode.push(pcode_json);
+                            }
+                            Err(e) => errors.push(format!(
+                                "Failed to extract pcode for basic block @ {:#x} in {:?} - Skipping: {}",
+                                bb.addr, self.file_path, e
+                            )),
+                        }
                     }
-                }
+                    (r2p, Ok((bb_pcode, errors)))
+                },
+                |function, ret| match ret {
+                    Ok((bb_pcode, errors)) => {
+                        for error in errors {
+                            error!("{}", error);
+                            result.errors.push(error);
+                        }
+                        result.functions_processed += 1;
 
-                function_pcode.push(PCodeJsonWithBBAndFuncName {
-                    function_name: function.name.clone(),
-                    pcode_blocks: bb_pcode,
-                });
-            }
+                        let formatted_obj = PCodeJsonWithBBAndFuncName {
+                            function_name: self.get_function_key(function),
+                            pcode_blocks: bb_pcode,
+                        };
+
+                        match ndjson_writer.as_mut() {
+                            Some(writer) => Self::write_ndjson_line(writer, &json!(formatted_obj)),
+                            None => function_pcode.push(formatted_obj),
+                        }
+                    }
+                    Err(e) => result.skip(format!(
+                        "Failed to get basic blocks for function @ {:#x} in {:?} - Skipping: {}",
+                        function.offset, self.file_path, e
+                    )),
+                },
+            );
             info!("Pcode extracted successfully for all functions.");
-            r2p.close();
+            for error in errors {
+                result.skip(error)
+            }
+            if let Some(mut r2p) = r2p {
+                r2p.close();
+            }
             info!("r2p closed");
-            info!("Writing extracted data to file");
-            self.write_to_json(&json!(function_pcode))
+            let written = match ndjson_writer {
+                Some(ndjson_writer) => ndjson_writer.finish(),
+                None => {
+                    info!("Writing extracted data to file");
+                    self.write_to_json(&json!(function_pcode))
+                }
+            };
+            result.with_output_written(written)
         } else {
-            error!(
+            result.fail(format!(
                 "Failed to extract function pcode - Error in r2 extraction for {:?}",
                 self.file_path
-            )
+            ))
         }
     }
 
-    pub fn extract_local_variable_xrefs(&self) {
+    pub fn extract_local_variable_xrefs(&self) -> ModeResult {
         info!("Starting local variable xref extraction");
-        let mut r2p = self.setup_r2_pipe();
+        let mut result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
         let function_details = self.get_function_name_list(&mut r2p);
-        let mut function_local_variable_xrefs: HashMap<String, LocalVariableXrefs> = HashMap::new();
+        let mut function_local_variable_xrefs: BTreeMap<String, LocalVariableXrefs> =
+            BTreeMap::new();
 
-        if function_details.is_ok() {
-            for function in function_details.unwrap().iter() {
-                let ret = self.get_local_variable_xref_details(function.offset, &mut r2p);
-                function_local_variable_xrefs.insert(function.name.clone(), ret.unwrap());
+        if let Ok(function_details) = function_details {
+            let mut ndjson_writer = match self.get_ndjson_writer() {
+                Ok(ndjson_writer) => ndjson_writer,
+                Err(e) => {
+                    r2p.close();
+                    return result.fail(e.to_string());
+                }
+            };
+            let (r2p, errors) = self.for_each_function(
+                r2p,
+                &function_details,
+                |function, mut r2p| {
+                    let ret = self.get_local_variable_xref_details(function, &mut r2p);
+                    (Some(r2p), ret)
+                },
+                |function, ret| match ret {
+                    Ok(ret) => {
+                        result.functions_processed += 1;
+                        match ndjson_writer.as_mut() {
+                            Some(writer) => Self::write_ndjson_line(
+                                writer,
+                                &json!({ self.get_function_key(function): ret }),
+                            ),
+                            None => {
+                                self.insert_function_output(
+                                    &mut function_local_variable_xrefs,
+                                    function,
+                                    ret,
+                                );
+                            }
+                        }
+                    }
+                    Err(e) => result.skip(format!(
+                        "Failed to get local variable xrefs for function @ {:#x} in {:?} - Skipping: {}",
+                        function.offset, self.file_path, e
+                    )),
+                },
+            );
+            info!("All functions processed");
+            for error in errors {
+                result.skip(error)
+            }
+            if let Some(mut r2p) = r2p {
+                r2p.close();
             }
-            info!("Local variable xrefs extracted successfully for all functions.");
-            r2p.close();
             info!("r2p closed");
 
-            info!("Writing extracted data to file");
-            self.write_to_json(&json!(function_local_variable_xrefs))
+            let written = match ndjson_writer {
+                Some(ndjson_writer) => ndjson_writer.finish(),
+                None => {
+                    info!("Writing extracted data to file");
+                    self.write_to_json(&json!(function_local_variable_xrefs))
+                }
+            };
+            result.with_output_written(written)
         } else {
-            error!(
+            result.fail(format!(
                 "Failed to extract local variable xrefs - Error in r2 extraction for {:?}",
                 self.file_path
-            )
+            ))
+        }
+    }
+
+    pub fn extract_def_use_chains(&self) -> ModeResult {
+        info!("Starting def-use chain extraction");
+        let mut result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
+        let function_details = self.get_function_name_list(&mut r2p);
+        let mut function_def_use_chains: BTreeMap<String, Vec<DefUseChain>> = BTreeMap::new();
+
+        if let Ok(function_details) = function_details {
+            let mut ndjson_writer = match self.get_ndjson_writer() {
+                Ok(ndjson_writer) => ndjson_writer,
+                Err(e) => {
+                    r2p.close();
+                    return result.fail(e.to_string());
+                }
+            };
+            let (r2p, errors) = self.for_each_function(
+                r2p,
+                &function_details,
+                |function, mut r2p| {
+                    let ret = self
+                        .get_local_variable_xref_details(function, &mut r2p)
+                        .map(|xrefs| xrefs.def_use_chains());
+                    (Some(r2p), ret)
+                },
+                |function, ret| match ret {
+                    Ok(ret) => {
+                        result.functions_processed += 1;
+                        match ndjson_writer.as_mut() {
+                            Some(writer) => Self::write_ndjson_line(
+                                writer,
+                                &json!({ self.get_function_key(function): ret }),
+                            ),
+                            None => {
+                                self.insert_function_output(
+                                    &mut function_def_use_chains,
+                                    function,
+                                    ret,
+                                );
+                            }
+                        }
+                    }
+                    Err(e) => result.skip(format!(
+                        "Failed to get def-use chains for function @ {:#x} in {:?} - Skipping: {}",
+                        function.offset, self.file_path, e
+                    )),
+                },
+            );
+            info!("All functions processed");
+            for error in errors {
+                result.skip(error)
+            }
+            if let Some(mut r2p) = r2p {
+                r2p.close();
+            }
+            info!("r2p closed");
+
+            let written = match ndjson_writer {
+                Some(ndjson_writer) => ndjson_writer.finish(),
+                None => {
+                    info!("Writing extracted data to file");
+                    self.write_to_json(&json!(function_def_use_chains))
+                }
+            };
+            result.with_output_written(written)
+        } else {
+            result.fail(format!(
+                "Failed to extract def-use chains - Error in r2 extraction for {:?}",
+                self.file_path
+            ))
         }
     }
 
-    pub fn extract_global_strings(&self) {
+    pub fn extract_global_strings(&self) -> ModeResult {
         info!("Stating Global String Extraction");
-        let mut r2p = self.setup_r2_pipe();
+        let result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
+        let json = r2p.cmd("izj");
+        r2p.close();
+        info!("r2p closed");
+
+        if let Ok(json) = json {
+            debug!("{}", json);
+            match deserialize_r2_output::<Vec<StringEntry>>("izj", &json) {
+                Ok(json_obj) => result.with_output_written(self.write_output(&json_obj)),
+                Err(e) => result.fail(format!("{} - {:?}", e, self.file_path)),
+            }
+        } else {
+            result.fail("Failed to execute izj command successfully".to_string())
+        }
+    }
+
+    pub fn extract_header_info(&self) -> ModeResult {
+        info!("Starting header extraction");
+        let result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
+        let bininfo = r2p.cmd("ij");
+        let header = r2p.cmd("ihj");
+        r2p.close();
+        info!("r2p closed");
+
+        match (bininfo, header) {
+            (Ok(bininfo), Ok(header)) => {
+                let bininfo = deserialize_r2_output::<Value>("ij", &bininfo);
+                let fields = deserialize_r2_output::<Vec<HeaderField>>("ihj", &header);
+                match (bininfo, fields) {
+                    (Ok(bininfo), Ok(fields)) => result.with_output_written(
+                        self.write_to_json(&json!(HeaderInfo::new(&bininfo, fields))),
+                    ),
+                    (Err(e), _) | (_, Err(e)) => {
+                        result.fail(format!("{} - {:?}", e, self.file_path))
+                    }
+                }
+            }
+            _ => result.fail("Failed to execute ij/ihj commands successfully".to_string()),
+        }
+    }
+
+    /// Extracts Mach-O load commands, code signature presence, entrypoints and linked
+    /// dylibs. Non Mach-O binaries are skipped with a warning.
+    pub fn extract_macho_info(&self) -> ModeResult {
+        info!("Starting Mach-O metadata extraction");
+        let result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
+        let bininfo = match r2p.cmd("ij") {
+            Ok(bininfo) => deserialize_r2_output::<Value>("ij", &bininfo),
+            Err(_) => {
+                r2p.close();
+                return result.fail("Failed to execute ij command successfully".to_string());
+            }
+        };
+        let bininfo = match bininfo {
+            Ok(bininfo) => bininfo,
+            Err(e) => {
+                r2p.close();
+                return result.fail(format!("{} - {:?}", e, self.file_path));
+            }
+        };
+
+        if !MachOInfo::is_macho(&bininfo) {
+            r2p.close();
+            warn!(
+                "{:?} is not a Mach-O binary (bintype: {}). Skipping Mach-O extraction",
+                self.file_path,
+                bininfo["bin"]["bintype"].as_str().unwrap_or("unknown")
+            );
+            return result;
+        }
+
+        let header = r2p.cmd("ihj");
+        let entrypoints = r2p.cmd("iej");
+        let libraries = r2p.cmd("ilj");
+        r2p.close();
+        info!("r2p closed");
+
+        match (header, entrypoints, libraries) {
+            (Ok(header), Ok(entrypoints), Ok(libraries)) => {
+                let fields = deserialize_r2_output::<Vec<HeaderField>>("ihj", &header);
+                let entrypoints = deserialize_r2_output::<Vec<EntryPoint>>("iej", &entrypoints);
+                let libraries = deserialize_r2_output::<Value>("ilj", &libraries);
+                match (fields, entrypoints, libraries) {
+                    (Ok(fields), Ok(entrypoints), Ok(libraries)) => {
+                        result.with_output_written(self.write_to_json(&json!(MachOInfo::new(
+                            &bininfo,
+                            &fields,
+                            entrypoints,
+                            &libraries
+                        ))))
+                    }
+                    (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+                        result.fail(format!("{} - {:?}", e, self.file_path))
+                    }
+                }
+            }
+            _ => result.fail("Failed to execute ihj/iej/ilj commands successfully".to_string()),
+        }
+    }
+
+    pub fn extract_sections(&self) -> ModeResult {
+        info!("Starting section extraction");
+        let mut result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
+        let json = r2p.cmd("iSj");
+
+        if let Ok(json) = json {
+            debug!("{}", json);
+            match deserialize_r2_output::<Vec<SectionEntry>>("iSj", &json) {
+                Ok(mut sections) => {
+                    for section in sections.iter_mut() {
+                        if section.entropy.is_none() {
+                            match self.get_section_entropy(section, &mut r2p) {
+                                Ok(entropy) => section.entropy = entropy,
+                                Err(e) => result.skip(format!("{} - {}", section.name, e)),
+                            }
+                        }
+                    }
+                    r2p.close();
+                    info!("r2p closed");
+                    result.with_output_written(self.write_to_json(&json!(sections)))
+                }
+                Err(e) => {
+                    r2p.close();
+                    result.fail(format!("{} - {:?}", e, self.file_path))
+                }
+            }
+        } else {
+            r2p.close();
+            result.fail("Failed to execute iSj command successfully".to_string())
+        }
+    }
+
+    pub fn extract_string_stats(&self) -> ModeResult {
+        info!("Starting string statistics extraction");
+        let result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
+        let json = r2p.cmd("izj");
+        r2p.close();
+        info!("r2p closed");
+
+        if let Ok(json) = json {
+            match deserialize_r2_output::<Vec<StringEntry>>("izj", &json) {
+                Ok(strings) => result.with_output_written(
+                    self.write_to_json(&json!(compute_string_stats(&strings))),
+                ),
+                Err(e) => result.fail(format!("{} - {:?}", e, self.file_path)),
+            }
+        } else {
+            result.fail("Failed to execute izj command successfully".to_string())
+        }
+    }
+
+    pub fn extract_global_strings_xrefs(&self) -> ModeResult {
+        info!("Starting global string xref extraction");
+        let mut result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
         let json = r2p.cmd("izj");
+
+        let strings = match json {
+            Ok(json) => match deserialize_r2_output::<Vec<StringEntry>>("izj", &json) {
+                Ok(strings) => strings,
+                Err(e) => {
+                    r2p.close();
+                    return result.fail(format!("{} - {:?}", e, self.file_path));
+                }
+            },
+            Err(_) => {
+                r2p.close();
+                return result.fail("Failed to execute izj command successfully".to_string());
+            }
+        };
+
+        let mut string_xrefs = Vec::with_capacity(strings.len());
+        for string in strings {
+            let referenced_by = match self.get_string_referencing_functions(string.vaddr, &mut r2p)
+            {
+                Ok(referenced_by) => referenced_by,
+                Err(e) => {
+                    result.skip(format!("{:#x} - {}", string.vaddr, e));
+                    Vec::new()
+                }
+            };
+            string_xrefs.push(StringXrefEntry {
+                string,
+                referenced_by,
+            });
+        }
+        r2p.close();
+        info!("r2p closed");
+
+        result.with_output_written(self.write_output(&string_xrefs))
+    }
+
+    pub fn extract_imports(&self) -> ModeResult {
+        info!("Starting import extraction");
+        let result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
+        let json = r2p.cmd("iij");
+        r2p.close();
+        info!("r2p closed");
+
+        if let Ok(json) = json {
+            debug!("{}", json);
+            match deserialize_r2_output::<Vec<ImportEntry>>("iij", &json) {
+                Ok(json_obj) => result.with_output_written(self.write_output(&json_obj)),
+                Err(e) => result.fail(format!("{} - {:?}", e, self.file_path)),
+            }
+        } else {
+            result.fail("Failed to execute iij command successfully".to_string())
+        }
+    }
+
+    pub fn extract_entrypoints(&self) -> ModeResult {
+        info!("Starting entrypoint extraction");
+        let result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
+        let json = r2p.cmd("iej");
+        r2p.close();
+        info!("r2p closed");
+
+        if let Ok(json) = json {
+            debug!("{}", json);
+            match deserialize_r2_output::<Vec<EntryPoint>>("iej", &json) {
+                Ok(json_obj) => result.with_output_written(self.write_to_json(&json!(json_obj))),
+                Err(e) => result.fail(format!("{} - {:?}", e, self.file_path)),
+            }
+        } else {
+            result.fail("Failed to execute iej command successfully".to_string())
+        }
+    }
+
+    pub fn extract_relocations(&self) -> ModeResult {
+        info!("Starting relocation extraction");
+        let result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
+        let json = r2p.cmd("irj");
         r2p.close();
         info!("r2p closed");
 
-        if json.is_ok() {
-            let json = json.unwrap();
+        if let Ok(json) = json {
             debug!("{}", json);
-            let json_obj: Vec<StringEntry> =
-                serde_json::from_str(&json).expect("Unable to convert to JSON object!");
+            match deserialize_r2_output::<Vec<RelocEntry>>("irj", &json) {
+                Ok(json_obj) => result.with_output_written(self.write_to_json(&json!(json_obj))),
+                Err(e) => result.fail(format!("{} - {:?}", e, self.file_path)),
+            }
+        } else {
+            result.fail("Failed to execute irj command successfully".to_string())
+        }
+    }
+
+    pub fn extract_byte_entropy(&self) -> ModeResult {
+        info!("Starting byte entropy histogram extraction");
+        let result = ModeResult::new(&self.job_type_suffix);
+        match fs::read(&self.file_path) {
+            Ok(bytes) => {
+                let histogram =
+                    byte_entropy_histogram(&bytes, BYTE_ENTROPY_WINDOW, BYTE_ENTROPY_STEP);
+                result.with_output_written(self.write_to_json(&json!(histogram)))
+            }
+            Err(e) => result.fail(format!("Unable to read {:?} - {}", self.file_path, e)),
+        }
+    }
+
+    pub fn extract_byte_histogram(&self) -> ModeResult {
+        info!("Starting byte histogram extraction");
+        let result = ModeResult::new(&self.job_type_suffix);
+        match fs::read(&self.file_path) {
+            Ok(bytes) => {
+                let binary_name = self
+                    .file_path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string();
+                let histogram = byte_histogram(&bytes);
+                result.with_output_written(
+                    self.write_to_json(&json!({ binary_name: histogram.to_vec() })),
+                )
+            }
+            Err(e) => result.fail(format!("Unable to read {:?} - {}", self.file_path, e)),
+        }
+    }
+
+    pub fn extract_exports(&self) -> ModeResult {
+        info!("Starting export extraction");
+        let result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
+        let json = r2p.cmd("iEj");
+        r2p.close();
+        info!("r2p closed");
 
-            self.write_to_json(&json!(json_obj))
+        if let Ok(json) = json {
+            debug!("{}", json);
+            match deserialize_r2_output::<Vec<ExportEntry>>("iEj", &json) {
+                Ok(json_obj) => result.with_output_written(self.write_output(&json_obj)),
+                Err(e) => result.fail(format!("{} - {:?}", e, self.file_path)),
+            }
         } else {
-            error!("Failed to execute axj command successfully")
+            result.fail("Failed to execute iEj command successfully".to_string())
         }
     }
 
-    pub fn extract_function_bytes(&self) {
+    pub fn extract_function_bytes(&self) -> ModeResult {
         info!("Starting function bytes extraction");
-        let mut r2p = self.setup_r2_pipe();
+        let mut result = ModeResult::new(&self.job_type_suffix);
+        let mut r2p = match self.setup_r2_pipe() {
+            Ok(r2p) => r2p,
+            Err(e) => return result.fail(e.to_string()),
+        };
         let function_details = self.get_function_name_list(&mut r2p);
 
-        if function_details.is_ok() {
-            for function in function_details.unwrap().iter() {
-                debug!(
-                    "Function Name: {} Offset: {} Size: {}",
-                    function.name, function.offset, function.size
-                );
-                let function_bytes = self.get_bytes_function(function.offset, &mut r2p);
-                if let Ok(valid_bytes_obj) = function_bytes {
-                    Self::write_to_bin(self, &function.name, &valid_bytes_obj.bytes)
-                        .expect("Failed to write bytes to bin.");
-                };
-            }
+        if let Ok(function_details) = function_details {
+            let functions = self.remove_oversized_functions(function_details, &mut result);
+            let mut archive: BTreeMap<String, String> = BTreeMap::new();
+            let mut write_error = None;
+            let (r2p, errors) = self.for_each_function(
+                r2p,
+                &functions,
+                |function, mut r2p| {
+                    debug!(
+                        "Function Name: {} Offset: {} Size: {}",
+                        function.name, function.offset, function.size
+                    );
+                    let function_bytes = self
+                        .get_bytes_function(function.offset, &mut r2p)
+                        .map_err(|e| anyhow!("{:?}", e));
+                    (Some(r2p), function_bytes)
+                },
+                |function, function_bytes| match function_bytes {
+                    Ok(valid_bytes_obj) => {
+                        match self.bytes_format {
+                            BytesFormat::Bin => {
+                                let function_filename = self.get_function_filename(function);
+                                if let Err(e) =
+                                    self.write_to_bin(&function_filename, &valid_bytes_obj.bytes)
+                                {
+                                    write_error.get_or_insert(e);
+                                    return;
+                                }
+                            }
+                            BytesFormat::Archive => self.insert_function_output(
+                                &mut archive,
+                                function,
+                                to_hex_string(&valid_bytes_obj.bytes),
+                            ),
+                        }
+                        result.functions_processed += 1;
+                    }
+                    Err(e) => result.skip(format!(
+                        "Failed to extract bytes for function @ {:#x} in {:?} - Skipping: {}",
+                        function.offset, self.file_path, e
+                    )),
+                },
+            );
             info!("Function bytes successfully extracted");
-            r2p.close();
+            for error in errors {
+                result.skip(error)
+            }
+            if let Some(mut r2p) = r2p {
+                r2p.close();
+            }
             info!("r2p closed");
+            let written = match write_error {
+                Some(e) => Err(e),
+                None if self.bytes_format == BytesFormat::Archive => {
+                    self.write_to_json(&json!(archive))
+                }
+                None => Ok(()),
+            };
+            result.with_output_written(written)
         } else {
-            error!(
+            result.fail(format!(
                 "Failed to extract function bytes - Error in r2 extraction for {:?}",
                 self.file_path
-            )
+            ))
+        }
+    }
+
+    /// Removes functions larger than --max-func-bytes before extracting their bytes
+    ///
+    /// Mis-analysed functions can report a huge size which would otherwise be dumped
+    /// and decoded in full.
+    fn remove_oversized_functions(
+        &self,
+        functions: Vec<AFIJFunctionInfo>,
+        result: &mut ModeResult,
+    ) -> Vec<AFIJFunctionInfo> {
+        let max_func_bytes = self.r2p_config.max_func_bytes;
+        functions
+            .into_iter()
+            .filter(|function| {
+                if function.size > max_func_bytes as i128 {
+                    result.skip(format!(
+                        "{} in {:?} has a reported size of {} bytes which exceeds the max of {} - Skipping",
+                        function.name, self.file_path, function.size, max_func_bytes
+                    ));
+                    false
+                } else {
+                    true
+                }
+            })
+            .collect()
+    }
+
+    /// Runs a per-function extraction across a list of functions
+    ///
+    /// Each result is passed to the sink on the calling thread in the same order as `functions`,
+    /// regardless of the number of intra file threads. If more than one intra file thread has
+    /// been configured, the function list is split into shards with each additional shard
+    /// processed by its own r2pipe instance. Results that arrive ahead of an earlier function
+    /// are held back until it has been sunk so streamed output (such as NDJSON) is
+    /// deterministic.
+    ///
+    /// The extraction returns `None` in place of the r2pipe if it has been lost (i.e a hung
+    /// r2 process was killed). A new r2pipe is spawned for the next function and if that
+    /// fails, the remaining functions within the shard are skipped. The errors for skipped
+    /// functions are returned alongside the r2pipe used for the first shard (if it is still
+    /// alive) once all functions have been processed.
+    ///
+    /// The time taken is logged at info level so the speedup from additional r2pipe
+    /// instances can be measured for a given binary - each instance re-runs analysis so
+    /// binaries with few functions may see no benefit.
+    fn for_each_function<T, F, S>(
+        &self,
+        r2p: R2Pipe,
+        functions: &[AFIJFunctionInfo],
+        extract: F,
+        mut sink: S,
+    ) -> (Option<R2Pipe>, Vec<String>)
+    where
+        T: Send,
+        F: Fn(&AFIJFunctionInfo, R2Pipe) -> (Option<R2Pipe>, T) + Sync,
+        S: FnMut(&AFIJFunctionInfo, T),
+    {
+        let num_threads = self
+            .r2p_config
+            .intra_file_threads
+            .clamp(1, functions.len().max(1));
+        let progress_bar = self.function_progress_bar(functions.len() as u64);
+        let start = Instant::now();
+        let mut errors = Vec::new();
+
+        if num_threads == 1 {
+            let r2p = self.run_function_shard(Some(r2p), functions, &extract, |_, ret| {
+                match ret {
+                    Ok((function, ret)) => sink(function, ret),
+                    Err(e) => errors.push(e),
+                }
+                progress_bar.inc(1);
+            });
+            progress_bar.finish();
+            self.log_function_timing(functions.len(), 1, start);
+            return (r2p, errors);
+        }
+
+        info!(
+            "Processing {} functions across {} r2pipe instances for {:?}",
+            functions.len(),
+            num_threads,
+            self.file_path
+        );
+        let shard_size = functions.len().div_ceil(num_threads);
+        let (tx, rx) = mpsc::channel();
+        let extract = &extract;
+
+        let r2p = thread::scope(|scope| {
+            let mut shards = functions.chunks(shard_size).enumerate();
+            let (_, first_shard) = shards.next().unwrap_or_default();
+            let first_tx = tx.clone();
+            let first_handle = scope.spawn(move || {
+                self.run_function_shard(Some(r2p), first_shard, extract, |idx, ret| {
+                    first_tx.send((idx, ret)).unwrap()
+                })
+            });
+
+            for (shard_idx, shard) in shards {
+                let shard_tx = tx.clone();
+                let shard_start = shard_idx * shard_size;
+                scope.spawn(move || {
+                    // The binary has already been opened successfully by the first pipe so
+                    // the additional pipe is spawned within the shard
+                    let r2p = self.run_function_shard(None, shard, extract, |idx, ret| {
+                        shard_tx.send((shard_start + idx, ret)).unwrap()
+                    });
+                    if let Some(mut r2p) = r2p {
+                        r2p.close();
+                    }
+                });
+            }
+            drop(tx);
+
+            // Shards finish out of order so hold results back until every earlier function
+            // has been passed to the sink
+            let mut pending = BTreeMap::new();
+            let mut next_idx = 0;
+            for (idx, ret) in rx.iter() {
+                pending.insert(idx, ret);
+                while let Some(ret) = pending.remove(&next_idx) {
+                    match ret {
+                        Ok((function, ret)) => sink(function, ret),
+                        Err(e) => errors.push(e),
+                    }
+                    progress_bar.inc(1);
+                    next_idx += 1;
+                }
+            }
+            progress_bar.finish();
+            first_handle
+                .join()
+                .expect("Intra file extraction thread panicked")
+        });
+        self.log_function_timing(functions.len(), num_threads, start);
+        (r2p, errors)
+    }
+
+    /// Runs a per-function extraction across a shard of functions using a single r2pipe
+    ///
+    /// A new r2pipe is spawned whenever one is not available. If that fails, every remaining
+    /// function is emitted as an error. Each result is emitted with its index in the shard.
+    fn run_function_shard<'a, T, F, E>(
+        &self,
+        mut r2p: Option<R2Pipe>,
+        functions: &'a [AFIJFunctionInfo],
+        extract: &F,
+        mut emit: E,
+    ) -> Option<R2Pipe>
+    where
+        F: Fn(&AFIJFunctionInfo, R2Pipe) -> (Option<R2Pipe>, T),
+        E: FnMut(usize, Result<(&'a AFIJFunctionInfo, T), String>),
+    {
+        for (idx, function) in functions.iter().enumerate() {
+            let pipe = match r2p.take() {
+                Some(pipe) => pipe,
+                None => match self.setup_r2_pipe() {
+                    Ok(pipe) => pipe,
+                    Err(e) => {
+                        for (idx, function) in functions.iter().enumerate().skip(idx) {
+                            emit(
+                                idx,
+                                Err(format!(
+                                    "Unable to spawn r2pipe for function @ {:#x} in {:?} - \
+                                    Skipping: {}",
+                                    function.offset, self.file_path, e
+                                )),
+                            );
+                        }
+                        return None;
+                    }
+                },
+            };
+            let (pipe, ret) = extract(function, pipe);
+            r2p = pipe;
+            emit(idx, Ok((function, ret)));
+        }
+        r2p
+    }
+
+    fn log_function_timing(&self, num_functions: usize, num_threads: usize, start: Instant) {
+        info!(
+            "Processed {} functions with {} r2pipe instance(s) in {:.2?} for {:?}",
+            num_functions,
+            num_threads,
+            start.elapsed(),
+            self.file_path
+        );
+    }
+
+    /// Create a progress bar (with an ETA) for the functions being processed. The
+    /// progress bar is hidden unless progress reporting has been enabled.
+    fn function_progress_bar(&self, num_functions: u64) -> ProgressBar {
+        if !self.r2p_config.progress {
+            return ProgressBar::hidden();
         }
+
+        let progress_bar = ProgressBar::new(num_functions);
+        progress_bar.set_style(
+            ProgressStyle::with_template(
+                "[{elapsed_precise}] {wide_bar} {pos}/{len} functions (ETA: {eta})",
+            )
+            .unwrap(),
+        );
+        progress_bar
     }
 
     // r2 commands to structs
@@ -778,7 +3241,7 @@ impl FileToBeProcessed {
         function_addr: u64,
         r2p: &mut R2Pipe,
     ) -> Result<FuncBytes, r2pipe::Error> {
-        Self::go_to_address(r2p, function_addr);
+        Self::go_to_address(r2p, function_addr)?;
 
         let function_bytes = r2p.cmd(format!("pcs @ {}", function_addr).as_str())?;
         let function_bytes = function_bytes.replace('"', "");
@@ -794,10 +3257,17 @@ impl FileToBeProcessed {
         &self,
         function_addr: u64,
         num_instructons: i64,
-        r2p: &mut R2Pipe,
-    ) -> Result<PCodeJSON, r2pipe::Error> {
-        Self::go_to_address(r2p, function_addr);
-        let pcode_ret = r2p.cmd(format!("pdgsd {}", num_instructons).as_str())?;
+        mut r2p: R2Pipe,
+    ) -> (Option<R2Pipe>, Result<PCodeJSON, Error>) {
+        if let Err(e) = Self::go_to_address(&mut r2p, function_addr) {
+            return (Some(r2p), Err(anyhow!("{:?}", e)));
+        }
+        let (r2p, pcode_ret) =
+            self.cmd_with_timeout(r2p, format!("pdgsd {}", num_instructons).as_str());
+        let pcode_ret = match pcode_ret {
+            Ok(pcode_ret) => pcode_ret,
+            Err(e) => return (r2p, Err(e)),
+        };
         let lines = pcode_ret.lines();
         let mut asm_ins = Vec::new();
         let mut pcode_ins = Vec::new();
@@ -810,89 +3280,214 @@ impl FileToBeProcessed {
             }
         }
 
-        Ok(PCodeJSON {
-            pcode: pcode_ins,
-            asm: Some(asm_ins),
-        })
+        (
+            r2p,
+            Ok(PCodeJSON {
+                pcode: pcode_ins,
+                asm: Some(asm_ins),
+            }),
+        )
     }
 
     fn get_ghidra_decomp(
         &self,
         function_addr: u64,
-        r2p: &mut R2Pipe,
-    ) -> Result<DecompJSON, r2pipe::Error> {
-        Self::go_to_address(r2p, function_addr);
-        let json = r2p.cmd("pdgj")?;
+        mut r2p: R2Pipe,
+    ) -> (Option<R2Pipe>, Result<DecompJSON, Error>) {
+        if let Err(e) = Self::go_to_address(&mut r2p, function_addr) {
+            return (Some(r2p), Err(anyhow!("{:?}", e)));
+        }
 
+        // The annotations are only available from pdgj so when they are not needed
+        // the plain text output of pdg is used to avoid parsing large JSON documents
         if self.with_annotations {
-            let json_obj: DecompJSON =
-                serde_json::from_str(&json).expect("Unable to convert to JSON object!");
-            Ok(json_obj)
+            let (r2p, json) = self.cmd_with_timeout(r2p, "pdgj");
+            let json = match json {
+                Ok(json) => json,
+                Err(e) => return (r2p, Err(e)),
+            };
+            (r2p, deserialize_r2_output::<DecompJSON>("pdgj", &json))
         } else {
-            let json_obj: Value =
-                serde_json::from_str(&json).expect("Unable to convert to JSON object!");
-            Ok(DecompJSON {
-                code: json_obj["code"].as_str().unwrap().to_string(),
-                annotations: Vec::new(),
-            })
+            let (r2p, code) = self.cmd_with_timeout(r2p, "pdg");
+            (r2p, code.map(|code| parse_pdg_output(&code)))
         }
     }
 
-    fn get_function_name_list(
-        &self,
-        r2p: &mut R2Pipe,
-    ) -> Result<Vec<AFIJFunctionInfo>, r2pipe::Error> {
-        info!("Getting function information from binary");
-        let json = r2p.cmd("aflj");
-
-        if let Ok(json_str) = json {
-            let json_obj: Vec<AFIJFunctionInfo> =
-                serde_json::from_str(json_str.as_ref()).expect("Unable to convert to JSON object!");
-            Ok(json_obj)
+    /// The key used for a function within per-function outputs
+    ///
+    /// Function names are not guaranteed to be unique within a binary (i.e static
+    /// functions) so the offset is included when keying by offset (i.e main@0x1139).
+    fn get_function_key(&self, function: &AFIJFunctionInfo) -> String {
+        if self.r2p_config.key_by_offset {
+            format!("{}@{:#x}", function.name, function.offset)
         } else {
-            Err(json.unwrap_err())
+            function.name.clone()
         }
     }
 
-    fn get_basic_block_addresses(
+    /// The filename used for a function within the bytes extraction mode. The symbol
+    /// is keyed by offset when keying by offset (see `get_function_key`).
+    fn get_function_filename(&self, function: &AFIJFunctionInfo) -> String {
+        format_function_filename(
+            &self.func_filename,
+            &self.get_function_key(function),
+            function.offset,
+        )
+    }
+
+    /// Inserts the output for a function, warning if the output of an earlier function
+    /// with the same key has been overwritten
+    fn insert_function_output<T>(
+        &self,
+        outputs: &mut BTreeMap<String, T>,
+        function: &AFIJFunctionInfo,
+        output: T,
+    ) {
+        if outputs
+            .insert(self.get_function_key(function), output)
+            .is_some()
+        {
+            warn!(
+                "Multiple functions named {} found in {:?} - Only the last is kept. Use --key-by-offset to keep all of them",
+                function.name, self.file_path
+            )
+        }
+    }
+
+    /// Lists the functions selected by --public-only and --function-filter for the graph
+    /// outputs (cfg, cfg-enriched and cg) which are generated for every function at once
+    /// rather than from the function list. Returns `None` when every function is selected.
+    fn get_selected_graph_functions(
+        &self,
+        r2p: &mut R2Pipe,
+    ) -> Result<Option<Vec<AFIJFunctionInfo>>, Error> {
+        if !self.r2p_config.public_only && self.r2p_config.function_filter.is_none() {
+            return Ok(None);
+        }
+        let functions: Vec<AFIJFunctionInfo> =
+            deserialize_r2_output("aflj", &r2p.cmd("aflj").map_err(|e| anyhow!("{:?}", e))?)?;
+        Ok(Some(
+            functions
+                .into_iter()
+                .filter(|function| !self.r2p_config.public_only || is_public_function(function))
+                .filter(|function| {
+                    self.r2p_config
+                        .function_filter
+                        .as_ref()
+                        .is_none_or(|function_filter| function_filter.is_match(function))
+                })
+                .collect(),
+        ))
+    }
+
+    fn get_function_name_list(&self, r2p: &mut R2Pipe) -> Result<Vec<AFIJFunctionInfo>, Error> {
+        info!("Getting function information from binary");
+        let reanalyze =
+            self.r2p_config.auto_reanalyze && self.r2p_config.analysis_level == AnalysisLevel::Aa;
+        let functions = list_functions_with_reanalysis(
+            |cmd| r2p.cmd(cmd).map_err(|e| anyhow!("{:?}", e)),
+            reanalyze,
+        )?;
+        if reanalyze && functions.is_empty() {
+            warn!(
+                "No functions found in {:?} even after reanalysis",
+                self.file_path
+            )
+        }
+
+        let functions = if self.r2p_config.public_only {
+            let functions: Vec<AFIJFunctionInfo> =
+                functions.into_iter().filter(is_public_function).collect();
+            info!(
+                "{} public functions found in {:?}",
+                functions.len(),
+                self.file_path
+            );
+            functions
+        } else {
+            functions
+        };
+
+        let functions = if self.r2p_config.min_instructions > 0 {
+            let min_instructions = self.r2p_config.min_instructions;
+            let functions: Vec<AFIJFunctionInfo> = functions
+                .into_iter()
+                .filter(|function| has_min_instructions(function, min_instructions))
+                .collect();
+            info!(
+                "{} functions with at least {} instructions found in {:?}",
+                functions.len(),
+                min_instructions,
+                self.file_path
+            );
+            functions
+        } else {
+            functions
+        };
+
+        let mut functions = match &self.r2p_config.function_filter {
+            Some(function_filter) => {
+                let functions: Vec<AFIJFunctionInfo> = functions
+                    .into_iter()
+                    .filter(|function| function_filter.is_match(function))
+                    .collect();
+                info!(
+                    "{} functions matched the function filter for {:?}",
+                    functions.len(),
+                    self.file_path
+                );
+                functions
+            }
+            None => functions,
+        };
+
+        // Demangling happens last so filters are matched against the names r2 uses
+        if self.r2p_config.demangle {
+            functions.iter_mut().for_each(demangle_function);
+        }
+        Ok(functions)
+    }
+
+    fn get_basic_block_addresses(
         &self,
         function_addr: u64,
         r2p: &mut R2Pipe,
-    ) -> Result<BasicBlockInfo, r2pipe::Error> {
+    ) -> Result<BasicBlockInfo, Error> {
         info!(
             "Getting the basic block information for function @ {}",
             function_addr
         );
-        Self::go_to_address(r2p, function_addr);
-        // Get basic block information
-        let json = r2p.cmd("afbj");
-
-        // Convert returned JSON into a BasicBlockInfo struct
-        if let Ok(json_str) = json {
-            let bb_addresses: BasicBlockInfo = serde_json::from_str(json_str.as_ref())
-                .expect("Unable to convert returned object into a BasicBlockInfo struct!");
-            Ok(bb_addresses)
-        } else {
-            Err(json.unwrap_err())
-        }
+        Self::go_to_address(r2p, function_addr)?;
+        // Get basic block information and convert it into a BasicBlockInfo struct
+        let json = r2p.cmd("afbj")?;
+        deserialize_r2_output("afbj", &json)
     }
 
     fn get_local_variable_xref_details(
         &self,
-        function_addr: u64,
+        function: &AFIJFunctionInfo,
         r2p: &mut R2Pipe,
     ) -> Result<LocalVariableXrefs, r2pipe::Error> {
         info!("Getting local variable xref details");
-        Self::go_to_address(r2p, function_addr);
-        let json = r2p.cmd("axvj");
-
-        // Convert returned JSON into a BasicBlockInfo struct
-        if let Ok(json_str) = json {
-            let local_variable_xrefs: LocalVariableXrefs = serde_json::from_str(json_str.as_ref())
-                .expect("Unable to convert returned object into a BasicBlockInfo struct!");
-            Ok(local_variable_xrefs)
-        } else {
-            Err(json.unwrap_err())
+        Self::go_to_address(r2p, function.offset)?;
+        let json = r2p.cmd("axvj")?;
+        Ok(Self::parse_local_variable_xrefs(&json, &function.name))
+    }
+
+    /// Parse the axvj output for a function
+    ///
+    /// r2 can return `null` or an empty string for some functions (i.e thunks) so
+    /// anything that is not a valid object is treated as no local variable xrefs.
+    fn parse_local_variable_xrefs(json: &str, function_name: &str) -> LocalVariableXrefs {
+        match serde_json::from_str(json) {
+            Ok(local_variable_xrefs) => local_variable_xrefs,
+            Err(e) => {
+                warn!(
+                    "Unable to parse local variable xrefs for {} - {}. Using empty xrefs.",
+                    function_name, e
+                );
+                LocalVariableXrefs::default()
+            }
         }
     }
 
@@ -900,12 +3495,11 @@ impl FileToBeProcessed {
         &self,
         function_addr: u64,
         r2p: &mut R2Pipe,
-    ) -> Vec<FunctionXrefDetails> {
+    ) -> Result<Vec<FunctionXrefDetails>, Error> {
         info!("Getting function xref details");
-        Self::go_to_address(r2p, function_addr);
-        let json = r2p.cmd("axffj").expect("axffj command failed");
-        let mut json_obj: Vec<FunctionXrefDetails> =
-            serde_json::from_str(&json).expect("Unable to convert to JSON object!");
+        Self::go_to_address(r2p, function_addr)?;
+        let json = r2p.cmd("axffj")?;
+        let mut json_obj: Vec<FunctionXrefDetails> = deserialize_r2_output("axffj", &json)?;
         debug!("Replacing all CALL xrefs with actual function name");
         // TODO: There is a minor bug in this where functions without any xrefs are included.
         // Been left in as may be useful later down the line.
@@ -913,18 +3507,113 @@ impl FileToBeProcessed {
             debug!("Replacing all CALL xrefs with actual function name");
             for element in json_obj.iter_mut() {
                 if element.type_field == "CALL" {
-                    let function_name = r2p
-                        .cmd(format!("afi. @ {}", &element.ref_field).as_str())
-                        .expect("afi. command failed");
-                    element.name = function_name;
+                    element.name = r2p.cmd(format!("afi. @ {}", element.ref_field).as_str())?;
                 }
             }
         };
-        json_obj
+        Ok(json_obj)
+    }
+
+    fn get_data_xref_details(
+        &self,
+        function_addr: u64,
+        r2p: &mut R2Pipe,
+    ) -> Result<Vec<DataXrefDetails>, Error> {
+        Self::go_to_address(r2p, function_addr)?;
+        let json = r2p.cmd("axffj")?;
+        let xrefs: Vec<FunctionXrefDetails> = deserialize_r2_output("axffj", &json)?;
+        Ok(DataXrefDetails::from_function_xrefs(xrefs))
+    }
+
+    fn get_string_referencing_functions(
+        &self,
+        string_addr: i64,
+        r2p: &mut R2Pipe,
+    ) -> Result<Vec<String>, Error> {
+        let json = r2p.cmd(format!("axtj @ {}", string_addr).as_str())?;
+        let xrefs: Vec<XrefToEntry> = deserialize_r2_output("axtj", &json)?;
+
+        let mut referenced_by: Vec<String> = Vec::new();
+        // References from outside of a recovered function have no fcn_name
+        for function_name in xrefs.into_iter().filter_map(|xref| xref.fcn_name) {
+            if !referenced_by.contains(&function_name) {
+                referenced_by.push(function_name);
+            }
+        }
+        Ok(referenced_by)
+    }
+
+    // Sections which are not mapped into memory or have no bytes in the file
+    // (i.e .bss) are skipped as there is nothing meaningful to read
+    fn get_section_entropy(
+        &self,
+        section: &SectionEntry,
+        r2p: &mut R2Pipe,
+    ) -> Result<Option<f64>, Error> {
+        if section.vaddr == 0 || section.size == 0 {
+            return Ok(None);
+        }
+        let hex = r2p.cmd(format!("p8 {} @ {}", section.size, section.vaddr).as_str())?;
+        Ok(Some(shannon_entropy(&parse_hex_string(&hex))))
     }
 
     // Helper Functions
-    fn write_to_json(&self, json_obj: &Value) {
+    fn get_existing_output_path(&self, job_type: &ExtractionJobType) -> PathBuf {
+        if *job_type == ExtractionJobType::CFGEnriched {
+            get_save_file_path(
+                &self.get_cfg_enriched_filename(),
+                &self.get_output_dir(),
+                None,
+                Some(self.feature_type.unwrap().to_string()),
+                None,
+                None,
+            )
+        } else if *job_type == ExtractionJobType::FunctionBytes
+            && self.bytes_format == BytesFormat::Bin
+        {
+            let mut output_dirpath = self.get_output_dir();
+            output_dirpath.push(self.file_path.file_name().expect("Unable to get filename"));
+            output_dirpath
+        } else {
+            self.get_output_filepath()
+        }
+    }
+
+    /// The values substituted into the output template for this binary
+    fn output_template_values(&self, func: Option<String>) -> OutputTemplateValues {
+        let mode = if self.with_annotations {
+            self.job_type_suffix.clone() + "_annotations"
+        } else {
+            self.job_type_suffix.clone()
+        };
+        OutputTemplateValues {
+            binary: self
+                .file_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string()),
+            mode: Some(mode),
+            arch: infer_arch_from_path(&self.file_path),
+            func,
+        }
+    }
+
+    fn get_output_filepath(&self) -> PathBuf {
+        let extension = match self.output_format {
+            OutputFormat::Json => ".json",
+            OutputFormat::Ndjson => ".ndjson",
+        };
+
+        if let Some(template) = &self.output_template {
+            return get_save_file_path(
+                &self.file_path,
+                &self.get_output_dir(),
+                Some(extension.to_string()),
+                None,
+                None,
+                Some((template, &self.output_template_values(None))),
+            );
+        }
+
         let mut fp_filename = self
             .file_path
             .file_name()
@@ -933,47 +3622,260 @@ impl FileToBeProcessed {
             .to_string();
 
         fp_filename = if self.with_annotations {
-            fp_filename + "_" + &self.job_type_suffix.clone() + "_annotations" + ".json"
+            fp_filename + "_" + &self.job_type_suffix.clone() + "_annotations" + extension
         } else {
-            fp_filename + "_" + &self.job_type_suffix.clone() + ".json"
+            fp_filename + "_" + &self.job_type_suffix.clone() + extension
         };
 
-        let mut output_filepath = PathBuf::new();
-        output_filepath.push(self.output_path.clone());
+        let mut output_filepath = self.get_output_dir();
         output_filepath.push(fp_filename);
         debug!("Save filename: {:?}", output_filepath);
+        output_filepath
+    }
 
-        serde_json::to_writer(
-            &File::create(&output_filepath).expect("Unable to create file!"),
-            &json_obj,
-        )
-        .unwrap_or_else(|_| panic!("the world is ending: {:?}", output_filepath));
+    /// The directory outputs are written into
+    ///
+    /// When nested output is enabled, each mode is written into its own
+    /// subdirectory of the output path (i.e output/reg/, output/cfg/)
+    fn get_output_dir(&self) -> PathBuf {
+        if self.nested_output {
+            self.output_path.join(&self.job_type_suffix)
+        } else {
+            self.output_path.clone()
+        }
     }
 
-    fn write_to_bin(&self, function_name: &String, func_bytes: &[u8]) -> Result<()> {
+    /// Writes a manifest summarising the extraction modes run for the binary
+    /// alongside the checksums of each output file written
+    pub fn write_manifest(&self, mode_results: Vec<ModeResult>) -> Result<()> {
+        let manifest = ExtractionManifest {
+            binary_path: self.file_path.clone(),
+            modes: mode_results,
+            outputs: self.get_output_checksums(),
+        };
+
         let mut fp_filename = self
             .file_path
             .file_name()
             .expect("Unable to get filename")
             .to_string_lossy()
             .to_string();
-
-        fp_filename = fp_filename + "/" + function_name + ".bin";
+        fp_filename += "_manifest.json";
 
         let mut output_filepath = PathBuf::new();
         output_filepath.push(self.output_path.clone());
         output_filepath.push(fp_filename);
+        debug!("Manifest filename: {:?}", output_filepath);
 
-        let prefix = output_filepath.parent().unwrap();
-        fs::create_dir_all(prefix).unwrap();
+        let file = File::create(&output_filepath)
+            .map_err(|e| anyhow!("Unable to create {:?} - {}", output_filepath, e))?;
+        serde_json::to_writer_pretty(file, &manifest)
+            .map_err(|e| anyhow!("Unable to write manifest {:?} - {}", output_filepath, e))
+    }
 
-        fs::write(output_filepath, func_bytes).unwrap();
-        Ok(())
+    /// Writes a <binary>.sha256 file containing the checksum of each output file in the
+    /// same format as sha256sum so it can be verified with `sha256sum -c`
+    pub fn write_checksums(&self) -> Result<()> {
+        let mut fp_filename = self
+            .file_path
+            .file_name()
+            .expect("Unable to get filename")
+            .to_string_lossy()
+            .to_string();
+        fp_filename += ".sha256";
+        let output_filepath = self.output_path.join(fp_filename);
+        debug!("Checksums filename: {:?}", output_filepath);
+
+        let write_checksums = || -> std::io::Result<()> {
+            let mut writer = BufWriter::new(File::create(&output_filepath)?);
+            for checksum in self.get_output_checksums() {
+                let relative_path = checksum
+                    .path
+                    .strip_prefix(&self.output_path)
+                    .unwrap_or(&checksum.path);
+                writeln!(writer, "{}  {}", checksum.sha256, relative_path.display())?;
+            }
+            writer.flush()
+        };
+        write_checksums()
+            .map_err(|e| anyhow!("Unable to write checksums {:?} - {}", output_filepath, e))
+    }
+
+    /// The checksums of the output files written so far, sorted by path
+    fn get_output_checksums(&self) -> Vec<OutputChecksum> {
+        let mut checksums = self
+            .output_checksums
+            .lock()
+            .expect("Output checksums lock poisoned")
+            .clone();
+        checksums.sort_by(|a, b| a.path.cmp(&b.path));
+        checksums
+    }
+
+    /// Creates an output file whose checksum is recorded once the returned writer is dropped
+    fn create_output_file(&self, output_filepath: &Path) -> std::io::Result<ChecksummedWriter<'_>> {
+        // Output templates can place files within subdirectories of the output directory
+        if let Some(parent) = output_filepath.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(ChecksummedWriter {
+            writer: BufWriter::new(File::create(output_filepath)?),
+            hasher: Sha256::new(),
+            path: output_filepath.to_path_buf(),
+            checksums: &self.output_checksums,
+            error: None,
+        })
+    }
+
+    fn write_to_json(&self, json_obj: &Value) -> Result<()> {
+        let output_filepath = self.get_output_filepath();
+
+        let mut writer = self
+            .create_output_file(&output_filepath)
+            .map_err(|e| anyhow!("Unable to create {:?} - {}", output_filepath, e))?;
+        serde_json::to_writer(&mut writer, &json_obj)
+            .map_err(|e| anyhow!("Unable to write {:?} - {}", output_filepath, e))?;
+        writer.finish()
     }
 
-    fn go_to_address(r2p: &mut R2Pipe, function_addr: u64) {
-        r2p.cmd(format!("s {}", function_addr).as_str())
-            .expect("failed to seek addr");
+    /// Writes a list of extracted objects based on the configured output format
+    ///
+    /// JSON output writes the list as a single array whereas NDJSON output
+    /// writes each element of the list on its own line.
+    fn write_output<T: Serialize>(&self, items: &[T]) -> Result<()> {
+        match self.get_ndjson_writer()? {
+            Some(mut writer) => {
+                for item in items {
+                    Self::write_ndjson_line(&mut writer, &json!(item))
+                }
+                writer.finish()
+            }
+            None => self.write_to_json(&json!(items)),
+        }
+    }
+
+    /// Creates a buffered writer for the output file if the output format is NDJSON
+    fn get_ndjson_writer(&self) -> Result<Option<ChecksummedWriter<'_>>> {
+        if self.output_format == OutputFormat::Ndjson {
+            let output_filepath = self.get_output_filepath();
+            let writer = self
+                .create_output_file(&output_filepath)
+                .map_err(|e| anyhow!("Unable to create {:?} - {}", output_filepath, e))?;
+            Ok(Some(writer))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Any error is recorded by the writer and reported once the output is finished
+    fn write_ndjson_line(writer: &mut ChecksummedWriter, json_obj: &Value) {
+        let _ = serde_json::to_writer(&mut *writer, json_obj);
+        let _ = writer.write_all(b"\n");
+    }
+
+    fn write_to_bin(&self, function_name: &str, func_bytes: &[u8]) -> Result<()> {
+        let output_dir = self.get_existing_output_path(&ExtractionJobType::FunctionBytes);
+        fs::create_dir_all(&output_dir)
+            .map_err(|e| anyhow!("Unable to create {:?} - {}", output_dir, e))?;
+
+        let output_filepath = match &self.output_template {
+            Some(template) => get_save_file_path(
+                &self.file_path,
+                &output_dir,
+                Some(".bin".to_string()),
+                None,
+                None,
+                Some((
+                    template,
+                    &self.output_template_values(Some(sanitize_function_name(function_name))),
+                )),
+            ),
+            None => output_dir.join(sanitize_function_name(function_name) + ".bin"),
+        };
+        if output_filepath.exists() {
+            warn!(
+                "{:?} already exists and will be overwritten",
+                output_filepath
+            )
+        }
+
+        let mut writer = self
+            .create_output_file(&output_filepath)
+            .map_err(|e| anyhow!("Unable to create {:?} - {}", output_filepath, e))?;
+        writer
+            .write_all(func_bytes)
+            .map_err(|e| anyhow!("Unable to write {:?} - {}", output_filepath, e))?;
+        writer.finish()
+    }
+
+    /// Executes an r2 command, optionally bounded by the configured function timeout
+    ///
+    /// r2pipe is synchronous so when a timeout is configured the command is executed on a
+    /// separate thread which takes ownership of the pipe. If the command does not return
+    /// within the timeout, the hung r2 process is killed and its pipe closed once the thread
+    /// has returned it. `None` is returned in its place so a fresh one is created before
+    /// extraction continues with the next function.
+    fn cmd_with_timeout(
+        &self,
+        mut r2p: R2Pipe,
+        cmd: &str,
+    ) -> (Option<R2Pipe>, Result<String, Error>) {
+        let timeout = match self.r2p_config.func_timeout {
+            Some(timeout) => timeout,
+            None => {
+                let ret = r2p.cmd(cmd).map_err(|e| anyhow!("{:?}", e));
+                return (Some(r2p), ret);
+            }
+        };
+
+        let pid = Self::get_r2_pid(&mut r2p);
+        let (tx, rx) = mpsc::channel();
+        let thread_cmd = cmd.to_string();
+        thread::spawn(move || {
+            let ret = r2p.cmd(&thread_cmd);
+            // The receiver is gone if the command timed out
+            let _ = tx.send((r2p, ret));
+        });
+
+        match rx.recv_timeout(timeout) {
+            Ok((r2p, ret)) => (Some(r2p), ret.map_err(|e| anyhow!("{:?}", e))),
+            Err(_) => {
+                warn!(
+                    "'{}' timed out after {:?} for {:?} - Killing r2 and creating a new r2pipe",
+                    cmd, timeout, self.file_path
+                );
+                match pid.map(|pid| (pid, kill_process(pid))) {
+                    // The killed process closes its output so the pending command returns
+                    Some((_, Ok(status))) if status.success() => {
+                        if let Ok((mut r2p, _)) = rx.recv() {
+                            r2p.close();
+                        }
+                    }
+                    Some((pid, ret)) => error!(
+                        "Unable to kill hung r2 process {} for {:?} - {:?}",
+                        pid, self.file_path, ret
+                    ),
+                    None => error!(
+                        "Unable to get the process id of the hung r2 process for {:?}",
+                        self.file_path
+                    ),
+                }
+                (
+                    None,
+                    Err(anyhow!("'{}' timed out after {:?}", cmd, timeout)),
+                )
+            }
+        }
+    }
+
+    /// Returns the process id of the r2 instance behind the pipe
+    fn get_r2_pid(r2p: &mut R2Pipe) -> Option<u32> {
+        r2p.cmd("?vi $p").ok()?.trim().parse().ok()
+    }
+
+    fn go_to_address(r2p: &mut R2Pipe, function_addr: u64) -> Result<(), r2pipe::Error> {
+        r2p.cmd(format!("s {}", function_addr).as_str())?;
+        Ok(())
     }
 
     fn handle_symbols_pdb(&self, r2p: &mut R2Pipe) -> Result<(), Error> {
@@ -993,68 +3895,1888 @@ impl FileToBeProcessed {
         }
     }
 
-    fn setup_r2_pipe(&self) -> R2Pipe {
+    /// Spawns and analyses an r2pipe for the file
+    ///
+    /// Returns an error rather than panicking if r2 cannot be spawned or fails
+    /// to load the file (i.e a corrupt binary) so that the file can be skipped.
+    fn setup_r2_pipe(&self) -> Result<R2Pipe, Error> {
         if self.r2p_config.use_curl_pdb {
             // Docs suggest this is unsafe
             env::set_var("R2_CURL", "1");
         }
 
-        let opts = if self.r2p_config.debug {
+        if self.r2p_config.debug {
             debug!("Creating r2 handle with debugging");
-            R2PipeSpawnOptions {
-                exepath: "radare2".to_owned(),
-                args: vec!["-e bin.cache=true", "-e log.level=0", "-e asm.pseudo=true"],
-            }
         } else {
             debug!("Creating r2 handle without debugging");
-            R2PipeSpawnOptions {
-                exepath: "radare2".to_owned(),
-                args: vec![
-                    "-e bin.cache=true",
-                    "-e log.level=1",
-                    "-2",
-                    "-e asm.pseudo=true",
-                ],
-            }
+        }
+        let opts = R2PipeSpawnOptions {
+            exepath: self.r2p_config.r2_path.to_string_lossy().to_string(),
+            args: self.r2p_config.spawn_args(),
         };
 
         debug!("Attempting to create r2pipe using {:?}", self.file_path);
+        let file_path = self
+            .file_path
+            .to_str()
+            .ok_or_else(|| anyhow!("{:?} is not a valid UTF-8 path", self.file_path))?;
         let mut r2p = match R2Pipe::in_session() {
-            Some(_) => R2Pipe::open().expect("Unable to open R2Pipe"),
-            None => R2Pipe::spawn(self.file_path.to_str().unwrap(), Some(opts))
-                .expect("Failed to spawn new R2Pipe"),
+            Some(_) => R2Pipe::open().map_err(|e| anyhow!("Unable to open R2Pipe - {:?}", e))?,
+            None => R2Pipe::spawn(file_path, Some(opts)).map_err(|e| {
+                anyhow!(
+                    "Failed to spawn new R2Pipe for {:?} - {:?}",
+                    self.file_path,
+                    e
+                )
+            })?,
         };
 
-        let info = r2p.cmdj("ij");
-        if info.is_ok() {
-            let info = info.unwrap();
-            if info["bin"]["bintype"].as_str().unwrap() == "pe" {
-                debug!("PE file found. Handling symbol download!");
-                let ret = self.handle_symbols_pdb(&mut r2p);
+        let info = r2p.cmdj("ij").map_err(|e| {
+            anyhow!(
+                "Unable to get binary information for {:?} - {:?}",
+                self.file_path,
+                e
+            )
+        })?;
+        if info["bin"]["bintype"].as_str() == Some("pe") {
+            debug!("PE file found. Handling symbol download!");
+            let ret = self.handle_symbols_pdb(&mut r2p);
 
-                if ret.is_err() {
-                    error!("Unable to get PDB info")
-                }
+            if ret.is_err() {
+                error!("Unable to get PDB info")
             }
         }
 
-        if self.r2p_config.extended_analysis {
+        let analysis_cmd = self.r2p_config.analysis_level.command();
+        debug!(
+            "Executing '{}' r2 command for {}",
+            analysis_cmd,
+            self.file_path.display()
+        );
+        r2p.cmd(analysis_cmd).map_err(|e| {
+            anyhow!(
+                "Unable to complete '{}' analysis for {:?} - {:?}",
+                analysis_cmd,
+                self.file_path,
+                e
+            )
+        })?;
+        debug!(
+            "'{}' r2 command complete for {}",
+            analysis_cmd,
+            self.file_path.display()
+        );
+
+        for script_cmd in self.r2p_config.r2_script_commands()? {
             debug!(
-                "Executing 'aaa' r2 command for {}",
+                "Executing '{}' r2 script command for {}",
+                script_cmd,
                 self.file_path.display()
             );
-            r2p.cmd("aaa")
-                .expect("Unable to complete standard analysis!");
-            debug!("'aaa' r2 command complete for {}", self.file_path.display());
-        } else {
-            debug!("Executing 'aa' r2 command for {}", self.file_path.display());
-            r2p.cmd("aa")
-                .expect("Unable to complete standard analysis!");
-            debug!(
-                "'aa' r2 command complete for {:?}",
-                self.file_path.display()
+            r2p.cmd(&script_cmd).map_err(|e| {
+                anyhow!(
+                    "Unable to run r2 script command '{}' for {:?} - {:?}",
+                    script_cmd,
+                    self.file_path,
+                    e
+                )
+            })?;
+        }
+        Ok(r2p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::get_json_paths_from_dir;
+
+    fn test_r2p_config() -> R2PipeConfig {
+        R2PipeConfig {
+            debug: false,
+            analysis_level: AnalysisLevel::Aa,
+            use_curl_pdb: false,
+            func_timeout: None,
+            r2_path: PathBuf::from("radare2"),
+            intra_file_threads: 1,
+            progress: false,
+            function_filter: None,
+            auto_reanalyze: false,
+            public_only: false,
+            key_by_offset: false,
+            asm_pseudo: true,
+            asm_comments: true,
+            demangle: false,
+            max_func_bytes: DEFAULT_MAX_FUNC_BYTES,
+            min_instructions: 0,
+            min_blocks: 5,
+            r2_script: None,
+        }
+    }
+
+    fn test_file_to_be_processed(job_type_suffix: &str) -> FileToBeProcessed {
+        FileToBeProcessed {
+            file_path: PathBuf::from("test-files/test_bin"),
+            output_path: PathBuf::from("output"),
+            job_type_suffix: job_type_suffix.to_string(),
+            r2p_config: test_r2p_config(),
+            with_annotations: false,
+            output_format: OutputFormat::Json,
+            func_filename: "symbol".to_string(),
+            feature_type: None,
+            sqlite_path: None,
+            nested_output: false,
+            bytes_format: BytesFormat::Bin,
+            output_template: None,
+            output_checksums: Mutex::default(),
+        }
+    }
+
+    #[test]
+    fn test_function_filter_name_and_address() {
+        let function = |name: &str, offset: u64| AFIJFunctionInfo {
+            name: name.to_string(),
+            offset,
+            ..Default::default()
+        };
+        let functions = [
+            function("main", 0x1149),
+            function("sym.openssl_crypto_init", 0x2000),
+            function("sym.crypto", 0x3000),
+            function("sym.imp.printf", 0x4000),
+            function("entry0", 0x1060),
+        ];
+
+        let filter = FunctionFilter::new("main, sym.*crypto*,0x1060").unwrap();
+        let filtered: Vec<&str> = functions
+            .iter()
+            .filter(|f| filter.is_match(f))
+            .map(|f| f.name.as_str())
+            .collect();
+        assert_eq!(
+            filtered,
+            vec!["main", "sym.openssl_crypto_init", "sym.crypto", "entry0"]
+        );
+
+        // Names must match in full and addresses are matched against the function offset
+        let filter = FunctionFilter::new("mai,0x4000").unwrap();
+        assert!(!filter.is_match(&functions[0]));
+        assert!(filter.is_match(&functions[3]));
+
+        // Regex characters within names are treated literally
+        let filter = FunctionFilter::new("sym.imp.print?").unwrap();
+        assert!(filter.is_match(&functions[3]));
+        assert!(!filter.is_match(&function("symXimpXprintf", 0x5000)));
+
+        assert!(FunctionFilter::new("0xzz").is_err());
+        assert!(FunctionFilter::new(" , ").is_err());
+    }
+
+    #[test]
+    fn test_public_only_filters_auto_named_and_imported_functions() {
+        let function = |name: &str, type_field: &str| AFIJFunctionInfo {
+            name: name.to_string(),
+            type_field: type_field.to_string(),
+            ..Default::default()
+        };
+        let functions = vec![
+            function("main", "fcn"),
+            function("fcn.00001040", "fcn"),
+            function("sym.imp.printf", "imp"),
+            function("sym.imp.puts", "fcn"),
+            function("sym.parse_header", "sym"),
+            function("entry0", "fcn"),
+            function("loc.00002000", "loc"),
+        ];
+
+        let public: Vec<String> = functions
+            .into_iter()
+            .filter(is_public_function)
+            .map(|function| function.name)
+            .collect();
+        assert_eq!(public, vec!["main", "sym.parse_header", "entry0"]);
+    }
+
+    #[test]
+    fn test_retain_selected_graph_functions() {
+        let function = |name: &str, offset: u64| AFIJFunctionInfo {
+            name: name.to_string(),
+            offset,
+            ..Default::default()
+        };
+        let selected = vec![function("main", 0x1149), function("entry0", 0x1060)];
+
+        let mut json: Vec<Value> = serde_json::from_str(
+            &fs::read_to_string("test-files/r2-output-samples/test_bin_agfj.json").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(json.len(), 11);
+        retain_selected_cfgs(&mut json, &selected);
+        let names: Vec<&str> = json
+            .iter()
+            .map(|cfg| cfg[0]["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["entry0", "main"]);
+
+        let call_graph = |name: &str| AGCJFunctionCallGraph {
+            name: name.to_string(),
+            size: 0,
+            imports: Some(vec!["sym.imp.printf".to_string()]),
+        };
+        let mut call_graphs = vec![
+            call_graph("main"),
+            call_graph("fcn.00001000"),
+            call_graph("sym.imp.printf"),
+        ];
+        retain_selected_call_graphs(&mut call_graphs, &selected);
+        assert_eq!(call_graphs, vec![call_graph("main")]);
+    }
+
+    #[test]
+    fn test_min_instructions_filters_fixture_functions() {
+        let functions: Vec<AFIJFunctionInfo> = serde_json::from_str(
+            &fs::read_to_string("test-files/cg_dedup/raw/test_bin_finfo.json").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(functions.len(), 12);
+
+        assert_eq!(
+            functions
+                .iter()
+                .filter(|function| has_min_instructions(function, 0))
+                .count(),
+            12
+        );
+
+        let filtered: Vec<&str> = functions
+            .iter()
+            .filter(|function| has_min_instructions(function, 10))
+            .map(|function| function.name.as_str())
+            .collect();
+        assert_eq!(
+            filtered,
+            vec![
+                "entry0",
+                "sym.register_tm_clones",
+                "sym.__do_global_dtors_aux",
+                "sym.__libc_csu_init",
+                "main"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_def_use_chains_from_local_variable_xrefs() {
+        let xrefs = LocalVariableXrefs {
+            reads: vec![
+                Reads {
+                    name: "var_4h".to_string(),
+                    addrs: vec![0x1030, 0x1010, 0x1020, 0x1050, 0x1005],
+                },
+                Reads {
+                    name: "var_8h".to_string(),
+                    addrs: vec![0x1060],
+                },
+                Reads {
+                    name: "arg_10h".to_string(),
+                    addrs: vec![0x1000],
+                },
+            ],
+            writes: vec![
+                Writes {
+                    name: "var_8h".to_string(),
+                    addrs: vec![0x1040],
+                },
+                Writes {
+                    name: "var_4h".to_string(),
+                    addrs: vec![0x1030, 0x1008],
+                },
+            ],
+        };
+
+        assert_eq!(
+            xrefs.def_use_chains(),
+            vec![
+                // The read before the first write is dropped and the read at 0x1030 uses
+                // the value written at 0x1008 before it is overwritten
+                DefUseChain {
+                    var: "var_4h".to_string(),
+                    def_addr: 0x1008,
+                    use_addrs: vec![0x1010, 0x1020, 0x1030],
+                },
+                DefUseChain {
+                    var: "var_4h".to_string(),
+                    def_addr: 0x1030,
+                    use_addrs: vec![0x1050],
+                },
+                DefUseChain {
+                    var: "var_8h".to_string(),
+                    def_addr: 0x1040,
+                    use_addrs: vec![0x1060],
+                },
+            ]
+        );
+        assert!(LocalVariableXrefs::default().def_use_chains().is_empty());
+    }
+
+    #[test]
+    fn test_r2_spawn_args_asm_pseudo_and_comments() {
+        let config = |debug: bool, asm_pseudo: bool, asm_comments: bool| R2PipeConfig {
+            debug,
+            asm_pseudo,
+            asm_comments,
+            ..test_r2p_config()
+        };
+
+        for debug in [false, true] {
+            for asm_pseudo in [false, true] {
+                for asm_comments in [false, true] {
+                    let args = config(debug, asm_pseudo, asm_comments).spawn_args();
+                    assert!(args.contains(&"-e bin.cache=true"));
+                    assert_eq!(args.contains(&"-2"), !debug);
+                    assert_eq!(args.contains(&"-e asm.pseudo=true"), asm_pseudo);
+                    assert_eq!(args.contains(&"-e asm.pseudo=false"), !asm_pseudo);
+                    assert_eq!(args.contains(&"-e asm.comments=true"), asm_comments);
+                    assert_eq!(args.contains(&"-e asm.comments=false"), !asm_comments);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_local_variable_xrefs_non_object() {
+        for json in ["null", "", "[]"] {
+            assert_eq!(
+                FileToBeProcessed::parse_local_variable_xrefs(json, "sym.thunk"),
+                LocalVariableXrefs::default()
             );
+        }
+
+        let xrefs = FileToBeProcessed::parse_local_variable_xrefs(
+            r#"{"reads":[{"name":"var_4h","addrs":[4425]}],"writes":[]}"#,
+            "main",
+        );
+        assert_eq!(xrefs.reads[0].name, "var_4h");
+        assert!(xrefs.writes.is_empty());
+    }
+
+    #[test]
+    fn test_remove_oversized_functions() {
+        let file = FileToBeProcessed {
+            r2p_config: R2PipeConfig {
+                max_func_bytes: 1024,
+                ..test_r2p_config()
+            },
+            ..test_file_to_be_processed("bytes")
         };
-        r2p
+        let function = |name: &str, size: i128| AFIJFunctionInfo {
+            name: name.to_string(),
+            size,
+            ..Default::default()
+        };
+        let functions = vec![
+            function("main", 35),
+            function("fcn.misanalysed", 0x7fffffff),
+            function("sym.at_limit", 1024),
+        ];
+
+        let mut result = ModeResult::new("bytes");
+        let functions: Vec<String> = file
+            .remove_oversized_functions(functions, &mut result)
+            .into_iter()
+            .map(|function| function.name)
+            .collect();
+        assert_eq!(functions, vec!["main", "sym.at_limit"]);
+        assert_eq!(result.functions_skipped, 1);
+        assert!(result.errors[0].contains("fcn.misanalysed"));
+    }
+
+    #[test]
+    fn test_key_by_offset_keeps_functions_with_the_same_name() {
+        let file = |key_by_offset: bool| FileToBeProcessed {
+            r2p_config: R2PipeConfig {
+                key_by_offset,
+                ..test_r2p_config()
+            },
+            ..test_file_to_be_processed("reg")
+        };
+        let function = |offset: u64| AFIJFunctionInfo {
+            name: "sym.static_helper".to_string(),
+            offset,
+            ..Default::default()
+        };
+
+        let mut outputs = BTreeMap::new();
+        let keyed_by_name = file(false);
+        keyed_by_name.insert_function_output(&mut outputs, &function(0x1139), 1);
+        keyed_by_name.insert_function_output(&mut outputs, &function(0x1200), 2);
+        assert_eq!(outputs.len(), 1);
+
+        let mut outputs = BTreeMap::new();
+        let keyed_by_offset = file(true);
+        keyed_by_offset.insert_function_output(&mut outputs, &function(0x1139), 1);
+        keyed_by_offset.insert_function_output(&mut outputs, &function(0x1200), 2);
+        assert_eq!(
+            outputs,
+            BTreeMap::from([
+                ("sym.static_helper@0x1139".to_string(), 1),
+                ("sym.static_helper@0x1200".to_string(), 2),
+            ])
+        );
+
+        // Bytes are written to a file per function so are keyed the same way
+        assert_eq!(
+            keyed_by_name.get_function_filename(&function(0x1139)),
+            keyed_by_name.get_function_filename(&function(0x1200))
+        );
+        assert_eq!(
+            keyed_by_offset.get_function_filename(&function(0x1139)),
+            "sym.static_helper@0x1139"
+        );
+    }
+
+    #[test]
+    fn test_register_behaviour_out_of_range_addresses() {
+        let json = r#"{"A":["rax"],"I":[],"R":["rdi"],"W":["rax"],"V":[],"N":[],"@R":[4198400,18446744073709551615,-1,1.8446744073709552e20],"@W":[18446744073709551615]}"#;
+        let behaviour: AEAFJRegisterBehaviour =
+            serde_json::from_str(json).expect("Unable to deserialize register behaviour");
+        assert_eq!(behaviour.r2, vec![4198400, u64::MAX, u64::MAX, u64::MAX]);
+        assert_eq!(behaviour.w2, vec![u64::MAX]);
+    }
+
+    #[test]
+    fn test_import_entry_deserialization() {
+        let json = r#"[{"ordinal":1,"bind":"GLOBAL","type":"FUNC","name":"puts","plt":4144},{"ordinal":2,"bind":"WEAK","type":"NOTYPE","name":"__gmon_start__"},{"ordinal":3,"bind":"NONE","type":"FUNC","name":"CreateFileA","libname":"KERNEL32.dll","plt":4202496}]"#;
+        let imports: Vec<ImportEntry> =
+            serde_json::from_str(json).expect("Unable to deserialize imports");
+        assert_eq!(imports.len(), 3);
+        assert_eq!(imports[0].name, "puts");
+        assert_eq!(imports[0].type_field, "FUNC");
+        assert_eq!(imports[0].plt, Some(4144));
+        assert_eq!(imports[1].plt, None);
+        assert_eq!(imports[2].libname, Some("KERNEL32.dll".to_string()));
+    }
+
+    #[test]
+    fn test_string_xref_entry_serialization() {
+        // axtj output for the "This is a very silly program!" string in test-files/test_bin
+        let json = r#"[{"from":4437,"type":"DATA","perm":"r--","opcode":"lea rax, str.This_is_a_very_silly_program_","fcn_addr":4425,"fcn_name":"main","refname":"str.This_is_a_very_silly_program_"}]"#;
+        let xrefs: Vec<XrefToEntry> =
+            deserialize_r2_output("axtj", json).expect("Unable to deserialize xrefs");
+        assert_eq!(xrefs.len(), 1);
+        assert_eq!(xrefs[0].from, 4437);
+        assert_eq!(xrefs[0].fcn_name, Some("main".to_string()));
+
+        let string_xref = StringXrefEntry {
+            string: StringEntry {
+                vaddr: 8196,
+                paddr: 8196,
+                ordinal: 0,
+                size: 30,
+                length: 29,
+                section: ".rodata".to_string(),
+                type_field: "ascii".to_string(),
+                string: "This is a very silly program!".to_string(),
+            },
+            referenced_by: vec!["main".to_string()],
+        };
+        let value = serde_json::to_value(&string_xref).unwrap();
+        assert_eq!(value["string"], "This is a very silly program!");
+        assert_eq!(value["type"], "ascii");
+        assert_eq!(value["referenced_by"], json!(["main"]));
+
+        let roundtrip: StringXrefEntry = serde_json::from_value(value).unwrap();
+        assert_eq!(roundtrip, string_xref);
+    }
+
+    #[test]
+    fn test_header_info_elf() {
+        let bininfo = json!({"bin": {"bintype": "elf", "class": "ELF64", "arch": "x86", "bits": 64, "machine": "AMD x86-64 architecture", "os": "linux", "endian": "little", "stripped": false}});
+        let fields: Vec<HeaderField> = deserialize_r2_output(
+            "ihj",
+            r#"[{"name":"ident","vaddr":0,"paddr":0,"value":"0x464c457f","comment":"ELF64"},{"name":"entry","vaddr":24,"paddr":24,"value":"0x1040"}]"#,
+        )
+        .unwrap();
+        let header_info = HeaderInfo::new(&bininfo, fields);
+
+        assert_eq!(header_info.bintype, "elf");
+        assert_eq!(header_info.class, Some("ELF64".to_string()));
+        assert_eq!(header_info.bits, Some(64));
+        assert_eq!(header_info.fields.len(), 2);
+        assert_eq!(header_info.timestamp, None);
+        assert_eq!(header_info.subsystem, None);
+        assert_eq!(header_info.data_directory_sizes, None);
+    }
+
+    #[test]
+    fn test_header_info_pe() {
+        let bininfo = json!({"bin": {"bintype": "pe", "class": "PE32", "arch": "x86", "bits": 32, "os": "windows", "stripped": true}});
+        let fields: Vec<HeaderField> = deserialize_r2_output(
+            "ihj",
+            r#"[{"name":"TimeDateStamp","vaddr":4194440,"paddr":136,"value":1609459200},{"name":"MajorLinkerVersion","value":14},{"name":"MinorLinkerVersion","value":"0x1c"},{"name":"Subsystem","value":3},{"name":"IMAGE_DIRECTORY_ENTRY_IMPORT","value":"0x2000"},{"name":"SIZE_IMAGE_DIRECTORY_ENTRY_IMPORT","value":"0x50"},{"name":"SIZE_IMAGE_DIRECTORY_ENTRY_EXPORT","value":0}]"#,
+        )
+        .unwrap();
+        let header_info = HeaderInfo::new(&bininfo, fields);
+
+        assert_eq!(header_info.bintype, "pe");
+        assert_eq!(header_info.machine, None);
+        assert_eq!(header_info.timestamp, Some(1609459200));
+        assert_eq!(header_info.subsystem, Some(3));
+        assert_eq!(header_info.major_linker_version, Some(14));
+        assert_eq!(header_info.minor_linker_version, Some(28));
+        let data_directory_sizes = header_info.data_directory_sizes.unwrap();
+        assert_eq!(data_directory_sizes.len(), 2);
+        assert_eq!(data_directory_sizes["IMPORT"], 0x50);
+        assert_eq!(data_directory_sizes["EXPORT"], 0);
+    }
+
+    #[test]
+    fn test_data_xrefs_from_function_xrefs() {
+        // Sampled axffj output for main in test_bin
+        let axffj = r#"[
+            {"type":"CALL","at":4461,"ref":4144,"name":"sym.imp.printf"},
+            {"type":"STRING","at":4449,"ref":8196,"name":"str.Hello__World_"},
+            {"type":"DATA","at":4482,"ref":16400,"name":"obj.counter"},
+            {"type":"DATA","at":4490,"ref":16408,"name":""},
+            {"type":"CODE","at":4470,"ref":4501,"name":"main+52"}
+        ]"#;
+        let xrefs: Vec<FunctionXrefDetails> = deserialize_r2_output("axffj", axffj).unwrap();
+        let data_xrefs = DataXrefDetails::from_function_xrefs(xrefs);
+
+        assert_eq!(
+            data_xrefs,
+            vec![
+                DataXrefDetails {
+                    from: 4449,
+                    to: 8196,
+                    target_name: Some("str.Hello__World_".to_string()),
+                },
+                DataXrefDetails {
+                    from: 4482,
+                    to: 16400,
+                    target_name: Some("obj.counter".to_string()),
+                },
+                DataXrefDetails {
+                    from: 4490,
+                    to: 16408,
+                    target_name: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_analysis_level_commands() {
+        let job = |analysis_level: &str| {
+            ExtractionJob::new(
+                &PathBuf::from("test-files/test_bin"),
+                &PathBuf::from("output"),
+                "byte-entropy",
+                &false,
+                analysis_level,
+                &false,
+                &false,
+                "json",
+                &None,
+                &PathBuf::from("radare2"),
+                "symbol",
+                &1,
+                &false,
+                &None,
+                &None,
+                &None,
+                &false,
+                &false,
+                &false,
+                &false,
+                &true,
+                &true,
+                &false,
+                &DEFAULT_MAX_FUNC_BYTES,
+                &0,
+                &5,
+                "bin",
+                &None,
+                &None,
+            )
+        };
+
+        for (analysis_level, expected) in [
+            ("aa", AnalysisLevel::Aa),
+            ("aaa", AnalysisLevel::Aaa),
+            ("aaaa", AnalysisLevel::Aaaa),
+        ] {
+            let job = job(analysis_level).unwrap();
+            let r2p_config = &job.files_to_be_processed[0].r2p_config;
+            assert_eq!(r2p_config.analysis_level, expected);
+            assert_eq!(r2p_config.analysis_level.command(), analysis_level);
+        }
+        assert!(job("aaaaa").is_err());
+    }
+
+    #[test]
+    fn test_r2_script_commands() {
+        let script_dir =
+            std::env::temp_dir().join(format!("bin2ml-r2-script-{}", std::process::id()));
+        fs::create_dir_all(&script_dir).unwrap();
+        let script_path = script_dir.join("rename.r2");
+        fs::write(
+            &script_path,
+            "# Rename main before extraction\nafn renamed_main @ main\n\n  zfs sigs.sdb  \n",
+        )
+        .unwrap();
+
+        let config = |r2_script: Option<PathBuf>| R2PipeConfig {
+            r2_script,
+            ..test_r2p_config()
+        };
+
+        assert!(config(None).r2_script_commands().unwrap().is_empty());
+        assert_eq!(
+            config(Some(script_path.clone()))
+                .r2_script_commands()
+                .unwrap(),
+            vec!["afn renamed_main @ main", "zfs sigs.sdb"]
+        );
+        // Directories are opened as r2 projects by name from their parent directory
+        let project_dir = fs::canonicalize(&script_dir).unwrap();
+        assert_eq!(
+            config(Some(script_dir.clone()))
+                .r2_script_commands()
+                .unwrap(),
+            vec![
+                format!("e dir.projects={}", project_dir.parent().unwrap().display()),
+                format!("Po {}", project_dir.file_name().unwrap().to_string_lossy()),
+            ]
+        );
+
+        let job = |r2_script: Option<PathBuf>| {
+            ExtractionJob::new(
+                &PathBuf::from("test-files/test_bin"),
+                &PathBuf::from("output"),
+                "byte-entropy",
+                &false,
+                "aa",
+                &false,
+                &false,
+                "json",
+                &None,
+                &PathBuf::from("radare2"),
+                "symbol",
+                &1,
+                &false,
+                &None,
+                &None,
+                &None,
+                &false,
+                &false,
+                &false,
+                &false,
+                &true,
+                &true,
+                &false,
+                &DEFAULT_MAX_FUNC_BYTES,
+                &0,
+                &5,
+                "bin",
+                &None,
+                &r2_script,
+            )
+        };
+        assert!(job(Some(script_path.clone())).is_ok());
+        assert!(job(Some(script_dir.join("missing.r2"))).is_err());
+
+        // The rename in the script is applied before the functions are listed
+        #[cfg(unix)]
+        {
+            let job = ExtractionJob::new(
+                &PathBuf::from("test-files/test_bin"),
+                &PathBuf::from("output"),
+                "finfo",
+                &false,
+                "aa",
+                &false,
+                &false,
+                "json",
+                &None,
+                Path::new("test-files/fake_r2"),
+                "symbol",
+                &1,
+                &false,
+                &None,
+                &None,
+                &None,
+                &false,
+                &false,
+                &false,
+                &false,
+                &true,
+                &true,
+                &false,
+                &DEFAULT_MAX_FUNC_BYTES,
+                &0,
+                &5,
+                "bin",
+                &None,
+                &Some(script_path),
+            )
+            .unwrap();
+            let file = &job.files_to_be_processed[0];
+            let mut r2p = file.setup_r2_pipe().unwrap();
+            let functions = file.get_function_name_list(&mut r2p).unwrap();
+            r2p.close();
+            assert_eq!(functions.len(), 1);
+            assert_eq!(functions[0].name, "renamed_main");
+        }
+
+        fs::remove_dir_all(&script_dir).unwrap();
+    }
+
+    #[test]
+    fn test_output_template_extraction_paths() {
+        let output_path =
+            std::env::temp_dir().join(format!("bin2ml-output-template-{}", std::process::id()));
+        let job = ExtractionJob::new(
+            &PathBuf::from("test-files/test_bin"),
+            &output_path,
+            "byte-histogram",
+            &false,
+            "aa",
+            &false,
+            &false,
+            "json",
+            &None,
+            Path::new("radare2"),
+            "func",
+            &1,
+            &false,
+            &None,
+            &None,
+            &None,
+            &true,
+            &true,
+            &false,
+            &false,
+            &true,
+            &true,
+            &false,
+            &DEFAULT_MAX_FUNC_BYTES,
+            &0,
+            &5,
+            "bin",
+            &Some("{arch}/{binary}.{mode}".to_string()),
+            &None,
+        )
+        .unwrap();
+        let file = &job.files_to_be_processed[0];
+        assert_eq!(
+            file.get_output_filepath(),
+            output_path.join("byte-histogram/unknown/test_bin.byte-histogram.json")
+        );
+
+        let job = ExtractionJob::new(
+            &PathBuf::from("test-files/test_bin"),
+            &output_path,
+            "byte-histogram",
+            &false,
+            "aa",
+            &false,
+            &false,
+            "json",
+            &None,
+            Path::new("radare2"),
+            "func",
+            &1,
+            &false,
+            &None,
+            &None,
+            &None,
+            &true,
+            &true,
+            &false,
+            &false,
+            &true,
+            &true,
+            &false,
+            &DEFAULT_MAX_FUNC_BYTES,
+            &0,
+            &5,
+            "bin",
+            &Some("{binary}-{optimisation}".to_string()),
+            &None,
+        );
+        assert!(job
+            .unwrap_err()
+            .to_string()
+            .contains("unknown placeholder {optimisation}"));
+
+        let new_job = |input_path: &str, mode: &str, template: &str| {
+            ExtractionJob::new(
+                &PathBuf::from(input_path),
+                &output_path,
+                mode,
+                &false,
+                "aa",
+                &false,
+                &false,
+                "json",
+                &None,
+                Path::new("test-files/fake_r2"),
+                "func",
+                &1,
+                &false,
+                &None,
+                &None,
+                &None,
+                &true,
+                &true,
+                &false,
+                &false,
+                &true,
+                &true,
+                &false,
+                &DEFAULT_MAX_FUNC_BYTES,
+                &0,
+                &5,
+                "bin",
+                &Some(template.to_string()),
+                &None,
+            )
+        };
+
+        // Every function would be written to the same file
+        assert!(new_job("test-files/test_bin", "bytes", "{binary}")
+            .unwrap_err()
+            .to_string()
+            .contains("must contain {func}"));
+        assert!(new_job("test-files/test_bin", "bytes", "{binary}/{func}").is_ok());
+
+        // Every binary would be written to the same file
+        assert!(new_job("test-files", "byte-histogram", "{arch}-{mode}")
+            .unwrap_err()
+            .to_string()
+            .contains("must contain {binary}"));
+
+        if output_path.exists() {
+            fs::remove_dir_all(&output_path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_macho_info_deserialization() {
+        let bininfo: Value = deserialize_r2_output(
+            "ij",
+            r#"{"core":{"file":"test_macho","format":"mach064"},"bin":{"arch":"arm","bits":64,"bintype":"mach0","class":"MACH064","os":"macos","signed":false,"stripped":false}}"#,
+        )
+        .unwrap();
+        let fields: Vec<HeaderField> = deserialize_r2_output(
+            "ihj",
+            r#"[{"name":"header","vaddr":4294967296,"paddr":0,"format":"mach0_header"},{"name":"load_command_0_LC_SEGMENT_64","vaddr":4294967328,"paddr":32,"format":"mach0_segment64"},{"name":"load_command_1_LC_DYLD_INFO_ONLY","vaddr":4294967400,"paddr":104},{"name":"load_command_2_LC_LOAD_DYLIB","vaddr":4294967448,"paddr":152},{"name":"load_command_3_LC_CODE_SIGNATURE","vaddr":4294967504,"paddr":208}]"#,
+        )
+        .unwrap();
+        let entrypoints: Vec<EntryPoint> = deserialize_r2_output(
+            "iej",
+            r#"[{"vaddr":4294983480,"paddr":16184,"baddr":4294967296,"laddr":0,"haddr":-1,"type":"program"}]"#,
+        )
+        .unwrap();
+        let libraries: Value =
+            deserialize_r2_output("ilj", r#"["/usr/lib/libSystem.B.dylib"]"#).unwrap();
+
+        assert!(MachOInfo::is_macho(&bininfo));
+        let macho_info = MachOInfo::new(&bininfo, &fields, entrypoints, &libraries);
+        assert_eq!(macho_info.bintype, "mach0");
+        assert_eq!(macho_info.bits, Some(64));
+        assert_eq!(macho_info.entrypoints.len(), 1);
+        assert_eq!(macho_info.load_commands.len(), 4);
+        assert_eq!(
+            macho_info.load_commands[0],
+            MachOLoadCommand {
+                index: 0,
+                name: "LC_SEGMENT_64".to_string(),
+                paddr: Some(32),
+            }
+        );
+        assert_eq!(macho_info.load_commands[3].name, "LC_CODE_SIGNATURE");
+        assert!(macho_info.has_code_signature);
+        assert_eq!(macho_info.linked_dylibs, vec!["/usr/lib/libSystem.B.dylib"]);
+
+        // Newer versions of r2 report each library as an object
+        let libraries = json!([{"name": "/usr/lib/libc++.1.dylib"}]);
+        let macho_info = MachOInfo::new(&bininfo, &[], vec![], &libraries);
+        assert_eq!(macho_info.linked_dylibs, vec!["/usr/lib/libc++.1.dylib"]);
+        assert!(!macho_info.has_code_signature);
+
+        let elf_bininfo = json!({"bin": {"bintype": "elf", "bits": 64}});
+        assert!(!MachOInfo::is_macho(&elf_bininfo));
+    }
+
+    #[test]
+    fn test_section_entry_deserialization() {
+        let json = r#"[{"name":"","size":0,"vsize":0,"perm":"----","flags":"0x0","paddr":0,"vaddr":0},{"name":".text","size":402,"vsize":402,"perm":"-r-x","flags":"0x6","paddr":4160,"vaddr":4160},{"name":".data","size":16,"vsize":16,"perm":"-rw-","flags":"0x3","paddr":12304,"vaddr":16400,"entropy":"1.500000"}]"#;
+        let sections: Vec<SectionEntry> =
+            deserialize_r2_output("iSj", json).expect("Unable to deserialize sections");
+        assert_eq!(sections.len(), 3);
+        assert_eq!(sections[1].name, ".text");
+        assert_eq!(sections[1].perm, "-r-x");
+        assert_eq!(sections[1].vaddr, 4160);
+        assert_eq!(sections[1].entropy, None);
+        assert_eq!(sections[2].paddr, 12304);
+        assert_eq!(sections[2].entropy, Some(1.5));
+    }
+
+    #[test]
+    fn test_export_entry_deserialization() {
+        let json = r#"[{"name":"main","flagname":"main","realname":"main","ordinal":12,"bind":"GLOBAL","size":35,"type":"FUNC","vaddr":4457,"paddr":4457,"is_imported":false},{"name":"_IO_stdin_used","flagname":"obj._IO_stdin_used","realname":"_IO_stdin_used","ordinal":15,"bind":"GLOBAL","size":4,"type":"OBJ","vaddr":8192,"paddr":8192,"is_imported":false}]"#;
+        let exports: Vec<ExportEntry> =
+            serde_json::from_str(json).expect("Unable to deserialize exports");
+        assert_eq!(exports.len(), 2);
+        assert_eq!(exports[0].name, "main");
+        assert_eq!(exports[0].size, 35);
+        assert_eq!(exports[1].type_field, "OBJ");
+        assert_eq!(exports[1].flagname, Some("obj._IO_stdin_used".to_string()));
+        assert!(!exports[1].is_imported);
+    }
+
+    #[test]
+    fn test_entrypoint_deserialization() {
+        // iej output for a PE with a TLS callback
+        let json = r#"[{"vaddr":4198400,"paddr":1024,"baddr":4194304,"laddr":0,"hvaddr":4194576,"haddr":272,"type":"program"},{"vaddr":4198656,"paddr":1280,"baddr":4194304,"laddr":0,"hvaddr":4202496,"haddr":4608,"type":"tls"}]"#;
+        let entrypoints: Vec<EntryPoint> =
+            serde_json::from_str(json).expect("Unable to deserialize entrypoints");
+        assert_eq!(entrypoints.len(), 2);
+        assert_eq!(entrypoints[0].vaddr, 4198400);
+        assert_eq!(entrypoints[0].paddr, 1024);
+        assert_eq!(entrypoints[0].type_field, "program");
+        assert_eq!(entrypoints[1].type_field, "tls");
+    }
+
+    #[test]
+    fn test_entrypoints_output_suffix() {
+        let job = ExtractionJob::new(
+            &PathBuf::from("test-files/test_bin"),
+            &PathBuf::from("output"),
+            "entrypoints",
+            &false,
+            "aa",
+            &false,
+            &false,
+            "json",
+            &None,
+            Path::new("false"),
+            "symbol",
+            &1,
+            &false,
+            &None,
+            &None,
+            &None,
+            &false,
+            &false,
+            &false,
+            &false,
+            &true,
+            &true,
+            &false,
+            &DEFAULT_MAX_FUNC_BYTES,
+            &0,
+            &5,
+            "bin",
+            &None,
+            &None,
+        )
+        .unwrap();
+
+        assert_eq!(job.job_type, ExtractionJobType::EntryPoints);
+        assert_eq!(
+            job.files_to_be_processed[0].get_existing_output_path(&job.job_type),
+            PathBuf::from("output/test_bin_entrypoints.json")
+        );
+    }
+
+    #[test]
+    fn test_reloc_entry_deserialization() {
+        // irj output for a dynamically linked x86-64 ELF with PLT relocations
+        let json = r#"[{"name":"puts","demname":"puts","type":"SET_64","vaddr":16408,"paddr":12312,"sym_va":0,"is_ifunc":false},{"name":"__libc_start_main","demname":"__libc_start_main","type":"SET_64","vaddr":16344,"paddr":12248,"sym_va":0,"is_ifunc":false},{"type":"ADD_64","vaddr":15856,"paddr":11760,"is_ifunc":false},{"name":"strchr","type":"SET_64","vaddr":16416,"is_ifunc":true}]"#;
+        let relocs: Vec<RelocEntry> =
+            serde_json::from_str(json).expect("Unable to deserialize relocations");
+        assert_eq!(relocs.len(), 4);
+        assert_eq!(relocs[0].name, Some("puts".to_string()));
+        assert_eq!(relocs[0].type_field, Some("SET_64".to_string()));
+        assert_eq!(relocs[0].vaddr, 16408);
+        assert_eq!(relocs[0].paddr, Some(12312));
+        assert_eq!(relocs[2].name, None);
+        assert_eq!(relocs[2].sym_va, None);
+        assert_eq!(relocs[3].paddr, None);
+        assert!(relocs[3].is_ifunc);
+    }
+
+    #[test]
+    fn test_validate_r2_path_missing_executable() {
+        assert!(validate_r2_path(Path::new("/not/a/real/path/radare2")).is_err());
+        assert!(validate_r2_path(Path::new("not-a-real-radare2-binary")).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_r2_output_malformed() {
+        let malformed = format!("[{{\"name\":\"main\",\"imports\":[{}", "a".repeat(500));
+        let ret = deserialize_r2_output::<Vec<AGCJFunctionCallGraph>>("agCj", &malformed);
+        assert!(ret.is_err());
+        let error = ret.unwrap_err().to_string();
+        assert!(error.contains("agCj"));
+        assert!(error.len() < malformed.len());
+
+        let ret = deserialize_r2_output::<Vec<StringEntry>>("izj", "not json at all");
+        assert!(ret.is_err());
+    }
+
+    #[test]
+    fn test_deserialize_r2_output_valid() {
+        let ret = deserialize_r2_output::<Vec<StringEntry>>(
+            "izj",
+            r#"[{"vaddr":8196,"paddr":8196,"ordinal":0,"size":13,"length":12,"section":".rodata","type":"ascii","string":"Hello World!"}]"#,
+        );
+        assert_eq!(ret.unwrap()[0].string, "Hello World!");
+    }
+
+    #[test]
+    fn test_sanitize_function_name() {
+        assert_eq!(sanitize_function_name("sym.main"), "sym.main");
+        assert_eq!(
+            sanitize_function_name("sym.operator/(int,int)"),
+            "sym.operator_(int,int)"
+        );
+        assert_eq!(sanitize_function_name("a\\b:c"), "a_b_c");
+    }
+
+    #[test]
+    fn test_format_function_filename_symbol() {
+        assert_eq!(
+            format_function_filename("symbol", "sym.main", 0x401000),
+            "sym.main"
+        );
+    }
+
+    #[test]
+    fn test_format_function_filename_address() {
+        assert_eq!(
+            format_function_filename("address", "sym.main", 0x401000),
+            "0x401000"
+        );
+    }
+
+    #[test]
+    fn test_format_function_filename_template() {
+        assert_eq!(
+            format_function_filename("{address}.{symbol}", "sym.main", 0x401000),
+            "0x401000.sym.main"
+        );
+        assert_eq!(
+            sanitize_function_name(&format_function_filename(
+                "{symbol}-{address}",
+                "sym.operator/",
+                16
+            )),
+            "sym.operator_-0x10"
+        );
+    }
+
+    #[test]
+    fn test_write_to_bin_writes_function_files() {
+        let output_path =
+            env::temp_dir().join(format!("bin2ml-write-to-bin-{}", std::process::id()));
+        let file = FileToBeProcessed {
+            output_path: output_path.clone(),
+            ..test_file_to_be_processed("bytes")
+        };
+
+        file.write_to_bin("sym.main", &[0x55, 0x48, 0x89, 0xe5])
+            .expect("Failed to write bytes to bin.");
+        file.write_to_bin("sym.foo", &[0xc3])
+            .expect("Failed to write bytes to bin.");
+
+        let main_bytes = fs::read(output_path.join("test_bin/sym.main.bin")).unwrap();
+        let foo_bytes = fs::read(output_path.join("test_bin/sym.foo.bin")).unwrap();
+        assert_eq!(main_bytes, vec![0x55, 0x48, 0x89, 0xe5]);
+        assert_eq!(foo_bytes, vec![0xc3]);
+
+        fs::remove_dir_all(output_path).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_bytes_archive_contains_all_functions() {
+        let output_path =
+            env::temp_dir().join(format!("bin2ml-bytes-archive-{}", std::process::id()));
+        // The fake r2 lists a single function (main) and returns its bytes from pcs
+        let job = ExtractionJob::new(
+            &PathBuf::from("test-files/test_bin"),
+            &output_path,
+            "bytes",
+            &false,
+            "aa",
+            &false,
+            &false,
+            "json",
+            &None,
+            Path::new("test-files/fake_r2"),
+            "symbol",
+            &1,
+            &false,
+            &None,
+            &None,
+            &None,
+            &false,
+            &false,
+            &false,
+            &false,
+            &true,
+            &true,
+            &false,
+            &DEFAULT_MAX_FUNC_BYTES,
+            &0,
+            &5,
+            "archive",
+            &None,
+            &None,
+        )
+        .unwrap();
+        let file = &job.files_to_be_processed[0];
+        let result = file.extract_function_bytes();
+        assert_eq!(result.functions_processed, 1);
+        assert!(result.errors.is_empty());
+
+        let archive_path = file.get_existing_output_path(&ExtractionJobType::FunctionBytes);
+        assert_eq!(archive_path, output_path.join("test_bin_bytes.json"));
+        let archive: BTreeMap<String, String> =
+            serde_json::from_str(&fs::read_to_string(&archive_path).unwrap()).unwrap();
+        assert_eq!(archive.len(), 1);
+        assert_eq!(
+            parse_hex_string(&archive["main"]),
+            vec![0x55, 0x48, 0x89, 0xe5, 0xc3]
+        );
+
+        fs::remove_dir_all(output_path).unwrap();
+    }
+
+    #[test]
+    fn test_output_checksums_match_file_contents() {
+        let output_path =
+            env::temp_dir().join(format!("bin2ml-output-checksums-{}", std::process::id()));
+        fs::create_dir_all(&output_path).unwrap();
+        let file = FileToBeProcessed {
+            output_path: output_path.clone(),
+            ..test_file_to_be_processed("bytes")
+        };
+
+        file.write_to_json(&json!({"sym.main": [1, 2, 3]})).unwrap();
+        file.write_to_bin("sym.main", &[0x55, 0x48, 0x89, 0xe5])
+            .expect("Failed to write bytes to bin.");
+        file.write_manifest(vec![ModeResult::new("bytes")]).unwrap();
+        file.write_checksums().unwrap();
+
+        let manifest: ExtractionManifest = serde_json::from_str(
+            &fs::read_to_string(output_path.join("test_bin_manifest.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(manifest.outputs.len(), 2);
+        for checksum in &manifest.outputs {
+            let expected = format!("{:x}", Sha256::digest(fs::read(&checksum.path).unwrap()));
+            assert_eq!(checksum.sha256, expected);
+        }
+
+        let sidecar = fs::read_to_string(output_path.join("test_bin.sha256")).unwrap();
+        let expected_sidecar: String = manifest
+            .outputs
+            .iter()
+            .map(|checksum| {
+                format!(
+                    "{}  {}\n",
+                    checksum.sha256,
+                    checksum.path.strip_prefix(&output_path).unwrap().display()
+                )
+            })
+            .collect();
+        assert_eq!(sidecar, expected_sidecar);
+
+        // A failed write means the file is incomplete so no checksum is recorded, even
+        // though the buffer is empty and the final flush succeeds
+        let read_only_path = output_path.join("read_only.json");
+        fs::write(&read_only_path, "").unwrap();
+        let checksums = Mutex::default();
+        let mut writer = ChecksummedWriter {
+            writer: BufWriter::new(File::open(&read_only_path).unwrap()),
+            hasher: Sha256::new(),
+            path: read_only_path,
+            checksums: &checksums,
+            error: None,
+        };
+        assert!(writer.write(&[0; 16 * 1024]).is_err());
+        assert!(writer
+            .finish()
+            .unwrap_err()
+            .to_string()
+            .contains("Unable to write"));
+        assert!(checksums.lock().unwrap().is_empty());
+
+        fs::remove_dir_all(output_path).unwrap();
+    }
+
+    #[test]
+    fn test_nested_output_layout() {
+        let output_path =
+            env::temp_dir().join(format!("bin2ml-nested-output-{}", std::process::id()));
+        let file = |job_type_suffix: &str| FileToBeProcessed {
+            output_path: output_path.clone(),
+            nested_output: true,
+            ..test_file_to_be_processed(job_type_suffix)
+        };
+
+        file("reg").write_to_json(&json!({})).unwrap();
+        file("cfg").write_to_json(&json!([])).unwrap();
+        file("bytes")
+            .write_to_bin("sym.main", &[0xc3])
+            .expect("Failed to write bytes to bin.");
+
+        assert!(output_path.join("reg/test_bin_reg.json").is_file());
+        assert!(output_path.join("cfg/test_bin_cfg.json").is_file());
+        assert!(output_path.join("bytes/test_bin/sym.main.bin").is_file());
+
+        // The generate path searches the output directory recursively
+        let cfg_paths = get_json_paths_from_dir(&output_path, Some("_cfg".to_string()));
+        assert_eq!(
+            cfg_paths,
+            vec![output_path
+                .join("cfg/test_bin_cfg.json")
+                .to_string_lossy()
+                .to_string()]
+        );
+
+        fs::remove_dir_all(output_path).unwrap();
+    }
+
+    #[test]
+    fn test_cfg_enriched_output_matches_generate_graphs() {
+        let file = FileToBeProcessed {
+            feature_type: Some(FeatureType::Tiknib),
+            ..test_file_to_be_processed("cfg-enriched")
+        };
+
+        assert_eq!(
+            file.get_cfg_enriched_filename(),
+            PathBuf::from("test_bin_cfg.json")
+        );
+        assert_eq!(
+            file.get_existing_output_path(&ExtractionJobType::CFGEnriched),
+            PathBuf::from("output/test_bin_cfg-tiknib")
+        );
+
+        let base_path = env::temp_dir().join(format!("bin2ml-cfg-enriched-{}", std::process::id()));
+        let enriched_path = base_path.join("enriched");
+        let generated_path = base_path.join("generated");
+        let cfg_path = base_path.join("test_bin_cfg.json");
+        fs::create_dir_all(&base_path).unwrap();
+        fs::copy("test-files/r2-output-samples/test_bin_agfj.json", &cfg_path).unwrap();
+
+        // Single pass extraction
+        let file = FileToBeProcessed {
+            output_path: enriched_path.clone(),
+            ..file
+        };
+        let json: Vec<Value> =
+            serde_json::from_str(&fs::read_to_string(&cfg_path).unwrap()).unwrap();
+        let result = file.write_func_cfgs_enriched(json, ModeResult::new("cfg-enriched"));
+        assert!(result.success);
+
+        // Extracting the CFGs followed by generate graphs
+        let mut agfj_file = AGFJFile {
+            filename: cfg_path,
+            functions: None,
+            output_path: generated_path.clone(),
+            min_blocks: 5,
+            max_blocks: None,
+            feature_type: Some(FeatureType::Tiknib),
+            architecture: None,
+            norm_config: NormalizationConfig::default(),
+            graph_format: GraphFormat::Json,
+            embed_func_meta: true,
+            edge_types: false,
+            relative_addrs: false,
+        };
+        agfj_file.load_and_deserialize().unwrap();
+        agfj_file.architecture = agfj_file.detect_architecture();
+        agfj_file.paralell_attributed_cfg_gen();
+
+        let enriched_dir = enriched_path.join("test_bin_cfg-tiknib");
+        let generated_dir = generated_path.join("test_bin_cfg-tiknib");
+        let mut enriched_files: Vec<_> = fs::read_dir(&enriched_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        let mut generated_files: Vec<_> = fs::read_dir(&generated_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        enriched_files.sort();
+        generated_files.sort();
+        assert!(!enriched_files.is_empty());
+        assert_eq!(enriched_files, generated_files);
+        for file_name in enriched_files {
+            assert_eq!(
+                fs::read(enriched_dir.join(&file_name)).unwrap(),
+                fs::read(generated_dir.join(&file_name)).unwrap(),
+                "{:?} differs",
+                file_name
+            );
+        }
+
+        fs::remove_dir_all(base_path).unwrap();
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_cmd_with_timeout_kills_hung_r2() {
+        // The fake r2 hangs on pdgsd, mimicking the decompiler hanging on a function
+        let job = ExtractionJob::new(
+            &PathBuf::from("test-files/test_bin"),
+            &env::temp_dir(),
+            "finfo",
+            &false,
+            "aa",
+            &false,
+            &false,
+            "json",
+            &Some(1),
+            Path::new("test-files/fake_r2"),
+            "symbol",
+            &1,
+            &false,
+            &None,
+            &None,
+            &None,
+            &false,
+            &false,
+            &false,
+            &false,
+            &true,
+            &true,
+            &false,
+            &DEFAULT_MAX_FUNC_BYTES,
+            &0,
+            &5,
+            "bin",
+            &None,
+            &None,
+        )
+        .unwrap();
+        let file = &job.files_to_be_processed[0];
+        let mut r2p = file.setup_r2_pipe().unwrap();
+        let pid = FileToBeProcessed::get_r2_pid(&mut r2p).unwrap();
+
+        let (r2p, ret) = file.cmd_with_timeout(r2p, "pdgsd 10");
+        assert!(r2p.is_none());
+        assert!(ret.is_err());
+
+        // The hung r2 process has been killed and collected
+        assert!(!PathBuf::from(format!("/proc/{}", pid)).exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_register_behaviour_skips_functions_with_invalid_r2_output() {
+        let output_path = env::temp_dir().join(format!("bin2ml-reg-skip-{}", std::process::id()));
+        fs::create_dir_all(&output_path).unwrap();
+        // The fake r2 lists a single function and returns [] for aeafj
+        let job = ExtractionJob::new(
+            &PathBuf::from("test-files/test_bin"),
+            &output_path,
+            "reg",
+            &false,
+            "aa",
+            &false,
+            &false,
+            "json",
+            &None,
+            Path::new("test-files/fake_r2"),
+            "symbol",
+            &1,
+            &false,
+            &None,
+            &None,
+            &None,
+            &false,
+            &false,
+            &false,
+            &false,
+            &true,
+            &true,
+            &false,
+            &DEFAULT_MAX_FUNC_BYTES,
+            &0,
+            &5,
+            "bin",
+            &None,
+            &None,
+        )
+        .unwrap();
+
+        let result = job.files_to_be_processed[0].extract(&job.job_type);
+        assert!(result.success);
+        assert_eq!(result.functions_processed, 0);
+        assert_eq!(result.functions_skipped, 1);
+        assert!(result.errors[0].contains("aeafj"));
+
+        fs::remove_dir_all(output_path).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_extract_all_reports_files_r2_cannot_open() {
+        let input_path = env::temp_dir().join(format!("bin2ml-skip-input-{}", std::process::id()));
+        let output_path = input_path.join("output");
+        fs::create_dir_all(&output_path).unwrap();
+        fs::write(input_path.join("good_bin"), "good").unwrap();
+        fs::write(input_path.join("bad_bin"), "bad").unwrap();
+
+        // The fake r2 exits immediately for bad_* files, mimicking r2 crashing when
+        // loading a corrupt binary
+        let job = ExtractionJob::new(
+            &input_path,
+            &output_path,
+            "sections",
+            &false,
+            "aa",
+            &false,
+            &false,
+            "json",
+            &None,
+            Path::new("test-files/fake_r2"),
+            "symbol",
+            &1,
+            &false,
+            &None,
+            &None,
+            &None,
+            &false,
+            &false,
+            &false,
+            &false,
+            &true,
+            &true,
+            &false,
+            &DEFAULT_MAX_FUNC_BYTES,
+            &0,
+            &5,
+            "bin",
+            &None,
+            &None,
+        )
+        .unwrap();
+        assert_eq!(job.files_to_be_processed.len(), 2);
+
+        let failed = job.extract_all(false, false);
+        assert_eq!(failed, vec![input_path.join("bad_bin")]);
+
+        fs::remove_dir_all(input_path).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_extract_all_reports_output_write_errors() {
+        // The output path is a file so no output can be written beneath it
+        let output_path =
+            env::temp_dir().join(format!("bin2ml-unwritable-output-{}", std::process::id()));
+        fs::write(&output_path, "").unwrap();
+
+        for (mode, bytes_format) in [("bytes", "bin"), ("bytes", "archive"), ("sections", "bin")] {
+            let job = ExtractionJob::new(
+                &PathBuf::from("test-files/test_bin"),
+                &output_path,
+                mode,
+                &false,
+                "aa",
+                &false,
+                &false,
+                "json",
+                &None,
+                Path::new("test-files/fake_r2"),
+                "symbol",
+                &1,
+                &false,
+                &None,
+                &None,
+                &None,
+                &false,
+                &false,
+                &false,
+                &false,
+                &true,
+                &true,
+                &false,
+                &DEFAULT_MAX_FUNC_BYTES,
+                &0,
+                &5,
+                bytes_format,
+                &None,
+                &None,
+            )
+            .unwrap();
+            let result = job.files_to_be_processed[0].extract(&job.job_type);
+            assert!(!result.success);
+            assert!(result.errors[0].contains("Unable to create"));
+            assert_eq!(
+                job.extract_all(true, false),
+                vec![PathBuf::from("test-files/test_bin")]
+            );
+        }
+
+        fs::remove_file(output_path).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_extract_all_continues_when_r2_dies_during_cfg_extraction() {
+        let input_path = env::temp_dir().join(format!("bin2ml-crash-input-{}", std::process::id()));
+        let output_path = input_path.join("output");
+        fs::create_dir_all(&output_path).unwrap();
+        fs::write(input_path.join("good_bin"), "good").unwrap();
+        fs::write(input_path.join("crash_bin"), "crash").unwrap();
+
+        // The fake r2 exits when agfj is run on crash_* files, mimicking r2 dying mid-run
+        let job = ExtractionJob::new(
+            &input_path,
+            &output_path,
+            "cfg",
+            &false,
+            "aa",
+            &false,
+            &false,
+            "json",
+            &None,
+            Path::new("test-files/fake_r2"),
+            "symbol",
+            &1,
+            &false,
+            &None,
+            &None,
+            &None,
+            &false,
+            &false,
+            &false,
+            &false,
+            &true,
+            &true,
+            &false,
+            &DEFAULT_MAX_FUNC_BYTES,
+            &0,
+            &5,
+            "bin",
+            &None,
+            &None,
+        )
+        .unwrap();
+        let crash_file = job
+            .files_to_be_processed
+            .iter()
+            .find(|file| file.file_path.ends_with("crash_bin"))
+            .unwrap();
+        assert!(crash_file
+            .get_func_cfgs_json()
+            .unwrap_err()
+            .contains("Failed to extract control flow graph information"));
+
+        let failed = job.extract_all(false, false);
+        assert_eq!(failed, vec![input_path.join("crash_bin")]);
+        assert!(output_path.join("good_bin_cfg.json").exists());
+
+        fs::remove_dir_all(input_path).unwrap();
+    }
+
+    #[test]
+    fn test_extraction_job_from_tar_archive() {
+        let work_dir = env::temp_dir().join(format!("bin2ml-tar-input-{}", std::process::id()));
+        let output_path = work_dir.join("output");
+        fs::create_dir_all(&output_path).unwrap();
+
+        let archive_path = work_dir.join("corpus.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(
+            File::create(&archive_path).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        builder
+            .append_path_with_name("test-files/test_bin", "bins/test_bin")
+            .unwrap();
+        builder
+            .append_path_with_name("test-files/test_bin_2", "bins/test_bin_2")
+            .unwrap();
+        builder
+            .append_path_with_name("test-files/ls_cg.json", "bins/ls_cg.json")
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let job = ExtractionJob::new(
+            &archive_path,
+            &output_path,
+            "byte-histogram",
+            &false,
+            "aa",
+            &false,
+            &false,
+            "json",
+            &None,
+            Path::new("radare2"),
+            "symbol",
+            &1,
+            &false,
+            &None,
+            &None,
+            &None,
+            &false,
+            &false,
+            &false,
+            &false,
+            &true,
+            &true,
+            &false,
+            &DEFAULT_MAX_FUNC_BYTES,
+            &0,
+            &5,
+            "bin",
+            &None,
+            &None,
+        )
+        .unwrap();
+
+        assert_eq!(job.input_path_type, PathType::Archive);
+        // The JSON file within the archive is skipped
+        assert_eq!(job.files_to_be_processed.len(), 2);
+        assert!(job.extract_all(false, false).is_empty());
+        assert!(output_path.join("test_bin_byte-histogram.json").is_file());
+        assert!(output_path.join("test_bin_2_byte-histogram.json").is_file());
+
+        let archive_dir = job.archive_dir.as_ref().unwrap().path().to_path_buf();
+        assert!(archive_dir.is_dir());
+        drop(job);
+        assert!(!archive_dir.exists());
+
+        // A corrupt archive fails part way through unpacking and is still cleaned up
+        let corrupt_path = work_dir.join("corrupt.tar.gz");
+        fs::write(&corrupt_path, "not a gzipped tarball").unwrap();
+        let unpack_dir =
+            env::temp_dir().join(format!("bin2ml-corrupt.tar.gz-{}", std::process::id()));
+        assert!(ExtractionJob::unpack_archive(&corrupt_path).is_err());
+        assert!(!unpack_dir.exists());
+
+        fs::remove_dir_all(work_dir).unwrap();
+    }
+
+    #[test]
+    fn test_extraction_job_describe() {
+        let job = ExtractionJob::new(
+            &PathBuf::from("test-files/test_bin"),
+            &PathBuf::from("output"),
+            "byte-entropy",
+            &false,
+            "aa",
+            &false,
+            &false,
+            "json",
+            &None,
+            Path::new("radare2"),
+            "symbol",
+            &1,
+            &false,
+            &None,
+            &None,
+            &None,
+            &false,
+            &false,
+            &false,
+            &false,
+            &true,
+            &true,
+            &false,
+            &DEFAULT_MAX_FUNC_BYTES,
+            &0,
+            &5,
+            "bin",
+            &None,
+            &None,
+        )
+        .unwrap();
+
+        let description = job.describe();
+        assert!(description.starts_with("Extraction job: ByteEntropy (1 file(s))"));
+        assert!(description.contains("\"test-files/test_bin\""));
+        assert!(description.contains("job type: ByteEntropy"));
+        assert!(description.contains("output: \"output/test_bin_byte-entropy.json\""));
+    }
+
+    #[test]
+    fn test_parse_agfj_output() {
+        let output = concat!(
+            r#"[{"name":"main","offset":4425,"blocks":[{"offset":4425,"ops":[{"disasm":"lea rdi, str.}]\n[{"}]}]}]"#,
+            "\n[]\n",
+            r#"[{"name":"sym.foo","offset":4500,"blocks":[{"offset":4500,"ops":[{"disasm":"mov eax, [{}]"}]}]}]"#,
+            "\n[]\n",
+        );
+        let (functions, errors) = parse_agfj_output(output);
+
+        assert!(errors.is_empty());
+        assert_eq!(functions.len(), 2);
+        assert_eq!(functions[0][0]["name"], "main");
+        assert_eq!(
+            functions[0][0]["blocks"][0]["ops"][0]["disasm"],
+            "lea rdi, str.}]\n[{"
+        );
+        assert_eq!(
+            functions[1][0]["blocks"][0]["ops"][0]["disasm"],
+            "mov eax, [{}]"
+        );
+    }
+
+    // Mocks r2 returning no functions until 'aaa' has been run
+    fn mock_r2_cmd<'a>(
+        commands: &'a mut Vec<String>,
+        analysed: &'a mut bool,
+    ) -> impl FnMut(&str) -> Result<String, Error> + 'a {
+        move |cmd| {
+            commands.push(cmd.to_string());
+            match cmd {
+                "aaa" => {
+                    *analysed = true;
+                    Ok(String::new())
+                }
+                "aflj" if *analysed => {
+                    let main = AFIJFunctionInfo {
+                        name: "main".to_string(),
+                        offset: 0x1149,
+                        ..Default::default()
+                    };
+                    Ok(json!([main]).to_string())
+                }
+                "aflj" => Ok("[]".to_string()),
+                _ => bail!("Unexpected command - {}", cmd),
+            }
+        }
+    }
+
+    #[test]
+    fn test_list_functions_reanalyzes_when_empty() {
+        let mut commands = Vec::new();
+        let mut analysed = false;
+        let functions =
+            list_functions_with_reanalysis(mock_r2_cmd(&mut commands, &mut analysed), true)
+                .unwrap();
+        assert_eq!(functions.len(), 1);
+        assert_eq!(functions[0].name, "main");
+        assert_eq!(commands, vec!["aflj", "aaa", "aflj"]);
+    }
+
+    #[test]
+    fn test_list_functions_without_reanalysis() {
+        let mut commands = Vec::new();
+        let mut analysed = false;
+        let functions =
+            list_functions_with_reanalysis(mock_r2_cmd(&mut commands, &mut analysed), false)
+                .unwrap();
+        assert!(functions.is_empty());
+        assert_eq!(commands, vec!["aflj"]);
+
+        // Functions found on the first attempt never trigger a reanalysis
+        let mut commands = Vec::new();
+        let mut analysed = true;
+        let functions =
+            list_functions_with_reanalysis(mock_r2_cmd(&mut commands, &mut analysed), true)
+                .unwrap();
+        assert_eq!(functions.len(), 1);
+        assert_eq!(commands, vec!["aflj"]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_pdg_decompilation_matches_pdgj_code() {
+        // The fake r2 returns the same function from pdg and pdgj
+        let decompile = |with_annotations: bool| {
+            let job = ExtractionJob::new(
+                &PathBuf::from("test-files/test_bin"),
+                &env::temp_dir(),
+                "decomp",
+                &false,
+                "aa",
+                &false,
+                &with_annotations,
+                "json",
+                &None,
+                Path::new("test-files/fake_r2"),
+                "symbol",
+                &1,
+                &false,
+                &None,
+                &None,
+                &None,
+                &false,
+                &false,
+                &false,
+                &false,
+                &true,
+                &true,
+                &false,
+                &DEFAULT_MAX_FUNC_BYTES,
+                &0,
+                &5,
+                "bin",
+                &None,
+                &None,
+            )
+            .unwrap();
+            let file = &job.files_to_be_processed[0];
+            let r2p = file.setup_r2_pipe().unwrap();
+            let (r2p, decomp) = file.get_ghidra_decomp(0x1000, r2p);
+            r2p.unwrap().close();
+            decomp.unwrap()
+        };
+
+        let pdgj_decomp = decompile(true);
+        let pdg_decomp = decompile(false);
+        assert_eq!(pdgj_decomp.annotations.len(), 1);
+        assert_eq!(pdg_decomp.code, pdgj_decomp.code);
+        assert!(pdg_decomp.code.starts_with("\nint main(void)"));
+        assert!(pdg_decomp.annotations.is_empty());
+    }
+
+    #[test]
+    fn test_add_bits_to_cfgs() {
+        let (mut functions, _) =
+            parse_agfj_output("[{\"name\":\"main\"}]\n[{\"name\":\"sym.foo\"}]\n");
+        add_bits_to_cfgs(&mut functions, 64);
+
+        assert_eq!(functions[0][0]["bits"], 64);
+        assert_eq!(functions[1][0]["bits"], 64);
+        assert_eq!(functions[1][0]["name"], "sym.foo");
+    }
+
+    #[test]
+    fn test_parse_agfj_output_skips_malformed_records() {
+        let output =
+            "[{\"name\":\"main\"}]\n[{\"name\":\"sym.broken\",\n[]\n[{\"name\":\"sym.foo\"}]\n";
+        let (functions, errors) = parse_agfj_output(output);
+
+        assert_eq!(functions.len(), 2);
+        assert_eq!(functions[0][0]["name"], "main");
+        assert_eq!(functions[1][0]["name"], "sym.foo");
+        assert_eq!(errors.len(), 1);
+
+        let (functions, errors) = parse_agfj_output("[]\n[]\n");
+        assert!(functions.is_empty());
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn test_register_behaviour_missing_addresses() {
+        let json = r#"{"A":[],"I":[],"R":[],"W":[],"V":[]}"#;
+        let behaviour: AEAFJRegisterBehaviour =
+            serde_json::from_str(json).expect("Unable to deserialize register behaviour");
+        assert!(behaviour.r2.is_empty());
+        assert!(behaviour.w2.is_empty());
     }
 }
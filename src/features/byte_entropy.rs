@@ -0,0 +1,128 @@
+// Implements the byte-entropy histogram from the EMBER paper - Anderson & Roth (2018)
+// EMBER: An Open Dataset for Training Static PE Malware Machine Learning Models.
+//
+// A window is slid across the raw bytes of a file and for each window the
+// entropy is calculated using the upper nibble of each byte. The entropy is
+// binned into one of 16 bins and the upper nibble counts of the window are added
+// to the row of that bin. The resulting 16x16 joint histogram is flattened
+// and normalised to give a 256-length vector.
+
+pub const BYTE_ENTROPY_WINDOW: usize = 2048;
+pub const BYTE_ENTROPY_STEP: usize = 1024;
+
+const NUM_BINS: usize = 16;
+
+// Returns the entropy bin and the coarse (upper nibble) counts for a single window
+fn entropy_bin_counts(block: &[u8], window: usize) -> (usize, [f64; NUM_BINS]) {
+    let mut counts = [0.0; NUM_BINS];
+    for byte in block {
+        counts[(byte >> 4) as usize] += 1.0;
+    }
+
+    let entropy: f64 = counts
+        .iter()
+        .filter(|count| **count > 0.0)
+        .map(|count| {
+            let p = count / window as f64;
+            -p * p.log2()
+        })
+        .sum::<f64>()
+        // x2 as the information has been halved from 8 bits to 4 bits
+        * 2.0;
+
+    // Max entropy is 8 bits so this gives up to 16 bins
+    let entropy_bin = ((entropy * 2.0) as usize).min(NUM_BINS - 1);
+    (entropy_bin, counts)
+}
+
+/// Generate the normalised byte-entropy histogram for the provided bytes.
+///
+/// Inputs smaller than the window are treated as a single window.
+pub fn byte_entropy_histogram(bytes: &[u8], window: usize, step: usize) -> Vec<f64> {
+    let mut histogram = vec![0.0; NUM_BINS * NUM_BINS];
+
+    if bytes.len() < window {
+        let (entropy_bin, counts) = entropy_bin_counts(bytes, window);
+        for (i, count) in counts.iter().enumerate() {
+            histogram[entropy_bin * NUM_BINS + i] += count;
+        }
+    } else {
+        for start in (0..=bytes.len() - window).step_by(step) {
+            let (entropy_bin, counts) = entropy_bin_counts(&bytes[start..start + window], window);
+            for (i, count) in counts.iter().enumerate() {
+                histogram[entropy_bin * NUM_BINS + i] += count;
+            }
+        }
+    }
+
+    let total: f64 = histogram.iter().sum();
+    if total > 0.0 {
+        histogram.iter_mut().for_each(|ele| *ele /= total);
+    }
+    histogram
+}
+
+/// Calculate the Shannon entropy (in bits, 0-8) of the provided bytes.
+///
+/// An empty input has an entropy of zero.
+pub fn shannon_entropy(bytes: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for byte in bytes {
+        counts[*byte as usize] += 1;
+    }
+
+    counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / bytes.len() as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_entropy_histogram_constant_bytes() {
+        // A single repeated byte has zero entropy so all counts land in the
+        // first entropy bin under the upper nibble of the byte
+        let bytes = vec![0x41; 4096];
+        let histogram = byte_entropy_histogram(&bytes, 2048, 1024);
+
+        assert_eq!(histogram.len(), 256);
+        assert_eq!(histogram[0x4], 1.0);
+        assert_eq!(histogram.iter().sum::<f64>(), 1.0);
+    }
+
+    #[test]
+    fn test_byte_entropy_histogram_high_entropy() {
+        // Every byte value present equally gives maximum entropy
+        let bytes: Vec<u8> = (0..2048).map(|i| (i % 256) as u8).collect();
+        let histogram = byte_entropy_histogram(&bytes, 2048, 1024);
+
+        let top_row = &histogram[15 * 16..];
+        for ele in top_row {
+            assert!((ele - 1.0 / 16.0).abs() < 1e-9);
+        }
+        assert!((histogram.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_byte_entropy_histogram_empty_input() {
+        let histogram = byte_entropy_histogram(&[], 2048, 1024);
+        assert_eq!(histogram, vec![0.0; 256]);
+    }
+
+    #[test]
+    fn test_shannon_entropy() {
+        assert_eq!(shannon_entropy(&[]), 0.0);
+        assert_eq!(shannon_entropy(&[0x41; 64]), 0.0);
+        assert_eq!(shannon_entropy(&[0x00, 0xff]), 1.0);
+
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert!((shannon_entropy(&bytes) - 8.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,37 @@
+/// Generate the normalised frequency of each of the 256 byte values within the provided bytes.
+///
+/// An empty input returns a histogram of zeros.
+pub fn byte_histogram(bytes: &[u8]) -> [f64; 256] {
+    let mut histogram = [0.0; 256];
+    for byte in bytes {
+        histogram[*byte as usize] += 1.0;
+    }
+
+    if !bytes.is_empty() {
+        let total = bytes.len() as f64;
+        histogram.iter_mut().for_each(|ele| *ele /= total);
+    }
+    histogram
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_histogram() {
+        let bytes = [0x00, 0x00, 0x41, 0xff];
+        let histogram = byte_histogram(&bytes);
+
+        assert_eq!(histogram[0x00], 0.5);
+        assert_eq!(histogram[0x41], 0.25);
+        assert_eq!(histogram[0xff], 0.25);
+        assert_eq!(histogram.iter().filter(|ele| **ele > 0.0).count(), 3);
+        assert_eq!(histogram.iter().sum::<f64>(), 1.0);
+    }
+
+    #[test]
+    fn test_byte_histogram_empty_input() {
+        assert_eq!(byte_histogram(&[]), [0.0; 256]);
+    }
+}
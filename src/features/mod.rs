@@ -0,0 +1,3 @@
+pub mod byte_entropy;
+pub mod byte_histogram;
+pub mod string_stats;
@@ -0,0 +1,141 @@
+// Aggregate string statistics in the spirit of the EMBER string features - Anderson & Roth (2018)
+// EMBER: An Open Dataset for Training Static PE Malware Machine Learning Models.
+//
+// The statistics are computed over the strings recovered by radare2 (izj) rather
+// than a raw scan of the file, so only strings r2 considers valid are included.
+
+use crate::extract::StringEntry;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+const NUM_CHAR_CLASSES: usize = 5;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StringStats {
+    pub num_strings: usize,
+    pub num_printable: usize,
+    pub avg_length: f64,
+    pub total_length: usize,
+    pub char_class_entropy: f64,
+    pub num_paths: usize,
+    pub num_urls: usize,
+    pub num_registry: usize,
+}
+
+// Lowercase, uppercase, digits, whitespace and everything else (punctuation etc)
+fn char_class(c: char) -> usize {
+    if c.is_lowercase() {
+        0
+    } else if c.is_uppercase() {
+        1
+    } else if c.is_numeric() {
+        2
+    } else if c.is_whitespace() {
+        3
+    } else {
+        4
+    }
+}
+
+/// Compute aggregate statistics over the strings extracted from a binary.
+///
+/// Lengths are measured in characters. The character class entropy is the
+/// Shannon entropy (in bits) of the distribution of characters across all
+/// strings over lowercase, uppercase, digit, whitespace and other classes.
+pub fn compute_string_stats(entries: &[StringEntry]) -> StringStats {
+    let path_re = Regex::new(r"(?i)(^[a-z]:\\|^\\\\|^/(?:[\w.\-]+/)+[\w.\-]*$)").unwrap();
+    let url_re = Regex::new(r"(?i)\b(?:https?|ftp)://").unwrap();
+    let registry_re = Regex::new(r"(?i)\bHKEY_|\bHK(?:LM|CU|CR|U|CC)\\").unwrap();
+
+    let mut stats = StringStats {
+        num_strings: entries.len(),
+        ..Default::default()
+    };
+    let mut class_counts = [0usize; NUM_CHAR_CLASSES];
+
+    for entry in entries {
+        let string = &entry.string;
+        stats.total_length += string.chars().count();
+        if !string.is_empty() && string.chars().all(|c| !c.is_control() || c.is_whitespace()) {
+            stats.num_printable += 1;
+        }
+        for c in string.chars() {
+            class_counts[char_class(c)] += 1;
+        }
+        if path_re.is_match(string) {
+            stats.num_paths += 1;
+        }
+        if url_re.is_match(string) {
+            stats.num_urls += 1;
+        }
+        if registry_re.is_match(string) {
+            stats.num_registry += 1;
+        }
+    }
+
+    if stats.num_strings > 0 {
+        stats.avg_length = stats.total_length as f64 / stats.num_strings as f64;
+    }
+
+    if stats.total_length > 0 {
+        stats.char_class_entropy = class_counts
+            .iter()
+            .filter(|count| **count > 0)
+            .map(|count| {
+                let p = *count as f64 / stats.total_length as f64;
+                -p * p.log2()
+            })
+            .sum();
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string_entry(string: &str) -> StringEntry {
+        StringEntry {
+            string: string.to_string(),
+            length: string.len() as i64,
+            size: string.len() as i64 + 1,
+            section: ".rodata".to_string(),
+            type_field: "ascii".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_compute_string_stats() {
+        let entries = vec![
+            string_entry("C:\\Windows\\System32\\cmd.exe"),
+            string_entry("https://example.com/payload"),
+            string_entry("HKEY_LOCAL_MACHINE\\Software"),
+            string_entry("/usr/lib/libc.so.6"),
+            string_entry("Hello, World!"),
+        ];
+        let stats = compute_string_stats(&entries);
+
+        assert_eq!(stats.num_strings, 5);
+        assert_eq!(stats.num_printable, 5);
+        assert_eq!(stats.num_paths, 2);
+        assert_eq!(stats.num_urls, 1);
+        assert_eq!(stats.num_registry, 1);
+        assert_eq!(stats.total_length, 27 + 27 + 27 + 18 + 13);
+        assert_eq!(stats.avg_length, stats.total_length as f64 / 5.0);
+        assert!(stats.char_class_entropy > 0.0);
+        assert!(stats.char_class_entropy <= (NUM_CHAR_CLASSES as f64).log2());
+    }
+
+    #[test]
+    fn test_compute_string_stats_single_class_entropy() {
+        let stats = compute_string_stats(&[string_entry("aaaa"), string_entry("bbbb")]);
+        assert_eq!(stats.char_class_entropy, 0.0);
+        assert_eq!(stats.avg_length, 4.0);
+    }
+
+    #[test]
+    fn test_compute_string_stats_empty() {
+        assert_eq!(compute_string_stats(&[]), StringStats::default());
+    }
+}
@@ -1,46 +1,82 @@
-use crate::afij::{AFIJFeatureSubset, AFIJFeatureSubsetExtended, AFIJFunctionInfo};
-use crate::agcj::AGCJFunctionCallGraph;
-use crate::agfj::{AGFJFunc, TikNibFunc};
-use crate::bb::{FeatureType, InstructionMode};
+use crate::afij::{
+    AFIJFeatureSubset, AFIJFeatureSubsetExtended, AFIJFeatureSubsetWithEdges, AFIJFunctionInfo,
+    ComplexityFeatures,
+};
+use crate::agcj::{add_call_edge, AGCJFunctionCallGraph, SelfLoops};
+use crate::agfj::{AGFJFunc, CfgMismatch, TikNibFunc};
+use crate::bb::{BlockFeatureCache, FeatureType, InstructionMode};
 use crate::consts::*;
 use crate::errors::FileLoadError;
 #[cfg(feature = "inference")]
 use crate::inference::InferenceJob;
 use crate::networkx::{
-    CallGraphFuncWithMetadata, CallGraphTikNibFeatures, CallGraphTypes, NetworkxDiGraph,
+    CallGraphFuncWithMetadata, CallGraphTikNibFeatures, CallGraphTypes, GraphFormat,
+    NetworkxDiGraph,
+};
+use crate::normalisation::NormalizationConfig;
+use crate::utils::{
+    get_save_file_path, open_json_reader, read_json_to_string, write_json_to_file,
+    FunctionKeyedJson,
 };
-use crate::utils::get_save_file_path;
 use enum_as_inner::EnumAsInner;
 use indicatif::ParallelProgressIterator;
 use itertools::Itertools;
 
 use crate::combos::FinfoTiknib;
 use crate::DataType;
+use petgraph::graph::NodeIndex;
 use petgraph::{Graph, Incoming, Outgoing};
 use rayon::iter::ParallelIterator;
 use rayon::prelude::{IntoParallelRefIterator, IntoParallelRefMutIterator};
-use serde::{Deserialize, Serialize};
+use serde::de::{SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::json;
 use std::collections::HashMap;
-use std::fs::{read_to_string, File};
+use std::fmt;
+use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::string::String;
-use std::sync::mpsc::channel;
 #[cfg(feature = "inference")]
 use std::sync::Arc;
 #[cfg(feature = "inference")]
 use tch::nn::func;
 
+// The max number of block feature vectors memoized by each BlockFeatureCache
+const BLOCK_FEATURE_CACHE_CAPACITY: usize = 10_000;
+
+// The number of functions sampled to detect the architecture when streaming
+const STREAMING_ARCH_SAMPLE_SIZE: usize = 64;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AGFJFile {
     pub filename: PathBuf,
     pub functions: Option<Vec<Vec<AGFJFunc>>>,
     pub output_path: PathBuf,
     pub min_blocks: u16,
+    pub max_blocks: Option<u16>,
     pub feature_type: Option<FeatureType>,
     pub architecture: Option<String>,
-    pub reg_norm: bool,
+    pub norm_config: NormalizationConfig,
+    pub graph_format: GraphFormat,
+    pub embed_func_meta: bool,
+    pub edge_types: bool,
+    pub relative_addrs: bool,
+}
+
+/// A single line of a JSONL NLP corpus
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NlpCorpusRecord {
+    pub function: String,
+    pub instructions: Vec<String>,
+    pub arch: Option<String>,
+}
+
+/// A structural issue found within a function of an AGFJ file
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureViolation {
+    pub function_name: String,
+    pub reason: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
@@ -54,24 +90,55 @@ impl AGFJFile {
     // Allowed to enable propagation of errors from both reading to wstring and serde from str.
     #[allow(clippy::result_unit_err)]
     /// Loads and desearializes an AGFJ JSON file into a Vec<Vec<AGFJFunc>> and
-    /// then detects the architecure of the functions stored within
+    /// then detects the architecure of the functions stored within (unless an
+    /// architecture has already been provided)
     ///
     /// `agfj` is the radare2 command used to generate the `cfg` data. The code for this
     /// can be found in extract.rs.
-    pub fn load_and_deserialize(&mut self) -> Result<(), ()> {
-        let data = read_to_string(&self.filename).expect("Unable to read file");
+    pub fn load_and_deserialize(&mut self) -> Result<(), FileLoadError> {
+        let data = read_json_to_string(&self.filename)?;
+        self.functions = Some(serde_json::from_str(&data)?);
 
-        // Kept in to ensure that the JSON decode error message is printed alongside the filename
-        let json = serde_json::from_str(&data);
+        if self.architecture.is_none() {
+            self.architecture = self.detect_architecture();
+        }
 
-        if json.is_ok() {
-            self.functions = Some(json.unwrap());
+        Ok(())
+    }
 
-            self.architecture = self.detect_architecture();
+    /// Runs a structural check across each function within the file
+    ///
+    /// This checks for functions without any blocks (when min_blocks > 0), duplicate
+    /// block offsets and edges which reference blocks which do not exist.
+    pub fn validate_structure(&self) -> Vec<StructureViolation> {
+        let mut violations = Vec::new();
+        for func in self.functions.as_ref().unwrap() {
+            for reason in func[0].validate_structure(&self.min_blocks) {
+                violations.push(StructureViolation {
+                    function_name: func[0].name.clone(),
+                    reason,
+                })
+            }
+        }
+        violations
+    }
 
-            Ok(())
-        } else {
-            Err(())
+    /// Removes functions which fail the structural check, logging each violation
+    pub fn remove_invalid_functions(&mut self) {
+        let violations = self.validate_structure();
+        for violation in violations.iter() {
+            warn!(
+                "Skipping {} in {:?} - {}",
+                violation.function_name, self.filename, violation.reason
+            );
+        }
+
+        if !violations.is_empty() {
+            let invalid_names: Vec<&String> = violations.iter().map(|v| &v.function_name).collect();
+            self.functions
+                .as_mut()
+                .unwrap()
+                .retain(|func| !invalid_names.contains(&&func[0].name));
         }
     }
 
@@ -79,60 +146,121 @@ impl AGFJFile {
     /// until a call instruction type is found. Once found, the opcode is then
     /// matched with architecture specific options.
     pub fn detect_architecture(&self) -> Option<String> {
-        let mut call_op: Option<String> = None;
+        let functions: Vec<&AGFJFunc> = self
+            .functions
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|func| &func[0])
+            .collect();
+        detect_functions_architecture(&functions)
+    }
 
-        for func in self.functions.as_ref().unwrap() {
-            for block in &func[0].blocks {
-                for op in &block.ops {
-                    if op.r#type == "call" || op.r#type == "rcall" {
-                        call_op = Some(op.disasm.as_ref().unwrap().clone())
-                    }
-                    if call_op.is_some() {
-                        let opcode = call_op.as_ref().unwrap().split_whitespace().next().unwrap();
-                        if X86_CALL.contains(&opcode) {
-                            return Some("X86".to_string());
-                        } else if ARM_CALL.contains(&opcode) {
-                            return Some("ARM".to_string());
-                        } else if MIPS_CALL.contains(&opcode) {
-                            return Some("MIPS".to_string());
-                        } else {
-                            continue;
-                        }
-                    }
+    /// Streams the functions within the file one at a time rather than loading the
+    /// whole file into memory with `load_and_deserialize`. This keeps the peak memory
+    /// usage bounded by the largest function rather than the size of the file.
+    ///
+    /// Returns the number of functions visited.
+    pub fn for_each_function<F: FnMut(AGFJFunc)>(
+        &self,
+        callback: F,
+    ) -> Result<usize, FileLoadError> {
+        let reader = open_json_reader(&self.filename)?;
+        let mut deserializer = serde_json::Deserializer::from_reader(reader);
+        let num_functions = deserializer.deserialize_seq(AGFJFunctionVisitor { callback })?;
+        deserializer.end()?;
+        Ok(num_functions)
+    }
+
+    /// Streams the functions within the file (see `for_each_function`) alongside the
+    /// architecture of the file.
+    ///
+    /// If no architecture has been provided, it is detected once from the first
+    /// `STREAMING_ARCH_SAMPLE_SIZE` functions, which are held back until then. The
+    /// architecture is `None` if it could not be detected.
+    pub fn for_each_function_with_architecture<F: FnMut(AGFJFunc, Option<&String>)>(
+        &self,
+        mut callback: F,
+    ) -> Result<usize, FileLoadError> {
+        let mut architecture = self.architecture.clone();
+        let mut detected = architecture.is_some();
+        let mut pending: Vec<AGFJFunc> = Vec::new();
+
+        let num_functions = self.for_each_function(|func| {
+            if detected {
+                callback(func, architecture.as_ref());
+                return;
+            }
+            pending.push(func);
+            if pending.len() == STREAMING_ARCH_SAMPLE_SIZE {
+                architecture = detect_functions_architecture(&pending.iter().collect_vec());
+                detected = true;
+                for func in pending.drain(..) {
+                    callback(func, architecture.as_ref());
                 }
             }
+        })?;
+
+        // Fewer functions than the sample size
+        if !detected {
+            architecture = detect_functions_architecture(&pending.iter().collect_vec());
+            for func in pending.drain(..) {
+                callback(func, architecture.as_ref());
+            }
         }
+        Ok(num_functions)
+    }
 
-        call_op
+    /// Logs a clear error if the architecture is unknown (i.e. detection failed
+    /// and no override was provided). Returns true if an architecture is available.
+    fn check_architecture(&self) -> bool {
+        if self.architecture.is_none() {
+            error!(
+                "Unable to detect the architecture of {:?} - Use --architecture to set it manually",
+                self.filename
+            );
+            false
+        } else {
+            true
+        }
     }
 
     /// Executes a generation option based on provided inputs
     /// This acts as the primary public API for creating downstream
     /// data from an AGFJ extracted JSON file
+    #[allow(clippy::too_many_arguments)]
     pub fn execute_data_generation(
         self,
         format_type: FormatMode,
         instruction_type: InstructionMode,
         random_walk: &bool,
+        max_hops: u16,
         pairs: bool,
+        ngram: Option<usize>,
+        block_marker: Option<String>,
+        low_memory: bool,
+        jsonl: bool,
+        sort_output: bool,
     ) {
-        if format_type == FormatMode::SingleInstruction {
+        if let Some(n) = ngram {
+            self.generate_opcode_ngrams(n, format_type == FormatMode::FuncAsString, sort_output);
+        } else if format_type == FormatMode::SingleInstruction {
             if !(*random_walk) {
                 if instruction_type == InstructionMode::Disasm {
-                    self.generate_linear_bb_walk(false);
+                    self.generate_linear_bb_walk(false, jsonl);
                 } else if instruction_type == InstructionMode::ESIL {
-                    self.generate_linear_bb_walk(true);
+                    self.generate_linear_bb_walk(true, jsonl);
                 }
             } else if instruction_type == InstructionMode::Disasm {
-                self.generate_random_bb_walk(false, pairs);
+                self.generate_random_bb_walk(false, max_hops, pairs, jsonl);
             } else if instruction_type == InstructionMode::ESIL {
-                self.generate_random_bb_walk(true, pairs);
+                self.generate_random_bb_walk(true, max_hops, pairs, jsonl);
             }
         } else if format_type == FormatMode::FuncAsString {
             if instruction_type == InstructionMode::Disasm {
-                self.generate_disasm_func_strings();
+                self.generate_disasm_func_strings(block_marker, low_memory, sort_output);
             } else if instruction_type == InstructionMode::ESIL {
-                self.generate_esil_func_strings();
+                self.generate_esil_func_strings(block_marker, low_memory, sort_output);
             }
         }
     }
@@ -148,43 +276,65 @@ impl AGFJFile {
     ///
     /// It is *not* suitable for doing any other sort of tasks such as Next Sentence
     /// Prediction (NSP) as there is not indication of where a basic block starts or ends.
-    pub fn generate_random_bb_walk(mut self, esil: bool, pairs: bool) {
+    ///
+    /// Each walk visits at most `max_hops` basic blocks.
+    ///
+    /// If `jsonl` is set, each walk is written as a JSON object alongside the name of
+    /// the function it was sampled from rather than one instruction per line.
+    pub fn generate_random_bb_walk(mut self, esil: bool, max_hops: u16, pairs: bool, jsonl: bool) {
         let fname_string: PathBuf =
-            get_save_file_path(&self.filename, &self.output_path, None, None, None);
+            get_save_file_path(&self.filename, &self.output_path, None, None, None, None);
+        let extension = if jsonl { "jsonl" } else { "txt" };
         let fname_string = if esil {
-            format!("{}-esil-singles-rwdfs.txt", fname_string.to_string_lossy())
+            format!(
+                "{}-esil-singles-rwdfs.{}",
+                fname_string.to_string_lossy(),
+                extension
+            )
         } else {
-            format!("{}-dis-singles-rwdfs.txt", fname_string.to_string_lossy())
+            format!(
+                "{}-dis-singles-rwdfs.{}",
+                fname_string.to_string_lossy(),
+                extension
+            )
         };
 
         if !Path::new(&fname_string).exists() {
             self.load_and_deserialize()
                 .expect("Unable to load and desearilize JSON");
+            let architecture = self.get_corpus_architecture(jsonl);
+            let norm_config = self.get_norm_config();
+
+            let res: Vec<(String, Vec<Vec<String>>)> = self
+                .functions
+                .unwrap()
+                .par_iter_mut()
+                .filter_map(|func| {
+                    func[0]
+                        .disasm_random_walks(
+                            &self.min_blocks,
+                            &self.max_blocks,
+                            max_hops,
+                            esil,
+                            &norm_config,
+                            pairs,
+                        )
+                        .map(|walks| (func[0].name.clone(), walks))
+                })
+                .collect();
 
-            let (sender, receiver) = channel();
-
-            self.functions.unwrap().par_iter_mut().for_each_with(
-                sender,
-                |s, func: &mut Vec<AGFJFunc>| {
-                    s.send(func[0].disasm_random_walks(
-                        &self.min_blocks,
-                        esil,
-                        self.reg_norm,
-                        pairs,
-                    ))
-                    .unwrap()
-                },
-            );
-
-            let res = receiver.iter();
+            if jsonl {
+                let records = res.into_iter().flat_map(|(name, walks)| {
+                    walks.into_iter().map(move |walk| (name.clone(), walk))
+                });
+                Self::write_jsonl_corpus(&fname_string, records, architecture);
+                return;
+            }
 
             let flattened: Vec<String> = res
                 .into_iter()
+                .flat_map(|(_, walks)| walks)
                 .flatten()
-                .flatten()
-                .flatten()
-                .collect::<Vec<_>>()
-                .into_iter()
                 .collect();
 
             // TODO - Turn this into an info level log
@@ -199,74 +349,137 @@ impl AGFJFile {
 
     /// Generates a single string which contains the ESIL representation of every
     /// instruction within a function
-    pub fn generate_esil_func_strings(mut self) {
+    ///
+    /// If a `block_marker` is provided, it is inserted between each basic block.
+    /// If `sort_output` is set, the functions are written sorted by name, otherwise
+    /// they are written in the order they appear within the input file.
+    pub fn generate_esil_func_strings(
+        mut self,
+        block_marker: Option<String>,
+        low_memory: bool,
+        sort_output: bool,
+    ) {
         let fname_string: PathBuf =
-            get_save_file_path(&self.filename, &self.output_path, None, None, None);
-        let fname_string = format!("{}-efs.json", fname_string.to_string_lossy());
+            get_save_file_path(&self.filename, &self.output_path, None, None, None, None);
+        let fname_string = match block_marker {
+            Some(_) => format!("{}-efs-bb.json", fname_string.to_string_lossy()),
+            None => format!("{}-efs.json", fname_string.to_string_lossy()),
+        };
 
         if !Path::new(&fname_string).exists() {
-            self.load_and_deserialize()
-                .expect("Unable to load and desearilize JSON");
-
-            if self.functions.is_some() {
-                let (sender, receiver) = channel();
-
-                self.functions.unwrap().par_iter_mut().for_each_with(
-                    sender,
-                    |s, func: &mut Vec<AGFJFunc>| {
-                        s.send(func[0].get_esil_function_string(&self.min_blocks, self.reg_norm))
-                            .unwrap()
-                    },
-                );
+            let res: Vec<Option<(String, String)>> = if low_memory {
+                let mut res = Vec::new();
+                self.for_each_function_with_architecture(|func, architecture| {
+                    let norm_config = self
+                        .norm_config
+                        .with_architecture(architecture.map(|arch| arch.as_str()));
+                    res.push(func.get_esil_function_string(
+                        &self.min_blocks,
+                        &self.max_blocks,
+                        &norm_config,
+                        block_marker.as_deref(),
+                    ))
+                })
+                .expect("Unable to stream and desearilize JSON");
+                res
+            } else {
+                self.load_and_deserialize()
+                    .expect("Unable to load and desearilize JSON");
+                let norm_config = self.get_norm_config();
 
-                let res: Vec<Option<(String, String)>> = receiver.iter().collect();
-                if !res.is_empty() {
-                    let fixed: Vec<(String, String)> =
-                        res.into_iter().filter(|x| x.is_some()).flatten().collect();
-                    let map: HashMap<_, _> = fixed.into_iter().collect();
+                self.functions
+                    .unwrap()
+                    .par_iter()
+                    .map(|func| {
+                        func[0].get_esil_function_string(
+                            &self.min_blocks,
+                            &self.max_blocks,
+                            &norm_config,
+                            block_marker.as_deref(),
+                        )
+                    })
+                    .collect()
+            };
 
-                    let json = json!(map);
+            if !res.is_empty() {
+                let fixed: Vec<(String, String)> =
+                    res.into_iter().filter(|x| x.is_some()).flatten().collect();
+                let json = FunctionKeyedJson::new(fixed, sort_output);
 
-                    serde_json::to_writer(
-                        &File::create(fname_string).expect("Failed to create writer"),
-                        &json,
-                    )
-                    .expect("Unable to write JSON");
-                }
+                serde_json::to_writer(
+                    &File::create(fname_string).expect("Failed to create writer"),
+                    &json,
+                )
+                .expect("Unable to write JSON");
             }
         }
     }
 
     /// Generates a single string which contains the every instruction within a function
-    pub fn generate_disasm_func_strings(mut self) {
+    ///
+    /// If a `block_marker` is provided, it is inserted between each basic block.
+    /// If `sort_output` is set, the functions are written sorted by name, otherwise
+    /// they are written in the order they appear within the input file.
+    ///
+    /// If `low_memory` is set, functions are streamed from disk one at a time
+    /// (see `for_each_function`) rather than loading the whole file.
+    pub fn generate_disasm_func_strings(
+        mut self,
+        block_marker: Option<String>,
+        low_memory: bool,
+        sort_output: bool,
+    ) {
         // This needs to be amended so that there is a AGFJFunc function
         // that returns a function as a func string.
         let fname_string: PathBuf =
-            get_save_file_path(&self.filename, &self.output_path, None, None, None);
-        let fname_string = format!("{}-dfs.json", fname_string.to_string_lossy());
+            get_save_file_path(&self.filename, &self.output_path, None, None, None, None);
+        let fname_string = match block_marker {
+            Some(_) => format!("{}-dfs-bb.json", fname_string.to_string_lossy()),
+            None => format!("{}-dfs.json", fname_string.to_string_lossy()),
+        };
 
         if !Path::new(&fname_string).exists() {
-            self.load_and_deserialize()
-                .expect("Unable to load and desearilize JSON");
-
-            if self.functions.is_some() {
-                let (sender, receiver) = channel();
-
+            let res: Vec<Option<(String, String)>> = if low_memory {
+                let mut res = Vec::new();
+                self.for_each_function_with_architecture(|func, architecture| {
+                    let norm_config = self
+                        .norm_config
+                        .with_architecture(architecture.map(|arch| arch.as_str()));
+                    res.push(func.get_disasm_function_string(
+                        &self.min_blocks,
+                        &self.max_blocks,
+                        &norm_config,
+                        block_marker.as_deref(),
+                    ))
+                })
+                .expect("Unable to stream and desearilize JSON");
+                res
+            } else {
+                self.load_and_deserialize()
+                    .expect("Unable to load and desearilize JSON");
+                if self.functions.is_none() {
+                    return;
+                }
+                let norm_config = self.get_norm_config();
                 self.functions
                     .unwrap()
-                    .par_iter_mut()
+                    .par_iter()
                     .progress()
-                    .for_each_with(sender, |s, func: &mut Vec<AGFJFunc>| {
-                        s.send(func[0].get_disasm_function_string(&self.min_blocks, self.reg_norm))
-                            .unwrap()
-                    });
+                    .map(|func| {
+                        func[0].get_disasm_function_string(
+                            &self.min_blocks,
+                            &self.max_blocks,
+                            &norm_config,
+                            block_marker.as_deref(),
+                        )
+                    })
+                    .collect()
+            };
 
-                let res: Vec<Option<(String, String)>> = receiver.iter().collect();
+            if !res.is_empty() {
                 let fixed: Vec<(String, String)> =
                     res.into_iter().filter(|x| x.is_some()).flatten().collect();
-                let map: HashMap<_, _> = fixed.into_iter().collect();
-
-                let json = json!(map);
+                let json = FunctionKeyedJson::new(fixed, sort_output);
 
                 serde_json::to_writer(
                     &File::create(fname_string).expect("Failed to create writer"),
@@ -277,40 +490,114 @@ impl AGFJFile {
         }
     }
 
+    /// Generates opcode n-grams for each of the functions within the AGFJFile
+    ///
+    /// If `func_string` is set, a JSON file mapping each function name to its
+    /// n-grams is generated (sorted by function name if `sort_output` is set),
+    /// otherwise every n-gram is written on a new line.
+    pub fn generate_opcode_ngrams(mut self, n: usize, func_string: bool, sort_output: bool) {
+        let fname_string: PathBuf =
+            get_save_file_path(&self.filename, &self.output_path, None, None, None, None);
+        let fname_string = if func_string {
+            format!("{}-ngram{}-fs.json", fname_string.to_string_lossy(), n)
+        } else {
+            format!("{}-ngram{}-singles.txt", fname_string.to_string_lossy(), n)
+        };
+
+        if !Path::new(&fname_string).exists() {
+            self.load_and_deserialize()
+                .expect("Unable to load and desearilize JSON");
+
+            let res: Vec<(String, Vec<String>)> = self
+                .functions
+                .unwrap()
+                .par_iter_mut()
+                .filter_map(|func| {
+                    let ngrams = func[0].get_opcode_ngrams(n, &self.min_blocks, &self.max_blocks);
+                    ngrams.map(|ngrams| (func[0].name.clone(), ngrams))
+                })
+                .collect();
+
+            if func_string {
+                serde_json::to_writer(
+                    &File::create(fname_string).expect("Failed to create writer"),
+                    &FunctionKeyedJson::new(res, sort_output),
+                )
+                .expect("Unable to write JSON");
+            } else {
+                let write_file = File::create(fname_string).unwrap();
+                let mut writer = BufWriter::new(&write_file);
+
+                for (_, ngrams) in res {
+                    for ngram in ngrams {
+                        writer
+                            .write_all(ngram.as_bytes())
+                            .expect("Unable to write bytes.");
+                        writer.write_all(b"\n").expect("Unable to write bytes.");
+                    }
+                }
+            }
+        }
+    }
+
     /// Generates a file containing every instruction within each of the functions
     /// within the AGFJFile.
     ///
     /// This ignores control flow and simple iterates the JSON objects from the top to
     /// the bottom.
-    pub fn generate_linear_bb_walk(mut self, esil: bool) {
+    ///
+    /// If `jsonl` is set, each function is written as a JSON object containing the
+    /// function name, its instructions and the architecture rather than one
+    /// instruction per line.
+    pub fn generate_linear_bb_walk(mut self, esil: bool, jsonl: bool) {
         let fname_string: PathBuf =
-            get_save_file_path(&self.filename, &self.output_path, None, None, None);
+            get_save_file_path(&self.filename, &self.output_path, None, None, None, None);
+        let extension = if jsonl { "jsonl" } else { "txt" };
         let fname_string = if esil {
-            format!("{}-esil-singles.txt", fname_string.to_string_lossy())
+            format!(
+                "{}-esil-singles.{}",
+                fname_string.to_string_lossy(),
+                extension
+            )
         } else {
-            format!("{}-dis-singles.txt", fname_string.to_string_lossy())
+            format!(
+                "{}-dis-singles.{}",
+                fname_string.to_string_lossy(),
+                extension
+            )
         };
 
         if !Path::new(&fname_string).exists() {
             self.load_and_deserialize()
                 .expect("Unable to load and desearlize JSON");
+            let architecture = self.get_corpus_architecture(jsonl);
+            let norm_config = self.get_norm_config();
+
+            let res: Vec<(String, Vec<String>)> = self
+                .functions
+                .unwrap()
+                .par_iter_mut()
+                .filter_map(|func| {
+                    func[0]
+                        .get_function_instructions(
+                            esil,
+                            &self.min_blocks,
+                            &self.max_blocks,
+                            &norm_config,
+                        )
+                        .map(|instructions| (func[0].name.clone(), instructions))
+                })
+                .collect();
 
-            let (sender, receiver) = channel();
-
-            self.functions.unwrap().par_iter_mut().for_each_with(
-                sender,
-                |s, func: &mut Vec<AGFJFunc>| {
-                    s.send(func[0].get_function_instructions(esil, &self.min_blocks, self.reg_norm))
-                        .unwrap()
-                },
-            );
-
-            let res: Vec<Vec<String>> = receiver.iter().filter(|x| x.is_some()).flatten().collect();
+            if jsonl {
+                Self::write_jsonl_corpus(&fname_string, res, architecture);
+                return;
+            }
 
             let write_file = File::create(fname_string).unwrap();
             let mut writer = BufWriter::new(&write_file);
 
-            for func in res {
+            for (_, func) in res {
                 for bb in func {
                     writer
                         .write_all(bb.as_bytes())
@@ -321,33 +608,167 @@ impl AGFJFile {
         }
     }
 
+    /// The normalisation config including the rules for the architecture of the file
+    ///
+    /// The functions need to be loaded so the architecture can be detected when it
+    /// has not been provided.
+    fn get_norm_config(&self) -> NormalizationConfig {
+        let architecture = self
+            .architecture
+            .clone()
+            .or_else(|| self.detect_architecture());
+        self.norm_config.with_architecture(architecture.as_deref())
+    }
+
+    /// The architecture recorded against each line of a JSONL corpus. Detection is
+    /// skipped when writing plain text as the architecture is not included.
+    fn get_corpus_architecture(&self, jsonl: bool) -> Option<String> {
+        if jsonl {
+            self.architecture
+                .clone()
+                .or_else(|| self.detect_architecture())
+        } else {
+            None
+        }
+    }
+
+    /// Writes a JSONL corpus with one [`NlpCorpusRecord`] per line so each sequence of
+    /// instructions can be traced back to the function it came from
+    fn write_jsonl_corpus<I>(fname_string: &str, records: I, architecture: Option<String>)
+    where
+        I: IntoIterator<Item = (String, Vec<String>)>,
+    {
+        let write_file = File::create(fname_string).expect("Failed to create writer");
+        let mut writer = BufWriter::new(&write_file);
+
+        for (function, instructions) in records {
+            let record = NlpCorpusRecord {
+                function,
+                instructions,
+                arch: architecture.clone(),
+            };
+            serde_json::to_writer(&mut writer, &record).expect("Unable to write JSON line.");
+            writer.write_all(b"\n").expect("Unable to write bytes.");
+        }
+    }
+
     /// Generate Attributed Control Flow Graph (ACFG)'s for each of the functions
     /// within an AGFJFile.
-    pub fn paralell_attributed_cfg_gen(self) {
-        self.functions.unwrap().par_iter().for_each(|func| {
-            func[0].generate_attributed_cfg(
-                &self.filename,
-                &self.min_blocks,
-                &self.output_path,
-                self.feature_type.unwrap(),
-                self.architecture.as_ref().unwrap(),
+    ///
+    /// Numeric block feature vectors are memoized (see `BlockFeatureCache`) with a
+    /// cache per rayon job so that no locking is needed.
+    ///
+    /// Returns the functions skipped due to a CFG node/block count mismatch.
+    pub fn paralell_attributed_cfg_gen(self) -> Vec<CfgMismatch> {
+        if !self.check_architecture() {
+            return Vec::new();
+        }
+        self.functions
+            .unwrap()
+            .par_iter()
+            .map_init(
+                || BlockFeatureCache::new(BLOCK_FEATURE_CACHE_CAPACITY),
+                |cache, func| {
+                    func[0].generate_attributed_cfg(
+                        &self.filename,
+                        &self.min_blocks,
+                        &self.max_blocks,
+                        &self.output_path,
+                        self.feature_type.unwrap(),
+                        self.architecture.as_ref().unwrap(),
+                        &self.graph_format,
+                        self.embed_func_meta,
+                        self.edge_types,
+                        self.relative_addrs,
+                        Some(cache),
+                    )
+                },
             )
-        });
+            .flatten()
+            .collect()
+    }
+
+    /// Generates the same ACFGs as `paralell_attributed_cfg_gen` but streams the
+    /// functions from disk one at a time (see `for_each_function`). This trades
+    /// parallelism for a much lower peak memory usage on very large files.
+    ///
+    /// If no architecture has been provided, it is detected once from the first
+    /// functions in the file (see `for_each_function_with_architecture`).
+    ///
+    /// Returns the number of functions streamed and those skipped due to a CFG
+    /// node/block count mismatch.
+    pub fn streaming_attributed_cfg_gen(
+        &self,
+        strict_validate: bool,
+    ) -> Result<(usize, Vec<CfgMismatch>), FileLoadError> {
+        let mut cache = BlockFeatureCache::new(BLOCK_FEATURE_CACHE_CAPACITY);
+        let mut mismatches = Vec::new();
+        let mut undetected = false;
+
+        let num_functions = self.for_each_function_with_architecture(|func, architecture| {
+            if strict_validate {
+                let violations = func.validate_structure(&self.min_blocks);
+                if !violations.is_empty() {
+                    for reason in violations {
+                        warn!("Skipping {} in {:?} - {}", func.name, self.filename, reason);
+                    }
+                    return;
+                }
+            }
+
+            match architecture {
+                Some(arch) => mismatches
+                    .extend(self.generate_function_attributed_cfg(&func, arch, &mut cache)),
+                None => undetected = true,
+            }
+        })?;
+
+        if undetected {
+            error!(
+                "Unable to detect the architecture of {:?} - Use --architecture to set it manually",
+                self.filename
+            );
+        }
+        Ok((num_functions, mismatches))
+    }
+
+    fn generate_function_attributed_cfg(
+        &self,
+        func: &AGFJFunc,
+        architecture: &String,
+        cache: &mut BlockFeatureCache,
+    ) -> Option<CfgMismatch> {
+        func.generate_attributed_cfg(
+            &self.filename,
+            &self.min_blocks,
+            &self.max_blocks,
+            &self.output_path,
+            self.feature_type.unwrap(),
+            architecture,
+            &self.graph_format,
+            self.embed_func_meta,
+            self.edge_types,
+            self.relative_addrs,
+            Some(cache),
+        )
     }
 
     pub fn tiknib_func_level_feature_gen(self) {
-        let arch = self.detect_architecture();
+        if !self.check_architecture() {
+            return;
+        }
+        let arch = self.architecture.as_ref().unwrap();
 
         let mut func_feature_vectors = Vec::new();
 
-        for func in self.functions.unwrap().iter() {
-            let feature_vec = func[0].generate_tiknib_cfg_global_features(arch.as_ref().unwrap());
+        for func in self.functions.as_ref().unwrap().iter() {
+            let feature_vec = func[0].generate_tiknib_cfg_global_features(arch);
             func_feature_vectors.push(feature_vec);
         }
 
         let json = json!(&func_feature_vectors);
         let fname_string: PathBuf =
-            get_save_file_path(&self.filename, &self.output_path, None, None, None);
+            get_save_file_path(&self.filename, &self.output_path, None, None, None, None);
         let fname_string = format!("{}-tiknib.json", fname_string.to_string_lossy());
         serde_json::to_writer(
             &File::create(fname_string).expect("Failed to create writer"),
@@ -370,6 +791,7 @@ impl AGFJFile {
                 func[0].generate_embedded_cfg(
                     &self.filename,
                     &self.min_blocks,
+                    &self.max_blocks,
                     &self.output_path,
                     self.feature_type.unwrap(),
                     &inference_job,
@@ -379,9 +801,100 @@ impl AGFJFile {
     }
 }
 
+// Visits each function within the top level list of an AGFJ file in turn so that
+// only a single function is ever deserialized at a time
+struct AGFJFunctionVisitor<F> {
+    callback: F,
+}
+
+impl<'de, F: FnMut(AGFJFunc)> Visitor<'de> for AGFJFunctionVisitor<F> {
+    type Value = usize;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list of AGFJ functions")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<usize, A::Error> {
+        let mut num_functions = 0;
+        while let Some(funcs) = seq.next_element::<Vec<AGFJFunc>>()? {
+            for func in funcs {
+                (self.callback)(func);
+                num_functions += 1;
+            }
+        }
+        Ok(num_functions)
+    }
+}
+
+fn detect_functions_architecture(functions: &[&AGFJFunc]) -> Option<String> {
+    let mut call_op: Option<String> = None;
+
+    for func in functions {
+        for block in &func.blocks {
+            for op in &block.ops {
+                if op.r#type == "call" || op.r#type == "rcall" {
+                    call_op = Some(op.disasm.as_ref().unwrap().clone())
+                }
+                if let Some(call_op) = &call_op {
+                    let opcode = call_op.split_whitespace().next().unwrap();
+                    // jal/jalr are shared with MIPS and radare2 can emit "call" so
+                    // RISC-V is picked out by mnemonics no other architecture uses
+                    if RISCV_CALL.contains(&opcode)
+                        && contains_any_opcode(functions, &RISCV_ONLY_OPCODES)
+                    {
+                        return Some("RISCV".to_string());
+                    } else if X86_CALL.contains(&opcode) {
+                        return Some("X86".to_string());
+                    } else if ARM_CALL.contains(&opcode) || AARCH64_CALL.contains(&opcode) {
+                        return Some(detect_arm_variant(functions, func.bits));
+                    } else if MIPS_CALL.contains(&opcode) {
+                        return Some("MIPS".to_string());
+                    } else if RISCV_CALL.contains(&opcode) {
+                        return Some("RISCV".to_string());
+                    } else {
+                        continue;
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Splits ARM into 32-bit ARM and AArch64 using the bits of the binary recorded
+/// at extraction time. CFGs extracted before this was recorded fall back to
+/// looking for AArch64 only mnemonics.
+fn detect_arm_variant(functions: &[&AGFJFunc], bits: Option<u64>) -> String {
+    let is_aarch64 = match bits {
+        Some(bits) => bits == 64,
+        None => contains_any_opcode(functions, &AARCH64_ONLY_OPCODES),
+    };
+    if is_aarch64 {
+        "AARCH64".to_string()
+    } else {
+        "ARM".to_string()
+    }
+}
+
+fn contains_any_opcode(functions: &[&AGFJFunc], opcodes: &[&str]) -> bool {
+    functions.iter().any(|func| {
+        func.blocks.iter().any(|block| {
+            block.ops.iter().any(|op| {
+                op.disasm
+                    .as_ref()
+                    .and_then(|disasm| disasm.split_whitespace().next())
+                    .is_some_and(|opcode| opcodes.contains(&opcode))
+            })
+        })
+    })
+}
+
 #[derive(Debug, Deserialize, Serialize, EnumAsInner)]
 #[serde(untagged)]
 pub enum FunctionMetadataTypes {
+    // Needs to come before AFIJ as serde would otherwise match it and drop the edges
+    AFIJWithEdges(Vec<AFIJFeatureSubsetWithEdges>),
     AFIJ(Vec<AFIJFeatureSubset>),
     AFIJExtended(Vec<AFIJFeatureSubsetExtended>),
     AGFJ(Vec<TikNibFunc>),
@@ -395,11 +908,14 @@ pub struct AGCJFile {
     pub output_path: PathBuf,
     pub function_metadata: Option<FunctionMetadataTypes>,
     pub include_unk: bool,
+    pub weighted_edges: bool,
+    pub self_loops: SelfLoops,
+    pub graph_format: GraphFormat,
 }
 
 impl AGCJFile {
     pub fn load_and_deserialize(&mut self) -> Result<(), FileLoadError> {
-        let data = read_to_string(&self.filename)?;
+        let data = read_json_to_string(&self.filename)?;
 
         #[allow(clippy::expect_fun_call)]
         // Kept in to ensure that the JSON decode error message is printed alongside the filename
@@ -413,12 +929,13 @@ impl AGCJFile {
     pub fn generate_global_call_graphs(&mut self, metadata_type: Option<String>) {
         let call_graph = self.build_global_call_graph();
         debug!("Num Nodes (Default): {}", call_graph.node_count());
-        let cleaned_graph = self.post_process_graph(call_graph);
+        let mut cleaned_graph = self.post_process_graph(call_graph);
         debug!("Num Nodes (Post-Clean): {}", cleaned_graph.node_count());
+        self.self_loops.apply(&mut cleaned_graph);
         self.save_global_call_graph_to_json(cleaned_graph, metadata_type)
     }
 
-    fn build_global_call_graph(&mut self) -> Graph<String, u32> {
+    pub fn build_global_call_graph(&mut self) -> Graph<String, u32> {
         if self.function_call_graphs.is_none() {
             let ret = self.load_and_deserialize();
             if ret.is_err() {
@@ -427,14 +944,17 @@ impl AGCJFile {
         }
 
         let mut graph = Graph::<String, u32>::new();
+        let mut node_indexes: HashMap<String, NodeIndex> = HashMap::new();
 
         for function in self.function_call_graphs.as_ref().unwrap().iter() {
-            let function_index_find = graph.node_indices().find(|i| graph[*i] == function.name);
+            let function_index_find = node_indexes.get(&function.name).copied();
 
             let function_index = if let Some(index) = function_index_find {
                 index
             } else {
-                graph.add_node(function.name.clone())
+                let index = graph.add_node(function.name.clone());
+                node_indexes.insert(function.name.clone(), index);
+                index
             };
 
             debug!(
@@ -447,15 +967,25 @@ impl AGCJFile {
                     if !self.include_unk && import.starts_with("unk.") {
                         debug!("Skipping {}", import);
                         continue;
+                    } else if self.self_loops == SelfLoops::Remove && *import == function.name {
+                        debug!("Skipping self-loop {}", import);
+                        continue;
                     } else {
-                        let import_index_find = graph.node_indices().find(|i| &graph[*i] == import);
-                        let import_index = if let Some(index) = import_index_find {
-                            index
+                        let import_index = if let Some(index) = node_indexes.get(import) {
+                            *index
                         } else {
-                            graph.add_node(import.clone())
+                            let index = graph.add_node(import.clone());
+                            node_indexes.insert(import.clone(), index);
+                            index
                         };
 
-                        graph.update_edge(function_index, import_index, 0);
+                        // Each occurrence of an import is treated as a call site
+                        add_call_edge(
+                            &mut graph,
+                            function_index,
+                            import_index,
+                            self.weighted_edges,
+                        );
                     }
                 }
             }
@@ -521,9 +1051,10 @@ impl AGCJFile {
         let full_output_path = get_save_file_path(
             &self.filename,
             &self.output_path,
-            Some(".json".to_string()),
+            Some(format!(".{}", self.graph_format.extension())),
             Some("gcg".to_string()),
             Some("_cg".to_string()),
+            None,
         );
 
         debug!(
@@ -531,11 +1062,23 @@ impl AGCJFile {
             full_output_path
         );
 
-        serde_json::to_writer(
-            &File::create(full_output_path).expect("Failed to create writer"),
-            &networkx_graph,
-        )
-        .expect("Unable to write JSON");
+        match self.graph_format {
+            GraphFormat::Json | GraphFormat::JsonGz => {
+                write_json_to_file(&full_output_path, &networkx_graph)
+                    .expect("Unable to write JSON")
+            }
+            GraphFormat::Dot => File::create(&full_output_path)
+                .and_then(|mut file| file.write_all(networkx_graph.to_dot().as_bytes()))
+                .expect("Unable to write DOT"),
+            GraphFormat::Pyg => serde_json::to_writer(
+                &File::create(&full_output_path).expect("Failed to create writer"),
+                &networkx_graph.to_pyg_json(),
+            )
+            .expect("Unable to write JSON"),
+            GraphFormat::GraphMl => File::create(&full_output_path)
+                .and_then(|mut file| file.write_all(networkx_graph.to_graphml().as_bytes()))
+                .expect("Unable to write GraphML"),
+        }
     }
 
     // Local Call Graph Helper Functions
@@ -622,7 +1165,7 @@ pub struct AFIJFile {
 
 impl AFIJFile {
     pub fn load_and_deserialize(&mut self) -> Result<(), FileLoadError> {
-        let data = read_to_string(&self.filename)?;
+        let data = read_json_to_string(&self.filename)?;
 
         #[allow(clippy::expect_fun_call)]
         // Kept in to ensure that the JSON decode error message is printed alongside the filename
@@ -632,8 +1175,18 @@ impl AFIJFile {
         Ok(())
     }
 
-    pub fn subset(&mut self, extended: bool) -> FunctionMetadataTypes {
-        if extended {
+    pub fn subset(&mut self, extended: bool, with_edges: bool) -> FunctionMetadataTypes {
+        if with_edges {
+            debug!("Starting to subset functions with edges");
+            let func_info_subsets_with_edges: Vec<AFIJFeatureSubsetWithEdges> = self
+                .function_info
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(AFIJFeatureSubsetWithEdges::from)
+                .collect();
+            FunctionMetadataTypes::AFIJWithEdges(func_info_subsets_with_edges)
+        } else if extended {
             let mut func_info_subsets_extended: Vec<AFIJFeatureSubsetExtended> = Vec::new();
             debug!("Starting to subset functions");
             for function in self.function_info.as_ref().unwrap().iter() {
@@ -651,10 +1204,10 @@ impl AFIJFile {
             FunctionMetadataTypes::AFIJ(func_info_subsets)
         }
     }
-    pub fn subset_and_save(&mut self, extended: bool) {
-        let func_info_subsets = self.subset(extended);
+    pub fn subset_and_save(&mut self, extended: bool, with_edges: bool) {
+        let func_info_subsets = self.subset(extended, with_edges);
         let fname_string: PathBuf =
-            get_save_file_path(&self.filename, &self.output_path, None, None, None);
+            get_save_file_path(&self.filename, &self.output_path, None, None, None, None);
         let filename = format!("{}-finfo-subset.json", fname_string.to_string_lossy());
         serde_json::to_writer(
             &File::create(filename).expect("Failed to create writer"),
@@ -662,6 +1215,26 @@ impl AFIJFile {
         )
         .expect("Unable to write JSON");
     }
+
+    /// Generates complexity features (see [`ComplexityFeatures`]) for each function
+    /// and saves them as a JSON array
+    pub fn complexity_and_save(&self) {
+        let complexity_features: Vec<ComplexityFeatures> = self
+            .function_info
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(ComplexityFeatures::from)
+            .collect();
+        let fname_string: PathBuf =
+            get_save_file_path(&self.filename, &self.output_path, None, None, None, None);
+        let filename = format!("{}-complexity.json", fname_string.to_string_lossy());
+        serde_json::to_writer(
+            &File::create(filename).expect("Failed to create writer"),
+            &complexity_features,
+        )
+        .expect("Unable to write JSON");
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -673,7 +1246,7 @@ pub struct TikNibFuncMetaFile {
 
 impl TikNibFuncMetaFile {
     pub fn load_and_deserialize(&mut self) -> Result<(), FileLoadError> {
-        let data = read_to_string(&self.filename)?;
+        let data = read_json_to_string(&self.filename)?;
 
         #[allow(clippy::expect_fun_call)]
         // Kept in to ensure that the JSON decode error message is printed alongside the filename
@@ -688,9 +1261,67 @@ impl TikNibFuncMetaFile {
     }
 }
 
+/// Merges finfo subset files (see [`AFIJFile::subset_and_save`]) into a single dataset
+///
+/// When `tag_source` is set each record has a `source` key added containing the name
+/// of the binary it was generated from. Files containing a different subset type to
+/// the first file loaded are skipped.
+pub fn merge_finfo_subsets(
+    file_paths: &[String],
+    tag_source: bool,
+) -> Result<Vec<serde_json::Value>, FileLoadError> {
+    let mut merged = Vec::new();
+    let mut subset_type = None;
+
+    for file_path in file_paths {
+        let data = read_json_to_string(file_path)?;
+        let subset: FunctionMetadataTypes = serde_json::from_str(&data)?;
+        let records = match &subset {
+            FunctionMetadataTypes::AFIJWithEdges(records) => serde_json::to_value(records)?,
+            FunctionMetadataTypes::AFIJ(records) => serde_json::to_value(records)?,
+            FunctionMetadataTypes::AFIJExtended(records) => serde_json::to_value(records)?,
+            _ => {
+                warn!("{} is not a finfo subset file. Skipping.", file_path);
+                continue;
+            }
+        };
+
+        let file_subset_type = std::mem::discriminant(&subset);
+        if *subset_type.get_or_insert(file_subset_type) != file_subset_type {
+            warn!(
+                "{} contains a different finfo subset type to previous files. Skipping.",
+                file_path
+            );
+            continue;
+        }
+
+        let source = Path::new(file_path)
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .trim_end_matches(".gz")
+            .trim_end_matches("-finfo-subset.json")
+            .to_string();
+
+        for mut record in records.as_array().cloned().unwrap_or_default() {
+            if tag_source {
+                if let Some(record) = record.as_object_mut() {
+                    record.insert("source".to_string(), json!(source));
+                }
+            }
+            merged.push(record)
+        }
+    }
+    Ok(merged)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::files::AGCJFile;
+    use crate::afij::AFIJFeatureSubset;
+    use crate::agcj::{AGCJFunctionCallGraph, SelfLoops};
+    use crate::files::{merge_finfo_subsets, AGCJFile};
+    use crate::networkx::{CallGraphFuncNameNode, GraphFormat, NetworkxDiGraph};
+    use crate::utils::{get_json_paths_from_dir, write_json_to_file};
     use std::collections::HashSet;
     use std::path::PathBuf;
 
@@ -701,6 +1332,9 @@ mod tests {
             output_path: PathBuf::new(),
             function_metadata: None,
             include_unk: false,
+            weighted_edges: false,
+            self_loops: SelfLoops::Keep,
+            graph_format: GraphFormat::Json,
         };
 
         call_graph_file
@@ -709,6 +1343,40 @@ mod tests {
         call_graph_file
     }
 
+    #[test]
+    fn test_merge_finfo_subsets() {
+        let input_dir =
+            std::env::temp_dir().join(format!("bin2ml-finfo-merge-{}", std::process::id()));
+        std::fs::create_dir_all(&input_dir).unwrap();
+
+        let subset = |name: &str| AFIJFeatureSubset {
+            name: name.to_string(),
+            ninstrs: 10,
+            edges: 2,
+            ..Default::default()
+        };
+        let first = input_dir.join("bin_a-finfo-subset.json");
+        let second = input_dir.join("bin_b-finfo-subset.json");
+        write_json_to_file(&first, &vec![subset("main"), subset("sym.foo")]).unwrap();
+        write_json_to_file(&second, &vec![subset("main")]).unwrap();
+
+        let mut file_paths = get_json_paths_from_dir(&input_dir, Some("-finfo-subset".to_string()));
+        file_paths.sort();
+        assert_eq!(file_paths.len(), 2);
+
+        let merged = merge_finfo_subsets(&file_paths, true).unwrap();
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[0]["source"], "bin_a");
+        assert_eq!(merged[1]["name"], "sym.foo");
+        assert_eq!(merged[2]["source"], "bin_b");
+
+        let merged = merge_finfo_subsets(&file_paths, false).unwrap();
+        assert_eq!(merged.len(), 3);
+        assert!(merged.iter().all(|record| record.get("source").is_none()));
+
+        std::fs::remove_dir_all(input_dir).unwrap();
+    }
+
     #[test]
     fn test_global_call_graph_generation() {
         let mut call_graph_file = return_test_file_oject("test-files/ls_cg.json");
@@ -716,6 +1384,7 @@ mod tests {
         let global_call_graph = call_graph_file.build_global_call_graph();
 
         assert_eq!(global_call_graph.node_count(), 111);
+        assert_eq!(global_call_graph.edge_count(), 170);
 
         let mut node_names = Vec::new();
 
@@ -728,6 +1397,91 @@ mod tests {
         assert_eq!(node_names.len(), unique_node_names.len());
     }
 
+    #[test]
+    fn test_global_call_graph_weighted_edges() {
+        let mut call_graph_file = AGCJFile {
+            filename: PathBuf::new(),
+            function_call_graphs: Some(vec![
+                AGCJFunctionCallGraph {
+                    name: "main".to_string(),
+                    size: 0,
+                    imports: Some(vec![
+                        "sym.imp.printf".to_string(),
+                        "sym.imp.printf".to_string(),
+                        "sym.imp.printf".to_string(),
+                        "sym.imp.exit".to_string(),
+                    ]),
+                },
+                AGCJFunctionCallGraph {
+                    name: "sym.helper".to_string(),
+                    size: 0,
+                    imports: Some(vec!["sym.imp.printf".to_string()]),
+                },
+            ]),
+            output_path: PathBuf::new(),
+            function_metadata: None,
+            include_unk: false,
+            weighted_edges: true,
+            self_loops: SelfLoops::Keep,
+            graph_format: GraphFormat::Json,
+        };
+
+        let global_call_graph = call_graph_file.build_global_call_graph();
+        assert_eq!(global_call_graph.edge_count(), 3);
+
+        let networkx_graph: NetworkxDiGraph<CallGraphFuncNameNode> =
+            NetworkxDiGraph::from(global_call_graph);
+        let main_idx = networkx_graph
+            .nodes
+            .iter()
+            .position(|n| n.func_name == "main")
+            .unwrap();
+        let mut weights = networkx_graph.adjacency[main_idx]
+            .iter()
+            .map(|adj| adj.weight)
+            .collect::<Vec<u32>>();
+        weights.sort();
+        assert_eq!(weights, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_global_call_graph_self_loops() {
+        let build_graph = |self_loops: SelfLoops| {
+            let mut call_graph_file = AGCJFile {
+                filename: PathBuf::new(),
+                function_call_graphs: Some(vec![AGCJFunctionCallGraph {
+                    name: "sym.fib".to_string(),
+                    size: 0,
+                    imports: Some(vec!["sym.fib".to_string(), "sym.imp.printf".to_string()]),
+                }]),
+                output_path: PathBuf::new(),
+                function_metadata: None,
+                include_unk: false,
+                weighted_edges: false,
+                self_loops,
+                graph_format: GraphFormat::Json,
+            };
+            let mut graph = call_graph_file.build_global_call_graph();
+            self_loops.apply(&mut graph);
+            graph
+        };
+
+        let graph = build_graph(SelfLoops::Keep);
+        let fib = graph
+            .node_indices()
+            .find(|i| graph[*i] == "sym.fib")
+            .unwrap();
+        assert!(graph.find_edge(fib, fib).is_some());
+        assert_eq!(graph.edge_count(), 2);
+
+        let graph = build_graph(SelfLoops::Remove);
+        assert!(graph.find_edge(fib, fib).is_none());
+        assert_eq!(graph.edge_count(), 1);
+
+        let graph = build_graph(SelfLoops::Force);
+        assert_eq!(graph.edge_count(), 3);
+    }
+
     #[test]
     fn test_global_graph_with_redudent_nodes() {
         let mut call_graph_file = return_test_file_oject("data-examples/raw/test_bin_cg.json");
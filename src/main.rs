@@ -4,17 +4,17 @@
 
 use clap::{Parser, Subcommand};
 use std::fmt;
+use std::fs::File;
 #[macro_use]
 extern crate log;
 use clap::builder::TypedValueParser;
 use env_logger::Env;
-use indicatif::{ParallelProgressIterator, ProgressIterator};
+use indicatif::ParallelProgressIterator;
 
 use mimalloc::MiMalloc;
 use rayon::iter::ParallelIterator;
 use rayon::prelude::IntoParallelRefIterator;
 use std::path::{Path, PathBuf};
-use std::process::exit;
 use walkdir::WalkDir;
 
 pub mod afij;
@@ -28,6 +28,7 @@ pub mod consts;
 pub mod dedup;
 pub mod errors;
 pub mod extract;
+pub mod features;
 pub mod files;
 #[cfg(feature = "inference")]
 pub mod inference;
@@ -35,31 +36,40 @@ pub mod networkx;
 pub mod normalisation;
 mod pcode;
 pub mod processors;
+pub mod storage;
 pub mod tokeniser;
 pub mod utils;
 mod validate;
 
+use crate::agcj::{CallGraphDiff, SelfLoops};
 use crate::dedup::{CGCorpus, EsilFuncStringCorpus};
-use crate::extract::ExtractionJobType;
-use crate::files::{AFIJFile, AGCJFile, FunctionMetadataTypes, TikNibFuncMetaFile};
-use crate::tokeniser::{train_byte_bpe_tokeniser, TokeniserType};
+use crate::errors::{ExitCode, RunSummary};
+use crate::files::{
+    merge_finfo_subsets, AFIJFile, AGCJFile, FunctionMetadataTypes, TikNibFuncMetaFile,
+};
+use crate::tokeniser::{
+    train_byte_bpe_tokeniser, train_comma_bpe_tokeniser, train_unigram_tokeniser,
+    train_wordpiece_tokeniser, TokeniserTrainingConfig, TokeniserType,
+};
 use crate::utils::get_save_file_path;
 
 use crate::combos::{ComboJob, FinfoTiknibFile};
-use crate::networkx::CallGraphNodeFeatureType;
+use crate::consts::SUPPORTED_ARCHITECTURES;
+use crate::networkx::{CallGraphNodeFeatureType, GraphFormat};
+use crate::normalisation::{NormalizationConfig, NORMALIZATION_PRESETS};
 use crate::pcode::{PCodeFile, PCodeFileTypes};
-use crate::validate::validate_input;
+use crate::validate::{validate_file, validate_input, VALIDATE_FILE_TYPES};
 use bb::{FeatureType, InstructionMode};
 #[cfg(feature = "goblin")]
-use binnfo::goblin_info;
+use binnfo::{goblin_info, write_goblin_info_json};
 use extract::{ExtractionJob, PathType};
 use files::{AGFJFile, FormatMode};
 #[cfg(feature = "inference")]
 use inference::inference;
 #[cfg(feature = "inference")]
 use processors::agfj_graph_embedded_feats;
-use processors::agfj_graph_statistical_features;
-use utils::get_json_paths_from_dir;
+use processors::{agfj_graph_statistical_features, agfj_nlp_data_generation};
+use utils::{build_thread_pool, get_json_paths_from_dir, write_json_to_file};
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
@@ -114,14 +124,79 @@ enum GenerateSubCommands {
         output_path: PathBuf,
 
         /// The type of features to generate per basic block (node)
-        #[arg(short, long, value_name = "FEATURE_TYPE", value_parser = clap::builder::PossibleValuesParser::new(["gemini", "discovre", "dgis", "tiknib", "disasm", "esil", "pcode", "pseudo"])
+        #[arg(short, long, value_name = "FEATURE_TYPE", value_parser = clap::builder::PossibleValuesParser::new(["gemini", "discovre", "dgis", "tiknib", "disasm", "esil", "pcode", "pseudo", "opcodehist", "bytes"])
         .map(|s| s.parse::<String>().unwrap()),)]
         feature_type: Option<String>,
 
-        /// The min number of basic blocks. Any CFG's below this number will be skipped
+        /// The min number of basic blocks. Any CFG's below this number will be skipped.
+        /// Set to 0 to keep every function
         #[arg(long, default_value = "5")]
         min_blocks: Option<u16>,
 
+        /// The max number of basic blocks. Any CFG's above this number will be skipped
+        #[arg(long)]
+        max_blocks: Option<u16>,
+
+        /// Toggle to structurally validate each CFG and skip invalid functions
+        #[arg(long, default_value = "false")]
+        strict_validate: bool,
+
+        /// The output format for generated graphs. DOT output is intended for visual inspection
+        /// and PyG output writes the edge_index and x arrays expected by torch_geometric. String
+        /// feature types (i.e disasm or esil) are written to x as token lists and need embedding.
+        /// GraphML output is intended for tools such as Gephi or Cytoscape
+        #[arg(long, value_name = "FORMAT", default_value = "json", value_parser = clap::builder::PossibleValuesParser::new(["json", "dot", "pyg", "graphml"])
+        .map(|s| s.parse::<String>().unwrap()),)]
+        format: String,
+
+        /// Compress generated graphs. gzip writes .json.gz files (JSON output only)
+        #[arg(long, value_name = "COMPRESS", value_parser = clap::builder::PossibleValuesParser::new(["gzip"])
+        .map(|s| s.parse::<String>().unwrap()),)]
+        compress: Option<String>,
+
+        /// Toggle to embed function level metadata (offset, nargs, nlocals and size)
+        /// as a top-level function_metadata object within each generated CFG. JSON output only
+        #[arg(long, default_value = "false")]
+        embed_func_meta: bool,
+
+        /// Toggle to label each CFG edge with how control leaves the source block
+        /// (jump, fail or switch) as an edge_type attribute. Switch case edges are only
+        /// added to the CFG when this is set. JSON output only
+        #[arg(long, default_value = "false")]
+        edge_types: bool,
+
+        /// Toggle to label each CFG node with the address of its basic block relative to
+        /// the start of the function (i.e +0x20) as a nodeLabels list. JSON and DOT output only
+        #[arg(long, default_value = "false")]
+        relative_addrs: bool,
+
+        /// Force the architecture used for CFG feature generation instead of detecting it
+        #[arg(long, value_parser = clap::builder::PossibleValuesParser::new(SUPPORTED_ARCHITECTURES)
+        .map(|s| s.parse::<String>().unwrap()))]
+        architecture: Option<String>,
+
+        /// Stream functions from disk one at a time instead of loading the whole file.
+        /// Much lower peak memory usage on very large files at the cost of parallelism.
+        /// Only supported for CFGs with statistical or string features. Call graphs and
+        /// PCode or model embedded CFGs always load the whole file
+        #[arg(long, default_value = "false")]
+        low_memory: bool,
+
+        /// Write a JSON summary of functions skipped because their recovered CFG does not
+        /// have a node for every basic block (CFG only)
+        #[arg(long, value_name = "FILE")]
+        report_mismatches: Option<PathBuf>,
+
+        /// Exit with a non-zero exit code if any function is skipped due to a CFG
+        /// node/block count mismatch (CFG only)
+        #[arg(long, default_value = "false")]
+        fail_on_mismatch: bool,
+
+        /// Toggle to rename PCode varnodes into SSA form (i.e RSP#1) before generating
+        /// graphs (PCode only)
+        #[arg(long, default_value = "false")]
+        ssa: bool,
+
         /// The filepath to a HuggingFace tokeniser.json
         #[cfg(feature = "inference")]
         #[arg(short, long, value_name = "TOKENISER_FP")]
@@ -158,6 +233,18 @@ enum GenerateSubCommands {
         #[arg(long, default_value = "false")]
         include_unk: bool,
 
+        /// Weight call graph edges by the number of call sites (For call graphs)
+        #[arg(long, default_value = "false")]
+        weighted_edges: bool,
+
+        /// Remove self-loops (i.e. recursive calls) from call graphs (For call graphs)
+        #[arg(long, default_value = "false", conflicts_with = "add_self_loops")]
+        no_self_loops: bool,
+
+        /// Add a self-loop to every node in call graphs (For call graphs)
+        #[arg(long, default_value = "false")]
+        add_self_loops: bool,
+
         /// Metadata Type (For call graphs)
         #[arg(short, long, value_name = "METADATA_TYPE", value_parser = clap::builder::PossibleValuesParser::new(["finfo", "tiknib", "finfo-tiknib"])
         .map(|s| s.parse::<String>().unwrap()),)]
@@ -174,10 +261,15 @@ enum GenerateSubCommands {
         .map(|s| s.parse::<String>().unwrap()),)]
         instruction_type: String,
 
-        /// The min number of basic blocks. Any CFG's below this number will be skipped
+        /// The min number of basic blocks. Any CFG's below this number will be skipped.
+        /// Set to 0 to keep every function
         #[arg(long, default_value = "5")]
         min_blocks: u16,
 
+        /// The max number of basic blocks. Any CFG's above this number will be skipped
+        #[arg(long)]
+        max_blocks: Option<u16>,
+
         /// The output path for the processed data
         #[arg(short, long, value_name = "OUTPUT_PATH")]
         data_out_path: PathBuf,
@@ -191,18 +283,71 @@ enum GenerateSubCommands {
         #[arg(long, default_value = "false")]
         random_walk: bool,
 
+        /// The max number of basic blocks visited in a single random walk
+        #[arg(long, default_value = "10")]
+        max_hops: u16,
+
         /// Toggle register normalisation
         #[arg(long, default_value = "false")]
         reg_norm: bool,
 
+        /// The normalisation preset to use instead of --reg-norm.
+        /// `none` leaves instructions as is, `regs` only normalises registers and
+        /// `aggressive` also normalises immediates, memory addresses and references
+        #[arg(long, conflicts_with = "reg_norm", value_parser = clap::builder::PossibleValuesParser::new(NORMALIZATION_PRESETS)
+        .map(|s| s.parse::<String>().unwrap()))]
+        norm_preset: Option<String>,
+
+        /// Toggle to replace immediates with tokens based on their magnitude and sign
+        /// (i.e IMM_SMALL, IMM_NEG or IMM_ADDR)
+        #[arg(long, default_value = "false")]
+        bucket_immediates: bool,
+
+        /// Toggle to rename PCode varnodes into SSA form (i.e RSP#1) (PCode only)
+        #[arg(long, default_value = "false")]
+        ssa: bool,
+
         /// Toggle to determine if pairs should be generated
         #[arg(long, default_value = "false")]
         pairs: bool,
 
+        /// Insert a marker token between basic blocks (For funcstring format)
+        #[arg(long, default_value = "false")]
+        block_markers: bool,
+
+        /// The marker token inserted between basic blocks when --block-markers is set
+        #[arg(long, default_value = "[BB]")]
+        block_marker_token: String,
+
+        /// Generate opcode n-grams of size N (Disasm only)
+        #[arg(long, value_name = "N")]
+        ngram: Option<usize>,
+
+        /// Stream functions from disk one at a time instead of loading the whole file.
+        /// Much lower peak memory usage on very large files (ESIL and Disasm funcstring
+        /// only). The single format and n-grams always load the whole file
+        #[arg(long, default_value = "false")]
+        low_memory: bool,
+
+        /// Write one JSON object per line containing the function name, instructions
+        /// and architecture instead of plain text (For single format)
+        #[arg(long, default_value = "false")]
+        jsonl: bool,
+
+        /// Write function keyed output (funcstring and n-gram JSON files) sorted by
+        /// function name so identical inputs produce identical files. When set to
+        /// false, functions are written in the order they appear within the input
+        #[arg(long, default_value = "true", action = clap::ArgAction::Set)]
+        sort_output: bool,
+
         /// Determine the pcode filetype
         #[arg(long, value_parser = clap::builder::PossibleValuesParser::new(["pcode-func", "pcode-bb"])
         .map(|s| s.parse::<String>().unwrap()))]
         pcode_file_format: Option<String>,
+
+        /// The number of threads Rayon can use when processing a directory
+        #[arg(short, long, value_name = "NUM_THREADS", default_value = "2")]
+        num_threads: usize,
     },
     /// Generate metadata/feature subsets from extracted data
     Metadata {
@@ -213,12 +358,19 @@ enum GenerateSubCommands {
         #[arg(short, long, value_name = "OUTPUT_PATH")]
         output_path: PathBuf,
         /// Data Source Type
-        #[arg(short, long, value_parser = clap::builder::PossibleValuesParser::new(["finfo", "tiknib"])
+        #[arg(short, long, value_parser = clap::builder::PossibleValuesParser::new(["finfo", "tiknib", "complexity"])
             .map(|s| s.parse::<String>().unwrap()))]
         data_source_type: String,
         /// Toggle for extended version of finfo
         #[arg(short, long)]
         extended: bool,
+        /// Include the in/out degree and call edges of each function in the finfo subset
+        #[arg(long, default_value = "false", conflicts_with = "extended")]
+        with_edges: bool,
+        /// Force the architecture used for tiknib feature generation instead of detecting it
+        #[arg(long, value_parser = clap::builder::PossibleValuesParser::new(SUPPORTED_ARCHITECTURES)
+        .map(|s| s.parse::<String>().unwrap()))]
+        architecture: Option<String>,
     },
     /// Generate tokenisers from extracted data
     Tokeniser {
@@ -235,8 +387,16 @@ enum GenerateSubCommands {
         /// The path to the text file containing the corpus to process
         #[arg(short, long, value_name = "VOCAB_SIZE", default_value = "10000")]
         vocab_size: usize,
+        /// The min frequency of a pair/token for it to be merged/kept (Unused for unigram)
+        #[arg(long, default_value = "0")]
+        min_frequency: u32,
+        /// Comma separated special tokens (i.e [CLS],[SEP],[PAD],[MASK],[UNK]). These are assigned
+        /// the lowest ids in the order provided. Defaults to <s>,<pad>,</s>,<unk>,<mask>
+        #[arg(long, value_delimiter = ',')]
+        special_tokens: Option<Vec<String>>,
         /// The type of tokeniser to create
-        #[arg(short, long, value_name = "BPE or Byte-BPE", default_value = "BPE")]
+        #[arg(short, long, value_name = "TOKENISER_TYPE", default_value = "bpe", value_parser = clap::builder::PossibleValuesParser::new(["bpe", "byte-bpe", "wordpiece", "unigram"])
+        .map(|s| s.parse::<String>().unwrap()))]
         tokeniser_type: String,
     },
     /// Generate combinations of extracted data - Primaryily metadata objects
@@ -247,13 +407,50 @@ enum GenerateSubCommands {
         #[arg(short, long, value_name = "OUTPUT_PATH")]
         output_path: PathBuf,
         /// Combo Type
-        #[arg(short, long, value_parser = clap::builder::PossibleValuesParser::new(["finfo+tiknib", "finfoe+tiknib"])
+        #[arg(short, long, value_parser = clap::builder::PossibleValuesParser::new(["finfo+tiknib", "finfoe+tiknib", "finfo+tiknib+strings"])
         .map(|s| s.parse::<String>().unwrap()))]
         combo_type: String,
+        /// How functions are matched by name across the combined files. Note: Functions used
+        /// to be paired by position; the default is now an inner join on the function name,
+        /// which drops functions not present in both files
+        #[arg(long, default_value = "inner", value_parser = clap::builder::PossibleValuesParser::new(["left", "inner", "outer"])
+        .map(|s| s.parse::<String>().unwrap()))]
+        join: String,
         /// Number of threads
         #[arg(short, long, default_value = "2")]
         num_threads: usize,
     },
+    /// Merge a directory of finfo subset files into a single dataset
+    Merge {
+        /// The path to a directory containing -finfo-subset.json files
+        #[arg(short, long, value_name = "INPUT_PATH")]
+        input_path: PathBuf,
+        /// The path for the merged JSON output
+        #[arg(short, long, value_name = "OUTPUT_PATH")]
+        output_path: PathBuf,
+        /// Toggle to tag each record with the name of the binary it came from
+        #[arg(long, default_value = "false")]
+        tag_source: bool,
+    },
+    /// Diff the global call graphs of two versions of a binary (i.e for patch analysis)
+    GraphDiff {
+        /// The path to the _cg.json file of the original binary
+        #[arg(long, value_name = "OLD_PATH")]
+        old: PathBuf,
+        /// The path to the _cg.json file of the new binary
+        #[arg(long, value_name = "NEW_PATH")]
+        new: PathBuf,
+        /// The path for the generated JSON diff
+        #[arg(short, long, value_name = "OUTPUT_PATH")]
+        output_path: PathBuf,
+        /// Toggle to ignore auto-named (unk. and fcn.) functions as their names are
+        /// derived from addresses and rarely match across versions
+        #[arg(long, default_value = "false")]
+        ignore_auto_named: bool,
+        /// Toggle to include unknown functions when building the call graphs
+        #[arg(long, default_value = "false")]
+        include_unk: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -264,6 +461,14 @@ enum Commands {
         /// The path to the target binary
         #[arg(short, long, value_name = "FILENAME")]
         path: Option<PathBuf>,
+
+        /// Toggle to output the info as JSON
+        #[arg(long, default_value = "false")]
+        json: bool,
+
+        /// The path to write the JSON output to. Printed to stdout if not provided
+        #[arg(short, long, value_name = "OUTPUT_PATH")]
+        output_path: Option<PathBuf>,
     },
     /// Generate processed data from extracted raw data
     Generate {
@@ -272,7 +477,7 @@ enum Commands {
     },
     /// Extract raw data from input binaries
     Extract {
-        /// The path to the dir or binary to be processed
+        /// The path to the dir, binary or tar archive (.tar, .tar.gz or .tgz) to be processed
         #[arg(short, long, value_name = "DIR")]
         fpath: PathBuf,
 
@@ -281,7 +486,7 @@ enum Commands {
         output_dir: PathBuf,
 
         /// The extraction mode
-        #[arg(short, long, value_name = "EXTRACT_MODE", value_parser = clap::builder::PossibleValuesParser::new(["finfo", "reg", "cfg", "func-xrefs","cg", "decomp", "pcode-func", "pcode-bb", "localvar-xrefs", "strings", "bytes"])
+        #[arg(short, long, value_name = "EXTRACT_MODE", value_parser = clap::builder::PossibleValuesParser::new(["finfo", "reg", "cfg", "func-xrefs", "data-xrefs", "cg", "decomp", "pcode-func", "pcode-bb", "localvar-xrefs", "defuse", "strings", "strings-xrefs", "string-stats", "sections", "header", "bytes", "imports", "exports", "relocs", "entrypoints", "byte-entropy", "byte-histogram", "cfg-enriched", "macho"])
         .map(|s| s.parse::<String>().unwrap()),)]
         mode: String,
 
@@ -292,14 +497,157 @@ enum Commands {
         #[arg(long, default_value = "false")]
         debug: bool,
 
-        #[arg(long, default_value = "false")]
-        extended_analysis: bool,
+        /// The radare2 analysis run before extraction. aaa and aaaa recover more
+        /// functions at the cost of (much) longer analysis times
+        #[arg(long, value_name = "LEVEL", default_value = "aa", value_parser = clap::builder::PossibleValuesParser::new(["aa", "aaa", "aaaa"])
+        .map(|s| s.parse::<String>().unwrap()))]
+        analysis_level: String,
 
         #[arg(long, default_value = "true")]
         use_curl_pdb: bool,
 
         #[arg(long, default_value = "false")]
         with_annotations: bool,
+
+        /// The output format - ndjson writes one JSON object per line (per function)
+        #[arg(long, value_name = "OUTPUT_FORMAT", default_value = "json", value_parser = clap::builder::PossibleValuesParser::new(["json", "ndjson"])
+        .map(|s| s.parse::<String>().unwrap()),)]
+        output_format: String,
+
+        /// Timeout (in seconds) for each per-function r2 command. Functions which time out are skipped.
+        /// Only honoured by the decomp, pcode-func and pcode-bb modes. Disabled by default.
+        #[arg(long, value_name = "SECONDS")]
+        func_timeout_secs: Option<u64>,
+
+        /// The path to (or name of) the radare2 executable
+        #[arg(long, value_name = "R2_PATH", default_value = "radare2")]
+        r2_path: PathBuf,
+
+        /// The filename used for each function in bytes mode - symbol, address or a
+        /// template containing {symbol} and/or {address} (i.e "{address}.{symbol}")
+        #[arg(long, value_name = "FUNC_FILENAME", default_value = "symbol")]
+        func_filename: String,
+
+        /// The number of r2pipe instances used to process the functions within a single binary.
+        /// Each additional instance re-runs analysis so this is most useful for binaries with
+        /// a large number of functions. Supported by reg, func-xrefs, data-xrefs, decomp,
+        /// pcode-func, pcode-bb, localvar-xrefs and bytes
+        #[arg(long, value_name = "INTRA_FILE_THREADS", default_value = "1")]
+        intra_file_threads: usize,
+
+        /// Toggle to show per-function progress (with an ETA). Only applies when
+        /// extracting a single file
+        #[arg(long, default_value = "false")]
+        progress: bool,
+
+        /// A comma separated list of function names and/or 0x prefixed addresses to extract.
+        /// Function names support glob style wildcards (i.e "main,sym.*crypto*,0x1149").
+        /// Applies to all per-function modes as well as finfo and the cfg, cfg-enriched
+        /// and cg graphs
+        #[arg(long, value_name = "FUNCTION_FILTER")]
+        function_filter: Option<String>,
+
+        /// The type of features to generate per basic block when using the cfg-enriched mode.
+        /// cfg-enriched writes attributed CFGs (with function metadata) directly without
+        /// keeping the raw CFG JSON, so extraction must be re-run to use another feature type
+        #[arg(long, value_name = "FEATURE_TYPE", value_parser = clap::builder::PossibleValuesParser::new(["gemini", "discovre", "dgis", "tiknib", "disasm", "esil", "pseudo", "opcodehist", "bytes"])
+        .map(|s| s.parse::<String>().unwrap()),)]
+        feature_type: Option<String>,
+
+        /// The min number of basic blocks for a function to be included when using the
+        /// cfg-enriched mode. Matches the default used by generate graphs
+        #[arg(long, default_value = "5")]
+        min_blocks: u16,
+
+        /// The path to a SQLite database to write extracted function metadata into instead of
+        /// JSON files. The database is created if it does not exist. Only supported by finfo
+        #[arg(long, value_name = "SQLITE_PATH")]
+        sqlite: Option<PathBuf>,
+
+        /// Toggle to re-extract binaries even if the output already exists
+        #[arg(long, default_value = "false")]
+        force: bool,
+
+        /// Toggle to write a <binary>_manifest.json summarising each extraction. The
+        /// manifest includes the SHA-256 checksum of each output file
+        #[arg(long, default_value = "false")]
+        manifest: bool,
+
+        /// Toggle to write a <binary>.sha256 file containing the SHA-256 checksum of each
+        /// output file. Not needed with --manifest as the checksums are included there
+        #[arg(long, default_value = "false")]
+        checksums: bool,
+
+        /// Toggle to print the files, job type and output paths of the extraction
+        /// job and exit without running radare2
+        #[arg(long, default_value = "false")]
+        dry_run: bool,
+
+        /// Toggle to write the output of each mode into its own subdirectory of the
+        /// output directory (i.e output_dir/reg/, output_dir/cfg/)
+        #[arg(long, default_value = "false")]
+        nested_output: bool,
+
+        /// Toggle to re-run analysis with 'aaa' and retry when no functions are found.
+        /// Has no effect when --analysis-level is aaa or aaaa
+        #[arg(long, default_value = "false")]
+        auto_reanalyze: bool,
+
+        /// Toggle to only extract named functions defined by the binary. Functions
+        /// auto-named by radare2 (i.e fcn.*) and imports are skipped. Applies to the
+        /// per-function modes as well as the cfg, cfg-enriched and cg graphs
+        #[arg(long, default_value = "false")]
+        public_only: bool,
+
+        /// Toggle to key per-function outputs by name and offset (i.e main@0x1139) so
+        /// functions sharing a name do not overwrite each other. This includes the bytes
+        /// filenames and the function names within pcode outputs
+        #[arg(long, default_value = "false")]
+        key_by_offset: bool,
+
+        /// Toggle r2's pseudo-syntax (asm.pseudo) in extracted disassembly
+        #[arg(long, default_value = "true", action = clap::ArgAction::Set)]
+        asm_pseudo: bool,
+
+        /// Toggle r2's comments (asm.comments) in extracted disassembly
+        #[arg(long, default_value = "true", action = clap::ArgAction::Set)]
+        asm_comments: bool,
+
+        /// Toggle to demangle Rust and C++ function names. The original name is kept
+        /// where the output format allows (i.e mangledName in finfo)
+        #[arg(long, default_value = "false")]
+        demangle: bool,
+
+        /// The max reported size of a function in bytes mode. Larger functions
+        /// (usually the result of mis-analysis) are skipped
+        #[arg(long, value_name = "BYTES", default_value = "1048576")]
+        max_func_bytes: u64,
+
+        /// Skip functions with fewer than this many instructions (i.e tiny stubs) before
+        /// any per-function modes are run. 0 keeps every function. Note: The cfg,
+        /// cfg-enriched and cg modes are generated for every function at once and ignore this
+        #[arg(long, value_name = "N", default_value = "0")]
+        min_instructions: u64,
+
+        /// The output format for bytes mode - a .bin file per function (bin) or a single
+        /// JSON file per binary mapping each function to its hex encoded bytes (archive)
+        #[arg(long, value_name = "BYTES_FORMAT", default_value = "bin", value_parser = clap::builder::PossibleValuesParser::new(["bin", "archive"])
+        .map(|s| s.parse::<String>().unwrap()))]
+        bytes_format: String,
+
+        /// A naming scheme for output files relative to the output directory. Supports the
+        /// {binary}, {mode}, {arch} and {func} placeholders (i.e "{arch}/{binary}-{mode}").
+        /// {arch} is inferred from the input path and {func} is only set for per-function
+        /// outputs. Must contain {binary} when processing a directory and {func} for
+        /// bytes extraction in the bin format
+        #[arg(long, value_name = "TEMPLATE")]
+        output_template: Option<String>,
+
+        /// The path to a file of r2 commands (one per line) or an r2 project directory.
+        /// Applied after analysis and before extraction (i.e to apply signatures, types
+        /// or custom function definitions)
+        #[arg(long, value_name = "R2_SCRIPT")]
+        r2_script: Option<PathBuf>,
     },
     /// Generate single embeddings on the fly
     ///
@@ -328,6 +676,16 @@ enum Commands {
         #[command(subcommand)]
         subcommands: DedupSubCommands,
     },
+    /// Check a file is a valid bin2ml extracted JSON file before generating data from it
+    Validate {
+        /// The path to the extracted JSON file
+        #[arg(short, long, value_name = "PATH")]
+        path: PathBuf,
+        /// The type of data the file is expected to contain
+        #[arg(short = 't', long, value_parser = clap::builder::PossibleValuesParser::new(VALIDATE_FILE_TYPES)
+        .map(|s| s.parse::<String>().unwrap()))]
+        file_type: String,
+    },
 }
 
 #[derive(Subcommand, Clone)]
@@ -359,6 +717,43 @@ enum DedupSubCommands {
         /// Toggle to remove inplace (i.e delete duplicates)
         #[arg(long)]
         inplace: bool,
+
+        /// Number of graphs in each chunk when a binary subset is chunked during inplace dedup
+        #[arg(
+            long,
+            value_name = "NUM_GRAPHS",
+            default_value = "2000000",
+            requires = "inplace"
+        )]
+        chunk_size: usize,
+
+        /// Number of graphs a binary subset must exceed before it is chunked during inplace
+        /// dedup
+        #[arg(
+            long,
+            value_name = "NUM_GRAPHS",
+            default_value = "2500000",
+            requires = "inplace"
+        )]
+        chunk_threshold: usize,
+
+        /// Toggle to dedup using a hash of the graph structure and node features only,
+        /// ignoring node ids and ordering
+        #[arg(long, default_value = "false")]
+        structural: bool,
+
+        /// Toggle to preserve the full subdirectory structure of the input path within the
+        /// output path instead of keeping only the binary directory and filename
+        #[arg(long, default_value = "false", conflicts_with = "inplace")]
+        preserve_tree: bool,
+
+        /// Toggle to print statistics of number of graphs before and after dedup
+        #[arg(long, default_value = "false", conflicts_with = "inplace")]
+        print_stats: bool,
+
+        /// Toggle for just calculating stats without creating any files
+        #[arg(long, default_value = "false", conflicts_with = "inplace")]
+        just_stats: bool,
     },
     /// De-dup generate ESIL strings
     Esil {
@@ -385,417 +780,545 @@ enum DedupSubCommands {
         /// Toggle whether to dedup based on hashing only the value (and ignoring the key)
         #[arg(short, long, default_value = "false")]
         just_hash_value: bool,
+
+        /// Toggle to dedup across the entire corpus instead of per binary name. Produces a
+        /// single global-dedup.json
+        #[arg(long, default_value = "false")]
+        global: bool,
+
+        /// Toggle to cluster near-duplicate functions across the corpus using MinHash
+        /// instead of exact hashing. Produces a single minhash-clusters.json
+        #[arg(long, default_value = "false", conflicts_with = "global")]
+        minhash: bool,
+
+        /// Toggle to compare the data of entries which share a hash and keep both (with a
+        /// warning) if they differ, guarding against 64-bit hash collisions
+        #[arg(long, default_value = "false", conflicts_with = "minhash")]
+        verify_collisions: bool,
+
+        /// Number of hash functions used for each MinHash signature. Must be at least 1
+        #[arg(long, default_value = "128", requires = "minhash", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        num_hashes: usize,
+
+        /// Estimated Jaccard similarity required for two functions to share a MinHash cluster
+        #[arg(long, default_value = "0.8", requires = "minhash")]
+        threshold: f32,
     },
 }
 
-fn main() {
-    let env = Env::default()
-        .filter_or("LOG_LEVEL", "warn")
-        .write_style_or("LOG_STYLE", "always");
+/// Generates graphs for the graphs subcommand
+///
+/// Called from within the thread pool built for the job so any parallel processing
+/// uses the requested number of threads.
+fn generate_graphs(subcommands: &GenerateSubCommands) {
+    match subcommands {
+        GenerateSubCommands::Graphs {
+            path,
+            data_type: graph_type,
+            min_blocks,
+            max_blocks,
+            output_path,
+            feature_type,
+            #[cfg(feature = "inference")]
+            tokeniser_fp,
+            #[cfg(feature = "inference")]
+            model_fp,
+            #[cfg(feature = "inference")]
+            mean_pool,
+            #[cfg(feature = "inference")]
+            embed_dim,
+            with_features,
+            metadata_path,
+            include_unk,
+            weighted_edges,
+            no_self_loops,
+            add_self_loops,
+            num_threads: _,
+            metadata_type,
+            strict_validate,
+            format,
+            compress,
+            embed_func_meta,
+            edge_types,
+            relative_addrs,
+            architecture,
+            low_memory,
+            report_mismatches,
+            fail_on_mismatch,
+            ssa,
+        } => {
+            let self_loops = SelfLoops::new(*no_self_loops, *add_self_loops);
+            let graph_data_type = match graph_type.as_str() {
+                "cfg" => DataType::Cfg,
+                "cg" => DataType::Cg,
+                "onehopcg" => DataType::OneHopCg,
+                "cgcallers" => DataType::CgWithCallers,
+                "onehopcgcallers" => DataType::OneHopCgWithcallers,
+                "globalcg" => DataType::GlobalCg,
+                _ => DataType::Invalid,
+            };
+            let graph_format = GraphFormat::new(format).with_compression(compress.as_deref());
+
+            if *ssa && feature_type.as_deref() != Some("pcode") {
+                warn!("--ssa is only supported for the pcode feature type. Ignoring")
+            }
 
-    env_logger::init_from_env(env);
-    let cli = Cli::parse();
-    match &cli.command {
-        #[cfg(feature = "goblin")]
-        Commands::Info { path } => {
-            info!("starting Information Gathering");
-            if let Some(fpath) = &path {
-                goblin_info(fpath).expect("Failed to get info!");
+            let low_memory_supported = graph_data_type == DataType::Cfg
+                && feature_type
+                    .as_deref()
+                    .map(FeatureType::new)
+                    .is_some_and(|feature_type| {
+                        !matches!(
+                            feature_type,
+                            FeatureType::Pcode
+                                | FeatureType::ModelEmbedded
+                                | FeatureType::Encoded
+                                | FeatureType::Invalid
+                        )
+                    });
+            if *low_memory && !low_memory_supported {
+                warn!("--low-memory is only supported for CFGs with statistical or string features. Ignoring")
             }
-        }
-        Commands::Generate { subcommands } => match subcommands {
-            GenerateSubCommands::Graphs {
-                path,
-                data_type: graph_type,
-                min_blocks,
-                output_path,
-                feature_type,
-                #[cfg(feature = "inference")]
-                tokeniser_fp,
-                #[cfg(feature = "inference")]
-                model_fp,
-                #[cfg(feature = "inference")]
-                mean_pool,
-                #[cfg(feature = "inference")]
-                embed_dim,
-                with_features,
-                metadata_path,
-                include_unk,
-                num_threads,
-                metadata_type,
-            } => {
-                let graph_data_type = match graph_type.as_str() {
-                    "cfg" => DataType::Cfg,
-                    "cg" => DataType::Cg,
-                    "onehopcg" => DataType::OneHopCg,
-                    "cgcallers" => DataType::CgWithCallers,
-                    "onehopcgcallers" => DataType::OneHopCgWithcallers,
-                    "globalcg" => DataType::GlobalCg,
-                    _ => DataType::Invalid,
-                };
 
-                rayon::ThreadPoolBuilder::new()
-                    .num_threads(*num_threads)
-                    .build_global()
-                    .unwrap();
+            if graph_data_type == DataType::Cfg && *with_features {
+                warn!(
+                    "The 'with_features' toggle is set but is not support for CFG \
+                    generation. Will ignore."
+                )
+            };
 
-                if graph_data_type == DataType::Cfg && *with_features {
-                    warn!("The 'with_features' toggle is set but is not support for CFG generation. Will ignore.")
-                };
+            if !path.exists() {
+                error!("{:?} does not exist!", path);
+                ExitCode::InputNotFound.exit()
+            }
+            info!("Chosen Graph Type: {}", graph_data_type);
+            if graph_data_type == DataType::Cfg {
+                if feature_type.is_some() {
+                    let feature_vec_type = FeatureType::new(feature_type.as_ref().unwrap());
+
+                    if feature_vec_type == FeatureType::Invalid {
+                        warn!("Invalid feature type: {}", feature_type.as_ref().unwrap());
+                        ExitCode::BadArgs.exit()
+                    } else if feature_vec_type == FeatureType::Gemini
+                        || feature_vec_type == FeatureType::DiscovRE
+                        || feature_vec_type == FeatureType::DGIS
+                        || feature_vec_type == FeatureType::Tiknib
+                        || feature_vec_type == FeatureType::Disasm
+                        || feature_vec_type == FeatureType::Esil
+                        || feature_vec_type == FeatureType::Pseudo
+                        || feature_vec_type == FeatureType::OpcodeHistogram
+                        || feature_vec_type == FeatureType::Bytes
+                    {
+                        info!(
+                            "Creating graphs with {:?} feature vectors.",
+                            feature_vec_type
+                        );
 
-                if !path.exists() {
-                    error!("{:?} does not exist!", path);
-                    exit(1)
-                }
-                info!("Chosen Graph Type: {}", graph_data_type);
-                if graph_data_type == DataType::Cfg {
-                    if feature_type.is_some() {
-                        let feature_vec_type = match feature_type.as_ref().unwrap().as_str() {
-                            "gemini" => FeatureType::Gemini,
-                            "discovre" => FeatureType::DiscovRE,
-                            "dgis" => FeatureType::DGIS,
-                            "encode" => FeatureType::Encoded,
-                            "tiknib" => FeatureType::Tiknib,
-                            "disasm" => FeatureType::Disasm,
-                            "esil" => FeatureType::Esil,
-                            #[cfg(feature = "inference")]
-                            "embed" => FeatureType::ModelEmbedded,
-                            "pcode" => FeatureType::Pcode,
-                            "pseudo" => FeatureType::Pseudo,
-                            _ => FeatureType::Invalid,
-                        };
+                        let mut mismatches = Vec::new();
+                        if Path::new(path).is_file() {
+                            validate_input(path, "cfg");
+                            info!("Single file found");
+                            mismatches = agfj_graph_statistical_features(
+                                path,
+                                &min_blocks.unwrap(),
+                                max_blocks,
+                                output_path,
+                                feature_vec_type,
+                                *strict_validate,
+                                graph_format,
+                                *embed_func_meta,
+                                *edge_types,
+                                *relative_addrs,
+                                architecture,
+                                *low_memory,
+                            )
+                        } else {
+                            info!("Multiple files found. Will parallel process.");
+                            for file in WalkDir::new(path).into_iter().filter_map(|file| file.ok())
+                            {
+                                if file.path().to_string_lossy().ends_with(".json") {
+                                    validate_input(file.path(), "cfg");
+                                    mismatches.extend(agfj_graph_statistical_features(
+                                        file.path(),
+                                        &min_blocks.unwrap(),
+                                        max_blocks,
+                                        output_path,
+                                        feature_vec_type,
+                                        *strict_validate,
+                                        graph_format,
+                                        *embed_func_meta,
+                                        *edge_types,
+                                        *relative_addrs,
+                                        architecture,
+                                        *low_memory,
+                                    ))
+                                }
+                            }
+                        }
 
-                        if feature_vec_type == FeatureType::Invalid {
-                            warn!("Invalid feature type: {}", feature_type.as_ref().unwrap());
-                            exit(1)
-                        } else if feature_vec_type == FeatureType::Gemini
-                            || feature_vec_type == FeatureType::DiscovRE
-                            || feature_vec_type == FeatureType::DGIS
-                            || feature_vec_type == FeatureType::Tiknib
-                            || feature_vec_type == FeatureType::Disasm
-                            || feature_vec_type == FeatureType::Esil
-                            || feature_vec_type == FeatureType::Pseudo
-                        {
-                            info!(
-                                "Creating graphs with {:?} feature vectors.",
-                                feature_vec_type
+                        if !mismatches.is_empty() {
+                            warn!(
+                                "Skipped {} function(s) due to a CFG node/block count mismatch",
+                                mismatches.len()
                             );
-
-                            if Path::new(path).is_file() {
-                                validate_input(path, "cfg");
-                                info!("Single file found");
-                                agfj_graph_statistical_features(
+                        }
+                        if let Some(report_path) = report_mismatches {
+                            write_json_to_file(report_path, &mismatches)
+                                .expect("Unable to write mismatch report");
+                            info!("Mismatch report written to {:?}", report_path);
+                        }
+                        if *fail_on_mismatch && !mismatches.is_empty() {
+                            error!("Failing due to CFG node/block count mismatches");
+                            ExitCode::Failure.exit()
+                        }
+                    } else if feature_vec_type == FeatureType::Encoded {
+                        todo!("Need to implement Encoded FeatureTypes!")
+                    } else if cfg!(inference) {
+                        #[cfg(feature = "inference")]
+                        if feature_vec_type == FeatureType::ModelEmbedded {
+                            if tokeniser_fp.is_none() || model_fp.is_none() {
+                                error!("Both --tokeniser-fp and --model-fp are needed for embedded features");
+                                ExitCode::BadArgs.exit()
+                            } else {
+                                agfj_graph_embedded_feats(
                                     path,
                                     &min_blocks.unwrap(),
+                                    max_blocks,
                                     output_path,
                                     feature_vec_type,
-                                )
-                            } else {
-                                info!("Multiple files found. Will parallel process.");
-                                for file in
-                                    WalkDir::new(path).into_iter().filter_map(|file| file.ok())
-                                {
-                                    if file.path().to_string_lossy().ends_with(".json") {
-                                        validate_input(file.path(), "cfg");
-                                        agfj_graph_statistical_features(
-                                            file.path(),
-                                            &min_blocks.unwrap(),
-                                            output_path,
-                                            feature_vec_type,
-                                        )
-                                    }
-                                }
+                                    tokeniser_fp,
+                                    model_fp,
+                                    mean_pool,
+                                    embed_dim,
+                                );
                             }
-                        } else if feature_vec_type == FeatureType::Encoded {
-                            todo!("Need to implement Encoded FeatureTypes!")
-                        } else if cfg!(inference) {
-                            #[cfg(feature = "inference")]
-                            if feature_vec_type == FeatureType::ModelEmbedded {
-                                if tokeniser_fp.is_none() || model_fp.is_none() {
-                                    println!("Both Tokenizer and Model file paths are needed");
-                                    exit(100)
+                        }
+                    } else if feature_vec_type == FeatureType::Pcode {
+                        if Path::new(path).is_file() {
+                            validate_input(path, "cfg");
+                            info!("Single file found");
+                            let mut file = PCodeFile {
+                                filename: path.to_owned(),
+                                pcode_obj: None,
+                                output_path: output_path.to_owned(),
+                                min_blocks: *min_blocks,
+                                instruction_pairs: false,
+                                format_type: FormatMode::SingleInstruction,
+                                pcode_file_type: PCodeFileTypes::PCodeJsonFile,
+                                reg_norm: false,
+                                ssa: *ssa,
+                                sort_output: true,
+                            };
+                            let file_ret = file.load_and_deserialize().is_ok();
+                            if file_ret {
+                                let cfg_gen_ret = file
+                                    .pcode_json_with_bb_info_generate_cfg(&graph_format)
+                                    .is_ok();
+                                if cfg_gen_ret {
+                                    info!("Successfully generated CFG's with PCode features")
                                 } else {
-                                    agfj_graph_embedded_feats(
-                                        path,
-                                        &min_blocks.unwrap(),
-                                        output_path,
-                                        feature_vec_type,
-                                        tokeniser_fp,
-                                        model_fp,
-                                        mean_pool,
-                                        embed_dim,
-                                    );
+                                    error!("Failed to generate CFG's with PCode features")
                                 }
                             }
-                        } else if feature_vec_type == FeatureType::Pcode {
-                            if Path::new(path).is_file() {
-                                validate_input(path, "cfg");
-                                info!("Single file found");
-                                let mut file = PCodeFile {
-                                    filename: path.to_owned(),
-                                    pcode_obj: None,
-                                    output_path: output_path.to_owned(),
-                                    min_blocks: *min_blocks,
-                                    instruction_pairs: false,
-                                    format_type: FormatMode::SingleInstruction,
-                                    pcode_file_type: PCodeFileTypes::PCodeJsonFile,
-                                };
-                                let file_ret = file.load_and_deserialize().is_ok();
-                                if file_ret {
-                                    let cfg_gen_ret =
-                                        file.pcode_json_with_bb_info_generate_cfg().is_ok();
-                                    if cfg_gen_ret {
-                                        info!("Successfully generated CFG's with PCode features")
-                                    } else {
-                                        error!("Failed to generate CFG's with PCode features")
-                                    }
-                                }
-                            } else {
-                                info!("[L551] Multiple files found. Will parallel process.");
-                                for file in
-                                    WalkDir::new(path).into_iter().filter_map(|file| file.ok())
-                                {
-                                    if file.path().to_string_lossy().ends_with(".json") {
-                                        validate_input(file.path(), "cfg");
-                                        let mut file = PCodeFile {
-                                            filename: file.path().to_owned(),
-                                            pcode_obj: None,
-                                            output_path: output_path.to_owned(),
-                                            min_blocks: *min_blocks,
-                                            instruction_pairs: false,
-                                            format_type: FormatMode::SingleInstruction,
-                                            pcode_file_type: PCodeFileTypes::PCodeJsonFile,
-                                        };
-                                        let file_ret = file.load_and_deserialize().is_ok();
-                                        if file_ret {
-                                            let cfg_gen_ret =
-                                                file.pcode_json_with_bb_info_generate_cfg().is_ok();
-                                            if cfg_gen_ret {
-                                                info!("Successfully generated CFG's with PCode features")
-                                            } else {
-                                                error!(
-                                                    "Failed to generate CFG's with PCode features"
-                                                )
-                                            }
+                        } else {
+                            info!("[L551] Multiple files found. Will parallel process.");
+                            for file in WalkDir::new(path).into_iter().filter_map(|file| file.ok())
+                            {
+                                if file.path().to_string_lossy().ends_with(".json") {
+                                    validate_input(file.path(), "cfg");
+                                    let mut file = PCodeFile {
+                                        filename: file.path().to_owned(),
+                                        pcode_obj: None,
+                                        output_path: output_path.to_owned(),
+                                        min_blocks: *min_blocks,
+                                        instruction_pairs: false,
+                                        format_type: FormatMode::SingleInstruction,
+                                        pcode_file_type: PCodeFileTypes::PCodeJsonFile,
+                                        reg_norm: false,
+                                        ssa: *ssa,
+                                        sort_output: true,
+                                    };
+                                    let file_ret = file.load_and_deserialize().is_ok();
+                                    if file_ret {
+                                        let cfg_gen_ret = file
+                                            .pcode_json_with_bb_info_generate_cfg(&graph_format)
+                                            .is_ok();
+                                        if cfg_gen_ret {
+                                            info!(
+                                                "Successfully generated CFG's with PCode \
+                                                features"
+                                            )
+                                        } else {
+                                            error!("Failed to generate CFG's with PCode features")
                                         }
                                     }
                                 }
                             }
                         }
-                    } else {
-                        error!("--feature-type/-f is required for creating CFG's")
                     }
-                } else if Path::new(path).is_file() {
-                    validate_input(path, "cg");
-                    let mut file = match with_features {
-                        true => {
-                            let mut metadata = AFIJFile {
-                                filename: metadata_path.as_ref().unwrap().to_path_buf(),
-                                function_info: None,
-                                output_path: PathBuf::new(),
-                            };
-                            debug!("AFIJ Object: {:?}", metadata);
-                            metadata
-                                .load_and_deserialize()
-                                .expect("Unable to load file");
-                            let metadata_subset = metadata.subset(false);
-                            AGCJFile {
-                                filename: path.clone(),
-                                function_call_graphs: None,
-                                output_path: output_path.clone(),
-                                function_metadata: Some(metadata_subset),
-                                include_unk: *include_unk,
-                            }
-                        }
-                        false => AGCJFile {
+                } else {
+                    error!("--feature-type/-f is required for creating CFG's")
+                }
+            } else if Path::new(path).is_file() {
+                validate_input(path, "cg");
+                let mut file = match with_features {
+                    true => {
+                        let mut metadata = AFIJFile {
+                            filename: metadata_path.as_ref().unwrap().to_path_buf(),
+                            function_info: None,
+                            output_path: PathBuf::new(),
+                        };
+                        debug!("AFIJ Object: {:?}", metadata);
+                        metadata
+                            .load_and_deserialize()
+                            .expect("Unable to load file");
+                        let metadata_subset = metadata.subset(false, false);
+                        AGCJFile {
                             filename: path.clone(),
                             function_call_graphs: None,
                             output_path: output_path.clone(),
-                            function_metadata: None,
+                            function_metadata: Some(metadata_subset),
                             include_unk: *include_unk,
-                        },
-                    };
+                            weighted_edges: *weighted_edges,
+                            self_loops,
+                            graph_format,
+                        }
+                    }
+                    false => AGCJFile {
+                        filename: path.clone(),
+                        function_call_graphs: None,
+                        output_path: output_path.clone(),
+                        function_metadata: None,
+                        include_unk: *include_unk,
+                        weighted_edges: *weighted_edges,
+                        self_loops,
+                        graph_format,
+                    },
+                };
 
-                    file.load_and_deserialize()
-                        .expect("Unable to load and deserialize JSON");
-                    file.process_based_on_graph_data_type(
-                        graph_data_type,
-                        with_features,
-                        metadata_type.clone(),
-                    );
-                } else {
-                    debug!("Multiple files found");
+                file.load_and_deserialize()
+                    .expect("Unable to load and deserialize JSON");
+                file.process_based_on_graph_data_type(
+                    graph_data_type,
+                    with_features,
+                    metadata_type.clone(),
+                );
+            } else {
+                debug!("Multiple files found");
+
+                if metadata_path.is_none() & with_features {
+                    error!("with features active - require --metadata-path argument");
+                    ExitCode::BadArgs.exit()
+                };
 
-                    if metadata_path.is_none() & with_features {
+                let mut file_paths_vec = get_json_paths_from_dir(path, Some("_cg".to_string()));
+                info!(
+                    "{} files found. Beginning Processing.",
+                    file_paths_vec.len()
+                );
+                // if without metadata
+                if !with_features & metadata_type.is_none() {
+                    debug!("Creating call graphs without any node features");
+                    file_paths_vec.par_iter().progress().for_each(|path| {
+                        let suffix = graph_type.to_owned().to_string();
+                        let full_output_path = get_save_file_path(
+                            &PathBuf::from(path),
+                            output_path,
+                            Some(".json".to_string()),
+                            Some(suffix),
+                            None,
+                            None,
+                        );
+                        if !full_output_path.is_dir() {
+                            let mut file = AGCJFile {
+                                filename: path.to_owned().parse().unwrap(),
+                                function_call_graphs: None,
+                                output_path: output_path.to_owned(),
+                                function_metadata: None,
+                                include_unk: *include_unk,
+                                weighted_edges: *weighted_edges,
+                                self_loops,
+                                graph_format,
+                            };
+                            debug!("Processing {:?}", file.filename);
+                            file.load_and_deserialize()
+                                .expect("Unable to load and deserialize JSON");
+                            file.process_based_on_graph_data_type(
+                                graph_data_type,
+                                with_features,
+                                metadata_type.clone(),
+                            );
+                        } else {
+                            info!(
+                                "Skipping {} as already exists",
+                                full_output_path.to_string_lossy()
+                            )
+                        }
+                    })
+                } else {
+                    info!("Creating call graphs with node features");
+                    debug!("Getting metadata file paths");
+                    // its more than one file
+                    if metadata_path.is_none() {
                         error!("with features active - require --metadata-path argument");
-                        exit(1)
+                        ExitCode::BadArgs.exit()
                     };
 
-                    let mut file_paths_vec = get_json_paths_from_dir(path, Some("_cg".to_string()));
-                    info!(
-                        "{} files found. Beginning Processing.",
-                        file_paths_vec.len()
+                    if with_features & metadata_type.is_none() {
+                        error!("with features requires metadata_type to be set");
+                        ExitCode::BadArgs.exit()
+                    };
+
+                    let mut metadata_paths_vec = get_json_paths_from_dir(
+                        metadata_path.as_ref().unwrap(),
+                        Some(metadata_type.as_ref().unwrap().to_string()),
                     );
-                    // if without metadata
-                    if !with_features & metadata_type.is_none() {
-                        debug!("Creating call graphs without any node features");
-                        file_paths_vec.par_iter().progress().for_each(|path| {
-                            let suffix = graph_type.to_owned().to_string();
+
+                    file_paths_vec.sort();
+                    metadata_paths_vec.sort();
+
+                    assert_eq!(file_paths_vec.len(), metadata_paths_vec.len());
+                    let combined_cgs_metadata = file_paths_vec
+                        .into_iter()
+                        .zip(metadata_paths_vec)
+                        .collect::<Vec<_>>();
+
+                    combined_cgs_metadata
+                        .par_iter()
+                        .progress()
+                        .for_each(|(filepath, metapath)| {
+                            let suffix = format!("{}-meta", graph_type.to_owned());
                             let full_output_path = get_save_file_path(
-                                &PathBuf::from(path),
+                                &PathBuf::from(filepath),
                                 output_path,
                                 Some(".json".to_string()),
                                 Some(suffix),
                                 None,
+                                None,
                             );
                             if !full_output_path.is_dir() {
-                                let mut file = AGCJFile {
-                                    filename: path.to_owned().parse().unwrap(),
-                                    function_call_graphs: None,
-                                    output_path: output_path.to_owned(),
-                                    function_metadata: None,
-                                    include_unk: *include_unk,
+                                let mut file = {
+                                    let metadata: Option<FunctionMetadataTypes>;
+                                    if metadata_type.clone().unwrap() == *"finfo" {
+                                        let mut metadata_file = AFIJFile {
+                                            filename: PathBuf::from(metapath),
+                                            function_info: None,
+                                            output_path: PathBuf::new(),
+                                        };
+                                        debug!("Attempting to load metadata file: {}", metapath);
+                                        metadata_file
+                                            .load_and_deserialize()
+                                            .expect("Unable to load associated metadata file");
+                                        metadata = Some(metadata_file.subset(false, false));
+                                    } else if metadata_type.clone().unwrap() == *"tiknib" {
+                                        let mut metadata_file = TikNibFuncMetaFile {
+                                            filename: PathBuf::from(metapath),
+                                            function_info: None,
+                                            output_path: PathBuf::new(),
+                                        };
+
+                                        metadata_file
+                                            .load_and_deserialize()
+                                            .expect("Unable to load associated metadata file");
+                                        metadata = Some(metadata_file.subset());
+                                    } else if metadata_type.clone().unwrap() == *"finfo-tiknib" {
+                                        let mut metadata_file = FinfoTiknibFile {
+                                            filename: PathBuf::from(metapath),
+                                            function_info: None,
+                                            output_path: PathBuf::new(),
+                                        };
+                                        debug!("Attempting to load metadata file: {}", metapath);
+                                        metadata_file
+                                            .load_and_deserialize()
+                                            .expect("Unable to load associated metadata file");
+                                        metadata = Some(FunctionMetadataTypes::FinfoTiknibCombo(
+                                            metadata_file.function_info.unwrap(),
+                                        ));
+                                    } else {
+                                        metadata = None
+                                    }
+
+                                    AGCJFile {
+                                        filename: PathBuf::from(filepath),
+                                        function_call_graphs: None,
+                                        output_path: output_path.to_owned(),
+                                        function_metadata: metadata,
+                                        include_unk: *include_unk,
+                                        weighted_edges: *weighted_edges,
+                                        self_loops,
+                                        graph_format,
+                                    }
                                 };
-                                debug!("Processing {:?}", file.filename);
+                                debug!("Attempting to load {:?}", file.filename);
                                 file.load_and_deserialize()
                                     .expect("Unable to load and deserialize JSON");
+
                                 file.process_based_on_graph_data_type(
                                     graph_data_type,
                                     with_features,
                                     metadata_type.clone(),
                                 );
+                                info!("Finished generating cgs + metadata for {:?}", file.filename);
                             } else {
                                 info!(
                                     "Skipping {} as already exists",
                                     full_output_path.to_string_lossy()
                                 )
                             }
-                        })
-                    } else {
-                        info!("Creating call graphs with node features");
-                        debug!("Getting metadata file paths");
-                        // its more than one file
-                        if metadata_path.is_none() {
-                            error!("with features active - require --metadata-path argument");
-                            exit(1)
-                        };
-
-                        if with_features & metadata_type.is_none() {
-                            error!("with features requires metadata_type to be set");
-                            exit(1)
-                        };
-
-                        let mut metadata_paths_vec = get_json_paths_from_dir(
-                            metadata_path.as_ref().unwrap(),
-                            Some(metadata_type.as_ref().unwrap().to_string()),
-                        );
-
-                        file_paths_vec.sort();
-                        metadata_paths_vec.sort();
-
-                        assert_eq!(file_paths_vec.len(), metadata_paths_vec.len());
-                        let combined_cgs_metadata = file_paths_vec
-                            .into_iter()
-                            .zip(metadata_paths_vec)
-                            .collect::<Vec<_>>();
+                        });
+                }
+            }
+        }
+        _ => unreachable!("generate_graphs is only called for the graphs subcommand"),
+    }
+}
 
-                        combined_cgs_metadata.par_iter().progress().for_each(
-                            |(filepath, metapath)| {
-                                let suffix = format!("{}-meta", graph_type.to_owned());
-                                let full_output_path = get_save_file_path(
-                                    &PathBuf::from(filepath),
-                                    output_path,
-                                    Some(".json".to_string()),
-                                    Some(suffix),
-                                    None,
-                                );
-                                if !full_output_path.is_dir() {
-                                    let mut file = {
-                                        let metadata: Option<FunctionMetadataTypes>;
-                                        if metadata_type.clone().unwrap() == *"finfo" {
-                                            let mut metadata_file = AFIJFile {
-                                                filename: PathBuf::from(metapath),
-                                                function_info: None,
-                                                output_path: PathBuf::new(),
-                                            };
-                                            debug!(
-                                                "Attempting to load metadata file: {}",
-                                                metapath
-                                            );
-                                            metadata_file
-                                                .load_and_deserialize()
-                                                .expect("Unable to load associated metadata file");
-                                            metadata = Some(metadata_file.subset(false));
-                                        } else if metadata_type.clone().unwrap() == *"tiknib" {
-                                            let mut metadata_file = TikNibFuncMetaFile {
-                                                filename: PathBuf::from(metapath),
-                                                function_info: None,
-                                                output_path: PathBuf::new(),
-                                            };
-
-                                            metadata_file
-                                                .load_and_deserialize()
-                                                .expect("Unable to load associated metadata file");
-                                            metadata = Some(metadata_file.subset());
-                                        } else if metadata_type.clone().unwrap() == *"finfo-tiknib"
-                                        {
-                                            let mut metadata_file = FinfoTiknibFile {
-                                                filename: PathBuf::from(metapath),
-                                                function_info: None,
-                                                output_path: PathBuf::new(),
-                                            };
-                                            debug!(
-                                                "Attempting to load metadata file: {}",
-                                                metapath
-                                            );
-                                            metadata_file
-                                                .load_and_deserialize()
-                                                .expect("Unable to load associated metadata file");
-                                            metadata =
-                                                Some(FunctionMetadataTypes::FinfoTiknibCombo(
-                                                    metadata_file.function_info.unwrap(),
-                                                ));
-                                        } else {
-                                            metadata = None
-                                        }
+fn main() {
+    let env = Env::default()
+        .filter_or("LOG_LEVEL", "warn")
+        .write_style_or("LOG_STYLE", "always");
 
-                                        AGCJFile {
-                                            filename: PathBuf::from(filepath),
-                                            function_call_graphs: None,
-                                            output_path: output_path.to_owned(),
-                                            function_metadata: metadata,
-                                            include_unk: *include_unk,
-                                        }
-                                    };
-                                    debug!("Attempting to load {:?}", file.filename);
-                                    file.load_and_deserialize()
-                                        .expect("Unable to load and deserialize JSON");
-
-                                    file.process_based_on_graph_data_type(
-                                        graph_data_type,
-                                        with_features,
-                                        metadata_type.clone(),
-                                    );
-                                    info!(
-                                        "Finished generating cgs + metadata for {:?}",
-                                        file.filename
-                                    );
-                                } else {
-                                    info!(
-                                        "Skipping {} as already exists",
-                                        full_output_path.to_string_lossy()
-                                    )
-                                }
-                            },
-                        );
+    env_logger::init_from_env(env);
+    let cli = Cli::parse();
+    match &cli.command {
+        #[cfg(feature = "goblin")]
+        Commands::Info {
+            path,
+            json,
+            output_path,
+        } => {
+            info!("starting Information Gathering");
+            if output_path.is_some() && !*json {
+                warn!("--output-path is only used with --json. Ignoring")
+            }
+            if let Some(fpath) = &path {
+                let info = match goblin_info(fpath) {
+                    Ok(info) => info,
+                    Err(e) => {
+                        error!("Failed to get info - {}", e);
+                        ExitCode::Failure.exit()
                     }
+                };
+                if *json {
+                    write_goblin_info_json(&info, output_path.as_deref())
+                        .expect("Failed to write info!");
+                } else {
+                    print!("{}", info);
                 }
             }
+        }
+        Commands::Generate { subcommands } => match subcommands {
+            GenerateSubCommands::Graphs { num_threads, .. } => {
+                let pool = build_thread_pool(*num_threads);
+                pool.install(|| generate_graphs(subcommands))
+            }
             GenerateSubCommands::Metadata {
                 input_path,
                 output_path,
                 data_source_type,
                 extended,
+                with_edges,
+                architecture,
             } => {
                 if data_source_type == "finfo" {
                     validate_input(input_path, "metadata_finfo");
@@ -808,7 +1331,20 @@ fn main() {
                     file.load_and_deserialize()
                         .expect("Unable to load and desearilize JSON");
                     info!("Successfully loaded JSON");
-                    file.subset_and_save(*extended);
+                    file.subset_and_save(*extended, *with_edges);
+                    info!("Generation complete");
+                } else if data_source_type == "complexity" {
+                    validate_input(input_path, "metadata_finfo");
+                    let mut file = AFIJFile {
+                        filename: input_path.to_owned(),
+                        function_info: None,
+                        output_path: output_path.to_owned(),
+                    };
+                    info!("Generating function complexity features");
+                    file.load_and_deserialize()
+                        .expect("Unable to load and desearilize JSON");
+                    info!("Successfully loaded JSON");
+                    file.complexity_and_save();
                     info!("Generation complete");
                 } else if data_source_type == "tiknib" {
                     warn!("This currently only supports making TikNib features for single files");
@@ -820,9 +1356,14 @@ fn main() {
                             filename: input_path.to_owned(),
                             output_path: output_path.to_owned(),
                             min_blocks: 1, // Dummy
+                            max_blocks: None,
                             feature_type: None,
-                            architecture: None,
-                            reg_norm: false, // Dummy
+                            architecture: architecture.clone(),
+                            norm_config: NormalizationConfig::default(), // Dummy
+                            graph_format: GraphFormat::Json,
+                            embed_func_meta: false,
+                            edge_types: false,
+                            relative_addrs: false,
                         };
 
                         file.load_and_deserialize().expect("Unable to load data");
@@ -837,9 +1378,14 @@ fn main() {
                                 filename: filepath.to_owned().parse().unwrap(),
                                 output_path: output_path.to_owned(),
                                 min_blocks: 1, // Dummy
+                                max_blocks: None,
                                 feature_type: None,
-                                architecture: None,
-                                reg_norm: false, // Dummy
+                                architecture: architecture.clone(),
+                                norm_config: NormalizationConfig::default(), // Dummy
+                                graph_format: GraphFormat::Json,
+                                embed_func_meta: false,
+                                edge_types: false,
+                                relative_addrs: false,
                             };
 
                             file.load_and_deserialize().expect("Unable to load data");
@@ -852,40 +1398,118 @@ fn main() {
                 input_path,
                 output_path,
                 combo_type,
+                join,
                 num_threads,
             } => {
                 warn!("This feature is experimental and should be used with caution!");
-                let combo_job = ComboJob::new(combo_type, input_path, output_path);
+                let combo_job = ComboJob::new(combo_type, join, input_path, output_path);
 
                 if combo_job.is_ok() {
                     let combo_job = combo_job.unwrap();
-                    rayon::ThreadPoolBuilder::new()
-                        .num_threads(*num_threads)
-                        .build_global()
-                        .unwrap();
-
-                    match combo_job.combo_type {
+                    let pool = build_thread_pool(*num_threads);
+                    pool.install(|| match combo_job.combo_type {
                         combos::ComboTypes::FinfoTikib => combo_job.process_finfo_tiknib(),
-                    }
+                        combos::ComboTypes::FinfoTiknibStrings => {
+                            combo_job.process_finfo_tiknib_strings()
+                        }
+                    })
                 } else {
                     error!("Invalid combo type: {}", combo_type);
-                    exit(1)
+                    ExitCode::BadArgs.exit()
                 }
             }
+            GenerateSubCommands::Merge {
+                input_path,
+                output_path,
+                tag_source,
+            } => {
+                if !input_path.is_dir() {
+                    error!("{:?} is not a directory!", input_path);
+                    ExitCode::InputNotFound.exit()
+                }
+                let mut file_paths =
+                    get_json_paths_from_dir(input_path, Some("-finfo-subset".to_string()));
+                file_paths.sort();
+                info!("Merging {} finfo subset files", file_paths.len());
+
+                match merge_finfo_subsets(&file_paths, *tag_source) {
+                    Ok(merged) => {
+                        write_json_to_file(output_path, &merged).expect("Unable to write JSON");
+                        info!("{} records written to {:?}", merged.len(), output_path);
+                    }
+                    Err(e) => {
+                        error!("Unable to merge finfo subsets - {}", e);
+                        ExitCode::Failure.exit()
+                    }
+                }
+            }
+            GenerateSubCommands::GraphDiff {
+                old,
+                new,
+                output_path,
+                ignore_auto_named,
+                include_unk,
+            } => {
+                let mut call_graphs = Vec::new();
+                for path in [old, new] {
+                    validate_input(path, "cg");
+                    let mut file = AGCJFile {
+                        filename: path.to_owned(),
+                        function_call_graphs: None,
+                        output_path: output_path.to_owned(),
+                        function_metadata: None,
+                        include_unk: *include_unk,
+                        weighted_edges: false,
+                        self_loops: SelfLoops::Keep,
+                        graph_format: GraphFormat::Json,
+                    };
+                    if let Err(e) = file.load_and_deserialize() {
+                        error!("Unable to load {:?} - {}", path, e);
+                        ExitCode::Failure.exit()
+                    }
+                    call_graphs.push(file.build_global_call_graph());
+                }
+
+                let diff = CallGraphDiff::new(&call_graphs[0], &call_graphs[1], *ignore_auto_named);
+                info!(
+                    "{} nodes added, {} nodes removed, {} edges added, {} edges removed",
+                    diff.added_nodes.len(),
+                    diff.removed_nodes.len(),
+                    diff.added_edges.len(),
+                    diff.removed_edges.len()
+                );
+                serde_json::to_writer(
+                    &File::create(output_path).expect("Failed to create writer"),
+                    &diff,
+                )
+                .expect("Unable to write JSON");
+            }
             GenerateSubCommands::Nlp {
                 path,
                 instruction_type,
                 min_blocks,
+                max_blocks,
                 data_out_path,
                 output_format,
                 random_walk,
+                max_hops,
                 reg_norm,
+                norm_preset,
+                bucket_immediates,
+                ssa,
                 pairs,
+                ngram,
+                block_markers,
+                block_marker_token,
+                low_memory,
+                jsonl,
+                sort_output,
                 pcode_file_format,
+                num_threads,
             } => {
                 if !path.exists() {
                     error!("The path {:?} does not exist!", path);
-                    exit(1)
+                    ExitCode::InputNotFound.exit()
                 }
 
                 let instruction_type = match instruction_type.as_str() {
@@ -901,7 +1525,11 @@ fn main() {
 
                 if instruction_type == InstructionMode::Invalid {
                     error!("Invalid instruction mode: {:?}", instruction_type);
-                    exit(1)
+                    ExitCode::BadArgs.exit()
+                }
+
+                if *ssa && instruction_type != InstructionMode::PCode {
+                    warn!("--ssa is only supported for PCode. Ignoring")
                 }
 
                 let format_type = match output_format.as_str() {
@@ -912,14 +1540,61 @@ fn main() {
 
                 if format_type == FormatMode::FuncAsString && *pairs {
                     error!("The pairs option is not supported for 'funcstring' format. Only 'single' is supported");
-                    exit(1)
+                    ExitCode::BadArgs.exit()
                 };
 
                 if format_type == FormatMode::Invalid {
                     error!("Invalid format type: {:?}", format_type);
-                    exit(1)
+                    ExitCode::BadArgs.exit()
+                }
+
+                if *block_markers && format_type != FormatMode::FuncAsString {
+                    warn!(
+                        "--block-markers is only supported for the 'funcstring' format. Ignoring"
+                    );
+                }
+                let block_marker = match *block_markers {
+                    true => Some(block_marker_token.clone()),
+                    false => None,
+                };
+
+                if *low_memory
+                    && (instruction_type == InstructionMode::PCode
+                        || format_type != FormatMode::FuncAsString
+                        || ngram.is_some())
+                {
+                    warn!("--low-memory is only supported for the 'esil' and 'disasm' instruction types with the 'funcstring' format. Ignoring");
                 }
 
+                if *jsonl
+                    && (instruction_type == InstructionMode::PCode
+                        || format_type != FormatMode::SingleInstruction
+                        || ngram.is_some())
+                {
+                    warn!("--jsonl is only supported for the 'esil' and 'disasm' instruction types with the 'single' format. Ignoring");
+                }
+
+                if let Some(n) = ngram {
+                    if *n == 0 {
+                        error!("--ngram must be greater than 0");
+                        ExitCode::BadArgs.exit()
+                    }
+                    if instruction_type != InstructionMode::Disasm {
+                        error!("--ngram is only supported for the 'disasm' instruction type");
+                        ExitCode::BadArgs.exit()
+                    }
+                    if *random_walk || *pairs {
+                        error!("--ngram cannot be combined with --random-walk or --pairs");
+                        ExitCode::BadArgs.exit()
+                    }
+                }
+
+                let norm_config = match norm_preset {
+                    Some(norm_preset) => NormalizationConfig::from_preset(norm_preset).unwrap(),
+                    None => NormalizationConfig::from_reg_norm(*reg_norm),
+                }
+                .with_bucket_immediates(*bucket_immediates);
+
                 if Path::new(path).is_file() {
                     info!("Single file found");
                     validate_input(path, "nlp");
@@ -930,16 +1605,27 @@ fn main() {
                                 filename: path.to_owned(),
                                 output_path: data_out_path.to_owned(),
                                 min_blocks: *min_blocks,
+                                max_blocks: *max_blocks,
                                 feature_type: None,
                                 architecture: None,
-                                reg_norm: *reg_norm,
+                                norm_config,
+                                graph_format: GraphFormat::Json,
+                                embed_func_meta: false,
+                                edge_types: false,
+                                relative_addrs: false,
                             };
 
                             file.execute_data_generation(
                                 format_type,
                                 instruction_type,
                                 random_walk,
+                                *max_hops,
                                 *pairs,
+                                *ngram,
+                                block_marker,
+                                *low_memory,
+                                *jsonl,
+                                *sort_output,
                             )
                         }
                         InstructionMode::PCode => {
@@ -958,6 +1644,9 @@ fn main() {
                                 instruction_pairs: *pairs,
                                 format_type,
                                 pcode_file_type,
+                                reg_norm: norm_config.registers,
+                                ssa: *ssa,
+                                sort_output: *sort_output,
                             };
 
                             file.load_and_deserialize()
@@ -969,7 +1658,7 @@ fn main() {
                                 "Invalid instruction type: {:?}. Exiting..",
                                 instruction_type
                             );
-                            exit(1)
+                            ExitCode::BadArgs.exit()
                         }
                     }
                 } else {
@@ -979,43 +1668,65 @@ fn main() {
                         "{} files found. Beginning Processing.",
                         file_paths_vec.len()
                     );
-                    for file in file_paths_vec.iter().progress() {
-                        let file = AGFJFile {
-                            functions: None,
-                            filename: PathBuf::from(file),
-                            output_path: data_out_path.to_owned(),
-                            min_blocks: *min_blocks,
-                            feature_type: None,
-                            architecture: None,
-                            reg_norm: *reg_norm,
-                        };
-                        file.execute_data_generation(
+                    let pool = build_thread_pool(*num_threads);
+                    pool.install(|| {
+                        agfj_nlp_data_generation(
+                            &file_paths_vec,
+                            data_out_path,
+                            *min_blocks,
+                            *max_blocks,
+                            norm_config,
                             format_type,
                             instruction_type,
-                            random_walk,
+                            *random_walk,
+                            *max_hops,
                             *pairs,
+                            *ngram,
+                            block_marker,
+                            *low_memory,
+                            *jsonl,
+                            *sort_output,
                         )
-                    }
+                    });
                 }
             }
             GenerateSubCommands::Tokeniser {
                 data,
                 output_name,
                 vocab_size,
+                min_frequency,
+                special_tokens,
                 tokeniser_type,
             } => {
+                let config = TokeniserTrainingConfig::new(
+                    *vocab_size,
+                    *min_frequency,
+                    special_tokens.clone(),
+                );
                 let t_type = match tokeniser_type.as_str() {
                     "bpe" => TokeniserType::CommaBPE,
                     "byte-bpe" => TokeniserType::ByteBPE,
+                    "wordpiece" => TokeniserType::WordPiece,
+                    "unigram" => TokeniserType::Unigram,
                     _ => TokeniserType::Invalid,
                 };
-                if t_type == TokeniserType::CommaBPE {
-                    todo!("not implemented")
-                } else if t_type == TokeniserType::ByteBPE {
-                    train_byte_bpe_tokeniser(data, output_name, *vocab_size).unwrap();
-                } else {
-                    println!("Invalid tokeniser type - Please choose either bpe or byte-bpe");
-                    exit(1)
+                let ret = match t_type {
+                    TokeniserType::CommaBPE => {
+                        train_comma_bpe_tokeniser(data, output_name, &config)
+                    }
+                    TokeniserType::ByteBPE => train_byte_bpe_tokeniser(data, output_name, &config),
+                    TokeniserType::WordPiece => {
+                        train_wordpiece_tokeniser(data, output_name, &config)
+                    }
+                    TokeniserType::Unigram => train_unigram_tokeniser(data, output_name, &config),
+                    TokeniserType::Invalid => {
+                        println!("Invalid tokeniser type - Please choose either bpe, byte-bpe, wordpiece or unigram");
+                        ExitCode::BadArgs.exit()
+                    }
+                };
+                if let Err(e) = ret {
+                    error!("Unable to train tokeniser - {}", e);
+                    ExitCode::Failure.exit()
                 }
             }
         },
@@ -1025,153 +1736,151 @@ fn main() {
             mode,
             num_threads,
             debug,
-            extended_analysis,
+            analysis_level,
             use_curl_pdb,
             with_annotations,
+            output_format,
+            func_timeout_secs,
+            r2_path,
+            func_filename,
+            intra_file_threads,
+            progress,
+            function_filter,
+            feature_type,
+            sqlite,
+            force,
+            manifest,
+            checksums,
+            dry_run,
+            nested_output,
+            auto_reanalyze,
+            public_only,
+            key_by_offset,
+            asm_pseudo,
+            asm_comments,
+            demangle,
+            max_func_bytes,
+            min_instructions,
+            min_blocks,
+            bytes_format,
+            output_template,
+            r2_script,
         } => {
             info!("Creating extraction job");
+            if !fpath.exists() {
+                error!("Input path does not exist - {:?}. Exiting...", fpath);
+                ExitCode::InputNotFound.exit()
+            }
             if !output_dir.exists() {
                 error!("Output directory does not exist - {:?}. Create the directory and re-run again. Exiting...", output_dir);
-                exit(1)
+                ExitCode::BadArgs.exit()
             }
             let job = ExtractionJob::new(
                 fpath,
                 output_dir,
                 mode,
                 debug,
-                extended_analysis,
+                analysis_level,
                 use_curl_pdb,
                 with_annotations,
-            )
-            .unwrap();
-
-            if job.input_path_type == PathType::Dir {
-                info!("Directory found - will parallel process");
-
-                info!("Creating thread pool with {} threads ", num_threads);
-                rayon::ThreadPoolBuilder::new()
-                    .num_threads(*num_threads)
-                    .build_global()
-                    .unwrap();
-
-                if job.job_type == ExtractionJobType::CFG {
-                    info!("Extraction Job Type: CFG");
-                    info!("Starting Parallel generation.");
-                    #[allow(clippy::redundant_closure)]
-                    job.files_to_be_processed
-                        .par_iter()
-                        .progress()
-                        .for_each(|path| path.extract_func_cfgs());
-                } else if job.job_type == ExtractionJobType::RegisterBehaviour {
-                    info!("Extraction Job Type: Register Behaviour");
-                    info!("Starting Parallel generation.");
-                    #[allow(clippy::redundant_closure)]
-                    job.files_to_be_processed
-                        .par_iter()
-                        .progress()
-                        .for_each(|path| path.extract_register_behaviour());
-                } else if job.job_type == ExtractionJobType::FunctionXrefs {
-                    info!("Extraction Job Type: Function Xrefs");
-                    info!("Starting Parallel generation.");
-                    #[allow(clippy::redundant_closure)]
-                    job.files_to_be_processed
-                        .par_iter()
-                        .progress()
-                        .for_each(|path| path.extract_function_xrefs());
-                } else if job.job_type == ExtractionJobType::CallGraphs {
-                    info!("Extraction Job Type: Call Graphs");
-                    info!("Starting Parallel generation.");
-                    #[allow(clippy::redundant_closure)]
-                    job.files_to_be_processed
-                        .par_iter()
-                        .progress()
-                        .for_each(|path| path.extract_function_call_graphs());
-                } else if job.job_type == ExtractionJobType::FuncInfo {
-                    info!("Extraction Job Type: Function Info");
-                    info!("Starting Parallel generation.");
-                    #[allow(clippy::redundant_closure)]
-                    job.files_to_be_processed
-                        .par_iter()
-                        .progress()
-                        .for_each(|path| path.extract_function_info());
-                } else if job.job_type == ExtractionJobType::Decompilation {
-                    info!("Extraction Job Type: Decompilation");
-                    info!("Starting Parallel generation.");
-                    #[allow(clippy::redundant_closure)]
-                    job.files_to_be_processed
-                        .par_iter()
-                        .progress()
-                        .for_each(|path| path.extract_decompilation());
-                } else if job.job_type == ExtractionJobType::PCodeFunc {
-                    info!("Extraction Job Type: PCode Function");
-                    info!("Starting Parallel generation.");
-                    #[allow(clippy::redundant_closure)]
-                    job.files_to_be_processed
-                        .par_iter()
-                        .progress()
-                        .for_each(|path| path.extract_pcode_function());
-                } else if job.job_type == ExtractionJobType::PCodeBB {
-                    info!("Extraction Job Type: PCode Basic Block");
-                    info!("Starting Parallel generation.");
-                    #[allow(clippy::redundant_closure)]
-                    job.files_to_be_processed
-                        .par_iter()
-                        .progress()
-                        .for_each(|path| path.extract_pcode_basic_block());
-                } else if job.job_type == ExtractionJobType::LocalVariableXrefs {
-                    info!("Extraction Job Type: Local Variable Xrefs");
-                    info!("Starting Parallel generation.");
-                    #[allow(clippy::redundant_closure)]
-                    job.files_to_be_processed
-                        .par_iter()
-                        .progress()
-                        .for_each(|path| path.extract_local_variable_xrefs());
-                } else if job.job_type == ExtractionJobType::GlobalStrings {
-                    job.files_to_be_processed
-                        .par_iter()
-                        .progress()
-                        .for_each(|path| path.extract_global_strings());
-                } else if job.job_type == ExtractionJobType::FunctionBytes {
-                    job.files_to_be_processed
-                        .par_iter()
-                        .progress()
-                        .for_each(|path| path.extract_function_bytes());
-                };
+                output_format,
+                func_timeout_secs,
+                r2_path,
+                func_filename,
+                intra_file_threads,
+                progress,
+                function_filter,
+                feature_type,
+                sqlite,
+                nested_output,
+                auto_reanalyze,
+                public_only,
+                key_by_offset,
+                asm_pseudo,
+                asm_comments,
+                demangle,
+                max_func_bytes,
+                min_instructions,
+                min_blocks,
+                bytes_format,
+                output_template,
+                r2_script,
+            );
+
+            let mut job = match job {
+                Ok(job) => job,
+                Err(e) => {
+                    error!("Failed to create extraction job - {}", e);
+                    ExitCode::BadArgs.exit()
+                }
+            };
+
+            let mut summary = RunSummary::default();
+            if !*force {
+                summary.skipped = job.remove_already_extracted();
+                if job.files_to_be_processed.is_empty() {
+                    info!("All outputs already exist. Use --force to re-extract. Exiting...");
+                    eprintln!("{}", summary);
+                    // Exiting skips destructors so the job is dropped to clean up any unpacked archive
+                    drop(job);
+                    ExitCode::Ok.exit()
+                }
+            }
+
+            if *dry_run {
+                print!("{}", job.describe());
+                drop(job);
+                ExitCode::Ok.exit()
+            }
+
+            if job.input_path_type == PathType::Dir || job.input_path_type == PathType::Archive {
+                info!("Directory or archive found - will parallel process");
+
+                let pool = build_thread_pool(*num_threads);
+
+                info!("Extraction Job Type: {:?}", job.job_type);
+                info!("Starting Parallel generation.");
+                let failed = pool.install(|| job.extract_all(*manifest, *checksums));
+                if failed.is_empty() {
+                    info!(
+                        "Extraction complete for {} files",
+                        job.files_to_be_processed.len()
+                    )
+                } else {
+                    warn!(
+                        "Extraction complete. Failed to extract {} of {} files: {:?}",
+                        failed.len(),
+                        job.files_to_be_processed.len(),
+                        failed
+                    )
+                }
+                summary.failed = failed.len();
             } else if job.input_path_type == PathType::File {
                 info!("Single file found");
-                if job.job_type == ExtractionJobType::CFG {
-                    info!("Extraction Job Type: CFG");
-                    job.files_to_be_processed[0].extract_func_cfgs();
-                } else if job.job_type == ExtractionJobType::RegisterBehaviour {
-                    info!("Extraction Job Type: Register Behaviour");
-                    job.files_to_be_processed[0].extract_register_behaviour()
-                } else if job.job_type == ExtractionJobType::FunctionXrefs {
-                    info!("Extraction Job type: Function Xrefs");
-                    job.files_to_be_processed[0].extract_function_xrefs()
-                } else if job.job_type == ExtractionJobType::CallGraphs {
-                    info!("Extraction Job type: Function Call Graphs");
-                    job.files_to_be_processed[0].extract_function_call_graphs()
-                } else if job.job_type == ExtractionJobType::FuncInfo {
-                    info!("Extraction Job type: Function Info");
-                    job.files_to_be_processed[0].extract_function_info()
-                } else if job.job_type == ExtractionJobType::Decompilation {
-                    info!("Extraction Job type: Decompilation");
-                    job.files_to_be_processed[0].extract_decompilation()
-                } else if job.job_type == ExtractionJobType::PCodeFunc {
-                    job.files_to_be_processed[0].extract_pcode_function()
-                } else if job.job_type == ExtractionJobType::PCodeBB {
-                    job.files_to_be_processed[0].extract_pcode_basic_block()
-                } else if job.job_type == ExtractionJobType::LocalVariableXrefs {
-                    job.files_to_be_processed[0].extract_local_variable_xrefs()
-                } else if job.job_type == ExtractionJobType::GlobalStrings {
-                    job.files_to_be_processed[0].extract_global_strings()
-                } else if job.job_type == ExtractionJobType::FunctionBytes {
-                    job.files_to_be_processed[0].extract_function_bytes()
+                info!("Extraction Job Type: {:?}", job.job_type);
+                let file = &job.files_to_be_processed[0];
+                let mode_result = file.extract(&job.job_type);
+                let mut success = mode_result.success;
+                let written = if *manifest {
+                    file.write_manifest(vec![mode_result])
+                } else if *checksums {
+                    file.write_checksums()
                 } else {
-                    error!("Unsupported ExtractionJobType of {:?}", job.job_type)
+                    Ok(())
+                };
+                if let Err(e) = written {
+                    error!("{}", e);
+                    success = false
+                }
+                if !success {
+                    summary.failed = 1;
                 }
                 info!("Extraction complete for {:?}", fpath)
             }
+            summary.processed = job.files_to_be_processed.len() - summary.failed;
+            eprintln!("{}", summary);
+            drop(job);
+            summary.exit_code().exit()
         }
 
         #[cfg(feature = "inference")]
@@ -1188,6 +1897,13 @@ fn main() {
                 sequence,
             );
         }
+        Commands::Validate { path, file_type } => match validate_file(path, file_type) {
+            Ok(summary) => println!("PASS: {}", summary),
+            Err(e) => {
+                println!("FAIL: {}", e);
+                e.exit_code().exit()
+            }
+        },
         Commands::Dedup { subcommands } => match subcommands {
             DedupSubCommands::Cgs {
                 filename,
@@ -1196,23 +1912,31 @@ fn main() {
                 filepath_format,
                 node_feature_type,
                 inplace,
+                chunk_size,
+                chunk_threshold,
+                structural,
+                preserve_tree,
+                print_stats,
+                just_stats,
             } => {
-                rayon::ThreadPoolBuilder::new()
-                    .num_threads(*num_threads)
-                    .build_global()
-                    .unwrap();
+                let pool = build_thread_pool(*num_threads);
 
                 if Path::new(filename).exists() {
                     let node_feature_type = CallGraphNodeFeatureType::new(node_feature_type);
                     info!("Starting duplication process for One Hop Call Graphs");
                     let corpus =
                         CGCorpus::new(filename, output_path, filepath_format, node_feature_type)
-                            .unwrap();
-                    if *inplace {
-                        corpus.process_corpus_inplace();
-                    } else {
-                        corpus.process_corpus();
-                    }
+                            .unwrap()
+                            .with_structural_hash(*structural)
+                            .with_preserve_tree(*preserve_tree)
+                            .with_chunking(*chunk_size, *chunk_threshold);
+                    pool.install(|| {
+                        if *inplace {
+                            corpus.process_corpus_inplace();
+                        } else {
+                            corpus.process_corpus(*print_stats, *just_stats);
+                        }
+                    })
                 } else {
                     error!("Filename provided does not exist! - {:?}", filename)
                 }
@@ -1224,17 +1948,38 @@ fn main() {
                 just_hash_value,
                 num_threads,
                 output_path,
+                global,
+                minhash,
+                verify_collisions,
+                num_hashes,
+                threshold,
             } => {
-                rayon::ThreadPoolBuilder::new()
-                    .num_threads(*num_threads)
-                    .build_global()
-                    .unwrap();
+                let pool = build_thread_pool(*num_threads);
 
                 warn!("This only supports the Cisco Talos Binary Sim Dataset naming convention");
                 let corpus = EsilFuncStringCorpus::new(filename, output_path).unwrap();
-                corpus.uniq_binaries.par_iter().progress().for_each(|name| {
-                    corpus.dedup_subset(name, *print_stats, *just_stats, *just_hash_value)
-                });
+                pool.install(|| {
+                    if *minhash {
+                        corpus.minhash_dedup(*num_hashes, *threshold, *print_stats, *just_stats)
+                    } else if *global {
+                        corpus.dedup_global(
+                            *print_stats,
+                            *just_stats,
+                            *just_hash_value,
+                            *verify_collisions,
+                        )
+                    } else {
+                        corpus.uniq_binaries.par_iter().progress().for_each(|name| {
+                            corpus.dedup_subset(
+                                name,
+                                *print_stats,
+                                *just_stats,
+                                *just_hash_value,
+                                *verify_collisions,
+                            )
+                        });
+                    }
+                })
             }
         },
     }
@@ -2,12 +2,18 @@ use crate::afij::AFIJFeatureSubset;
 use crate::agfj::TikNibFunc;
 use crate::bb::{FeatureType, TikNibFeaturesBB};
 use crate::combos::FinfoTiknib;
+use crate::consts::{CFG_EDGE_FAIL, CFG_EDGE_JUMP, CFG_EDGE_SWITCH};
 use crate::extract::PCodeJsonWithBBAndFuncName;
+use crate::utils::write_json_to_file;
+use anyhow::{bail, Result};
 use enum_as_inner::EnumAsInner;
 use petgraph::prelude::Graph;
 use petgraph::visit::EdgeRef;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
 use std::fs::File;
+use std::hash::{Hash, Hasher};
 use std::io::Write;
 use std::path::Path;
 
@@ -19,9 +25,88 @@ pub struct NetworkxDiGraph<N> {
     pub graph: Vec<char>,
     pub multigraph: bool,
     pub nodes: Vec<N>,
+    /// Optional graph level metadata (i.e function metadata for a CFG). Only serialized when present
+    #[serde(
+        rename = "function_metadata",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub graph_meta: Option<Value>,
+    /// Optional label for each node (i.e the block address for a CFG). Only serialized when present
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_labels: Option<Vec<String>>,
 }
 
 impl<N: Serialize> NetworkxDiGraph<N> {
+    /// Attach graph level metadata. This is only emitted as part of JSON output
+    pub fn with_graph_meta(mut self, graph_meta: Option<Value>) -> Self {
+        self.graph_meta = graph_meta;
+        self
+    }
+
+    /// Attach a label to each node. Used in place of the node's [`DotLabel`] for DOT output
+    pub fn with_node_labels(mut self, node_labels: Option<Vec<String>>) -> Self {
+        self.node_labels = node_labels;
+        self
+    }
+
+    /// Keep or strip the edge type of each edge. Edge types are only serialized when kept
+    pub fn with_edge_types(mut self, edge_types: bool) -> Self {
+        if !edge_types {
+            for edge in self.adjacency.iter_mut().flatten() {
+                edge.edge_type = None;
+            }
+        }
+        self
+    }
+
+    /// A hash of the structure and node features of the graph
+    ///
+    /// Node ids, node ordering and presentation fields (i.e directed, multigraph
+    /// and the graph metadata) are ignored so isomorphic graphs whose nodes are
+    /// listed in a different order hash the same. Each node is represented by its
+    /// serialized features and each edge by the features of its endpoints and its
+    /// weight, with both sets sorted before hashing.
+    pub fn structural_hash(&self) -> u64 {
+        let node_features: Vec<String> = self
+            .nodes
+            .iter()
+            .map(|node| match serde_json::to_value(node) {
+                Ok(Value::Object(mut node)) => {
+                    node.remove("id");
+                    Value::Object(node).to_string()
+                }
+                Ok(node) => node.to_string(),
+                Err(_) => String::new(),
+            })
+            .collect();
+
+        let node_feature_refs = &node_features;
+        let mut edges: Vec<(&String, &String, u32)> = self
+            .adjacency
+            .iter()
+            .enumerate()
+            .flat_map(|(src, edges)| {
+                edges.iter().filter_map(move |edge| {
+                    Some((
+                        node_feature_refs.get(src)?,
+                        node_feature_refs.get(edge.id)?,
+                        edge.weight,
+                    ))
+                })
+            })
+            .collect();
+        edges.sort_unstable();
+
+        let mut sorted_node_features: Vec<&String> = node_features.iter().collect();
+        sorted_node_features.sort_unstable();
+
+        let mut hasher = DefaultHasher::new();
+        sorted_node_features.hash(&mut hasher);
+        edges.hash(&mut hasher);
+        hasher.finish()
+    }
+
     pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
         // Serialize the struct to a JSON string
         let json = serde_json::to_string(self)?;
@@ -32,6 +117,250 @@ impl<N: Serialize> NetworkxDiGraph<N> {
 
         Ok(())
     }
+
+    /// Render the graph as GraphML. Node attributes are flattened into typed
+    /// `<data>` elements and edge weights are added to each `<edge>`.
+    pub fn to_graphml(&self) -> String {
+        let node_attributes: Vec<Vec<(String, Value)>> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let mut attributes = Vec::new();
+                flatten_graphml_attributes(
+                    &serde_json::to_value(node).unwrap_or(Value::Null),
+                    &mut attributes,
+                );
+                attributes
+            })
+            .collect();
+
+        let mut keys: Vec<(&String, &str)> = Vec::new();
+        for (name, value) in node_attributes.iter().flatten() {
+            if !keys.iter().any(|(key, _)| *key == name) {
+                keys.push((name, graphml_type(value)));
+            }
+        }
+
+        let mut graphml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        );
+        for (name, attr_type) in keys.iter() {
+            let name = escape_xml(name);
+            graphml.push_str(&format!(
+                "  <key id=\"{}\" for=\"node\" attr.name=\"{}\" attr.type=\"{}\"/>\n",
+                name, name, attr_type
+            ));
+        }
+        graphml.push_str(
+            "  <key id=\"edge_weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"long\"/>\n",
+        );
+        graphml.push_str("  <graph id=\"G\" edgedefault=\"directed\">\n");
+        for (i, attributes) in node_attributes.iter().enumerate() {
+            graphml.push_str(&format!("    <node id=\"n{}\">\n", i));
+            for (name, value) in attributes {
+                graphml.push_str(&format!(
+                    "      <data key=\"{}\">{}</data>\n",
+                    escape_xml(name),
+                    graphml_value(value)
+                ));
+            }
+            graphml.push_str("    </node>\n");
+        }
+        for (src, edges) in self.adjacency.iter().enumerate() {
+            for edge in edges {
+                graphml.push_str(&format!(
+                    "    <edge source=\"n{}\" target=\"n{}\">\n      <data key=\"edge_weight\">{}</data>\n    </edge>\n",
+                    src, edge.id, edge.weight
+                ));
+            }
+        }
+        graphml.push_str("  </graph>\n</graphml>\n");
+        graphml
+    }
+}
+
+fn flatten_graphml_attributes(value: &Value, attributes: &mut Vec<(String, Value)>) {
+    if let Value::Object(map) = value {
+        for (key, value) in map {
+            match value {
+                Value::Object(_) => flatten_graphml_attributes(value, attributes),
+                _ if key == "id" => {}
+                _ => attributes.push((key.clone(), value.clone())),
+            }
+        }
+    }
+}
+
+fn graphml_type(value: &Value) -> &'static str {
+    match value {
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_f64() => "double",
+        Value::Number(_) => "long",
+        _ => "string",
+    }
+}
+
+fn graphml_value(value: &Value) -> String {
+    match value {
+        Value::String(string) => escape_xml(string),
+        Value::Bool(_) | Value::Number(_) => value.to_string(),
+        _ => escape_xml(&value.to_string()),
+    }
+}
+
+fn escape_xml(string: &str) -> String {
+    string
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+impl<N: Serialize + DotLabel + PygFeatures> NetworkxDiGraph<N> {
+    /// Render the graph as GraphViz DOT. Nodes are labelled using their node label
+    /// (if set) or [`DotLabel`] and edges are annotated with their weight.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph {\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            let label = match self.node_labels.as_ref().and_then(|labels| labels.get(i)) {
+                Some(label) => label.clone(),
+                None => node.dot_label(),
+            };
+            dot.push_str(&format!(
+                "    {} [label=\"{}\"];\n",
+                i,
+                escape_dot_label(&label)
+            ));
+        }
+        for (src, edges) in self.adjacency.iter().enumerate() {
+            for edge in edges {
+                dot.push_str(&format!(
+                    "    {} -> {} [weight={}, label=\"{}\"];\n",
+                    src, edge.id, edge.weight, edge.weight
+                ));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Convert the graph into the layout used by `torch_geometric.data.Data` with
+    /// `edge_index` being a 2 x num_edges array of source/target node indices and `x`
+    /// a num_nodes x feature_dim array of node features.
+    ///
+    /// String feature types (such as disasm or esil) produce `x` as a list of token
+    /// strings per node. These need to be embedded by the user before use.
+    pub fn to_pyg_json(&self) -> Value {
+        let mut sources = Vec::new();
+        let mut targets = Vec::new();
+        for (src, edges) in self.adjacency.iter().enumerate() {
+            for edge in edges {
+                sources.push(src);
+                targets.push(edge.id);
+            }
+        }
+        let x: Vec<PygNodeFeatures> = self.nodes.iter().map(|n| n.pyg_features()).collect();
+
+        json!({
+            "edge_index": [sources, targets],
+            "x": x,
+        })
+    }
+
+    /// Save the graph to the given path in the requested format
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: &GraphFormat) -> std::io::Result<()> {
+        match format {
+            GraphFormat::Json => self.save_to_json(path),
+            GraphFormat::JsonGz => write_json_to_file(path, self),
+            GraphFormat::Dot => {
+                let mut file = File::create(path)?;
+                file.write_all(self.to_dot().as_bytes())?;
+                Ok(())
+            }
+            GraphFormat::Pyg => {
+                let file = File::create(path)?;
+                serde_json::to_writer(file, &self.to_pyg_json())?;
+                Ok(())
+            }
+            GraphFormat::GraphMl => {
+                let mut file = File::create(path)?;
+                file.write_all(self.to_graphml().as_bytes())?;
+                Ok(())
+            }
+        }
+    }
+}
+
+fn escape_dot_label(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// The on-disk format used when saving generated graphs
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GraphFormat {
+    #[default]
+    Json,
+    JsonGz,
+    Dot,
+    Pyg,
+    GraphMl,
+}
+
+impl GraphFormat {
+    pub fn new(graph_format: &str) -> GraphFormat {
+        match graph_format {
+            "json" => GraphFormat::Json,
+            "dot" => GraphFormat::Dot,
+            "pyg" => GraphFormat::Pyg,
+            "graphml" => GraphFormat::GraphMl,
+            _ => unreachable!("Invalid graph format"),
+        }
+    }
+
+    /// Applies the requested output compression. Only JSON output supports compression
+    pub fn with_compression(self, compress: Option<&str>) -> GraphFormat {
+        match (self, compress) {
+            (GraphFormat::Json, Some("gzip")) => GraphFormat::JsonGz,
+            (_, None) => self,
+            (_, Some(compress)) => {
+                warn!(
+                    "{} compression is only supported for JSON output. Will ignore.",
+                    compress
+                );
+                self
+            }
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            GraphFormat::Json => "json",
+            GraphFormat::JsonGz => "json.gz",
+            GraphFormat::Dot => "dot",
+            GraphFormat::Pyg => "pyg.json",
+            GraphFormat::GraphMl => "graphml",
+        }
+    }
+}
+
+/// Provides the label used for a node when a graph is rendered as DOT
+pub trait DotLabel {
+    fn dot_label(&self) -> String;
+}
+
+/// The features of a single node within a PyTorch Geometric `x` array
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PygNodeFeatures {
+    Numeric(Vec<f64>),
+    Tokens(Vec<String>),
+}
+
+/// Provides the features used for a node when a graph is exported in PyG layout
+pub trait PygFeatures {
+    fn pyg_features(&self) -> PygNodeFeatures;
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -39,6 +368,27 @@ impl<N: Serialize> NetworkxDiGraph<N> {
 pub struct Adjacency {
     pub id: usize,
     pub weight: u32,
+    /// How control leaves the source block (jump, fail or switch). CFG edges only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edge_type: Option<String>,
+}
+
+impl Adjacency {
+    /// Create a CFG edge from an edge list weight encoding the edge type. The
+    /// weight of the resulting edge is always 1
+    pub fn from_cfg_edge(id: usize, edge_type: u32) -> Adjacency {
+        let edge_type = match edge_type {
+            CFG_EDGE_JUMP => Some("jump".to_string()),
+            CFG_EDGE_FAIL => Some("fail".to_string()),
+            CFG_EDGE_SWITCH => Some("switch".to_string()),
+            _ => None,
+        };
+        Adjacency {
+            id,
+            weight: 1,
+            edge_type,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, EnumAsInner)]
@@ -51,6 +401,8 @@ pub enum NodeType {
     Esil(EsilNode),
     PCode(PCodeNode),
     Pseudo(PseudoNode),
+    OpcodeHistogram(OpcodeHistogramNode),
+    Bytes(BytesNode),
 }
 
 #[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize, EnumAsInner)]
@@ -62,6 +414,45 @@ pub enum CallGraphTypes {
     TikNibFinfo(NetworkxDiGraph<CallGraphTikNibFinfoFeatures>),
 }
 
+impl CallGraphTypes {
+    /// See [`NetworkxDiGraph::structural_hash`]
+    pub fn structural_hash(&self) -> u64 {
+        match self {
+            CallGraphTypes::TikNib(graph) => graph.structural_hash(),
+            CallGraphTypes::CGMeta(graph) => graph.structural_hash(),
+            CallGraphTypes::CGName(graph) => graph.structural_hash(),
+            CallGraphTypes::TikNibFinfo(graph) => graph.structural_hash(),
+        }
+    }
+
+    pub fn to_dot(&self) -> String {
+        match self {
+            CallGraphTypes::TikNib(graph) => graph.to_dot(),
+            CallGraphTypes::CGMeta(graph) => graph.to_dot(),
+            CallGraphTypes::CGName(graph) => graph.to_dot(),
+            CallGraphTypes::TikNibFinfo(graph) => graph.to_dot(),
+        }
+    }
+
+    pub fn to_pyg_json(&self) -> Value {
+        match self {
+            CallGraphTypes::TikNib(graph) => graph.to_pyg_json(),
+            CallGraphTypes::CGMeta(graph) => graph.to_pyg_json(),
+            CallGraphTypes::CGName(graph) => graph.to_pyg_json(),
+            CallGraphTypes::TikNibFinfo(graph) => graph.to_pyg_json(),
+        }
+    }
+
+    pub fn to_graphml(&self) -> String {
+        match self {
+            CallGraphTypes::TikNib(graph) => graph.to_graphml(),
+            CallGraphTypes::CGMeta(graph) => graph.to_graphml(),
+            CallGraphTypes::CGName(graph) => graph.to_graphml(),
+            CallGraphTypes::TikNibFinfo(graph) => graph.to_graphml(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum CallGraphNodeFeatureType {
@@ -96,6 +487,18 @@ impl From<(i64, &Vec<String>)> for DisasmNode {
     }
 }
 
+impl DotLabel for DisasmNode {
+    fn dot_label(&self) -> String {
+        format!("bb{}", self.id)
+    }
+}
+
+impl PygFeatures for DisasmNode {
+    fn pyg_features(&self) -> PygNodeFeatures {
+        PygNodeFeatures::Tokens(self.features.clone())
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EsilNode {
     pub id: i64,
@@ -111,6 +514,18 @@ impl From<(i64, &Vec<String>)> for EsilNode {
     }
 }
 
+impl DotLabel for EsilNode {
+    fn dot_label(&self) -> String {
+        format!("bb{}", self.id)
+    }
+}
+
+impl PygFeatures for EsilNode {
+    fn pyg_features(&self) -> PygNodeFeatures {
+        PygNodeFeatures::Tokens(self.features.clone())
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PseudoNode {
     pub id: i64,
@@ -126,6 +541,45 @@ impl From<(i64, &Vec<String>)> for PseudoNode {
     }
 }
 
+impl DotLabel for PseudoNode {
+    fn dot_label(&self) -> String {
+        format!("bb{}", self.id)
+    }
+}
+
+impl PygFeatures for PseudoNode {
+    fn pyg_features(&self) -> PygNodeFeatures {
+        PygNodeFeatures::Tokens(self.features.clone())
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BytesNode {
+    pub id: i64,
+    pub bytes: String,
+}
+
+impl From<(i64, &Vec<String>)> for BytesNode {
+    fn from(src: (i64, &Vec<String>)) -> BytesNode {
+        BytesNode {
+            id: src.0,
+            bytes: src.1.concat(),
+        }
+    }
+}
+
+impl DotLabel for BytesNode {
+    fn dot_label(&self) -> String {
+        format!("bb{}", self.id)
+    }
+}
+
+impl PygFeatures for BytesNode {
+    fn pyg_features(&self) -> PygNodeFeatures {
+        PygNodeFeatures::Tokens(vec![self.bytes.clone()])
+    }
+}
+
 #[derive(Copy, Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TiknibNode {
     pub id: i64,
@@ -140,6 +594,45 @@ impl From<(i64, &Vec<f64>)> for TiknibNode {
     }
 }
 
+impl DotLabel for TiknibNode {
+    fn dot_label(&self) -> String {
+        format!("bb{}", self.id)
+    }
+}
+
+impl PygFeatures for TiknibNode {
+    fn pyg_features(&self) -> PygNodeFeatures {
+        PygNodeFeatures::Numeric(self.features.to_vec())
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpcodeHistogramNode {
+    pub id: i64,
+    pub features: Vec<f64>,
+}
+
+impl From<(i64, &Vec<f64>)> for OpcodeHistogramNode {
+    fn from(src: (i64, &Vec<f64>)) -> OpcodeHistogramNode {
+        OpcodeHistogramNode {
+            id: src.0,
+            features: src.1.to_owned(),
+        }
+    }
+}
+
+impl DotLabel for OpcodeHistogramNode {
+    fn dot_label(&self) -> String {
+        format!("bb{}", self.id)
+    }
+}
+
+impl PygFeatures for OpcodeHistogramNode {
+    fn pyg_features(&self) -> PygNodeFeatures {
+        PygNodeFeatures::Numeric(self.features.clone())
+    }
+}
+
 #[derive(Default, Copy, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GeminiNode {
@@ -168,6 +661,26 @@ impl From<(i64, &Vec<f64>)> for GeminiNode {
     }
 }
 
+impl DotLabel for GeminiNode {
+    fn dot_label(&self) -> String {
+        format!("bb{}", self.id)
+    }
+}
+
+impl PygFeatures for GeminiNode {
+    fn pyg_features(&self) -> PygNodeFeatures {
+        PygNodeFeatures::Numeric(vec![
+            self.num_calls,
+            self.num_transfer,
+            self.num_arith,
+            self.num_ins,
+            self.numeric_consts,
+            self.string_consts,
+            self.num_offspring,
+        ])
+    }
+}
+
 #[derive(Default, Copy, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DGISNode {
@@ -198,6 +711,27 @@ impl From<(i64, &Vec<f64>)> for DGISNode {
     }
 }
 
+impl DotLabel for DGISNode {
+    fn dot_label(&self) -> String {
+        format!("bb{}", self.id)
+    }
+}
+
+impl PygFeatures for DGISNode {
+    fn pyg_features(&self) -> PygNodeFeatures {
+        PygNodeFeatures::Numeric(vec![
+            self.num_stack_ops,
+            self.num_arith_ops,
+            self.num_logic_ops,
+            self.num_cmp_ops,
+            self.num_lib_calls,
+            self.num_uncon_jumps,
+            self.num_con_jumps,
+            self.num_generic_ins,
+        ])
+    }
+}
+
 #[derive(Default, Copy, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DiscovreNode {
@@ -224,6 +758,25 @@ impl From<(i64, &Vec<f64>)> for DiscovreNode {
     }
 }
 
+impl DotLabel for DiscovreNode {
+    fn dot_label(&self) -> String {
+        format!("bb{}", self.id)
+    }
+}
+
+impl PygFeatures for DiscovreNode {
+    fn pyg_features(&self) -> PygNodeFeatures {
+        PygNodeFeatures::Numeric(vec![
+            self.num_calls,
+            self.num_transfer,
+            self.num_arith,
+            self.num_ins,
+            self.numeric_consts,
+            self.string_consts,
+        ])
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CallGraphFuncNameNode {
@@ -231,6 +784,18 @@ pub struct CallGraphFuncNameNode {
     pub func_name: String,
 }
 
+impl DotLabel for CallGraphFuncNameNode {
+    fn dot_label(&self) -> String {
+        self.func_name.clone()
+    }
+}
+
+impl PygFeatures for CallGraphFuncNameNode {
+    fn pyg_features(&self) -> PygNodeFeatures {
+        PygNodeFeatures::Tokens(vec![self.func_name.clone()])
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CallGraphFuncWithMetadata {
@@ -238,6 +803,26 @@ pub struct CallGraphFuncWithMetadata {
     pub func_name: String,
     pub function_feature_subset: AFIJFeatureSubset,
 }
+
+impl DotLabel for CallGraphFuncWithMetadata {
+    fn dot_label(&self) -> String {
+        self.func_name.clone()
+    }
+}
+
+impl PygFeatures for CallGraphFuncWithMetadata {
+    fn pyg_features(&self) -> PygNodeFeatures {
+        let subset = &self.function_feature_subset;
+        PygNodeFeatures::Numeric(vec![
+            subset.ninstrs as f64,
+            subset.edges as f64,
+            subset.indegree as f64,
+            subset.outdegree as f64,
+            subset.nlocals as f64,
+            subset.nargs as f64,
+        ])
+    }
+}
 impl From<Graph<String, u32>> for NetworkxDiGraph<CallGraphFuncNameNode> {
     fn from(src_graph: Graph<String, u32>) -> NetworkxDiGraph<CallGraphFuncNameNode> {
         let node_weights = src_graph.node_weights();
@@ -258,6 +843,7 @@ impl From<Graph<String, u32>> for NetworkxDiGraph<CallGraphFuncNameNode> {
                 let edge_entry = Adjacency {
                     id: edge.target().index(),
                     weight: edge.weight().to_owned(),
+                    edge_type: None,
                 };
                 node_adjacency_vec.push(edge_entry)
             }
@@ -270,6 +856,8 @@ impl From<Graph<String, u32>> for NetworkxDiGraph<CallGraphFuncNameNode> {
             graph: vec![],
             multigraph: false,
             nodes,
+            graph_meta: None,
+            node_labels: None,
         }
     }
 }
@@ -308,6 +896,7 @@ impl From<(Graph<String, u32>, &Vec<AFIJFeatureSubset>)>
                 let edge_entry = Adjacency {
                     id: edge.target().index(),
                     weight: edge.weight().to_owned(),
+                    edge_type: None,
                 };
                 node_adjacency_vec.push(edge_entry)
             }
@@ -320,6 +909,8 @@ impl From<(Graph<String, u32>, &Vec<AFIJFeatureSubset>)>
             graph: vec![],
             multigraph: false,
             nodes,
+            graph_meta: None,
+            node_labels: None,
         }
     }
 }
@@ -332,6 +923,18 @@ pub struct CallGraphTikNibFeatures {
     pub features: TikNibFunc,
 }
 
+impl DotLabel for CallGraphTikNibFeatures {
+    fn dot_label(&self) -> String {
+        self.func_name.clone()
+    }
+}
+
+impl PygFeatures for CallGraphTikNibFeatures {
+    fn pyg_features(&self) -> PygNodeFeatures {
+        PygNodeFeatures::Numeric(self.features.features.to_vec())
+    }
+}
+
 impl From<(Graph<String, u32>, &Vec<TikNibFunc>)> for NetworkxDiGraph<CallGraphTikNibFeatures> {
     fn from(
         src_graph: (Graph<String, u32>, &Vec<TikNibFunc>),
@@ -364,6 +967,7 @@ impl From<(Graph<String, u32>, &Vec<TikNibFunc>)> for NetworkxDiGraph<CallGraphT
                 let edge_entry = Adjacency {
                     id: edge.target().index(),
                     weight: edge.weight().to_owned(),
+                    edge_type: None,
                 };
                 node_adjacency_vec.push(edge_entry)
             }
@@ -376,6 +980,8 @@ impl From<(Graph<String, u32>, &Vec<TikNibFunc>)> for NetworkxDiGraph<CallGraphT
             graph: vec![],
             multigraph: false,
             nodes,
+            graph_meta: None,
+            node_labels: None,
         }
     }
 }
@@ -388,6 +994,39 @@ pub struct CallGraphTikNibFinfoFeatures {
     pub features: FinfoTiknib,
 }
 
+impl DotLabel for CallGraphTikNibFinfoFeatures {
+    fn dot_label(&self) -> String {
+        self.func_name.clone()
+    }
+}
+
+impl PygFeatures for CallGraphTikNibFinfoFeatures {
+    fn pyg_features(&self) -> PygNodeFeatures {
+        let features = &self.features;
+        PygNodeFeatures::Numeric(vec![
+            features.edges as f64,
+            features.indegree as f64,
+            features.outdegree as f64,
+            features.nlocals as f64,
+            features.nargs as f64,
+            features.avg_arithshift.into_inner() as f64,
+            features.avg_compare.into_inner() as f64,
+            features.avg_ctransfer.into_inner() as f64,
+            features.avg_ctransfercond.into_inner() as f64,
+            features.avg_dtransfer.into_inner() as f64,
+            features.avg_float.into_inner() as f64,
+            features.avg_total.into_inner() as f64,
+            features.sum_arithshift.into_inner() as f64,
+            features.sum_compare.into_inner() as f64,
+            features.sum_ctransfer.into_inner() as f64,
+            features.sum_ctransfercond.into_inner() as f64,
+            features.sum_dtransfer.into_inner() as f64,
+            features.sum_float.into_inner() as f64,
+            features.sum_total.into_inner() as f64,
+        ])
+    }
+}
+
 impl From<(Graph<String, u32>, &Vec<FinfoTiknib>)>
     for NetworkxDiGraph<CallGraphTikNibFinfoFeatures>
 {
@@ -422,6 +1061,7 @@ impl From<(Graph<String, u32>, &Vec<FinfoTiknib>)>
                 let edge_entry = Adjacency {
                     id: edge.target().index(),
                     weight: edge.weight().to_owned(),
+                    edge_type: None,
                 };
                 node_adjacency_vec.push(edge_entry)
             }
@@ -434,14 +1074,18 @@ impl From<(Graph<String, u32>, &Vec<FinfoTiknib>)>
             graph: vec![],
             multigraph: false,
             nodes,
+            graph_meta: None,
+            node_labels: None,
         }
     }
 }
 
-impl From<(&Graph<String, u32>, &Vec<Vec<String>>, FeatureType)> for NetworkxDiGraph<NodeType> {
-    fn from(
+impl TryFrom<(&Graph<String, u32>, &Vec<Vec<String>>, FeatureType)> for NetworkxDiGraph<NodeType> {
+    type Error = anyhow::Error;
+
+    fn try_from(
         input: (&Graph<String, u32>, &Vec<Vec<String>>, FeatureType),
-    ) -> NetworkxDiGraph<NodeType> {
+    ) -> Result<NetworkxDiGraph<NodeType>> {
         let mut nodes: Vec<NodeType> = vec![];
 
         for (i, node_vector) in input.1.iter().enumerate() {
@@ -453,12 +1097,18 @@ impl From<(&Graph<String, u32>, &Vec<Vec<String>>, FeatureType)> for NetworkxDiG
                 FeatureType::Pseudo => {
                     Some(NodeType::Pseudo(PseudoNode::from((i as i64, node_vector))))
                 }
-                _ => todo!(),
+                FeatureType::Bytes => {
+                    Some(NodeType::Bytes(BytesNode::from((i as i64, node_vector))))
+                }
+                _ => None,
             };
-            if let Some(node) = node {
-                nodes.push(node);
-            } else {
-                error!("Failed to create node for input!")
+            match node {
+                Some(node) => nodes.push(node),
+                None => bail!(
+                    "Unable to create nodes with {:?} features from {} node features",
+                    input.2,
+                    "string"
+                ),
             }
         }
 
@@ -469,29 +1119,31 @@ impl From<(&Graph<String, u32>, &Vec<Vec<String>>, FeatureType)> for NetworkxDiG
             let mut node_adjacency_vec = vec![];
             let node_edges = input.0.edges(node);
             for edge in node_edges {
-                let edge_entry = Adjacency {
-                    id: edge.target().index(),
-                    weight: edge.weight().to_owned(),
-                };
+                let edge_entry =
+                    Adjacency::from_cfg_edge(edge.target().index(), edge.weight().to_owned());
                 node_adjacency_vec.push(edge_entry)
             }
             adjacency.push(node_adjacency_vec)
         }
 
-        NetworkxDiGraph {
+        Ok(NetworkxDiGraph {
             adjacency,
             directed: "True".to_string(),
             graph: vec![],
             multigraph: false,
             nodes,
-        }
+            graph_meta: None,
+            node_labels: None,
+        })
     }
 }
 
-impl From<(&Graph<String, u32>, &Vec<Vec<f64>>, FeatureType)> for NetworkxDiGraph<NodeType> {
-    fn from(
+impl TryFrom<(&Graph<String, u32>, &Vec<Vec<f64>>, FeatureType)> for NetworkxDiGraph<NodeType> {
+    type Error = anyhow::Error;
+
+    fn try_from(
         input: (&Graph<String, u32>, &Vec<Vec<f64>>, FeatureType),
-    ) -> NetworkxDiGraph<NodeType> {
+    ) -> Result<NetworkxDiGraph<NodeType>> {
         let mut nodes: Vec<NodeType> = vec![];
 
         // Get nodes into the JSON thingie
@@ -508,14 +1160,20 @@ impl From<(&Graph<String, u32>, &Vec<Vec<f64>>, FeatureType)> for NetworkxDiGrap
                 FeatureType::Tiknib => {
                     Some(NodeType::Tiknib(TiknibNode::from((i as i64, node_vector))))
                 }
+                FeatureType::OpcodeHistogram => Some(NodeType::OpcodeHistogram(
+                    OpcodeHistogramNode::from((i as i64, node_vector)),
+                )),
 
                 _ => None,
             };
 
-            if let Some(node) = node {
-                nodes.push(node);
-            } else {
-                error!("Failed to create node for input!")
+            match node {
+                Some(node) => nodes.push(node),
+                None => bail!(
+                    "Unable to create nodes with {:?} features from {} node features",
+                    input.2,
+                    "numeric"
+                ),
             }
         }
 
@@ -527,22 +1185,22 @@ impl From<(&Graph<String, u32>, &Vec<Vec<f64>>, FeatureType)> for NetworkxDiGrap
             let mut node_adjacency_vec = vec![];
             let node_edges = input.0.edges(node);
             for edge in node_edges {
-                let edge_entry = Adjacency {
-                    id: edge.target().index(),
-                    weight: edge.weight().to_owned(),
-                };
+                let edge_entry =
+                    Adjacency::from_cfg_edge(edge.target().index(), edge.weight().to_owned());
                 node_adjacency_vec.push(edge_entry)
             }
             adjacency.push(node_adjacency_vec)
         }
 
-        NetworkxDiGraph {
+        Ok(NetworkxDiGraph {
             adjacency,
             directed: "True".to_string(),
             graph: vec![],
             multigraph: false,
             nodes,
-        }
+            graph_meta: None,
+            node_labels: None,
+        })
     }
 }
 
@@ -561,6 +1219,8 @@ impl From<NetworkxDiGraph<NodeType>> for NetworkxDiGraph<GeminiNode> {
             graph: vec![],
             multigraph: false,
             nodes: inner_nodes_types,
+            graph_meta: src.graph_meta,
+            node_labels: src.node_labels,
         }
     }
 }
@@ -580,6 +1240,8 @@ impl From<NetworkxDiGraph<NodeType>> for NetworkxDiGraph<DGISNode> {
             graph: vec![],
             multigraph: false,
             nodes: inner_nodes_types,
+            graph_meta: src.graph_meta,
+            node_labels: src.node_labels,
         }
     }
 }
@@ -599,6 +1261,8 @@ impl From<NetworkxDiGraph<NodeType>> for NetworkxDiGraph<DiscovreNode> {
             graph: vec![],
             multigraph: false,
             nodes: inner_nodes_types,
+            graph_meta: src.graph_meta,
+            node_labels: src.node_labels,
         }
     }
 }
@@ -618,6 +1282,8 @@ impl From<NetworkxDiGraph<NodeType>> for NetworkxDiGraph<TiknibNode> {
             graph: vec![],
             multigraph: false,
             nodes: inner_nodes_types,
+            graph_meta: src.graph_meta,
+            node_labels: src.node_labels,
         }
     }
 }
@@ -637,6 +1303,8 @@ impl From<NetworkxDiGraph<NodeType>> for NetworkxDiGraph<DisasmNode> {
             graph: vec![],
             multigraph: false,
             nodes: inner_nodes_types,
+            graph_meta: src.graph_meta,
+            node_labels: src.node_labels,
         }
     }
 }
@@ -656,6 +1324,29 @@ impl From<NetworkxDiGraph<NodeType>> for NetworkxDiGraph<EsilNode> {
             graph: vec![],
             multigraph: false,
             nodes: inner_nodes_types,
+            graph_meta: src.graph_meta,
+            node_labels: src.node_labels,
+        }
+    }
+}
+
+impl From<NetworkxDiGraph<NodeType>> for NetworkxDiGraph<OpcodeHistogramNode> {
+    fn from(src: NetworkxDiGraph<NodeType>) -> NetworkxDiGraph<OpcodeHistogramNode> {
+        let inner_nodes_types: Vec<OpcodeHistogramNode> = src
+            .clone()
+            .nodes
+            .into_iter()
+            .map(|el| el.as_opcode_histogram().unwrap().clone())
+            .collect();
+
+        NetworkxDiGraph {
+            adjacency: src.adjacency,
+            directed: src.directed,
+            graph: vec![],
+            multigraph: false,
+            nodes: inner_nodes_types,
+            graph_meta: src.graph_meta,
+            node_labels: src.node_labels,
         }
     }
 }
@@ -675,6 +1366,29 @@ impl From<NetworkxDiGraph<NodeType>> for NetworkxDiGraph<PseudoNode> {
             graph: vec![],
             multigraph: false,
             nodes: inner_nodes_types,
+            graph_meta: src.graph_meta,
+            node_labels: src.node_labels,
+        }
+    }
+}
+
+impl From<NetworkxDiGraph<NodeType>> for NetworkxDiGraph<BytesNode> {
+    fn from(src: NetworkxDiGraph<NodeType>) -> NetworkxDiGraph<BytesNode> {
+        let inner_nodes_types: Vec<BytesNode> = src
+            .clone()
+            .nodes
+            .into_iter()
+            .map(|el| el.as_bytes().unwrap().clone())
+            .collect();
+
+        NetworkxDiGraph {
+            adjacency: src.adjacency,
+            directed: src.directed,
+            graph: vec![],
+            multigraph: false,
+            nodes: inner_nodes_types,
+            graph_meta: src.graph_meta,
+            node_labels: src.node_labels,
         }
     }
 }
@@ -696,27 +1410,52 @@ impl From<(u64, u64, &Vec<String>)> for PCodeNode {
     }
 }
 
+impl DotLabel for PCodeNode {
+    fn dot_label(&self) -> String {
+        format!("{:#x}", self.start_addr)
+    }
+}
+
+impl PygFeatures for PCodeNode {
+    fn pyg_features(&self) -> PygNodeFeatures {
+        PygNodeFeatures::Tokens(self.features.clone())
+    }
+}
+
+/// Builds a PCode CFG from the graph and block start addresses returned by
+/// `PCodeJsonWithBBAndFuncName::get_cfg`. Each node holds the PCode of its basic
+/// block. Nodes for edge targets without PCode (i.e blocks outside of the function)
+/// have no features so that the node ids always line up with the adjacency.
 impl From<(&Graph<String, u32>, &PCodeJsonWithBBAndFuncName, &Vec<u32>)>
-    for NetworkxDiGraph<PCodeNode>
+    for NetworkxDiGraph<NodeType>
 {
     fn from(
         input: (&Graph<String, u32>, &PCodeJsonWithBBAndFuncName, &Vec<u32>),
-    ) -> NetworkxDiGraph<PCodeNode> {
+    ) -> NetworkxDiGraph<NodeType> {
         let mut nodes: Vec<NodeType> = vec![];
 
-        for (idx, address) in input.2.iter().enumerate() {
-            let pcode_node = input
+        for node in input.0.node_indices() {
+            let address = input.2[node.index()];
+            let pcode_block = input
                 .1
                 .pcode_blocks
                 .iter()
-                .find(|ele| ele.block_start_adr as u32 == *address);
-            if let Some(pcode_node) = pcode_node {
-                nodes.push(NodeType::PCode(PCodeNode::from((
-                    idx as u64,
-                    pcode_node.block_start_adr,
-                    &pcode_node.pcode,
-                ))))
-            }
+                .find(|ele| ele.block_start_adr as u32 == address);
+            let pcode_node = match pcode_block {
+                Some(pcode_block) => PCodeNode::from((
+                    node.index() as u64,
+                    pcode_block.block_start_adr,
+                    &pcode_block.pcode,
+                )),
+                None => {
+                    debug!(
+                        "No PCode found for block {:#x} in {}",
+                        address, input.1.function_name
+                    );
+                    PCodeNode::from((node.index() as u64, address as u64, &vec![]))
+                }
+            };
+            nodes.push(NodeType::PCode(pcode_node))
         }
 
         // Sort edges out
@@ -730,23 +1469,287 @@ impl From<(&Graph<String, u32>, &PCodeJsonWithBBAndFuncName, &Vec<u32>)>
                 let edge_entry = Adjacency {
                     id: edge.target().index(),
                     weight: edge.weight().to_owned(),
+                    edge_type: None,
                 };
                 node_adjacency_vec.push(edge_entry)
             }
             adjacency.push(node_adjacency_vec)
         }
 
-        let inner_nodes_types: Vec<PCodeNode> = nodes
+        NetworkxDiGraph {
+            adjacency,
+            directed: "True".to_string(),
+            graph: vec![],
+            multigraph: false,
+            nodes,
+            graph_meta: None,
+            node_labels: None,
+        }
+    }
+}
+
+impl From<NetworkxDiGraph<NodeType>> for NetworkxDiGraph<PCodeNode> {
+    fn from(src: NetworkxDiGraph<NodeType>) -> NetworkxDiGraph<PCodeNode> {
+        let inner_nodes_types: Vec<PCodeNode> = src
+            .nodes
             .into_iter()
             .map(|el| el.as_p_code().unwrap().clone())
             .collect();
 
         NetworkxDiGraph {
-            adjacency,
-            directed: "True".to_string(),
+            adjacency: src.adjacency,
+            directed: src.directed,
             graph: vec![],
             multigraph: false,
             nodes: inner_nodes_types,
+            graph_meta: src.graph_meta,
+            node_labels: src.node_labels,
+        }
+    }
+}
+
+impl From<(&Graph<String, u32>, &PCodeJsonWithBBAndFuncName, &Vec<u32>)>
+    for NetworkxDiGraph<PCodeNode>
+{
+    fn from(
+        input: (&Graph<String, u32>, &PCodeJsonWithBBAndFuncName, &Vec<u32>),
+    ) -> NetworkxDiGraph<PCodeNode> {
+        NetworkxDiGraph::<PCodeNode>::from(NetworkxDiGraph::<NodeType>::from(input))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn func_name_graph(
+        names: &[&str],
+        edges: &[(usize, usize)],
+    ) -> NetworkxDiGraph<CallGraphFuncNameNode> {
+        let mut graph = Graph::<String, u32>::new();
+        let nodes: Vec<_> = names
+            .iter()
+            .map(|name| graph.add_node(name.to_string()))
+            .collect();
+        for (src, dst) in edges {
+            graph.add_edge(nodes[*src], nodes[*dst], 1);
         }
+        NetworkxDiGraph::<CallGraphFuncNameNode>::from(graph)
+    }
+
+    #[test]
+    fn test_structural_hash_ignores_node_order() {
+        let graph = func_name_graph(&["main", "foo", "bar"], &[(0, 1), (0, 2), (1, 2)]);
+        let reordered = func_name_graph(&["bar", "main", "foo"], &[(1, 2), (1, 0), (2, 0)]);
+
+        assert_ne!(graph, reordered);
+        assert_eq!(graph.structural_hash(), reordered.structural_hash());
+        assert_eq!(
+            CallGraphTypes::CGName(graph).structural_hash(),
+            CallGraphTypes::CGName(reordered).structural_hash()
+        );
+    }
+
+    #[test]
+    fn test_structural_hash_differs_for_different_graphs() {
+        let graph = func_name_graph(&["main", "foo", "bar"], &[(0, 1), (0, 2)]);
+        let different_edges = func_name_graph(&["main", "foo", "bar"], &[(0, 1), (1, 2)]);
+        let different_features = func_name_graph(&["main", "foo", "baz"], &[(0, 1), (0, 2)]);
+
+        assert_ne!(graph.structural_hash(), different_edges.structural_hash());
+        assert_ne!(
+            graph.structural_hash(),
+            different_features.structural_hash()
+        );
+    }
+
+    #[test]
+    fn test_call_graph_to_dot() {
+        let mut graph = Graph::<String, u32>::new();
+        let main = graph.add_node("main".to_string());
+        let callee = graph.add_node("sym.imp.\"quoted\"".to_string());
+        graph.add_edge(main, callee, 3);
+
+        let networkx_graph = NetworkxDiGraph::<CallGraphFuncNameNode>::from(graph);
+        let dot = networkx_graph.to_dot();
+
+        assert_eq!(
+            dot,
+            "digraph {\n    0 [label=\"main\"];\n    1 [label=\"sym.imp.\\\"quoted\\\"\"];\n    0 -> 1 [weight=3, label=\"3\"];\n}\n"
+        );
+        assert!(dot.starts_with("digraph {"));
+        assert!(dot.trim_end().ends_with('}'));
+    }
+
+    #[test]
+    fn test_cfg_to_dot_uses_block_labels() {
+        let networkx_graph = NetworkxDiGraph {
+            adjacency: vec![
+                vec![Adjacency {
+                    id: 1,
+                    weight: 0,
+                    edge_type: None,
+                }],
+                vec![],
+            ],
+            directed: "True".to_string(),
+            graph: vec![],
+            multigraph: false,
+            graph_meta: None,
+            node_labels: None,
+            nodes: vec![
+                PCodeNode::from((0, 0x1000, &vec![])),
+                PCodeNode::from((1, 0x1010, &vec![])),
+            ],
+        };
+        let dot = networkx_graph.to_dot();
+        assert!(dot.contains("0 [label=\"0x1000\"];"));
+        assert!(dot.contains("1 [label=\"0x1010\"];"));
+        assert!(dot.contains("0 -> 1 [weight=0, label=\"0\"];"));
+    }
+
+    #[test]
+    fn test_cfg_to_pyg_json() {
+        let networkx_graph = NetworkxDiGraph {
+            adjacency: vec![
+                vec![
+                    Adjacency {
+                        id: 1,
+                        weight: 0,
+                        edge_type: None,
+                    },
+                    Adjacency {
+                        id: 2,
+                        weight: 0,
+                        edge_type: None,
+                    },
+                ],
+                vec![Adjacency {
+                    id: 2,
+                    weight: 0,
+                    edge_type: None,
+                }],
+                vec![],
+            ],
+            directed: "True".to_string(),
+            graph: vec![],
+            multigraph: false,
+            graph_meta: None,
+            node_labels: None,
+            nodes: vec![
+                DiscovreNode::from((0, &vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0])),
+                DiscovreNode::from((1, &vec![0.0; 6])),
+                DiscovreNode::from((2, &vec![1.0; 6])),
+            ],
+        };
+        let pyg = networkx_graph.to_pyg_json();
+
+        assert_eq!(pyg["edge_index"], json!([[0, 0, 1], [1, 2, 2]]));
+        assert_eq!(pyg["x"].as_array().unwrap().len(), 3);
+        assert_eq!(pyg["x"][0], json!([1.0, 2.0, 3.0, 4.0, 5.0, 6.0]));
+    }
+
+    #[test]
+    fn test_string_features_to_pyg_json() {
+        let networkx_graph = NetworkxDiGraph {
+            adjacency: vec![
+                vec![Adjacency {
+                    id: 1,
+                    weight: 0,
+                    edge_type: None,
+                }],
+                vec![],
+            ],
+            directed: "True".to_string(),
+            graph: vec![],
+            multigraph: false,
+            graph_meta: None,
+            node_labels: None,
+            nodes: vec![
+                DisasmNode::from((0, &vec!["push rbp".to_string()])),
+                DisasmNode::from((1, &vec!["ret".to_string()])),
+            ],
+        };
+        let pyg = networkx_graph.to_pyg_json();
+
+        assert_eq!(pyg["edge_index"], json!([[0], [1]]));
+        assert_eq!(pyg["x"], json!([["push rbp"], ["ret"]]));
+    }
+
+    #[test]
+    fn test_unsupported_feature_type_is_an_error() {
+        let graph = Graph::<String, u32>::from_edges([(0, 1, 1)]);
+        let string_features = vec![vec!["push rbp".to_string()], vec!["ret".to_string()]];
+        let numeric_features = vec![vec![1.0, 2.0], vec![3.0, 4.0]];
+
+        assert!(NetworkxDiGraph::<NodeType>::try_from((
+            &graph,
+            &string_features,
+            FeatureType::Gemini
+        ))
+        .is_err());
+        assert!(NetworkxDiGraph::<NodeType>::try_from((
+            &graph,
+            &numeric_features,
+            FeatureType::Esil
+        ))
+        .is_err());
+
+        let networkx_graph =
+            NetworkxDiGraph::<NodeType>::try_from((&graph, &string_features, FeatureType::Disasm))
+                .unwrap();
+        assert_eq!(networkx_graph.nodes.len(), 2);
+    }
+
+    #[test]
+    fn test_call_graph_to_graphml_escapes_names() {
+        let mut graph = Graph::<String, u32>::new();
+        let caller = graph.add_node("std::vector<int>::operator[]".to_string());
+        let callee = graph.add_node("operator&<'a', \"b\">".to_string());
+        graph.add_edge(caller, callee, 1);
+
+        let networkx_graph = NetworkxDiGraph::<CallGraphFuncNameNode>::from(graph);
+        let graphml = networkx_graph.to_graphml();
+
+        assert!(graphml.contains(
+            "<key id=\"funcName\" for=\"node\" attr.name=\"funcName\" attr.type=\"string\"/>"
+        ));
+        assert!(
+            graphml.contains("<data key=\"funcName\">std::vector&lt;int&gt;::operator[]</data>")
+        );
+        assert!(graphml.contains(
+            "<data key=\"funcName\">operator&amp;&lt;&apos;a&apos;, &quot;b&quot;&gt;</data>"
+        ));
+        assert!(graphml.contains(
+            "<edge source=\"n0\" target=\"n1\">\n      <data key=\"edge_weight\">1</data>"
+        ));
+        assert!(!graphml.contains("<'a'"));
+    }
+
+    #[test]
+    fn test_call_graph_with_metadata_to_graphml() {
+        let mut graph = Graph::<String, u32>::new();
+        graph.add_node("main".to_string());
+        let metadata = vec![AFIJFeatureSubset {
+            name: "main".to_string(),
+            ninstrs: 10,
+            edges: 2,
+            indegree: 0,
+            outdegree: 1,
+            nlocals: 3,
+            nargs: 2,
+            signature: "int main(int argc, char **argv);".to_string(),
+        }];
+
+        let networkx_graph = NetworkxDiGraph::<CallGraphFuncWithMetadata>::from((graph, &metadata));
+        let graphml = networkx_graph.to_graphml();
+
+        assert!(graphml.contains(
+            "<key id=\"ninstrs\" for=\"node\" attr.name=\"ninstrs\" attr.type=\"long\"/>"
+        ));
+        assert!(graphml.contains("<data key=\"funcName\">main</data>"));
+        assert!(graphml.contains("<data key=\"ninstrs\">10</data>"));
+        assert!(graphml.contains("<data key=\"nlocals\">3</data>"));
+        assert!(!graphml.contains("key=\"id\""));
     }
 }
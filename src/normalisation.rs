@@ -5,64 +5,248 @@ use crate::consts::{
     GENERAL_PURPOSE_32_BIT_REGS, GENERAL_PURPOSE_64_BIT_REGS, MULTI_ARCH_FRAME_POINTERS,
     RISCV_32_BIT_REGS,
 };
-use regex::Regex;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+/// The architectures which have their own normalisation rules
+///
+/// These differ in how immediates and memory operands are written in the disassembly
+/// i.e `[rax + 0x10]` for X86/ARM or `0x10(sp)` for MIPS/RISC-V.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NormalizationArch {
+    X86,
+    Arm,
+    Mips,
+    RiscV,
+}
+
+impl NormalizationArch {
+    pub fn from_architecture(architecture: &str) -> Option<Self> {
+        match architecture.to_uppercase().as_str() {
+            "X86" => Some(Self::X86),
+            "ARM" | "AARCH64" => Some(Self::Arm),
+            "MIPS" => Some(Self::Mips),
+            "RISCV" => Some(Self::RiscV),
+            _ => None,
+        }
+    }
+
+    fn has_bracketed_operands(arch: Option<Self>) -> bool {
+        matches!(arch, None | Some(Self::X86) | Some(Self::Arm))
+    }
+
+    fn has_offset_operands(arch: Option<Self>) -> bool {
+        matches!(arch, None | Some(Self::Mips) | Some(Self::RiscV))
+    }
+}
+
+/// The parts of an instruction which are normalised
+///
+/// When `arch` is not set, the rules for every architecture are applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NormalizationConfig {
+    /// Replace registers with a mask based on their size i.e reg64
+    pub registers: bool,
+    /// Replace immediates with IMM
+    pub immediates: bool,
+    /// Replace memory addresses with MEM
+    pub memory: bool,
+    /// Replace string, function and data references with STR, FUNC and DATA
+    pub references: bool,
+    /// Replace numeric immediates with a bucket token (see [`normalise_immediates`])
+    /// before any other normalisation is applied
+    pub bucket_immediates: bool,
+    pub arch: Option<NormalizationArch>,
+}
+
+pub const NORMALIZATION_PRESETS: [&str; 3] = ["none", "regs", "aggressive"];
+
+impl Default for NormalizationConfig {
+    fn default() -> Self {
+        Self::from_reg_norm(false)
+    }
+}
+
+impl NormalizationConfig {
+    /// The normalisation applied by --reg-norm. Everything other than registers is
+    /// always normalised with registers toggled by `reg_norm`.
+    pub fn from_reg_norm(reg_norm: bool) -> Self {
+        NormalizationConfig {
+            registers: reg_norm,
+            immediates: true,
+            memory: true,
+            references: true,
+            bucket_immediates: false,
+            arch: None,
+        }
+    }
+
+    /// Create a config from one of the [`NORMALIZATION_PRESETS`]
+    ///
+    /// * `none` - Instructions are left as is other than removing commas and collapsing
+    ///   multi byte nops
+    /// * `regs` - Only registers are normalised
+    /// * `aggressive` - Registers, immediates, memory addresses and references are normalised
+    pub fn from_preset(preset: &str) -> Option<Self> {
+        let (registers, others) = match preset {
+            "none" => (false, false),
+            "regs" => (true, false),
+            "aggressive" => (true, true),
+            _ => return None,
+        };
+        Some(NormalizationConfig {
+            registers,
+            immediates: others,
+            memory: others,
+            references: others,
+            bucket_immediates: false,
+            arch: None,
+        })
+    }
+
+    pub fn with_bucket_immediates(mut self, bucket_immediates: bool) -> Self {
+        self.bucket_immediates = bucket_immediates;
+        self
+    }
+
+    /// Sets the architecture specific rules to apply. Unknown architectures apply
+    /// the rules for every architecture.
+    pub fn with_architecture(mut self, architecture: Option<&str>) -> Self {
+        self.arch = architecture.and_then(NormalizationArch::from_architecture);
+        self
+    }
+}
+
+// The characters which separate the operands of an instruction. Brackets are not
+// included for ESIL as they denote the size of a memory access i.e [8]
+const DISASM_OPERAND_SEPARATORS: [char; 8] = [' ', ',', '[', ']', '(', ')', '+', '*'];
+const ESIL_OPERAND_SEPARATORS: [char; 2] = [' ', ','];
+
+/// Maps a numeric immediate to a token based on its sign and magnitude
+///
+/// * `IMM_NEG` - Negative values i.e stack offsets such as -0x10
+/// * `IMM_SMALL` - Values below 0x100 i.e loop counters and struct offsets
+/// * `IMM_MEDIUM` - Values below 0x10000
+/// * `IMM_ADDR` - Anything larger, which is most likely an address
+///
+/// Tokens which are not numeric (including ARM # prefixed values) are returned as is.
+pub fn normalise_immediates(token: &str) -> String {
+    let value = token.trim_start_matches('#');
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value),
+        None => (false, value),
+    };
+    let parsed = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse::<u64>(),
+    };
+
+    match parsed {
+        Ok(0) => "IMM_SMALL".to_string(),
+        Ok(_) if negative => "IMM_NEG".to_string(),
+        Ok(value) if value < 0x100 => "IMM_SMALL".to_string(),
+        Ok(value) if value < 0x10000 => "IMM_MEDIUM".to_string(),
+        Ok(_) => "IMM_ADDR".to_string(),
+        Err(_) => token.to_string(),
+    }
+}
+
+// Applies normalise_immediates to every operand of an instruction
+fn bucket_immediates(input: &str, separators: &[char]) -> String {
+    let mut bucketed = String::with_capacity(input.len());
+    let mut token = String::new();
+    for c in input.chars() {
+        if separators.contains(&c) {
+            bucketed.push_str(&normalise_immediates(&token));
+            bucketed.push(c);
+            token.clear();
+        } else {
+            token.push(c);
+        }
+    }
+    bucketed.push_str(&normalise_immediates(&token));
+    bucketed
+}
 
 // Cross Arch Disasm Normalisation
-pub fn normalise_disasm_simple(input: &str, reg_norm: bool) -> String {
-    let orig = input.to_owned();
+pub fn normalise_disasm_simple(input: &str, config: &NormalizationConfig) -> String {
+    let orig = if config.bucket_immediates {
+        bucket_immediates(input, &DISASM_OPERAND_SEPARATORS)
+    } else {
+        input.to_owned()
+    };
     // Remove commas
     let normalised = orig.replace(',', " ");
     // Replace any cases where there are two spaces with only one
-    let normalised = normalised.replace("  ", " ");
+    let mut normalised = normalised.replace("  ", " ");
 
-    let re = Regex::new(r"(0xffff[0-9a-fA-F]{1,})").unwrap();
-    let normalised = re.replace_all(&normalised, "IMM");
+    if config.immediates {
+        let re = Regex::new(r"(0xffff[0-9a-fA-F]{1,})").unwrap();
+        normalised = re.replace_all(&normalised, "IMM").to_string();
 
-    // Immediates used as mem offsets in X86
-    let re = Regex::new(r"(0[xX][0-9a-fA-F]{1,3}])").unwrap();
-    let normalised = re.replace_all(&normalised, "IMM]");
+        // ARM immediates can be prefixed with a # i.e #0x10 or #-4
+        if config.arch == Some(NormalizationArch::Arm) {
+            let re = Regex::new(r"#-?(0[xX][0-9a-fA-F]+|[0-9]+)").unwrap();
+            normalised = re.replace_all(&normalised, "IMM").to_string();
+        }
 
-    // Offsets used in MIPS
-    let re = Regex::new(r"(0[xX][0-9a-fA-F]{1,4})\(").unwrap();
-    let normalised = re.replace_all(&normalised, "IMM(");
+        // Immediates used as mem offsets in X86 and ARM
+        if NormalizationArch::has_bracketed_operands(config.arch) {
+            let re = Regex::new(r"(0[xX][0-9a-fA-F]{1,3}])").unwrap();
+            normalised = re.replace_all(&normalised, "IMM]").to_string();
+        }
+
+        // Offsets used in MIPS and RISC-V
+        if NormalizationArch::has_offset_operands(config.arch) {
+            let re = Regex::new(r"(0[xX][0-9a-fA-F]{1,4})\(").unwrap();
+            normalised = re.replace_all(&normalised, "IMM(").to_string();
+        }
+    }
 
-    // Memory addresses
-    // This normalisation is very naive. It assume any hex value longer than 0x+4 digits
-    // is a memory address. This regex also includes to variants - One to catch straight
-    // memory addrs and another to catch an edge case in r2 output.
-    let re = Regex::new(r"(case\.|0x|aav\.){0,1}0x[0-9a-fA-F]{3,}(.[0-9]){0,}").unwrap();
-    let normalised = re.replace_all(&normalised, "MEM");
+    if config.memory {
+        // Memory addresses
+        // This normalisation is very naive. It assume any hex value longer than 0x+4 digits
+        // is a memory address. This regex also includes to variants - One to catch straight
+        // memory addrs and another to catch an edge case in r2 output.
+        let re = Regex::new(r"(case\.|0x|aav\.){0,1}0x[0-9a-fA-F]{3,}(.[0-9]){0,}").unwrap();
+        normalised = re.replace_all(&normalised, "MEM").to_string();
+    }
 
-    // Strings
-    let re = Regex::new(r"(str\S*[^!\s][_|s]{0,1})").unwrap();
-    let normalised = re.replace_all(&normalised, "STR");
+    if config.references {
+        // Strings
+        let re = Regex::new(r"(str\S*[^!\s][_|s]{0,1})").unwrap();
+        normalised = re.replace_all(&normalised, "STR").to_string();
 
-    // c++ funcs
-    let re = Regex::new(r"method.*[^!\s]\(*.*(\)|>*)").unwrap();
-    let normalised = re.replace_all(&normalised, "FUNC");
+        // c++ funcs
+        let re = Regex::new(r"method.*[^!\s]\(*.*(\)|>*)").unwrap();
+        normalised = re.replace_all(&normalised, "FUNC").to_string();
 
-    // fcn and sym calls
-    let re = Regex::new(r"(fcn|sym).*[^!\s]").unwrap();
-    let normalised = re.replace_all(&normalised, "FUNC");
+        // fcn and sym calls
+        let re = Regex::new(r"(fcn|sym).*[^!\s]").unwrap();
+        normalised = re.replace_all(&normalised, "FUNC").to_string();
 
-    // obj in brackets
-    let re = Regex::new(r"[-]{0,1}[\[]{0,1}obj\S*[\]]{0,1}").unwrap();
-    let normalised = re.replace_all(&normalised, "DATA");
+        // obj in brackets
+        let re = Regex::new(r"[-]{0,1}[\[]{0,1}obj\S*[\]]{0,1}").unwrap();
+        normalised = re.replace_all(&normalised, "DATA").to_string();
 
-    // reloc in brackets
-    let re = Regex::new(r"\[reloc\S*\]").unwrap();
-    let normalised = re.replace_all(&normalised, "FUNC");
+        // reloc in brackets
+        let re = Regex::new(r"\[reloc\S*\]").unwrap();
+        normalised = re.replace_all(&normalised, "FUNC").to_string();
 
-    // loc calls
-    let re = Regex::new(r"loc.[a-z]+.[a-z_]+").unwrap();
-    let normalised = re.replace_all(&normalised, "FUNC");
+        // loc calls
+        let re = Regex::new(r"loc.[a-z]+.[a-z_]+").unwrap();
+        normalised = re.replace_all(&normalised, "FUNC").to_string();
+    }
 
     // Normalise multi byte nops
     let re = Regex::new(r"nop.*").unwrap();
     let normalised = re.replace_all(&normalised, "nop");
 
     // Register Normalisation
-    if reg_norm {
+    if config.registers {
         // Split the disasm into it's parts
         let split: Vec<&str> = normalised.split(' ').filter(|e| !e.is_empty()).collect();
         // Match parts of the split instruction with known regs and apply mask
@@ -122,28 +306,37 @@ pub fn normalise_disasm_simple(input: &str, reg_norm: bool) -> String {
     }
 }
 
-pub fn normalise_esil_simple(input: &str, op_type: &str, reg_norm: bool) -> String {
-    let orig = input.to_owned();
+pub fn normalise_esil_simple(input: &str, op_type: &str, config: &NormalizationConfig) -> String {
+    let mut normalised = if config.bucket_immediates {
+        bucket_immediates(input, &ESIL_OPERAND_SEPARATORS)
+    } else {
+        input.to_owned()
+    };
+
+    if config.immediates {
+        let re = Regex::new(r"(0xffff[0-9a-fA-F]{1,},)").unwrap();
+        normalised = re.replace_all(&normalised, "IMM,").to_string();
 
-    let re = Regex::new(r"(0xffff[0-9a-fA-F]{1,},)").unwrap();
-    let normalised = re.replace_all(&orig, "IMM,");
+        let re = Regex::new(r"(0[xX][0-9a-fA-F]{1,3},)").unwrap();
+        normalised = re.replace_all(&normalised, "IMM,").to_string();
+    }
 
-    let re = Regex::new(r"(0[xX][0-9a-fA-F]{1,3},)").unwrap();
-    let normalised = re.replace_all(&normalised, "IMM,");
+    if config.memory {
+        let re = Regex::new(r"(0[xX][0-9a-fA-F]{4,},)").unwrap();
+        normalised = re.replace_all(&normalised, "MEM,").to_string();
+    }
 
-    let re = Regex::new(r"(0[xX][0-9a-fA-F]{4,},)").unwrap();
-    let normalised = re.replace_all(&normalised, "MEM,");
-    // let n_features = if reduced { 6 } else { 7 };
-    let normalised = if op_type == "call" {
-        let re = Regex::new(r"([0-9]{4,}?,)").unwrap();
-        re.replace_all(&normalised, "FUNC,")
-    } else {
+    if config.references {
         let re = Regex::new(r"([0-9]{4,}?,)").unwrap();
-        re.replace_all(&normalised, "DATA,")
-    };
-    debug!("Reg Norm: {:?}", reg_norm);
+        normalised = if op_type == "call" {
+            re.replace_all(&normalised, "FUNC,").to_string()
+        } else {
+            re.replace_all(&normalised, "DATA,").to_string()
+        };
+    }
+    debug!("Normalisation Config: {:?}", config);
     debug!("Pre-Reg Norm: {:?}", normalised);
-    if reg_norm {
+    if config.registers {
         // Split the esil into it's parts
         let split: Vec<&str> = normalised.split(',').filter(|e| !e.is_empty()).collect();
         debug!("Reg Norm - Post Split: {:?}", split);
@@ -167,21 +360,158 @@ pub fn normalise_esil_simple(input: &str, op_type: &str, reg_norm: bool) -> Stri
     }
 }
 
+// PCode Normalisation
+//
+// PCode is left untouched unless reg_norm is set. Named registers are masked
+// in the same way as ESIL, alongside register and unique (temporary) varnodes
+// which are masked based on their size in bits.
+pub fn normalise_pcode_simple(input: &str, reg_norm: bool) -> String {
+    if !reg_norm {
+        return input.to_string();
+    }
+
+    // Register varnodes i.e (register,0x20,8)
+    let re = Regex::new(r"\(register,0[xX][0-9a-fA-F]+,([0-9]+)\)").unwrap();
+    let normalised = re.replace_all(input, |caps: &Captures| {
+        let size: u32 = caps[1].parse().unwrap();
+        format!("reg{}", size * 8)
+    });
+
+    // Unique varnodes i.e (unique,0x12e80,4)
+    let re = Regex::new(r"\(unique,0[xX][0-9a-fA-F]+,([0-9]+)\)").unwrap();
+    let normalised = re.replace_all(&normalised, |caps: &Captures| {
+        let size: u32 = caps[1].parse().unwrap();
+        format!("unique{}", size * 8)
+    });
+
+    // Named registers - PCode uses upper case register names i.e RBP or EAX
+    let re = Regex::new(r"\b[A-Za-z_][A-Za-z0-9_]*\b").unwrap();
+    let normalised = re.replace_all(&normalised, |caps: &Captures| {
+        let token = caps[0].to_lowercase();
+        if GENERAL_PURPOSE_32_BIT_REGS.contains(&token.as_str())
+            || RISCV_32_BIT_REGS.contains(&token.as_str())
+        {
+            "reg32".to_string()
+        } else if GENERAL_PURPOSE_64_BIT_REGS.contains(&token.as_str()) {
+            "reg64".to_string()
+        } else {
+            caps[0].to_string()
+        }
+    });
+    debug!("PCode Post Reg Norm: {:?}", normalised);
+    normalised.to_string()
+}
+
+#[cfg(test)]
 mod tests {
-    use super::normalise_esil_simple;
+    use super::{
+        normalise_esil_simple, normalise_immediates, normalise_pcode_simple, NormalizationConfig,
+    };
     use crate::normalisation::normalise_disasm_simple;
 
     // Helper Normalisation Functions
     #[allow(dead_code)]
     fn normalise_esil(input: &str, op_type: &str, norm_regs: bool) -> String {
         let ins: String = String::from(input);
-        normalise_esil_simple(&ins, op_type, norm_regs)
+        normalise_esil_simple(
+            &ins,
+            op_type,
+            &NormalizationConfig::from_reg_norm(norm_regs),
+        )
     }
 
     #[allow(dead_code)]
     fn normalise_disasm(input: &str, norm_regs: bool) -> String {
         let ins: String = String::from(input);
-        normalise_disasm_simple(&ins, norm_regs)
+        normalise_disasm_simple(&ins, &NormalizationConfig::from_reg_norm(norm_regs))
+    }
+
+    #[allow(dead_code)]
+    fn normalise_disasm_preset(input: &str, preset: &str, arch: &str) -> String {
+        let config = NormalizationConfig::from_preset(preset)
+            .unwrap()
+            .with_architecture(Some(arch));
+        normalise_disasm_simple(input, &config)
+    }
+
+    // Normalisation Preset Tests
+    #[test]
+    fn test_disasm_x86_norm_presets() {
+        let ins = "mov dword [rax + 0x10], 0x1234";
+        assert_eq!(
+            normalise_disasm_preset(ins, "none", "X86"),
+            "mov dword [rax + 0x10] 0x1234"
+        );
+        assert_eq!(
+            normalise_disasm_preset(ins, "regs", "X86"),
+            "mov dword [reg64 + 0x10] 0x1234"
+        );
+        assert_eq!(
+            normalise_disasm_preset(ins, "aggressive", "X86"),
+            "mov dword [reg64 + IMM] MEM"
+        );
+
+        let ins = "call sym.imp.puts";
+        assert_eq!(normalise_disasm_preset(ins, "none", "X86"), ins);
+        assert_eq!(normalise_disasm_preset(ins, "regs", "X86"), ins);
+        assert_eq!(
+            normalise_disasm_preset(ins, "aggressive", "X86"),
+            "call FUNC"
+        );
+    }
+
+    #[test]
+    fn test_disasm_arm_norm_presets() {
+        let ins = "ldr w0, [sp, #0x1c]";
+        assert_eq!(
+            normalise_disasm_preset(ins, "none", "AARCH64"),
+            "ldr w0 [sp #0x1c]"
+        );
+        assert_eq!(
+            normalise_disasm_preset(ins, "regs", "AARCH64"),
+            "ldr reg32 [sp #0x1c]"
+        );
+        assert_eq!(
+            normalise_disasm_preset(ins, "aggressive", "AARCH64"),
+            "ldr reg32 [sp IMM]"
+        );
+
+        let ins = "mov r0, #5";
+        assert_eq!(normalise_disasm_preset(ins, "none", "ARM"), "mov r0 #5");
+        assert_eq!(normalise_disasm_preset(ins, "regs", "ARM"), "mov reg32 #5");
+        assert_eq!(
+            normalise_disasm_preset(ins, "aggressive", "ARM"),
+            "mov reg32 IMM"
+        );
+    }
+
+    #[test]
+    fn test_disasm_norm_arch_specific_offsets() {
+        // MIPS style offsets are only normalised for architectures which use them
+        assert_eq!(
+            normalise_disasm_preset("sw ra, 0x1c(sp)", "aggressive", "MIPS"),
+            "sw ra IMM(sp)"
+        );
+        assert_eq!(
+            normalise_disasm_preset("sw ra, 0x1c(sp)", "aggressive", "X86"),
+            "sw ra 0x1c(sp)"
+        );
+    }
+
+    #[test]
+    fn test_esil_norm_presets() {
+        let ins = "0x30,rbp,-,[8],rax,=";
+        let config = |preset| NormalizationConfig::from_preset(preset).unwrap();
+        assert_eq!(normalise_esil_simple(ins, "not_call", &config("none")), ins);
+        assert_eq!(
+            normalise_esil_simple(ins, "not_call", &config("regs")),
+            "0x30 rbp - [8] reg64 ="
+        );
+        assert_eq!(
+            normalise_esil_simple(ins, "not_call", &config("aggressive")),
+            "IMM rbp - [8] reg64 ="
+        );
+        assert!(NormalizationConfig::from_preset("unknown").is_none());
     }
 
     #[test]
@@ -292,6 +622,106 @@ mod tests {
                    "0 MEM reg32 & == 31 $s nf := $z zf := 0 cf := 0 vf := xzr 16 sp + DUP tmp = =[8] DATA")
     }
 
+    // Immediate Bucketing Tests
+    #[test]
+    fn test_normalise_immediates_buckets() {
+        // Hex addresses
+        assert_eq!(normalise_immediates("0x401000"), "IMM_ADDR");
+        assert_eq!(normalise_immediates("0x7fffffffe000"), "IMM_ADDR");
+        // Small constants
+        assert_eq!(normalise_immediates("0"), "IMM_SMALL");
+        assert_eq!(normalise_immediates("8"), "IMM_SMALL");
+        assert_eq!(normalise_immediates("0x3d"), "IMM_SMALL");
+        assert_eq!(normalise_immediates("#0x1c"), "IMM_SMALL");
+        assert_eq!(normalise_immediates("0x1234"), "IMM_MEDIUM");
+        // Negative offsets
+        assert_eq!(normalise_immediates("-16"), "IMM_NEG");
+        assert_eq!(normalise_immediates("-0x10"), "IMM_NEG");
+        // Non-numeric tokens are untouched
+        assert_eq!(normalise_immediates("rax"), "rax");
+        assert_eq!(normalise_immediates("0xzz"), "0xzz");
+        assert_eq!(normalise_immediates("-"), "-");
+    }
+
+    #[test]
+    fn test_bucket_immediates_disasm_and_esil() {
+        let config = NormalizationConfig::from_preset("none")
+            .unwrap()
+            .with_bucket_immediates(true);
+        assert_eq!(
+            normalise_disasm_simple("mov qword [rbp + -0x8], 0x401000", &config),
+            "mov qword [rbp + IMM_NEG] IMM_ADDR"
+        );
+        assert_eq!(
+            normalise_disasm_simple("sw ra, 0x1c(sp)", &config),
+            "sw ra IMM_SMALL(sp)"
+        );
+        assert_eq!(
+            normalise_esil_simple("sp,-16,+,sp,=,0x30,rbp,-,[8],rax,=", "not_call", &config),
+            "sp,IMM_NEG,+,sp,=,IMM_SMALL,rbp,-,[8],rax,="
+        );
+
+        // Bucketing happens before the rest of the normalisation
+        let config = NormalizationConfig::from_reg_norm(true).with_bucket_immediates(true);
+        assert_eq!(
+            normalise_disasm_simple("add byte [rax + 0x3d], bh", &config),
+            "add byte [reg64 + IMM_SMALL] bh"
+        );
+    }
+
+    // PCode Normalisation Tests
+    #[test]
+    fn test_pcode_no_reg_norm() {
+        assert_eq!(
+            normalise_pcode_simple("EBP = INT_XOR EBP, EBP", false),
+            "EBP = INT_XOR EBP, EBP"
+        );
+        assert_eq!(
+            normalise_pcode_simple("(unique,0x12e80,4) = INT_AND EBP, 0xff", false),
+            "(unique,0x12e80,4) = INT_AND EBP, 0xff"
+        );
+    }
+
+    #[test]
+    fn test_pcode_x86_reg_norm() {
+        assert_eq!(
+            normalise_pcode_simple("EAX = INT_XOR EAX, EAX", true),
+            "reg32 = INT_XOR reg32, reg32"
+        );
+        assert_eq!(
+            normalise_pcode_simple("RAX = INT_ZEXT EAX", true),
+            "reg64 = INT_ZEXT reg32"
+        );
+        assert_eq!(
+            normalise_pcode_simple("ZF = INT_EQUAL EAX, 0x0", true),
+            "ZF = INT_EQUAL reg32, 0x0"
+        );
+    }
+
+    #[test]
+    fn test_pcode_arm_reg_norm() {
+        assert_eq!(
+            normalise_pcode_simple("r0 = INT_ADD r1, 0x4", true),
+            "reg32 = INT_ADD reg32, 0x4"
+        );
+        assert_eq!(
+            normalise_pcode_simple("x0 = LOAD ram(x1)", true),
+            "reg64 = LOAD ram(reg64)"
+        );
+    }
+
+    #[test]
+    fn test_pcode_varnode_reg_norm() {
+        assert_eq!(
+            normalise_pcode_simple("(register,0x20,4) = COPY (register,0x0,8)", true),
+            "reg32 = COPY reg64"
+        );
+        assert_eq!(
+            normalise_pcode_simple("(unique,0x12f00,1) = POPCOUNT (unique,0x12e80,4)", true),
+            "unique8 = POPCOUNT unique32"
+        );
+    }
+
     // x86 Disasm Normalisation Tests
     #[test]
     fn test_disasm_x86_imm_offset() {
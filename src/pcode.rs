@@ -1,19 +1,20 @@
+use crate::errors::ExitCode;
 use crate::extract::{PCodeJSONWithFuncName, PCodeJsonWithBB, PCodeJsonWithBBAndFuncName};
 use crate::files::FormatMode;
-use crate::networkx::NetworkxDiGraph;
-use crate::utils::get_save_file_path;
+use crate::networkx::{GraphFormat, NetworkxDiGraph, PCodeNode};
+use crate::normalisation::normalise_pcode_simple;
+use crate::utils::{get_save_file_path, read_json_to_string};
 use enum_as_inner::EnumAsInner;
 use indicatif::ParallelProgressIterator;
 use petgraph::Graph;
-use rayon::iter::IntoParallelRefIterator;
+use rayon::iter::{IntoParallelRefIterator, IntoParallelRefMutIterator};
 use rayon::prelude::ParallelIterator;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs::{read_to_string, File};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
-use std::process::exit;
-use std::sync::mpsc::channel;
 
 #[derive(Serialize, Deserialize, Debug, EnumAsInner, Clone)]
 #[serde(untagged)]
@@ -22,6 +23,109 @@ pub enum PCodeDataTypes {
     PCodeJsonWithBB(PCodeJsonWithBBAndFuncName),
 }
 
+impl PCodeDataTypes {
+    /// Rename the varnodes within the function into SSA form (see [`PCodeSSA`])
+    pub fn rename_to_ssa(&mut self) {
+        let mut ssa = PCodeSSA::new();
+        match self {
+            PCodeDataTypes::PCodeJSON(function) => {
+                for instruction in function.pcode.pcode.iter_mut() {
+                    *instruction = ssa.rename(instruction)
+                }
+            }
+            PCodeDataTypes::PCodeJsonWithBB(function) => {
+                for block in function.pcode_blocks.iter_mut() {
+                    for instruction in block.pcode.iter_mut() {
+                        *instruction = ssa.rename(instruction)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Lightweight SSA renaming of the varnodes within a function's PCode
+///
+/// Each definition of a varnode increments its subscript and each use refers to the
+/// latest definition i.e RSP#1, (register,0x20,8) -> register0x20:8#1 and
+/// (unique,0xea00,8) -> unique0xea00:8#1. Varnodes used before they are defined
+/// have a subscript of 0. Instructions are renamed in the order they are provided so
+/// no phi nodes are inserted where control flow joins.
+pub struct PCodeSSA {
+    varnode_re: Regex,
+    versions: HashMap<String, u32>,
+}
+
+impl PCodeSSA {
+    pub fn new() -> Self {
+        PCodeSSA {
+            varnode_re: Regex::new(
+                r"\((unique|register),(0[xX][0-9a-fA-F]+),([0-9]+)\)|\b[A-Z][A-Z0-9_]*\b",
+            )
+            .unwrap(),
+            versions: HashMap::new(),
+        }
+    }
+
+    /// Rename a single PCode instruction i.e RSP = INT_SUB RSP, 0x8 -> RSP#1 = INT_SUB RSP#0, 0x8
+    pub fn rename(&mut self, instruction: &str) -> String {
+        let (output, expression) = match instruction.split_once(" = ") {
+            Some((output, expression)) => (Some(output), expression),
+            None => (None, instruction),
+        };
+        // Uses are renamed before the definition so INT_SUB RSP refers to the previous RSP
+        let expression = match expression.split_once(' ') {
+            Some((opcode, inputs)) => format!("{} {}", opcode, self.rename_uses(inputs)),
+            None => expression.to_string(),
+        };
+
+        match output {
+            // The output of a STORE is the value being stored so is a use rather than a definition
+            Some(output) if expression.starts_with("STORE") || !self.is_varnode(output) => {
+                format!("{} = {}", self.rename_uses(output), expression)
+            }
+            Some(output) => {
+                let name = Self::varnode_name(&self.varnode_re.captures(output).unwrap());
+                let version = self.versions.entry(name.clone()).or_insert(0);
+                *version += 1;
+                format!("{}#{} = {}", name, version, expression)
+            }
+            None => expression,
+        }
+    }
+
+    fn is_varnode(&self, operand: &str) -> bool {
+        self.varnode_re
+            .find(operand)
+            .is_some_and(|m| m.start() == 0 && m.end() == operand.len())
+    }
+
+    fn rename_uses(&self, operands: &str) -> String {
+        self.varnode_re
+            .replace_all(operands, |caps: &regex::Captures| {
+                let name = Self::varnode_name(caps);
+                let version = self.versions.get(&name).copied().unwrap_or(0);
+                format!("{}#{}", name, version)
+            })
+            .to_string()
+    }
+
+    fn varnode_name(caps: &regex::Captures) -> String {
+        match (caps.get(1), caps.get(2), caps.get(3)) {
+            (Some(space), Some(offset), Some(size)) => {
+                format!("{}{}:{}", space.as_str(), offset.as_str(), size.as_str())
+            }
+            _ => caps[0].to_string(),
+        }
+    }
+}
+
+impl Default for PCodeSSA {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum PCodeFileTypes {
     PCodeJsonFile,
@@ -37,17 +141,25 @@ pub struct PCodeFile {
     pub instruction_pairs: bool,
     pub format_type: FormatMode,
     pub pcode_file_type: PCodeFileTypes,
+    pub reg_norm: bool,
+    pub ssa: bool,
+    pub sort_output: bool,
 }
 
 pub trait PCodeToNLP {
-    fn get_linear_walk(&self, pairs: bool) -> Vec<String>;
-    fn get_func_string(&self) -> HashMap<String, String>;
+    fn get_linear_walk(&self, pairs: bool, reg_norm: bool) -> Vec<String>;
+    fn get_func_string(&self, reg_norm: bool) -> BTreeMap<String, String>;
 }
 
 impl PCodeToNLP for PCodeJSONWithFuncName {
-    fn get_linear_walk(&self, pairs: bool) -> Vec<String> {
+    fn get_linear_walk(&self, pairs: bool, reg_norm: bool) -> Vec<String> {
+        let pcode: Vec<String> = self
+            .pcode
+            .pcode
+            .iter()
+            .map(|x| normalise_pcode_simple(x, reg_norm))
+            .collect();
         if pairs {
-            let pcode: &Vec<String> = self.pcode.pcode.as_ref();
             let ret = pcode.iter().zip(pcode.iter().skip(1)).collect::<Vec<_>>();
 
             let ret = ret
@@ -56,17 +168,15 @@ impl PCodeToNLP for PCodeJSONWithFuncName {
                 .collect();
             ret
         } else {
-            self.pcode.pcode.clone()
+            pcode
         }
     }
 
-    fn get_func_string(&self) -> HashMap<String, String> {
-        let mut func_string_mapping: HashMap<String, String> = HashMap::new();
-        let func_string = self
-            .pcode
-            .pcode
-            .iter()
-            .fold(String::new(), |acc, x| format!("{} {}", acc, x));
+    fn get_func_string(&self, reg_norm: bool) -> BTreeMap<String, String> {
+        let mut func_string_mapping: BTreeMap<String, String> = BTreeMap::new();
+        let func_string = self.pcode.pcode.iter().fold(String::new(), |acc, x| {
+            format!("{} {}", acc, normalise_pcode_simple(x, reg_norm))
+        });
         let func_string = func_string.trim().to_string();
         func_string_mapping.insert(self.function_name.clone(), func_string);
         func_string_mapping
@@ -74,13 +184,13 @@ impl PCodeToNLP for PCodeJSONWithFuncName {
 }
 
 impl PCodeToNLP for PCodeJsonWithBBAndFuncName {
-    fn get_linear_walk(&self, pairs: bool) -> Vec<String> {
+    fn get_linear_walk(&self, pairs: bool, reg_norm: bool) -> Vec<String> {
         let pcode_blocks: &Vec<PCodeJsonWithBB> = self.pcode_blocks.as_ref();
         let mut pcode_output: Vec<String> = Vec::new();
 
         if pairs {
             for block in pcode_blocks {
-                let pcode: &Vec<String> = block.pcode.as_ref();
+                let pcode = block.get_normalised_pcode(reg_norm);
                 let ret_inner = pcode.iter().zip(pcode.iter().skip(1)).collect::<Vec<_>>();
                 let ret_inner: Vec<String> = ret_inner
                     .iter()
@@ -90,7 +200,7 @@ impl PCodeToNLP for PCodeJsonWithBBAndFuncName {
             }
         } else {
             for block in pcode_blocks {
-                let pcode: &Vec<String> = block.pcode.as_ref();
+                let pcode = block.get_normalised_pcode(reg_norm);
                 pcode_output.push(pcode.join("\n"));
             }
         }
@@ -98,12 +208,12 @@ impl PCodeToNLP for PCodeJsonWithBBAndFuncName {
         pcode_output
     }
 
-    fn get_func_string(&self) -> HashMap<String, String> {
-        let mut func_string_mapping: HashMap<String, String> = HashMap::new();
+    fn get_func_string(&self, reg_norm: bool) -> BTreeMap<String, String> {
+        let mut func_string_mapping: BTreeMap<String, String> = BTreeMap::new();
         let pcode_blocks: &Vec<PCodeJsonWithBB> = self.pcode_blocks.as_ref();
         let mut func_string: Vec<String> = Vec::new();
         for block in pcode_blocks {
-            let pcode: &Vec<String> = block.pcode.as_ref();
+            let pcode = block.get_normalised_pcode(reg_norm);
             func_string.push(pcode.join(" "))
         }
 
@@ -114,7 +224,7 @@ impl PCodeToNLP for PCodeJsonWithBBAndFuncName {
 }
 
 impl PCodeFile {
-    #[allow(dead_code)]
+    #[allow(dead_code, clippy::too_many_arguments)]
     pub fn new(
         filename: PathBuf,
         output_path: PathBuf,
@@ -122,6 +232,9 @@ impl PCodeFile {
         instruction_pairs: bool,
         format_type: FormatMode,
         pcode_file_type: PCodeFileTypes,
+        reg_norm: bool,
+        ssa: bool,
+        sort_output: bool,
     ) -> Self {
         PCodeFile {
             filename,
@@ -131,11 +244,14 @@ impl PCodeFile {
             instruction_pairs,
             format_type,
             pcode_file_type,
+            reg_norm,
+            ssa,
+            sort_output,
         }
     }
 
     pub fn load_and_deserialize(&mut self) -> Result<(), ()> {
-        let data = read_to_string(&self.filename);
+        let data = read_json_to_string(&self.filename);
 
         if let Ok(data) = data {
             let pcode_obj: Vec<PCodeDataTypes> = serde_json::from_str(&data).unwrap();
@@ -147,7 +263,13 @@ impl PCodeFile {
         }
     }
 
-    pub fn set_pcode_obj(&mut self, pcode_obj: Vec<PCodeDataTypes>) {
+    /// Sets the PCode for the file, renaming the varnodes into SSA form if required
+    pub fn set_pcode_obj(&mut self, mut pcode_obj: Vec<PCodeDataTypes>) {
+        if self.ssa {
+            pcode_obj
+                .par_iter_mut()
+                .for_each(PCodeDataTypes::rename_to_ssa);
+        }
         self.pcode_obj = Some(pcode_obj);
     }
 
@@ -165,18 +287,15 @@ impl PCodeFile {
     fn pcode_json_single_instruction(&mut self, fname_string: PathBuf) {
         let pcode_obj = self.pcode_obj.clone().unwrap();
 
-        let (sender, receiver) = channel();
-
-        pcode_obj.par_iter().for_each_with(sender, |s, func| {
-            s.send(
+        // Collected in order (rather than via a channel) so the output is reproducible
+        let res: Vec<Vec<String>> = pcode_obj
+            .par_iter()
+            .map(|func| {
                 func.as_p_code_json()
                     .unwrap()
-                    .get_linear_walk(self.instruction_pairs),
-            )
-            .unwrap()
-        });
-
-        let res: Vec<Vec<String>> = receiver.iter().collect();
+                    .get_linear_walk(self.instruction_pairs, self.reg_norm)
+            })
+            .collect();
         let write_file = File::create(fname_string).unwrap();
         let mut writer = BufWriter::new(&write_file);
 
@@ -196,14 +315,17 @@ impl PCodeFile {
     fn pcode_json_func_as_string(&mut self, fname_string: PathBuf) {
         let pcode_obj = self.pcode_obj.clone().unwrap();
 
-        let (sender, receiver) = channel();
-
-        pcode_obj.par_iter().for_each_with(sender, |s, func| {
-            s.send(func.as_p_code_json().unwrap().get_func_string())
-                .unwrap()
-        });
-
-        let res: Vec<HashMap<String, String>> = receiver.iter().collect();
+        let mut res: Vec<BTreeMap<String, String>> = pcode_obj
+            .par_iter()
+            .map(|func| {
+                func.as_p_code_json()
+                    .unwrap()
+                    .get_func_string(self.reg_norm)
+            })
+            .collect();
+        if self.sort_output {
+            res.sort();
+        }
         let write_file = File::create(fname_string).unwrap();
         let mut writer = BufWriter::new(&write_file);
 
@@ -217,7 +339,7 @@ impl PCodeFile {
     /// format type and input PCode file type.
     fn get_output_filepath(&self) -> PathBuf {
         let fname_string: PathBuf =
-            get_save_file_path(&self.filename, &self.output_path, None, None, None);
+            get_save_file_path(&self.filename, &self.output_path, None, None, None, None);
 
         let fname_string = match (self.format_type, self.pcode_file_type.clone()) {
             (FormatMode::SingleInstruction, PCodeFileTypes::PCodeJsonFile) => {
@@ -248,7 +370,7 @@ impl PCodeFile {
                     "Invalid Format Mode - Got {:?} and {:?}",
                     self.format_type, self.pcode_file_type
                 );
-                exit(1)
+                ExitCode::BadArgs.exit()
             }
         };
 
@@ -267,7 +389,7 @@ impl PCodeFile {
 
             if ret.is_err() {
                 error!("Error loading and deserializing PCode file");
-                exit(1)
+                ExitCode::Failure.exit()
             }
 
             match (self.format_type, self.pcode_file_type.clone()) {
@@ -296,18 +418,14 @@ impl PCodeFile {
     fn pcode_json_with_bb_info_single_instruction(&mut self, fname_string: PathBuf) {
         let pcode_obj = self.pcode_obj.clone().unwrap();
 
-        let (sender, receiver) = channel();
-
-        pcode_obj.par_iter().for_each_with(sender, |s, func| {
-            s.send(
+        let res: Vec<Vec<String>> = pcode_obj
+            .par_iter()
+            .map(|func| {
                 func.as_p_code_json_with_bb()
                     .unwrap()
-                    .get_linear_walk(self.instruction_pairs),
-            )
-            .unwrap()
-        });
-
-        let res: Vec<Vec<String>> = receiver.iter().collect();
+                    .get_linear_walk(self.instruction_pairs, self.reg_norm)
+            })
+            .collect();
         let write_file = File::create(fname_string).unwrap();
         let mut writer = BufWriter::new(&write_file);
 
@@ -324,14 +442,17 @@ impl PCodeFile {
     fn pcode_json_with_bb_info_func_as_string(&mut self, fname_string: PathBuf) {
         let pcode_obj = self.pcode_obj.clone().unwrap();
 
-        let (sender, receiver) = channel();
-
-        pcode_obj.par_iter().for_each_with(sender, |s, func| {
-            s.send(func.as_p_code_json_with_bb().unwrap().get_func_string())
-                .unwrap()
-        });
-
-        let res: Vec<HashMap<String, String>> = receiver.iter().collect();
+        let mut res: Vec<BTreeMap<String, String>> = pcode_obj
+            .par_iter()
+            .map(|func| {
+                func.as_p_code_json_with_bb()
+                    .unwrap()
+                    .get_func_string(self.reg_norm)
+            })
+            .collect();
+        if self.sort_output {
+            res.sort();
+        }
         let write_file = File::create(fname_string).unwrap();
         let mut writer = BufWriter::new(&write_file);
 
@@ -343,7 +464,10 @@ impl PCodeFile {
         }
     }
 
-    pub fn pcode_json_with_bb_info_generate_cfg(&mut self) -> Result<(), ()> {
+    pub fn pcode_json_with_bb_info_generate_cfg(
+        &mut self,
+        graph_format: &GraphFormat,
+    ) -> Result<(), ()> {
         let pcode_obj = self.pcode_obj.clone().unwrap();
 
         pcode_obj.par_iter().progress().for_each(|function| {
@@ -352,18 +476,20 @@ impl PCodeFile {
                 .unwrap()
                 .function_name
                 .clone();
-            let pcode_json_with_bb = function.as_p_code_json_with_bb().unwrap();
-            let (graph, start_addrs) = pcode_json_with_bb.get_cfg();
-            let nx_graph = NetworkxDiGraph::from((&graph, pcode_json_with_bb, &start_addrs));
+            let nx_graph = function.as_p_code_json_with_bb().unwrap().to_networkx();
             let mut file_out_path =
-                get_save_file_path(&self.filename, &self.output_path, None, None, None);
-            file_out_path.push(&format!("{}_pcode_cfg.json", &function_name));
+                get_save_file_path(&self.filename, &self.output_path, None, None, None, None);
+            file_out_path.push(&format!(
+                "{}_pcode_cfg.{}",
+                &function_name,
+                graph_format.extension()
+            ));
 
             if !file_out_path.parent().unwrap().exists() {
                 std::fs::create_dir_all(file_out_path.parent().unwrap()).unwrap();
             }
 
-            let ret = nx_graph.save_to_json(&file_out_path);
+            let ret = nx_graph.save(&file_out_path, graph_format);
             if ret.is_ok() {
                 debug!("Successfully saved CFG for function: {}", &function_name);
             } else {
@@ -378,7 +504,23 @@ impl PCodeFile {
     }
 }
 
+impl PCodeJsonWithBB {
+    /// Get the PCode for the basic block, applying register normalisation if required
+    fn get_normalised_pcode(&self, reg_norm: bool) -> Vec<String> {
+        self.pcode
+            .iter()
+            .map(|x| normalise_pcode_simple(x, reg_norm))
+            .collect()
+    }
+}
+
 impl PCodeJsonWithBBAndFuncName {
+    /// Build the CFG of the function with the PCode of each basic block as the node features
+    pub fn to_networkx(&self) -> NetworkxDiGraph<PCodeNode> {
+        let (graph, start_addrs) = self.get_cfg();
+        NetworkxDiGraph::from((&graph, self, &start_addrs))
+    }
+
     pub fn get_cfg(&self) -> (Graph<String, u32>, Vec<u32>) {
         let pcode_blocks: &Vec<PCodeJsonWithBB> = self.pcode_blocks.as_ref();
         let mut edge_list: Vec<(u32, u32, u32)> = Vec::new();
@@ -423,9 +565,10 @@ impl PCodeJsonWithBBAndFuncName {
 
 #[cfg(test)]
 mod tests {
+    use crate::extract::{BasicBlockMetadataEntry, PCodeJsonWithBB, PCodeJsonWithBBAndFuncName};
     use crate::files::FormatMode;
-    use crate::networkx::{NetworkxDiGraph, PCodeNode};
-    use crate::pcode::{PCodeFile, PCodeFileTypes};
+    use crate::networkx::{NetworkxDiGraph, NodeType, PCodeNode};
+    use crate::pcode::{PCodeDataTypes, PCodeFile, PCodeFileTypes, PCodeSSA};
     use petgraph::graph::NodeIndex;
     use petgraph::{Incoming, Outgoing};
     use std::path::PathBuf;
@@ -440,6 +583,9 @@ mod tests {
             instruction_pairs: false,
             format_type: FormatMode::SingleInstruction,
             pcode_file_type: PCodeFileTypes::PCodeWithBBFile,
+            reg_norm: false,
+            ssa: false,
+            sort_output: true,
         };
 
         pcode_file
@@ -498,4 +644,135 @@ mod tests {
         assert_eq!(save_ret, true);
         std::fs::remove_file("test_pcode_graph.json").unwrap()
     }
+
+    #[test]
+    fn test_pcode_ssa_repeated_definitions() {
+        let mut ssa = PCodeSSA::new();
+        let renamed: Vec<String> = [
+            "(unique,0xea00,8) = COPY RBP",
+            "RSP = INT_SUB RSP, 0x8",
+            "(unique,0xea00,8) = STORE ram[RSP]",
+            "RSP = INT_SUB RSP, 0x10",
+            "(register,0x20,8) = INT_ADD (register,0x20,8), 0x1",
+            "(unique,0xea00,8) = INT_ADD (unique,0xea00,8), RSP",
+            "0x1166 = STORE ram[RSP]",
+            "CALL qword_ptr(0x00001050)",
+        ]
+        .iter()
+        .map(|instruction| ssa.rename(instruction))
+        .collect();
+
+        assert_eq!(
+            renamed,
+            vec![
+                "unique0xea00:8#1 = COPY RBP#0",
+                "RSP#1 = INT_SUB RSP#0, 0x8",
+                "unique0xea00:8#1 = STORE ram[RSP#1]",
+                "RSP#2 = INT_SUB RSP#1, 0x10",
+                "register0x20:8#1 = INT_ADD register0x20:8#0, 0x1",
+                "unique0xea00:8#2 = INT_ADD unique0xea00:8#1, RSP#2",
+                "0x1166 = STORE ram[RSP#2]",
+                "CALL qword_ptr(0x00001050)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pcode_ssa_is_per_function() {
+        let mut pcode_file = PCodeFile::new(
+            PathBuf::from("test-files/test_bin_pcode-bb.json"),
+            PathBuf::new(),
+            None,
+            false,
+            FormatMode::SingleInstruction,
+            PCodeFileTypes::PCodeWithBBFile,
+            false,
+            true,
+            true,
+        );
+        pcode_file
+            .load_and_deserialize()
+            .expect("Unable to load and deserialize PCode file");
+
+        // Subscripts restart for each function and continue across basic blocks
+        for function in pcode_file.pcode_obj.unwrap() {
+            let PCodeDataTypes::PCodeJsonWithBB(function) = function else {
+                panic!("Expected PCode with basic block information")
+            };
+            let first_rsp_def = function
+                .pcode_blocks
+                .iter()
+                .flat_map(|block| block.pcode.iter())
+                .find(|instruction| instruction.starts_with("RSP#"));
+            if let Some(first_rsp_def) = first_rsp_def {
+                assert!(first_rsp_def.starts_with("RSP#1 = "));
+            }
+        }
+    }
+
+    fn pcode_block(
+        start: u64,
+        pcode: &[&str],
+        jump: Option<u64>,
+        fail: Option<u64>,
+    ) -> PCodeJsonWithBB {
+        PCodeJsonWithBB {
+            block_start_adr: start,
+            pcode: pcode.iter().map(|ins| ins.to_string()).collect(),
+            asm: None,
+            bb_info: BasicBlockMetadataEntry {
+                addr: start,
+                jump,
+                fail,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_pcode_cfg_from_basic_blocks() {
+        let function = PCodeJsonWithBBAndFuncName {
+            function_name: "sym.test".to_string(),
+            pcode_blocks: vec![
+                pcode_block(
+                    0x1000,
+                    &["RSP = INT_SUB RSP 8", "CBRANCH 0x1020 ZF"],
+                    Some(0x1020),
+                    Some(0x1010),
+                ),
+                pcode_block(0x1010, &["RAX = COPY 1"], Some(0x1020), None),
+                pcode_block(0x1020, &["CALL 0x2000"], Some(0x2000), None),
+            ],
+        };
+
+        let (graph, start_addrs) = function.get_cfg();
+        let nx_graph: NetworkxDiGraph<NodeType> =
+            NetworkxDiGraph::from((&graph, &function, &start_addrs));
+        assert_eq!(nx_graph.nodes.len(), 4);
+        assert_eq!(nx_graph.adjacency.len(), 4);
+        assert!(nx_graph.nodes.iter().all(|node| node.as_p_code().is_some()));
+
+        let nx_graph: NetworkxDiGraph<PCodeNode> = function.to_networkx();
+        let start_addrs: Vec<u64> = nx_graph.nodes.iter().map(|node| node.start_addr).collect();
+        assert_eq!(start_addrs, vec![0x1000, 0x1010, 0x1020, 0x2000]);
+        assert_eq!(
+            nx_graph.nodes[0].features,
+            vec!["RSP = INT_SUB RSP 8", "CBRANCH 0x1020 ZF"]
+        );
+        assert_eq!(nx_graph.nodes[2].features, vec!["CALL 0x2000"]);
+        // 0x2000 is outside of the function so has no PCode
+        assert!(nx_graph.nodes[3].features.is_empty());
+
+        // Edges are (target id, weight) where fail = 0 and jump = 1
+        let mut edges: Vec<Vec<(usize, u32)>> = nx_graph
+            .adjacency
+            .iter()
+            .map(|node_edges| node_edges.iter().map(|e| (e.id, e.weight)).collect())
+            .collect();
+        edges.iter_mut().for_each(|node_edges| node_edges.sort());
+        assert_eq!(
+            edges,
+            vec![vec![(1, 0), (2, 1)], vec![(2, 1)], vec![(3, 1)], vec![]]
+        );
+    }
 }
@@ -1,10 +1,15 @@
 /*
 ALOT OF THIS IS DEPRECATED - NEED TO WORK OUT WHAT TO KEEP AND WHAT TO REMOVE
  */
-use crate::bb::FeatureType;
-use crate::files::AGFJFile;
+use crate::agfj::CfgMismatch;
+use crate::bb::{FeatureType, InstructionMode};
+use crate::files::{AGFJFile, FormatMode};
 #[cfg(feature = "inference")]
 use crate::inference::InferenceJob;
+use crate::networkx::GraphFormat;
+use crate::normalisation::NormalizationConfig;
+use indicatif::ParallelProgressIterator;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 #[cfg(feature = "inference")]
@@ -44,6 +49,7 @@ struct EdgePair {
 pub fn agfj_graph_embedded_feats(
     path: &Path,
     min_blocks: &u16,
+    max_blocks: &Option<u16>,
     output_path: &Path,
     feature_type: FeatureType,
     tokeniser_fp: &Option<String>,
@@ -56,10 +62,15 @@ pub fn agfj_graph_embedded_feats(
         filename: path.to_owned(),
         output_path: output_path.to_owned(),
         min_blocks: *min_blocks,
+        max_blocks: *max_blocks,
         feature_type: Some(feature_type),
         architecture: None,
         // This may actually break this feature in certain examples. May need to be togglable
-        reg_norm: false,
+        norm_config: NormalizationConfig::default(),
+        graph_format: GraphFormat::Json,
+        embed_func_meta: false,
+        edge_types: false,
+        relative_addrs: false,
     };
 
     // TODO: Add logic here that creates an inference job differently depending on if tokeniser_fp and model_fp
@@ -86,32 +97,198 @@ pub fn agfj_graph_embedded_feats(
     file.parallel_embedded_cfg_gen(inference_job)
 }
 
+/// Generate attributed CFGs for a single AGFJ file
+///
+/// Returns the functions skipped due to a CFG node/block count mismatch.
+#[allow(clippy::too_many_arguments)]
 pub fn agfj_graph_statistical_features(
     path: &Path,
     min_blocks: &u16,
+    max_blocks: &Option<u16>,
     output_path: &PathBuf,
     feature_type: FeatureType,
-) {
+    strict_validate: bool,
+    graph_format: GraphFormat,
+    embed_func_meta: bool,
+    edge_types: bool,
+    relative_addrs: bool,
+    architecture: &Option<String>,
+    low_memory: bool,
+) -> Vec<CfgMismatch> {
     let mut file = AGFJFile {
         functions: None,
         filename: path.to_owned(),
         output_path: output_path.to_owned(),
         min_blocks: *min_blocks,
+        max_blocks: *max_blocks,
         feature_type: Some(feature_type),
-        architecture: None,
-        reg_norm: false,
+        architecture: architecture.clone(),
+        norm_config: NormalizationConfig::default(),
+        graph_format,
+        embed_func_meta,
+        edge_types,
+        relative_addrs,
     };
 
+    if low_memory {
+        let (_, mismatches) = file
+            .streaming_attributed_cfg_gen(strict_validate)
+            .expect("Unable to stream and deserialise file.");
+        return mismatches;
+    }
+
     file.load_and_deserialize()
         .expect("Unable to load and deserialise file.");
+    if strict_validate {
+        file.remove_invalid_functions();
+    }
     file.paralell_attributed_cfg_gen()
 }
 
+/// Generate NLP data for a directory of AGFJ files in parallel
+///
+/// Each file writes to its own output file so they can be processed independently.
+#[allow(clippy::too_many_arguments)]
+pub fn agfj_nlp_data_generation(
+    file_paths: &[String],
+    output_path: &Path,
+    min_blocks: u16,
+    max_blocks: Option<u16>,
+    norm_config: NormalizationConfig,
+    format_type: FormatMode,
+    instruction_type: InstructionMode,
+    random_walk: bool,
+    max_hops: u16,
+    pairs: bool,
+    ngram: Option<usize>,
+    block_marker: Option<String>,
+    low_memory: bool,
+    jsonl: bool,
+    sort_output: bool,
+) {
+    file_paths.par_iter().progress().for_each(|path| {
+        let file = AGFJFile {
+            functions: None,
+            filename: PathBuf::from(path),
+            output_path: output_path.to_owned(),
+            min_blocks,
+            max_blocks,
+            feature_type: None,
+            architecture: None,
+            norm_config,
+            graph_format: GraphFormat::Json,
+            embed_func_meta: false,
+            edge_types: false,
+            relative_addrs: false,
+        };
+        file.execute_data_generation(
+            format_type,
+            instruction_type,
+            &random_walk,
+            max_hops,
+            pairs,
+            ngram,
+            block_marker.clone(),
+            low_memory,
+            jsonl,
+            sort_output,
+        )
+    });
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
 
     #[test]
     fn test_example_in_graph_rs() {
         assert_eq!(1, 1);
     }
+
+    #[test]
+    fn test_nlp_data_generation_parallel_matches_serial() {
+        let base = env::temp_dir().join(format!("bin2ml-nlp-parallel-{}", std::process::id()));
+        let input_dir = base.join("input");
+        let serial_dir = base.join("serial");
+        let parallel_dir = base.join("parallel");
+        for dir in [&input_dir, &serial_dir, &parallel_dir] {
+            fs::create_dir_all(dir).unwrap();
+        }
+
+        let mut file_paths = Vec::new();
+        for name in ["first", "second", "third"] {
+            let path = input_dir.join(format!("{}_cfg.json", name));
+            fs::copy("test-files/r2-output-samples/test_bin_agfj.json", &path).unwrap();
+            file_paths.push(path.to_string_lossy().to_string());
+        }
+
+        for path in &file_paths {
+            let file = AGFJFile {
+                functions: None,
+                filename: PathBuf::from(path),
+                output_path: serial_dir.clone(),
+                min_blocks: 1,
+                max_blocks: None,
+                feature_type: None,
+                architecture: None,
+                norm_config: NormalizationConfig::default(),
+                graph_format: GraphFormat::Json,
+                embed_func_meta: false,
+                edge_types: false,
+                relative_addrs: false,
+            };
+            file.execute_data_generation(
+                FormatMode::SingleInstruction,
+                InstructionMode::Disasm,
+                &false,
+                10,
+                false,
+                None,
+                None,
+                false,
+                false,
+                true,
+            )
+        }
+
+        agfj_nlp_data_generation(
+            &file_paths,
+            &parallel_dir,
+            1,
+            None,
+            NormalizationConfig::default(),
+            FormatMode::SingleInstruction,
+            InstructionMode::Disasm,
+            false,
+            10,
+            false,
+            None,
+            None,
+            false,
+            false,
+            true,
+        );
+
+        let outputs = |dir: &PathBuf| {
+            let mut outputs: Vec<(String, String)> = fs::read_dir(dir)
+                .unwrap()
+                .map(|entry| {
+                    let path = entry.unwrap().path();
+                    (
+                        path.file_name().unwrap().to_string_lossy().to_string(),
+                        fs::read_to_string(&path).unwrap(),
+                    )
+                })
+                .collect();
+            outputs.sort();
+            outputs
+        };
+        let serial_outputs = outputs(&serial_dir);
+        assert_eq!(serial_outputs.len(), 3);
+        assert_eq!(serial_outputs, outputs(&parallel_dir));
+
+        fs::remove_dir_all(base).unwrap();
+    }
 }
@@ -0,0 +1,163 @@
+use crate::afij::{AFIJFeatureSubset, AFIJFunctionInfo};
+use rusqlite::{params, Connection, Result};
+use std::path::Path;
+use std::time::Duration;
+
+// How long to wait for another writer (i.e. another thread processing a
+// different binary) to release the database lock
+const SQLITE_BUSY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Stores extracted function metadata within a SQLite database
+///
+/// Each function is stored as a row within the functions table keyed by the binary,
+/// function name and offset. The feature subset is stored as individual columns alongside
+/// the full function info as JSON.
+pub struct FunctionStore {
+    conn: Connection,
+}
+
+impl FunctionStore {
+    /// Open (or create) a SQLite database at the provided path
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<FunctionStore> {
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(SQLITE_BUSY_TIMEOUT)?;
+        let store = FunctionStore { conn };
+        store.create_schema()?;
+        Ok(store)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<FunctionStore> {
+        let store = FunctionStore {
+            conn: Connection::open_in_memory()?,
+        };
+        store.create_schema()?;
+        Ok(store)
+    }
+
+    fn create_schema(&self) -> Result<()> {
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS functions (
+                binary TEXT NOT NULL,
+                name TEXT NOT NULL,
+                offset INTEGER NOT NULL,
+                size INTEGER NOT NULL,
+                ninstrs INTEGER NOT NULL,
+                edges INTEGER NOT NULL,
+                indegree INTEGER NOT NULL,
+                outdegree INTEGER NOT NULL,
+                nlocals INTEGER NOT NULL,
+                nargs INTEGER NOT NULL,
+                signature TEXT NOT NULL,
+                info TEXT NOT NULL,
+                PRIMARY KEY (binary, name, offset)
+            );",
+        )
+    }
+
+    /// Insert the functions of a single binary within a single transaction
+    ///
+    /// Re-inserting a function (i.e. when re-extracting a binary) replaces the existing row.
+    pub fn insert_functions(
+        &mut self,
+        binary: &str,
+        functions: &[AFIJFunctionInfo],
+    ) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR REPLACE INTO functions
+                    (binary, name, offset, size, ninstrs, edges, indegree, outdegree, nlocals, nargs, signature, info)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            )?;
+            for function in functions {
+                let subset = AFIJFeatureSubset::from(function);
+                let info = serde_json::to_string(function)
+                    .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+                stmt.execute(params![
+                    binary,
+                    subset.name,
+                    function.offset as i64,
+                    function.size as i64,
+                    subset.ninstrs,
+                    subset.edges,
+                    subset.indegree,
+                    subset.outdegree,
+                    subset.nlocals,
+                    subset.nargs,
+                    subset.signature,
+                    info,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(functions.len())
+    }
+
+    /// Get the number of functions stored, optionally only for a single binary
+    #[cfg(test)]
+    pub fn count_functions(&self, binary: Option<&str>) -> Result<usize> {
+        let count: i64 = match binary {
+            Some(binary) => self.conn.query_row(
+                "SELECT COUNT(*) FROM functions WHERE binary = ?1",
+                params![binary],
+                |row| row.get(0),
+            )?,
+            None => self
+                .conn
+                .query_row("SELECT COUNT(*) FROM functions", [], |row| row.get(0))?,
+        };
+        Ok(count as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(name: &str, offset: u64) -> AFIJFunctionInfo {
+        AFIJFunctionInfo {
+            name: name.to_string(),
+            offset,
+            ninstrs: 10,
+            nargs: Some(2),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_insert_and_count_functions() {
+        let mut store = FunctionStore::open_in_memory().unwrap();
+        let functions = vec![
+            function("main", 0x1149),
+            function("entry0", 0x1060),
+            function("sym.imp.printf", 0x1030),
+        ];
+
+        assert_eq!(store.insert_functions("test_bin", &functions).unwrap(), 3);
+        assert_eq!(
+            store
+                .insert_functions("test_bin_2", &functions[..1])
+                .unwrap(),
+            1
+        );
+
+        assert_eq!(store.count_functions(None).unwrap(), 4);
+        assert_eq!(store.count_functions(Some("test_bin")).unwrap(), 3);
+        assert_eq!(store.count_functions(Some("test_bin_2")).unwrap(), 1);
+
+        // Re-inserting a binary replaces the existing rows
+        store.insert_functions("test_bin", &functions).unwrap();
+        assert_eq!(store.count_functions(None).unwrap(), 4);
+
+        let nargs: i64 = store
+            .conn
+            .query_row(
+                "SELECT nargs FROM functions WHERE binary = ?1 AND name = ?2",
+                params!["test_bin", "main"],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(nargs, 2);
+    }
+}
@@ -1,39 +1,106 @@
 use std::path::PathBuf;
+use tokenizers::decoders::wordpiece::WordPiece as WordPieceDecoder;
+use tokenizers::decoders::DecoderWrapper;
 use tokenizers::models::bpe::{BpeTrainerBuilder, BPE};
-use tokenizers::normalizers::{strip::Strip, unicode::NFC, utils::Sequence};
+use tokenizers::models::unigram::{Unigram, UnigramTrainerBuilder};
+use tokenizers::models::wordpiece::{WordPiece, WordPieceTrainer};
+use tokenizers::models::{ModelWrapper, TrainerWrapper};
+use tokenizers::normalizers::{strip::Strip, unicode::NFC, utils::Sequence, NormalizerWrapper};
 use tokenizers::pre_tokenizers::byte_level::ByteLevel;
+use tokenizers::pre_tokenizers::delimiter::CharDelimiterSplit;
+use tokenizers::pre_tokenizers::metaspace::Metaspace;
+use tokenizers::pre_tokenizers::whitespace::WhitespaceSplit;
+use tokenizers::pre_tokenizers::PreTokenizerWrapper;
+use tokenizers::processors::PostProcessorWrapper;
 use tokenizers::{AddedToken, Result, TokenizerBuilder};
 
 #[derive(PartialEq)]
 pub enum TokeniserType {
     ByteBPE,
     CommaBPE,
+    WordPiece,
+    Unigram,
     Invalid,
 }
 
+const UNK_TOKEN: &str = "<unk>";
+const DEFAULT_SPECIAL_TOKENS: [&str; 5] = ["<s>", "<pad>", "</s>", UNK_TOKEN, "<mask>"];
+
+/// Options passed through to the `tokenizers` trainers
+#[derive(Debug, Clone)]
+pub struct TokeniserTrainingConfig {
+    pub vocab_size: usize,
+    pub min_frequency: u32,
+    pub special_tokens: Vec<String>,
+}
+
+impl TokeniserTrainingConfig {
+    pub fn new(
+        vocab_size: usize,
+        min_frequency: u32,
+        special_tokens: Option<Vec<String>>,
+    ) -> TokeniserTrainingConfig {
+        let special_tokens = special_tokens.unwrap_or_else(|| {
+            DEFAULT_SPECIAL_TOKENS
+                .iter()
+                .map(|t| t.to_string())
+                .collect()
+        });
+        TokeniserTrainingConfig {
+            vocab_size,
+            min_frequency,
+            special_tokens,
+        }
+    }
+
+    /// The special token used for unknown inputs. The first special token containing
+    /// "unk" is used, falling back to `<unk>` if there isn't one.
+    fn unk_token(&self) -> String {
+        self.special_tokens
+            .iter()
+            .find(|token| token.to_lowercase().contains("unk"))
+            .cloned()
+            .unwrap_or_else(|| UNK_TOKEN.to_string())
+    }
+
+    // Special tokens are registered in the order provided so they occupy the lowest ids.
+    // The unk token is appended if not present as WordPiece/Unigram models require one.
+    fn special_tokens(&self) -> Vec<AddedToken> {
+        let mut special_tokens = self.special_tokens.clone();
+        let unk_token = self.unk_token();
+        if !special_tokens.contains(&unk_token) {
+            special_tokens.push(unk_token)
+        }
+        special_tokens
+            .into_iter()
+            .map(|token| AddedToken::from(token, true))
+            .collect()
+    }
+}
+
+fn get_training_files(file_or_dir_fp: &String) -> Result<Vec<String>> {
+    let file_or_dir_fp_path = PathBuf::from(file_or_dir_fp);
+    if file_or_dir_fp_path.is_dir() {
+        Err(format!(
+            "{} is a directory - Using a directory as input to tokeniser generation is currently not supported",
+            file_or_dir_fp
+        )
+        .into())
+    } else {
+        Ok(vec![file_or_dir_fp.to_string()])
+    }
+}
+
 pub fn train_byte_bpe_tokeniser(
     file_or_dir_fp: &String,
     output_path: &String,
-    vocab_size: usize,
+    config: &TokeniserTrainingConfig,
 ) -> Result<()> {
-    let file_or_dir_fp_path = PathBuf::from(file_or_dir_fp);
-    let fps = if file_or_dir_fp_path.is_dir() {
-        todo!("Using a directory as files as input to tokeniser generation is currently not supported!")
-    } else {
-        file_or_dir_fp
-    };
-
     let mut trainer = BpeTrainerBuilder::new()
         .show_progress(true)
-        .vocab_size(vocab_size)
-        .min_frequency(0)
-        .special_tokens(vec![
-            AddedToken::from(String::from("<s>"), true),
-            AddedToken::from(String::from("<pad>"), true),
-            AddedToken::from(String::from("</s>"), true),
-            AddedToken::from(String::from("<unk>"), true),
-            AddedToken::from(String::from("<mask>"), true),
-        ])
+        .vocab_size(config.vocab_size)
+        .min_frequency(config.min_frequency)
+        .special_tokens(config.special_tokens())
         .build();
 
     let mut tokenizer = TokenizerBuilder::new()
@@ -49,49 +116,240 @@ pub fn train_byte_bpe_tokeniser(
 
     let pretty = false;
     tokenizer
-        .train_from_files(&mut trainer, vec![fps.to_string()])?
+        .train_from_files(&mut trainer, get_training_files(file_or_dir_fp)?)?
         .save(output_path, pretty)?;
 
     Ok(())
 }
 
-/*
+/// Trains a BPE tokeniser where the input is split into words on commas (i.e ESIL)
 pub fn train_comma_bpe_tokeniser(
     file_or_dir_fp: &String,
     output_path: &String,
-    vocab_size: usize,
+    config: &TokeniserTrainingConfig,
 ) -> Result<()> {
-    let file_or_dir_fp_path = PathBuf::from(file_or_dir_fp);
-    let fps = if file_or_dir_fp_path.is_dir() {
-        todo!("Using a directory as files as input to tokeniser generation is currently not supported!")
-    } else {
-        file_or_dir_fp
-    };
+    let trainer = BpeTrainerBuilder::new()
+        .show_progress(true)
+        .vocab_size(config.vocab_size)
+        .min_frequency(config.min_frequency)
+        .special_tokens(config.special_tokens())
+        .build();
 
-    let mut trainer = BpeTrainerBuilder::new()
+    train_and_save_tokeniser(
+        BPE::default().into(),
+        trainer.into(),
+        CharDelimiterSplit::new(',').into(),
+        None,
+        file_or_dir_fp,
+        output_path,
+    )
+}
+
+/// Trains a WordPiece tokeniser where the input is split into words on whitespace
+pub fn train_wordpiece_tokeniser(
+    file_or_dir_fp: &String,
+    output_path: &String,
+    config: &TokeniserTrainingConfig,
+) -> Result<()> {
+    let trainer = WordPieceTrainer::builder()
         .show_progress(true)
-        .vocab_size(vocab_size)
-        .min_frequency(0)
-        .special_tokens(vec![
-            AddedToken::from(String::from("<s>"), true),
-            AddedToken::from(String::from("<pad>"), true),
-            AddedToken::from(String::from("</s>"), true),
-            AddedToken::from(String::from("<unk>"), true),
-            AddedToken::from(String::from("<mask>"), true),
-        ])
+        .vocab_size(config.vocab_size)
+        .min_frequency(config.min_frequency)
+        .special_tokens(config.special_tokens())
         .build();
+    let model = WordPiece::builder().unk_token(config.unk_token()).build()?;
 
-    let mut tokenizer = TokenizerBuilder::new()
-        .with_model(BPE::default())
-        .with_normalizer(Some(Sequence::new(vec![Strip::new(true, true).into()])))
-        .with_pre_tokenizer(Some(CharDelimiterSplit::new(',')))
+    train_and_save_tokeniser(
+        model.into(),
+        trainer.into(),
+        WhitespaceSplit.into(),
+        Some(WordPieceDecoder::new("##".to_string(), false).into()),
+        file_or_dir_fp,
+        output_path,
+    )
+}
+
+/// Trains a Unigram (SentencePiece style) tokeniser
+///
+/// The Unigram trainer has no notion of a minimum frequency so `min_frequency` is ignored.
+pub fn train_unigram_tokeniser(
+    file_or_dir_fp: &String,
+    output_path: &String,
+    config: &TokeniserTrainingConfig,
+) -> Result<()> {
+    let trainer = UnigramTrainerBuilder::default()
+        .show_progress(true)
+        .vocab_size(config.vocab_size as u32)
+        .special_tokens(config.special_tokens())
+        .unk_token(Some(config.unk_token()))
         .build()?;
 
+    train_and_save_tokeniser(
+        Unigram::default().into(),
+        trainer.into(),
+        Metaspace::default().into(),
+        Some(Metaspace::default().into()),
+        file_or_dir_fp,
+        output_path,
+    )
+}
+
+fn train_and_save_tokeniser(
+    model: ModelWrapper,
+    mut trainer: TrainerWrapper,
+    pre_tokenizer: PreTokenizerWrapper,
+    decoder: Option<DecoderWrapper>,
+    file_or_dir_fp: &String,
+    output_path: &String,
+) -> Result<()> {
+    let mut tokenizer = TokenizerBuilder::<
+        ModelWrapper,
+        NormalizerWrapper,
+        PreTokenizerWrapper,
+        PostProcessorWrapper,
+        DecoderWrapper,
+    >::new()
+    .with_model(model)
+    .with_normalizer(Some(
+        Sequence::new(vec![Strip::new(true, true).into()]).into(),
+    ))
+    .with_pre_tokenizer(Some(pre_tokenizer))
+    .with_decoder(decoder)
+    .build()?;
+
     let pretty = false;
     tokenizer
-        .train_from_files(&mut trainer, vec![fps.to_string()])?
+        .train_from_files(&mut trainer, get_training_files(file_or_dir_fp)?)?
         .save(output_path, pretty)?;
 
     Ok(())
 }
-*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizers::Tokenizer;
+
+    const CORPUS: &str = "test-files/esil-corpus.txt";
+    const VOCAB_SIZE: usize = 200;
+
+    fn train_and_load(
+        train_fn: fn(&String, &String, &TokeniserTrainingConfig) -> Result<()>,
+        name: &str,
+        config: &TokeniserTrainingConfig,
+    ) -> Tokenizer {
+        let output_path = std::env::temp_dir()
+            .join(format!("bin2ml-test-{}-{}.json", name, std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        train_fn(&CORPUS.to_string(), &output_path, config).expect("Unable to train");
+        let tokenizer = Tokenizer::from_file(&output_path).expect("Unable to load tokeniser");
+        std::fs::remove_file(&output_path).unwrap();
+
+        let vocab_size = tokenizer.get_vocab_size(true);
+        assert!(vocab_size > config.special_tokens().len());
+        assert!(vocab_size <= VOCAB_SIZE);
+        assert!(tokenizer.token_to_id(&config.unk_token()).is_some());
+        tokenizer
+    }
+
+    fn round_trip(tokenizer: &Tokenizer, input: &str) -> String {
+        let encoding = tokenizer.encode(input, false).unwrap();
+        assert!(!encoding.get_ids().is_empty());
+        tokenizer.decode(encoding.get_ids(), true).unwrap()
+    }
+
+    #[test]
+    fn test_comma_bpe_tokeniser() {
+        let tokenizer = train_and_load(
+            train_comma_bpe_tokeniser,
+            "comma-bpe",
+            &TokeniserTrainingConfig::new(VOCAB_SIZE, 0, None),
+        );
+        // Commas are consumed by the pre-tokeniser so decoded tokens are space separated
+        assert_eq!(
+            round_trip(&tokenizer, "rsp,[8],rbp,=,8,rsp,+="),
+            "rsp [8] rbp = 8 rsp +="
+        );
+    }
+
+    #[test]
+    fn test_wordpiece_tokeniser() {
+        let tokenizer = train_and_load(
+            train_wordpiece_tokeniser,
+            "wordpiece",
+            &TokeniserTrainingConfig::new(VOCAB_SIZE, 0, None),
+        );
+        assert_eq!(
+            round_trip(&tokenizer, "rsp,[8],rbp,=,8,rsp,+="),
+            "rsp,[8],rbp,=,8,rsp,+="
+        );
+    }
+
+    #[test]
+    fn test_unigram_tokeniser() {
+        let tokenizer = train_and_load(
+            train_unigram_tokeniser,
+            "unigram",
+            &TokeniserTrainingConfig::new(VOCAB_SIZE, 0, None),
+        );
+        assert_eq!(
+            round_trip(&tokenizer, "rsp,[8],rbp,=,8,rsp,+="),
+            "rsp,[8],rbp,=,8,rsp,+="
+        );
+    }
+
+    #[test]
+    fn test_custom_special_tokens() {
+        let special_tokens = ["[CLS]", "[SEP]", "[PAD]", "[MASK]", "[UNK]"];
+        let config = TokeniserTrainingConfig::new(
+            VOCAB_SIZE,
+            2,
+            Some(special_tokens.iter().map(|t| t.to_string()).collect()),
+        );
+        assert_eq!(config.unk_token(), "[UNK]");
+        assert_eq!(config.special_tokens().len(), 5);
+
+        for (train_fn, name) in [
+            (
+                train_comma_bpe_tokeniser
+                    as fn(&String, &String, &TokeniserTrainingConfig) -> Result<()>,
+                "special-comma-bpe",
+            ),
+            (train_wordpiece_tokeniser, "special-wordpiece"),
+        ] {
+            let tokenizer = train_and_load(train_fn, name, &config);
+            assert_eq!(tokenizer.token_to_id("<unk>"), None);
+            for (id, token) in special_tokens.iter().enumerate() {
+                assert_eq!(tokenizer.token_to_id(token), Some(id as u32));
+            }
+
+            let encoding = tokenizer.encode("[CLS] rsp [SEP]", false).unwrap();
+            let tokens = encoding.get_tokens();
+            assert_eq!(tokens.first().unwrap(), "[CLS]");
+            assert_eq!(tokens.last().unwrap(), "[SEP]");
+        }
+    }
+
+    #[test]
+    fn test_directory_input_is_an_error() {
+        let output_path = std::env::temp_dir()
+            .join(format!("bin2ml-test-dir-input-{}.json", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        let config = TokeniserTrainingConfig::new(VOCAB_SIZE, 0, None);
+        let err = train_comma_bpe_tokeniser(&"test-files".to_string(), &output_path, &config)
+            .unwrap_err();
+        assert!(err.to_string().contains("is a directory"));
+        assert!(!PathBuf::from(&output_path).exists());
+    }
+
+    #[test]
+    fn test_unk_token_appended_when_missing() {
+        let config = TokeniserTrainingConfig::new(VOCAB_SIZE, 0, Some(vec!["[CLS]".to_string()]));
+        assert_eq!(config.unk_token(), UNK_TOKEN);
+        let special_tokens = config.special_tokens();
+        assert_eq!(special_tokens.len(), 2);
+        assert_eq!(special_tokens[1].content, UNK_TOKEN);
+    }
+}
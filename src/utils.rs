@@ -1,4 +1,13 @@
-use std::fs::create_dir_all;
+use crate::consts::SUPPORTED_ARCHITECTURES;
+use crate::errors::OutputTemplateError;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Serialize, Serializer};
+use std::collections::HashMap;
+use std::fs::{create_dir_all, read_to_string, File};
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
@@ -14,12 +23,17 @@ use walkdir::WalkDir;
 /// + its calees + the callees of the callees).
 ///
 /// See agcj.rs for an example of this optional suffix being used
+///
+/// If an [`OutputTemplate`] is provided, it is rendered with the given values and used
+/// as the file name (relative to the output path) instead of the fixed naming scheme.
+/// The rendered template can contain subdirectories (i.e `{arch}/{binary}`).
 pub fn get_save_file_path(
     binary_path: &Path,
     output_path: &Path,
     extension: Option<String>,
     optional_suffix: Option<String>,
     remove_suffix: Option<String>,
+    template: Option<(&OutputTemplate, &OutputTemplateValues)>,
 ) -> PathBuf {
     let extension = if extension.is_some() {
         let extension = extension.unwrap();
@@ -32,6 +46,20 @@ pub fn get_save_file_path(
         "".to_string()
     };
 
+    if let Some((template, values)) = template {
+        let full_output_path =
+            output_path.join(format!("{}{}", template.render(values), extension));
+        debug!("Full Output Path: {:?}", full_output_path);
+        return full_output_path;
+    }
+
+    // Compressed inputs (i.e hello_cfg.json.gz) have both extensions removed
+    let binary_path = if is_gzipped(binary_path) {
+        Path::new(binary_path.file_stem().unwrap())
+    } else {
+        binary_path
+    };
+
     let file_name = binary_path
         .file_stem()
         .unwrap()
@@ -74,11 +102,167 @@ pub fn get_save_file_path(
     }
 }
 
+/// Placeholders which can be used within an [`OutputTemplate`]
+pub const OUTPUT_TEMPLATE_PLACEHOLDERS: [&str; 4] = ["binary", "mode", "arch", "func"];
+
+#[derive(Debug, Clone, PartialEq)]
+enum TemplateSegment {
+    Literal(String),
+    Placeholder(String),
+}
+
+/// A user provided naming scheme for output files (i.e `{arch}/{binary}-{mode}`)
+///
+/// Placeholders are validated when the template is created. Any placeholder without a
+/// value when rendered (i.e `{func}` for a per binary output) is replaced with "unknown".
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputTemplate {
+    segments: Vec<TemplateSegment>,
+}
+
+/// The values substituted into an [`OutputTemplate`]
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct OutputTemplateValues {
+    pub binary: Option<String>,
+    pub mode: Option<String>,
+    pub arch: Option<String>,
+    pub func: Option<String>,
+}
+
+impl OutputTemplateValues {
+    fn get(&self, placeholder: &str) -> Option<&String> {
+        match placeholder {
+            "binary" => self.binary.as_ref(),
+            "mode" => self.mode.as_ref(),
+            "arch" => self.arch.as_ref(),
+            "func" => self.func.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl OutputTemplate {
+    pub fn new(template: &str) -> Result<OutputTemplate, OutputTemplateError> {
+        if template.trim().is_empty() {
+            return Err(OutputTemplateError::Empty);
+        }
+
+        let mut segments = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                segments.push(TemplateSegment::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| OutputTemplateError::UnclosedPlaceholder(template.to_string()))?;
+            let placeholder = &rest[start + 1..start + end];
+            if !OUTPUT_TEMPLATE_PLACEHOLDERS.contains(&placeholder) {
+                return Err(OutputTemplateError::UnknownPlaceholder(
+                    placeholder.to_string(),
+                ));
+            }
+            segments.push(TemplateSegment::Placeholder(placeholder.to_string()));
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(TemplateSegment::Literal(rest.to_string()));
+        }
+
+        Ok(OutputTemplate { segments })
+    }
+
+    /// Whether the template contains the given placeholder (i.e `func`)
+    pub fn has_placeholder(&self, placeholder: &str) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| *segment == TemplateSegment::Placeholder(placeholder.to_string()))
+    }
+
+    pub fn render(&self, values: &OutputTemplateValues) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                TemplateSegment::Literal(literal) => literal.as_str(),
+                TemplateSegment::Placeholder(placeholder) => values
+                    .get(placeholder)
+                    .map(|value| value.as_str())
+                    .unwrap_or("unknown"),
+            })
+            .collect()
+    }
+}
+
+/// Infers the architecture of a binary from its path (i.e x86-gcc-9-O3_nping or
+/// arm64/libfoo.so) for use within output templates
+pub fn infer_arch_from_path(path: &Path) -> Option<String> {
+    path.components().rev().find_map(|component| {
+        component
+            .as_os_str()
+            .to_string_lossy()
+            .split(['-', '_', '.'])
+            .map(|token| token.to_lowercase())
+            .find(|token| {
+                SUPPORTED_ARCHITECTURES
+                    .iter()
+                    .any(|arch| token.starts_with(&arch.to_lowercase()))
+            })
+    })
+}
+
+/// Checks if a file is gzip compressed based on its extension
+pub fn is_gzipped(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "gz")
+}
+
+/// Reads a JSON file into a string
+///
+/// Files ending in .gz are transparently decompressed
+pub fn read_json_to_string<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let path = path.as_ref();
+    if is_gzipped(path) {
+        let mut data = String::new();
+        GzDecoder::new(File::open(path)?).read_to_string(&mut data)?;
+        Ok(data)
+    } else {
+        read_to_string(path)
+    }
+}
+
+/// Writes a value to a JSON file
+///
+/// Files ending in .gz are gzip compressed
+pub fn write_json_to_file<P: AsRef<Path>, T: Serialize>(path: P, value: &T) -> io::Result<()> {
+    let path = path.as_ref();
+    let writer = BufWriter::new(File::create(path)?);
+    if is_gzipped(path) {
+        let mut encoder = GzEncoder::new(writer, Compression::default());
+        serde_json::to_writer(&mut encoder, value)?;
+        encoder.finish()?.flush()
+    } else {
+        let mut writer = writer;
+        serde_json::to_writer(&mut writer, value)?;
+        writer.flush()
+    }
+}
+
+/// Opens a buffered reader over a JSON file, transparently decompressing gzipped
+/// (.json.gz) files. Used when a file is too large to read into memory in one go.
+pub fn open_json_reader<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn Read>> {
+    let path = path.as_ref();
+    let file = File::open(path)?;
+    if is_gzipped(path) {
+        Ok(Box::new(BufReader::new(GzDecoder::new(file))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
 /// Get the JSON paths from a directory
 ///
 /// This function takes a path to a directory and traverses all
-/// files present within identifying files ending in .json before
-/// returning a Vec<String> where each string is an absolute path
+/// files present within identifying files ending in .json (or .json.gz)
+/// before returning a Vec<String> where each string is an absolute path
 /// to a given JSON file
 pub fn get_json_paths_from_dir(path: &PathBuf, identifier: Option<String>) -> Vec<String> {
     let mut str_vec: Vec<String> = Vec::new();
@@ -87,9 +271,11 @@ pub fn get_json_paths_from_dir(path: &PathBuf, identifier: Option<String>) -> Ve
     } else {
         format!("{}.json", identifier.unwrap())
     };
+    let gz_pattern = format!("{}.gz", pattern);
     for file in WalkDir::new(path).into_iter().filter_map(|file| file.ok()) {
+        let file_name = file.file_name().to_string_lossy().to_string();
         if file.metadata().unwrap().is_file()
-            && file.file_name().to_string_lossy().ends_with(&pattern)
+            && (file_name.ends_with(&pattern) || file_name.ends_with(&gz_pattern))
         {
             let f_string = String::from(<&std::path::Path>::clone(&file.path()).to_str().unwrap());
             str_vec.push(f_string.clone());
@@ -129,9 +315,178 @@ pub fn parse_hex_escapes(s: String) -> Vec<u8> {
     bytes
 }
 
+/// Encode bytes as a plain lowercase hex string (i.e 5548)
+pub fn to_hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Parse a plain hex string output from radare2 (i.e p8) into Vec<u8>
+pub fn parse_hex_string(s: &str) -> Vec<u8> {
+    let hex: Vec<char> = s.chars().filter(|c| c.is_ascii_hexdigit()).collect();
+    hex.chunks_exact(2)
+        .filter_map(|pair| u8::from_str_radix(&pair.iter().collect::<String>(), 16).ok())
+        .collect()
+}
+
+// r2 flag prefixes which are kept in front of a demangled function name
+const R2_SYMBOL_PREFIXES: [&str; 5] = ["sym.imp.", "loc.imp.", "sym.", "dbg.", "reloc."];
+
+/// Demangles a Rust or Itanium C++ function name from r2 (i.e sym._Z3fooi)
+///
+/// Any r2 flag prefix is kept (i.e sym.foo(int)). Returns None if the name
+/// is not mangled.
+pub fn demangle_function_name(name: &str) -> Option<String> {
+    let prefix = R2_SYMBOL_PREFIXES
+        .iter()
+        .find(|prefix| name.starts_with(*prefix))
+        .copied()
+        .unwrap_or("");
+    let symbol = &name[prefix.len()..];
+
+    // Mach-O symbols have an additional leading underscore (i.e __Z3fooi)
+    let demangled =
+        demangle_symbol(symbol).or_else(|| symbol.strip_prefix('_').and_then(demangle_symbol))?;
+    Some(format!("{}{}", prefix, demangled))
+}
+
+/// Build a Rayon thread pool with the given number of threads
+///
+/// Parallel work should be run within the returned pool using `pool.install(|| ...)`
+/// rather than installing a global pool, which can only be done once per process.
+pub fn build_thread_pool(num_threads: usize) -> rayon::ThreadPool {
+    info!("Creating thread pool with {} threads ", num_threads);
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build()
+        .expect("Unable to create thread pool")
+}
+
+/// A function name keyed JSON object which can be written either sorted by key
+/// or in the order the functions were processed
+///
+/// If a key is repeated, the last value is kept (matching the behaviour of
+/// collecting into a map).
+pub struct FunctionKeyedJson<T: Serialize> {
+    entries: Vec<(String, T)>,
+}
+
+impl<T: Serialize> FunctionKeyedJson<T> {
+    pub fn new(entries: Vec<(String, T)>, sort: bool) -> Self {
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        let mut deduped: Vec<(String, T)> = Vec::with_capacity(entries.len());
+        for (key, value) in entries {
+            match positions.get(&key) {
+                Some(idx) => deduped[*idx].1 = value,
+                None => {
+                    positions.insert(key.clone(), deduped.len());
+                    deduped.push((key, value));
+                }
+            }
+        }
+        if sort {
+            deduped.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        FunctionKeyedJson { entries: deduped }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<T: Serialize> Serialize for FunctionKeyedJson<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.entries.iter().map(|(k, v)| (k, v)))
+    }
+}
+
+fn demangle_symbol(symbol: &str) -> Option<String> {
+    if let Ok(demangled) = rustc_demangle::try_demangle(symbol) {
+        // The alternate format drops the trailing hash
+        return Some(format!("{:#}", demangled));
+    }
+    if symbol.starts_with("_Z") {
+        if let Ok(demangled) = cpp_demangle::Symbol::new(symbol) {
+            return Some(demangled.to_string());
+        }
+    }
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rayon::prelude::*;
+
+    #[test]
+    fn test_function_keyed_json_order() {
+        let entries = || {
+            vec![
+                ("main".to_string(), 1),
+                ("entry0".to_string(), 2),
+                ("main".to_string(), 3),
+            ]
+        };
+        let sorted = serde_json::to_string(&FunctionKeyedJson::new(entries(), true)).unwrap();
+        let unsorted = serde_json::to_string(&FunctionKeyedJson::new(entries(), false)).unwrap();
+        assert_eq!(sorted, r#"{"entry0":2,"main":3}"#);
+        assert_eq!(unsorted, r#"{"main":3,"entry0":2}"#);
+    }
+
+    #[test]
+    fn test_build_thread_pool_sequential_jobs() {
+        for num_threads in [2, 3] {
+            let pool = build_thread_pool(num_threads);
+            let (threads_used, total) = pool.install(|| {
+                (
+                    rayon::current_num_threads(),
+                    (1..=100).into_par_iter().sum::<u32>(),
+                )
+            });
+            assert_eq!(threads_used, num_threads);
+            assert_eq!(total, 5050);
+        }
+    }
+
+    #[test]
+    fn test_build_thread_pool_separate_jobs() {
+        let first_pool = build_thread_pool(2);
+        let second_pool = build_thread_pool(3);
+        assert_eq!(first_pool.install(rayon::current_num_threads), 2);
+        assert_eq!(second_pool.install(rayon::current_num_threads), 3);
+
+        // A job started from within another job's pool uses its own pool
+        let (outer, inner) = first_pool.install(|| {
+            (
+                rayon::current_num_threads(),
+                second_pool.install(rayon::current_num_threads),
+            )
+        });
+        assert_eq!(outer, 2);
+        assert_eq!(inner, 3);
+    }
+
+    #[test]
+    fn test_demangle_function_name() {
+        assert_eq!(
+            demangle_function_name("_ZN4core3fmt5write17h0123456789abcdefE"),
+            Some("core::fmt::write".to_string())
+        );
+        assert_eq!(
+            demangle_function_name("sym._ZN4core3fmt5write17h0123456789abcdefE"),
+            Some("sym.core::fmt::write".to_string())
+        );
+        assert_eq!(
+            demangle_function_name("sym._Z3fooi"),
+            Some("sym.foo(int)".to_string())
+        );
+        assert_eq!(
+            demangle_function_name("sym.imp.__Z3fooi"),
+            Some("sym.imp.foo(int)".to_string())
+        );
+        assert_eq!(demangle_function_name("main"), None);
+        assert_eq!(demangle_function_name("sym.imp.printf"), None);
+    }
 
     // TESTS FOR SAVE PATH BUILDING
     #[test]
@@ -144,6 +499,7 @@ mod tests {
             Some(".json".to_string()),
             Some("cg".to_string()),
             None,
+            None,
         );
         assert_eq!(output_path, PathBuf::from("processed_data/hello-cg.json"))
     }
@@ -151,17 +507,104 @@ mod tests {
     fn test_get_save_file_path_2() {
         let path: &PathBuf = &PathBuf::from("test_bin/extra_dir/hello.json");
         let output_path: &PathBuf = &PathBuf::from("with_more/processed_data/");
-        let output = get_save_file_path(path, output_path, None, None, None);
+        let output = get_save_file_path(path, output_path, None, None, None, None);
         assert_eq!(output, PathBuf::from("with_more/processed_data/hello"))
     }
     #[test]
     fn test_get_save_file_path_3() {
         let path: &PathBuf = &PathBuf::from("hello.json");
         let output_path: &PathBuf = &PathBuf::from("processed_data");
-        let output = get_save_file_path(path, &output_path, None, None, None);
+        let output = get_save_file_path(path, &output_path, None, None, None, None);
         assert_eq!(output, PathBuf::from("processed_data/hello"))
     }
 
+    #[test]
+    fn test_get_save_file_path_gzipped() {
+        let path: &PathBuf = &PathBuf::from("test_bin/hello_cfg.json.gz");
+        let output_path: &PathBuf = &PathBuf::from("processed_data/");
+        let output = get_save_file_path(
+            path,
+            output_path,
+            Some(".json".to_string()),
+            None,
+            None,
+            None,
+        );
+        assert_eq!(output, PathBuf::from("processed_data/hello_cfg.json"))
+    }
+
+    #[test]
+    fn test_output_template_rendering() {
+        let values = OutputTemplateValues {
+            binary: Some("nping".to_string()),
+            mode: Some("cfg".to_string()),
+            arch: infer_arch_from_path(Path::new("dataset/x86-gcc-9-O3_nping")),
+            func: None,
+        };
+        assert_eq!(values.arch, Some("x86".to_string()));
+
+        let template = OutputTemplate::new("{binary}_{mode}").unwrap();
+        assert_eq!(template.render(&values), "nping_cfg");
+
+        let template = OutputTemplate::new("{arch}/{binary}-{mode}").unwrap();
+        assert_eq!(
+            get_save_file_path(
+                Path::new("dataset/x86-gcc-9-O3_nping"),
+                Path::new("processed_data"),
+                Some("json".to_string()),
+                Some("ignored".to_string()),
+                None,
+                Some((&template, &values))
+            ),
+            PathBuf::from("processed_data/x86/nping-cfg.json")
+        );
+        assert!(template.has_placeholder("binary"));
+        assert!(!template.has_placeholder("func"));
+
+        let template = OutputTemplate::new("static-name").unwrap();
+        assert_eq!(template.render(&values), "static-name");
+
+        // Placeholders without a value are replaced rather than left in the path
+        let template = OutputTemplate::new("{binary}/{func}").unwrap();
+        assert_eq!(template.render(&values), "nping/unknown");
+    }
+
+    #[test]
+    fn test_output_template_invalid_placeholders() {
+        assert_eq!(
+            OutputTemplate::new("{binary}-{opt}"),
+            Err(OutputTemplateError::UnknownPlaceholder("opt".to_string()))
+        );
+        assert_eq!(
+            OutputTemplate::new("{}"),
+            Err(OutputTemplateError::UnknownPlaceholder("".to_string()))
+        );
+        assert_eq!(
+            OutputTemplate::new("{binary"),
+            Err(OutputTemplateError::UnclosedPlaceholder(
+                "{binary".to_string()
+            ))
+        );
+        assert_eq!(OutputTemplate::new(""), Err(OutputTemplateError::Empty));
+        assert_eq!(
+            OutputTemplate::new("{opt}").unwrap_err().to_string(),
+            "unknown placeholder {opt} in output template - expected one of {binary}, {mode}, {arch}, {func}"
+        );
+    }
+
+    #[test]
+    fn test_infer_arch_from_path() {
+        assert_eq!(
+            infer_arch_from_path(Path::new("arm32-gcc-9-03_testbin")),
+            Some("arm32".to_string())
+        );
+        assert_eq!(
+            infer_arch_from_path(Path::new("mips64/O2/libfoo.so")),
+            Some("mips64".to_string())
+        );
+        assert_eq!(infer_arch_from_path(Path::new("test-files/test_bin")), None);
+    }
+
     #[test]
     fn test_get_save_file_path_with_suffix_removal() {
         let path: &PathBuf = &PathBuf::from("hello_cg.json");
@@ -172,7 +615,23 @@ mod tests {
             Some(".json".to_string()),
             Some("gcg".to_string()),
             Some("_cg".to_string()),
+            None,
         );
         assert_eq!(output, PathBuf::from("processed_data/hello-gcg.json"))
     }
+
+    #[test]
+    fn test_to_hex_string() {
+        assert_eq!(to_hex_string(&[0x55, 0x48, 0x89, 0xe5]), "554889e5");
+        assert_eq!(
+            parse_hex_string(&to_hex_string(&[0x00, 0xff])),
+            vec![0x00, 0xff]
+        );
+    }
+
+    #[test]
+    fn test_parse_hex_string() {
+        assert_eq!(parse_hex_string("7f454c46\n"), vec![0x7f, 0x45, 0x4c, 0x46]);
+        assert_eq!(parse_hex_string(""), Vec::<u8>::new());
+    }
 }
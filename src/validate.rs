@@ -1,28 +1,179 @@
+use crate::agcj::SelfLoops;
+use crate::errors::ExitCode;
+use crate::files::{AFIJFile, AGCJFile, AGFJFile};
+use crate::networkx::GraphFormat;
+use crate::normalisation::NormalizationConfig;
 use std::ffi::OsStr;
-use std::path::Path;
-use std::process::exit;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// The file types which can be checked using the validate subcommand
+pub const VALIDATE_FILE_TYPES: [&str; 3] = ["cfg", "cg", "finfo"];
+
+/// The reason an input file failed validation
+#[derive(Debug)]
+pub enum InputError {
+    NotFound(PathBuf),
+    NotJson(String),
+    WrongType(String),
+    Invalid(String),
+}
+
+impl InputError {
+    pub fn exit_code(&self) -> ExitCode {
+        match self {
+            InputError::NotFound(_) => ExitCode::InputNotFound,
+            InputError::NotJson(_) | InputError::WrongType(_) => ExitCode::BadArgs,
+            InputError::Invalid(_) => ExitCode::Failure,
+        }
+    }
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputError::NotFound(filepath) => write!(f, "{:?} does not exist!", filepath),
+            InputError::NotJson(e) | InputError::WrongType(e) | InputError::Invalid(e) => {
+                write!(f, "{}", e)
+            }
+        }
+    }
+}
 
 pub fn validate_input(filepath: &Path, command: &str) {
-    check_file_is_json(filepath);
+    if let Err(e) = check_input(filepath, command) {
+        error!("{}", e);
+        e.exit_code().exit()
+    }
+}
+
+fn check_input(filepath: &Path, command: &str) -> Result<(), InputError> {
+    check_file_exists(filepath)?;
+    check_file_is_json(filepath)?;
     check_file_is_expected_type(filepath, command)
 }
 
-fn check_file_is_json(filepath: &Path) {
+/// Checks that a file is a valid bin2ml extracted JSON file of the given type by
+/// loading it and, for cfg files, checking the structure of each function
+///
+/// Returns a summary of the file if it is valid.
+pub fn validate_file(filepath: &Path, file_type: &str) -> Result<String, InputError> {
+    let command = match file_type {
+        "finfo" => "metadata_finfo",
+        _ => file_type,
+    };
+    check_input(filepath, command)?;
+
+    let invalid = |e: &dyn fmt::Display| {
+        InputError::Invalid(format!("Unable to load {:?} - {}", filepath, e))
+    };
+
+    match file_type {
+        "cfg" => {
+            let mut file = AGFJFile {
+                functions: None,
+                filename: filepath.to_owned(),
+                output_path: PathBuf::new(),
+                min_blocks: 1,
+                max_blocks: None,
+                feature_type: None,
+                architecture: None,
+                norm_config: NormalizationConfig::default(),
+                graph_format: GraphFormat::Json,
+                embed_func_meta: false,
+                edge_types: false,
+                relative_addrs: false,
+            };
+            file.load_and_deserialize().map_err(|e| invalid(&e))?;
+
+            let violations = file.validate_structure();
+            if let Some(violation) = violations.first() {
+                return Err(InputError::Invalid(format!(
+                    "{} structural issue(s) found in {:?} - first is {} in {}",
+                    violations.len(),
+                    filepath,
+                    violation.reason,
+                    violation.function_name
+                )));
+            }
+            Ok(format!(
+                "{:?} is a valid cfg file containing {} function(s)",
+                filepath,
+                file.functions.map(|functions| functions.len()).unwrap_or(0)
+            ))
+        }
+        "cg" => {
+            let mut file = AGCJFile {
+                filename: filepath.to_owned(),
+                function_call_graphs: None,
+                output_path: PathBuf::new(),
+                function_metadata: None,
+                include_unk: false,
+                weighted_edges: false,
+                self_loops: SelfLoops::Keep,
+                graph_format: GraphFormat::Json,
+            };
+            file.load_and_deserialize().map_err(|e| invalid(&e))?;
+            Ok(format!(
+                "{:?} is a valid cg file containing {} function(s)",
+                filepath,
+                file.function_call_graphs
+                    .map(|graphs| graphs.len())
+                    .unwrap_or(0)
+            ))
+        }
+        "finfo" => {
+            let mut file = AFIJFile {
+                filename: filepath.to_owned(),
+                function_info: None,
+                output_path: PathBuf::new(),
+            };
+            file.load_and_deserialize().map_err(|e| invalid(&e))?;
+            Ok(format!(
+                "{:?} is a valid finfo file containing {} function(s)",
+                filepath,
+                file.function_info.map(|info| info.len()).unwrap_or(0)
+            ))
+        }
+        _ => Err(InputError::WrongType(format!(
+            "Unable to validate file type {}. Expected one of {:?}",
+            file_type, VALIDATE_FILE_TYPES
+        ))),
+    }
+}
+
+fn check_file_exists(filepath: &Path) -> Result<(), InputError> {
+    if filepath.exists() {
+        Ok(())
+    } else {
+        Err(InputError::NotFound(filepath.to_owned()))
+    }
+}
+
+fn check_file_is_json(filepath: &Path) -> Result<(), InputError> {
     debug!("Filepath: {}", filepath.display());
-    let file_extension = filepath.extension();
+    // Gzipped JSON files (.json.gz) are checked using the inner extension
+    let file_extension = if filepath.extension() == Some(OsStr::new("gz")) {
+        filepath
+            .file_stem()
+            .map(Path::new)
+            .and_then(Path::extension)
+    } else {
+        filepath.extension()
+    };
     debug!("File extension: {:?}", file_extension);
     if Some(OsStr::new("json")) == file_extension {
-        debug!("Found the correct file format!")
+        debug!("Found the correct file format!");
+        Ok(())
     } else {
-        error!(
+        Err(InputError::NotJson(format!(
             "Incorrect file type passed. Expected file to end with .json not {}",
-            file_extension.unwrap().to_string_lossy()
-        );
-        exit(1)
+            file_extension.unwrap_or_default().to_string_lossy()
+        )))
     }
 }
 
-fn check_file_is_expected_type(filepath: &Path, command: &str) {
+fn check_file_is_expected_type(filepath: &Path, command: &str) -> Result<(), InputError> {
     debug!("Filepath: {} Command: {}", filepath.display(), command);
     let filepath_str = filepath.to_str().unwrap_or("");
 
@@ -47,7 +198,8 @@ fn check_file_is_expected_type(filepath: &Path, command: &str) {
     };
 
     if valid {
-        debug!("Provided filepath and command pair are valid")
+        debug!("Provided filepath and command pair are valid");
+        Ok(())
     } else {
         let expected_file_type = match command {
             "cfg" => "controlflow (_cfg.json)",
@@ -58,10 +210,44 @@ fn check_file_is_expected_type(filepath: &Path, command: &str) {
             _ => "",
         };
 
-        error!(
+        Err(InputError::WrongType(format!(
             "Incorrect file type and command pair. Got {} ({}) for command {} (expected {})",
             filepath_str, file_type_provided, command, expected_file_type
-        );
-        exit(1)
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_validate_file_valid_cg() {
+        let summary = validate_file(Path::new("test-files/ls_cg.json"), "cg").unwrap();
+        assert!(summary.contains("is a valid cg file"));
+    }
+
+    #[test]
+    fn test_validate_file_malformed() {
+        let input_dir = env::temp_dir().join(format!("bin2ml-validate-{}", std::process::id()));
+        fs::create_dir_all(&input_dir).unwrap();
+        let malformed = input_dir.join("malformed_cg.json");
+        fs::write(&malformed, r#"[{"name": "main", "imports": "#).unwrap();
+
+        let error = validate_file(&malformed, "cg").unwrap_err();
+        assert!(matches!(error, InputError::Invalid(_)));
+        assert_eq!(error.exit_code(), ExitCode::Failure);
+        assert!(error.to_string().contains("Unable to load"));
+
+        // The file name does not match the expected type
+        let error = validate_file(&malformed, "cfg").unwrap_err();
+        assert!(matches!(error, InputError::WrongType(_)));
+
+        let error = validate_file(&input_dir.join("missing_cg.json"), "cg").unwrap_err();
+        assert_eq!(error.exit_code(), ExitCode::InputNotFound);
+
+        fs::remove_dir_all(input_dir).unwrap();
     }
 }
@@ -0,0 +1,75 @@
+#![cfg(unix)]
+
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::Command;
+
+const INPUT_NOT_FOUND: i32 = 3;
+const PARTIAL_SUCCESS: i32 = 5;
+
+// Mimics `r2 -q0` closely enough for r2pipe. Files named bad_* fail to open
+// and every command returns an empty JSON array.
+const FAKE_R2: &str = r#"#!/bin/sh
+for arg in "$@"; do file="$arg"; done
+case "$(basename "$file")" in bad_*) exit 1 ;; esac
+printf '\000'
+while read -r cmd; do
+    printf '[]\000'
+    case "$cmd" in q*) exit 0 ;; esac
+done
+"#;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("bin2ml-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_extract_missing_input_exit_code() {
+    let output_dir = temp_dir("exit-missing-output");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_bin2ml"))
+        .args(["extract", "--mode", "finfo", "--fpath"])
+        .arg(output_dir.join("does_not_exist"))
+        .arg("--output-dir")
+        .arg(&output_dir)
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(INPUT_NOT_FOUND));
+    fs::remove_dir_all(output_dir).unwrap();
+}
+
+#[test]
+fn test_extract_partial_success_exit_code() {
+    let input_dir = temp_dir("exit-mixed-input");
+    let output_dir = temp_dir("exit-mixed-output");
+    let r2_dir = temp_dir("exit-mixed-r2");
+
+    fs::write(input_dir.join("good_bin"), "good").unwrap();
+    fs::write(input_dir.join("bad_bin"), "bad").unwrap();
+    let r2_path = r2_dir.join("r2");
+    fs::write(&r2_path, FAKE_R2).unwrap();
+    fs::set_permissions(&r2_path, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_bin2ml"))
+        .args(["extract", "--mode", "sections", "--fpath"])
+        .arg(&input_dir)
+        .arg("--output-dir")
+        .arg(&output_dir)
+        .arg("--r2-path")
+        .arg(&r2_path)
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(PARTIAL_SUCCESS));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("1 file(s) processed, 0 file(s) skipped, 1 file(s) failed"));
+
+    for dir in [input_dir, output_dir, r2_dir] {
+        fs::remove_dir_all(dir).unwrap();
+    }
+}